@@ -0,0 +1,36 @@
+//! Measures how many stagings per second `SerialReactor` and `ReactorPool` get
+//! through on the workloads in `paws::bench`. Give part of a benchmark's name,
+//! like `branch-heavy` or `deep-lookups/256`, to run only the matching ones.
+
+extern crate paws;
+extern crate native;
+
+use std::os;
+
+use paws::bench::{Harness, workloads, reactor_setups};
+
+#[start]
+fn start(argc: int, argv: *const *const u8) -> int {
+  // Make sure we use the native (not green thread) runtime
+  native::start(argc, argv, main)
+}
+
+fn main() {
+  let args    = os::args();
+  let filter  = args.as_slice().get(1).map(|filter| filter.clone());
+  let harness = Harness::new();
+
+  for workload in workloads().move_iter() {
+    let name = format!("{}", workload);
+
+    match filter {
+      Some(ref filter) if !name.as_slice().contains(filter.as_slice()) =>
+        continue,
+      _ => ()
+    }
+
+    for reactors in reactor_setups().move_iter() {
+      println!("{}", harness.run(workload, reactors));
+    }
+  }
+}
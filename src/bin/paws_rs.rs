@@ -7,7 +7,10 @@ use std::os;
 use std::fmt;
 
 use std::io::fs::File;
+use std::io::{BufferedWriter, BufReader};
+use std::str;
 use std::path::Path;
+use std::time::duration::Duration;
 
 use getopts::{optopt, optflag, optflagmulti, getopts};
 use getopts::{ArgumentMissing, UnrecognizedOption, OptionMissing};
@@ -15,14 +18,26 @@ use getopts::{OptionDuplicated, UnexpectedArgument};
 
 use paws::cpaws;
 
+use paws::script::{Script, Span, SpanTable};
+
 use paws::machine::Machine;
+use paws::machine::trace::{TraceFormat, JsonLines};
+use paws::machine::replay;
 use paws::machine::reactor::{Reactor, SerialReactor, ReactorPool};
+use paws::machine::reactor::ReactorStats;
 
 use paws::nuketype::Execution;
 
+use paws::object::{CacheConfig, CacheStats};
+use paws::object::registry;
+use paws::object::deadlock;
+
 use paws::specification::Suite;
 
+use paws::package::Package;
+
 use paws::interact::start as interact;
+use paws::interact::start_parallel as interact_parallel;
 
 #[start]
 fn start(argc: int, argv: *const *const u8) -> int {
@@ -35,16 +50,21 @@ fn help() {
 
   print!("{white}Paws.rs                                            “it's less fancy, but faster”{reset}
 
-  {bold}Usage: {reset}{cyan}{program} [options] [file.paws]{reset}
+  {bold}Usage: {reset}{cyan}{program} [options] [file.paws [arguments...]]{reset}
 
     By default, Paws.rs will consume a cPaws script from stdin and attempt to
     react it. If all goes well, it won't exit at all. If you provide a Paws
-    file, that will be loaded instead.
+    file, that will be loaded instead. Either may also be bytecode written by
+    {cyan}--compile{reset}.
+
+    Any arguments after the file are available to the program as
+    {cyan}implementation arguments{reset}. Put {cyan}--{reset} before them if any start with {cyan}-{reset}.
 
   {bold}Options:{reset}
 
     {cyan}-i, --interact{reset}
-      Starts a Paws.rs read-eval-print loop. All other options will be ignored.
+      Starts a Paws.rs read-eval-print loop. All other options except
+      {cyan}--reactors{reset} will be ignored.
 
     {cyan}--[no-]stall{reset}
       The default mode is {cyan}--stall{reset}, in which Paws.rs continues to run in an
@@ -62,12 +82,106 @@ fn help() {
       Note: this is highly experimental, and is more likely to result in a
       decrease in performance than an increase.
 
+    {cyan}--package PATH{reset}
+      Runs a Paws package (a directory or tar archive containing a
+      {cyan}package.manifest{reset}) instead of a single file, starting at its entry
+      module. Modules may be bytecode written by {cyan}--compile{reset}.
+
+    {cyan}--compile OUTPUT{reset}
+      Parses the input and writes it to {cyan}OUTPUT{reset} as bytecode, which loads
+      without having to be parsed again, instead of running it. Source spans
+      aren't kept, so warnings from bytecode don't say where they came from.
+
+    {cyan}--optimize{reset}
+      Removes instructions that have no effect from the input as it's compiled,
+      such as values that are pushed and then immediately discarded, and looks
+      up constant Symbols in frozen objects ahead of time. Doesn't change what
+      the program does, only how many steps it takes. Bytecode is loaded as it
+      is, so combine with {cyan}--compile{reset} to write optimized bytecode.
+
+    {cyan}--dropped-continuations{reset}
+      Reports (as warnings) every Execution that was handed to a receiver as a
+      caller but never re-staged, each time the reactor stalls. Useful when a
+      program goes quiet for no apparent reason. Slows everything down.
+
+    {cyan}--responsibility{reset}
+      Makes each Execution take responsibility for the objects it combines
+      against (and their children), holding it until the Execution completes.
+      Combinations against objects another Execution is responsible for wait
+      until it's released. Slows everything down.
+
+    {cyan}--trace-file FILE{reset}, {cyan}--trace FILE{reset}
+      Writes every combination (caller, subject, message, and the receiver that
+      was chosen) and every staging to {cyan}FILE{reset}, with timestamps, one JSON
+      object per line. Works with any number of reactors. Slows everything
+      down, a lot. The two names are the same option.
+
+    {cyan}--trace-format FORMAT{reset}
+      Sets the format for {cyan}--trace-file{reset}: {cyan}json{reset} (the default), or {cyan}text{reset}
+      for a timeline that's easier to read directly.
+
+    {cyan}--record FILE{reset}
+      Writes the order stagings were realized in to {cyan}FILE{reset}, so that a run
+      (especially with several reactors) can be reproduced with {cyan}--replay{reset}.
+
+    {cyan}--replay FILE{reset}
+      Realizes stagings in the order recorded in {cyan}FILE{reset} by {cyan}--record{reset}, on a
+      single reactor. Warns and goes back to the usual order if the program
+      does something different this time.
+
+    {cyan}--leak-check{reset}
+      Keeps track of every object created, and once the machine is done, warns
+      about the ones that are still alive, grouped by tag. Useful for finding
+      reference cycles. Slows everything down.
+
+    {cyan}--deadlock-check{reset}
+      Keeps track of which reactor holds which object locks, and fails with the
+      objects involved instead of hanging when two of them would wait on each
+      other forever. Slows everything down.
+
+    {cyan}--cache-size SIZE{reset}
+      Sets how many entries each of a reactor's caches (symbol lookups, and
+      receivers when running in parallel) can hold. The default is 64. Use
+      {cyan}--stats{reset} to see whether it helps.
+
+    {cyan}--cache-config SPEC{reset}
+      Configures each of a reactor's caches separately, after {cyan}--cache-size{reset}.
+      {cyan}SPEC{reset} is a comma-separated list of {cyan}name=value{reset}, where the name is
+      {cyan}sym-lookup{reset}, {cyan}receiver{reset} or {cyan}params-pool{reset}, and the value is a size,
+      {cyan}on{reset} or {cyan}off{reset}. For example: {cyan}sym-lookup=1024,receiver=off{reset}
+
+    {cyan}--shared-cache SIZE{reset}
+      With more than one reactor, has them share a cache of up to {cyan}SIZE{reset}
+      receivers behind their own, so that an object's receiver only has to be
+      looked up once for all of them.
+
+    {cyan}--stats{reset}
+      Once the machine is done, prints statistics (stagings realized and cache
+      hit rates) to stderr, for each reactor and then added up across all of
+      them.
+
+    {cyan}--cache-stats{reset}
+      Like {cyan}--stats{reset}, but only prints the cache hits and misses, added up
+      across all reactors.
+
+    {cyan}--metrics{reset}
+      Once the machine is done, prints the metrics every reactor reported to
+      it (stagings made and realized, stalls, cache hit rates, and stagings
+      realized per second) to stderr. Paws programs can get the same numbers
+      from {cyan}implementation metrics{reset}.
+
     {cyan}--spec{reset}
       Runs Paws.rs in specification mode, allowing it to run tests provided by
       the Paws Rulebook. The output conforms to the Test Anything Protocol.
 
       This option implies {cyan}--no-stall{reset}.
 
+    {cyan}--spec-timeout MS{reset}
+      In specification mode, fails each rule that hasn't passed or failed
+      within {cyan}MS{reset} milliseconds of starting, and goes on without it. Rules
+      with {cyan}eventually{reset} have it run once the time is up instead, and then
+      get as long again.
+
     {cyan}-h, --help{reset}
       Displays this message.
 
@@ -96,7 +210,28 @@ fn main() {
     optflagmulti("",  "no-stall", ""),
     optflagmulti("",     "stall", ""),
 
-         optflag("",      "spec", "")
+          optopt("",   "package", "", ""),
+          optopt("",   "compile", "", ""),
+         optflag("",  "optimize", ""),
+
+         optflag("",   "dropped-continuations", ""),
+         optflag("",   "responsibility", ""),
+          optopt("",   "trace-file", "", ""),
+          optopt("",   "trace", "", ""),
+          optopt("",   "trace-format", "", ""),
+          optopt("",   "record", "", ""),
+          optopt("",   "replay", "", ""),
+         optflag("",   "leak-check", ""),
+         optflag("",   "deadlock-check", ""),
+          optopt("",   "cache-size", "", ""),
+          optopt("",   "cache-config", "", ""),
+          optopt("",   "shared-cache", "", ""),
+         optflag("",     "stats", ""),
+         optflag("",     "cache-stats", ""),
+         optflag("",     "metrics", ""),
+
+         optflag("",      "spec", ""),
+          optopt("",      "spec-timeout", "", "")
   ];
 
   let matches = match getopts(args.tail(), opts) {
@@ -113,12 +248,6 @@ fn main() {
     return
   }
 
-  // Flag: -i, --interact
-  if matches.opt_present("i") {
-    interact();
-    return
-  }
-
   // Option: -R, --reactors COUNT
   let mut reactors: int = 1;
 
@@ -144,6 +273,16 @@ fn main() {
     None => ()
   }
 
+  // Flag: -i, --interact
+  if matches.opt_present("i") {
+    if reactors == 1 {
+      interact();
+    } else {
+      interact_parallel(reactors as uint);
+    }
+    return
+  }
+
   // Flags: --no-stall, --stall
   let mut no_stall = false;
 
@@ -154,29 +293,76 @@ fn main() {
   // Flag: --spec
   let spec_ = matches.opt_present("spec");
 
-  // Now get input, either from stdin or files
+  // Option: --spec-timeout MS
+  let spec_timeout = match matches.opt_str("spec-timeout") {
+    Some(n) => {
+      if !spec_ {
+        format_args!(argument_error,
+          "Error: --spec-timeout can only be used with --spec.\n");
+        return
+      }
+
+      match from_str::<i64>(n.as_slice()) {
+        Some(n) if n > 0 => Some(Duration::milliseconds(n)),
+
+        _ => {
+          format_args!(argument_error,
+            concat!("Error: --spec-timeout should be given a number of",
+                    " milliseconds greater than zero.\n"));
+          return
+        }
+      }
+    },
+    None => None
+  };
+
+  // Option: --package PATH
+  let package = match matches.opt_str("package") {
+    Some(path) => {
+      if spec_ {
+        format_args!(argument_error,
+          "Error: --package can't be combined with --spec.\n");
+        return
+      }
+
+      match Package::load(&Path::new(path.as_slice())) {
+        Ok(package) => Some(package),
+
+        Err(message) => {
+          format_args!(generic_error, "Package error: {}\n", message);
+          return
+        }
+      }
+    },
+
+    None => None
+  };
+
+  // Now get input, either from stdin or files, and the arguments for the
+  // program: any non-option arguments after the file (or all of them, for a
+  // package)
   let input;
   let filename;
+  let arguments;
 
-  if matches.free.len() > 1 {
-    format_args!(argument_error,
-      concat!("Error: must provide either a single file to run, or none, in",
-              " which case input is taken from stdin.\n",
-              "\n",
-              "You provided {} non-option argument(s).\n"),
-      matches.free.len());
-    return
+  if package.is_some() {
+    input     = Vec::new();
+    filename  = String::new();
+    arguments = matches.free.clone();
 
   } else if matches.free.is_empty() {
-    input    = io::stdin().read_to_string().unwrap();
-    filename = "<stdin>".to_string();
+    input     = io::stdin().read_to_end().unwrap();
+    filename  = "<stdin>".to_string();
+    arguments = vec![];
 
   } else {
     let path = Path::new(matches.free[0].as_slice());
 
-    match File::open(&path).read_to_string() {
-      Ok(string) => {
-        input    = string;
+    arguments = matches.free.tail().to_vec();
+
+    match File::open(&path).read_to_end() {
+      Ok(bytes) => {
+        input    = bytes;
         filename = format!("{}", path.display());
       },
 
@@ -187,16 +373,225 @@ fn main() {
     }
   }
 
+  // Flag: --optimize
+  let optimize = matches.opt_present("optimize");
+
+  // Option: --compile OUTPUT
+  match matches.opt_str("compile") {
+    Some(path) => {
+      if package.is_some() || spec_ {
+        format_args!(argument_error,
+          concat!("Error: --compile can't be combined with --package or",
+                  " --spec.\n"));
+      } else {
+        compile(input.as_slice(), filename.as_slice(), optimize,
+                &Path::new(path.as_slice()));
+      }
+      return
+    },
+
+    None => ()
+  }
+
+  // Flag: --leak-check
+  //
+  // Has to be enabled before the machine is created, so that the machine's own
+  // objects are registered too.
+  let leak_check = matches.opt_present("leak-check");
+
+  if leak_check {
+    registry::enable();
+  }
+
+  // Flag: --deadlock-check
+  if matches.opt_present("deadlock-check") {
+    deadlock::enable();
+  }
+
+  // Flag: --stats
+  let show_stats = matches.opt_present("stats");
+
+  // Flag: --cache-stats
+  let show_cache_stats = matches.opt_present("cache-stats") && !show_stats;
+
+  // Flag: --metrics
+  let show_metrics = matches.opt_present("metrics");
+
   // Set up machine as requested
-  let machine = Machine::new();
+  let mut machine = Machine::new();
+
+  let metrics = machine.metrics.clone();
+
+  machine.arguments = arguments;
+
+  // Option: --cache-size SIZE
+  match matches.opt_str("cache-size") {
+    Some(n) =>
+      match from_str::<uint>(n.as_slice()) {
+        Some(n) if n > 0 =>
+          machine.cache_config = CacheConfig::new().with_size(n),
+
+        _ => {
+          format_args!(argument_error,
+            concat!("Error: --cache-size should be given a number greater",
+                    " than zero.\n"));
+          return
+        }
+      },
+    None => ()
+  }
+
+  // Option: --cache-config SPEC
+  match matches.opt_str("cache-config") {
+    Some(spec) =>
+      match machine.cache_config.clone().with_spec(spec.as_slice()) {
+        Ok(config) =>
+          machine.cache_config = config,
+
+        Err(message) => {
+          format_args!(argument_error, "Error: --cache-config: {}.\n",
+                       message);
+          return
+        }
+      },
+    None => ()
+  }
+
+  // Option: --shared-cache SIZE
+  match matches.opt_str("shared-cache") {
+    Some(n) =>
+      match from_str::<uint>(n.as_slice()) {
+        Some(n) if n > 0 =>
+          machine.shared_cache.enable(n),
+
+        _ => {
+          format_args!(argument_error,
+            concat!("Error: --shared-cache should be given a number greater",
+                    " than zero.\n"));
+          return
+        }
+      },
+    None => ()
+  }
+
+  // Flag: --dropped-continuations
+  if matches.opt_present("dropped-continuations") {
+    machine.continuations.enable();
+  }
+
+  // Flag: --responsibility
+  if matches.opt_present("responsibility") {
+    machine.responsibility.enable();
+  }
+
+  // Option: --trace-format FORMAT
+  let trace_format = match matches.opt_str("trace-format") {
+    Some(name) =>
+      match TraceFormat::from_name(name.as_slice()) {
+        Some(format) => format,
+
+        None => {
+          format_args!(argument_error,
+            "Error: --trace-format should be either 'json' or 'text'.\n");
+          return
+        }
+      },
+
+    None => JsonLines
+  };
+
+  // Option: --record FILE
+  let replay = machine.replay.clone();
+
+  match matches.opt_str("record") {
+    Some(path) =>
+      match File::create(&Path::new(path.as_slice())) {
+        Ok(file) => replay.record(box BufferedWriter::new(file)),
+
+        Err(e) => {
+          format_args!(generic_error,
+                       "Error: can't open recording file: {}\n", e);
+          return
+        }
+      },
+
+    None => ()
+  }
+
+  // Option: --replay FILE
+  match matches.opt_str("replay") {
+    Some(path) =>
+      match File::open(&Path::new(path.as_slice())) {
+        Ok(file) =>
+          match replay::read_recording(file) {
+            Ok(recording) => {
+              replay.replay(recording);
+
+              // Only a single reactor can follow the recording exactly.
+              reactors = 1;
+            },
+
+            Err(message) => {
+              format_args!(generic_error, "Error: {}\n", message);
+              return
+            }
+          },
+
+        Err(e) => {
+          format_args!(generic_error,
+                       "Error: can't open recording file: {}\n", e);
+          return
+        }
+      },
+
+    None => ()
+  }
+
+  // Option: --trace-file FILE (or --trace FILE)
+  let trace = machine.trace.clone();
+
+  match matches.opt_str("trace-file").or(matches.opt_str("trace")) {
+    Some(path) =>
+      match File::create(&Path::new(path.as_slice())) {
+        Ok(file) =>
+          trace.enable_with_format(box BufferedWriter::new(file),
+                                   trace_format),
+
+        Err(e) => {
+          format_args!(generic_error, "Error: can't open trace file: {}\n", e);
+          return
+        }
+      },
+
+    None => ()
+  }
 
   let start = proc (reactor: &mut Reactor) {
-    if spec_ {
+    if package.is_some() {
+      // Load and stage the package's entry module
+      match package.unwrap().run(reactor) {
+        Ok(()) => (),
+
+        Err(message) => {
+          format_args!(generic_error, "Package error: {}\n", message);
+          return false
+        }
+      }
+
+      if no_stall {
+        reactor.on_stall(proc(reactor) {
+          reactor.stop();
+        });
+      }
+
+      true
+    } else if spec_ {
       // Parse and stage input (in spec mode)
-      spec(reactor, input.as_slice(), filename.as_slice())
+      spec(reactor, input.as_slice(), filename.as_slice(), optimize,
+           spec_timeout)
     } else {
       // Parse and stage input
-      if !eval(reactor, input.as_slice(), filename.as_slice()) {
+      if !eval(reactor, input.as_slice(), filename.as_slice(), optimize) {
         return false
       }
 
@@ -216,7 +611,13 @@ fn main() {
 
     if !start(&mut reactor) { return }
 
-    reactor.run()
+    reactor.run();
+
+    if show_stats {
+      print_stats(&reactor.stats());
+    } else if show_cache_stats {
+      print_cache_stats(&reactor.stats().cache);
+    }
   } else {
     let mut pool = ReactorPool::spawn(machine, reactors as uint);
 
@@ -228,7 +629,45 @@ fn main() {
       }
     });
 
-    pool.wait()
+    pool.wait();
+
+    if show_stats {
+      let mut stderr = io::stderr();
+
+      for (index, stats) in pool.finished_stats().iter().enumerate() {
+        (writeln!(stderr, "-- reactor {} of {} to finish:",
+                  index + 1, reactors)).unwrap();
+        print_stats(stats);
+      }
+
+      (writeln!(stderr, "-- all reactors:")).unwrap();
+      print_stats(&pool.stats());
+    } else if show_cache_stats {
+      print_cache_stats(&pool.stats().cache);
+    }
+  }
+
+  if show_metrics {
+    match metrics.snapshot().write_text(&mut io::stderr()) {
+      Ok(()) => (),
+      Err(e) => format_args!(generic_error, "Error: writing metrics: {}\n", e)
+    }
+  }
+
+  match replay.flush() {
+    Ok(()) => (),
+    Err(e) => format_args!(generic_error, "Error: writing recording: {}\n", e)
+  }
+
+  match trace.flush() {
+    Ok(()) => (),
+    Err(e) => format_args!(generic_error, "Error: writing trace: {}\n", e)
+  }
+
+  // The reactors (and with them, the machine) are gone by now, so anything
+  // left is a leak.
+  if leak_check {
+    registry::report();
   }
 }
 
@@ -260,6 +699,47 @@ fn handle_getopts_error(error: getopts::Fail_) {
   }
 }
 
+fn print_stats(stats: &ReactorStats) {
+  let mut stderr = io::stderr();
+
+  (writeln!(stderr, "stagings realized: {} ({} executions, {} aliens)",
+            stats.steps, stats.executions, stats.aliens)).unwrap();
+  (writeln!(stderr, "stagings stolen:   {} ({} taken locally, {})",
+            stats.steals, stats.local_hits,
+            format_steal_rate(stats.steal_rate()))).unwrap();
+  (writeln!(stderr, "stalls handled:    {}", stats.stalls)).unwrap();
+
+  print_cache_stats(&stats.cache);
+}
+
+fn print_cache_stats(stats: &CacheStats) {
+  let mut stderr = io::stderr();
+
+  (writeln!(stderr, "symbol lookups:    {} hits, {} misses ({}), {} frozen",
+            stats.sym_lookup_hits,
+            stats.sym_lookup_misses,
+            format_rate(stats.sym_lookup_hit_rate()),
+            stats.frozen_lookups)).unwrap();
+  (writeln!(stderr, "receivers:         {} hits, {} misses ({})",
+            stats.receiver_hits,
+            stats.receiver_misses,
+            format_rate(stats.receiver_hit_rate()))).unwrap();
+}
+
+fn format_rate(rate: Option<f64>) -> String {
+  match rate {
+    Some(rate) => format!("{:.1}% hit", rate * 100.0),
+    None       => "unused".to_string()
+  }
+}
+
+fn format_steal_rate(rate: Option<f64>) -> String {
+  match rate {
+    Some(rate) => format!("{:.1}% stolen", rate * 100.0),
+    None       => "unused".to_string()
+  }
+}
+
 fn generic_error(args: &fmt::Arguments) {
   let mut stderr = io::stderr();
 
@@ -283,39 +763,117 @@ fn argument_error(args: &fmt::Arguments) {
   os::set_exit_status(1);
 }
 
-fn eval(reactor: &mut Reactor, input: &str, filename: &str) -> bool {
-  match cpaws::parse_nodes(input.as_slice(), filename) {
-    Ok(nodes) => {
-      // Compile an execution...
-      let script        = cpaws::build_script(reactor.machine(),
-                                              nodes.as_slice());
-      let execution_ref = Execution::create(reactor.machine(), script);
+fn eval(reactor: &mut Reactor, input: &[u8], filename: &str, optimize: bool)
+        -> bool {
+  // Compile (or load) an execution...
+  let (script, spans) =
+    match load(reactor.machine(), input, filename, optimize) {
+      Some(loaded) => loaded,
+      None         => return false
+    };
 
-      // ...expose the system interface to it...
-      reactor.machine().expose_system_to(&execution_ref);
+  let execution_ref = Execution::create_with_spans(reactor.machine(),
+                                                   script, spans);
 
-      // and stage!
-      reactor.stage(execution_ref.clone(), execution_ref.clone());
+  // ...expose the system interface to it...
+  reactor.machine().expose_system_to(&execution_ref);
 
-      true
+  // and stage!
+  reactor.stage(execution_ref.clone(), execution_ref.clone());
+
+  true
+}
+
+/// Loads `input` as bytecode if it is bytecode, or parses it as cPaws source
+/// otherwise. Reports errors and returns `None` if neither works.
+///
+/// Source is compiled with `cpaws::build_optimized_script_with_spans()` if
+/// `optimize` is set.
+fn load(machine: &Machine, input: &[u8], filename: &str, optimize: bool)
+        -> Option<(Script, SpanTable)> {
+
+  if Script::is_bytecode(input) {
+    match Script::deserialize(&mut BufReader::new(input), machine) {
+      Ok(script) => Some((script, SpanTable(vec![]))),
+
+      Err(e) => {
+        format_args!(generic_error, "Bytecode error: {}\n", e);
+        None
+      }
+    }
+  } else {
+    let text = match source_of(input) {
+      Some(text) => text,
+      None       => return None
+    };
+
+    match cpaws::parse_nodes_with_spans(text, filename) {
+      Ok((nodes, spans)) =>
+        Some(build(machine, nodes.as_slice(), spans.as_slice(), optimize)),
+
+      Err(message) => {
+        format_args!(generic_error, "Parse error: {}", message);
+        None
+      }
     }
+  }
+}
 
-    Err(message) => {
-      format_args!(generic_error, "Parse error: {}", message);
-      false
+/// Checks that `input` is UTF-8, as cPaws source must be.
+fn source_of<'a>(input: &'a [u8]) -> Option<&'a str> {
+  match str::from_utf8(input) {
+    Some(text) => Some(text),
+
+    None => {
+      format_args!(generic_error, "Error: input is not valid UTF-8.\n");
+      None
     }
   }
 }
 
-fn spec(reactor: &mut Reactor, input: &str, filename: &str) -> bool {
-  match cpaws::parse_nodes(input.as_slice(), filename) {
-    Ok(nodes) => {
-      let suite = Suite::new();
+/// Compiles parsed source, optimized if `optimize` is set.
+fn build(machine: &Machine, nodes: &[cpaws::Node], spans: &[Span],
+         optimize: bool) -> (Script, SpanTable) {
+  if optimize {
+    cpaws::build_optimized_script_with_spans(machine, nodes, spans)
+  } else {
+    cpaws::build_fused_script_with_spans(machine, nodes, spans)
+  }
+}
+
+fn compile(input: &[u8], filename: &str, optimize: bool, output: &Path) {
+  let machine = Machine::new();
+
+  let script = match load(&machine, input, filename, optimize) {
+    Some((script, _)) => script,
+    None              => return
+  };
+
+  match File::create(output).and_then(|mut file| script.serialize(&mut file)) {
+    Ok(()) => (),
+    Err(e) => format_args!(generic_error, "Error: writing bytecode: {}\n", e)
+  }
+}
+
+fn spec(reactor: &mut Reactor, input: &[u8], filename: &str, optimize: bool,
+        timeout: Option<Duration>) -> bool {
+  let input = match source_of(input) {
+    Some(text) => text,
+    None       => return false
+  };
+
+  match cpaws::parse_nodes_with_spans(input, filename) {
+    Ok((nodes, spans)) => {
+      let suite = match timeout {
+        Some(timeout) => Suite::new().with_timeout(timeout),
+        None          => Suite::new()
+      };
 
       // Compile an execution...
-      let script        = cpaws::build_script(reactor.machine(),
-                                              nodes.as_slice());
-      let execution_ref = Execution::create(reactor.machine(), script);
+      let (script, spans) =
+        build(reactor.machine(), nodes.as_slice(), spans.as_slice(), optimize);
+      let execution_ref   = Execution::create_with_spans(reactor.machine(),
+                                                         script, spans);
 
       // ...expose the system interface to it...
       reactor.machine().expose_system_to(&execution_ref);
@@ -0,0 +1,400 @@
+//! Representative workloads, and a small harness that measures how quickly
+//! reactors get through them, so that performance regressions in the cache or
+//! the scheduler show up as numbers. The programs in `benches/` are built on
+//! this; run them with `make bench`.
+//!
+//! Each `Workload` is an Execution, prepared on a fresh `Machine` along with
+//! whatever it expects to find in its locals, that ends by stalling. A
+//! `Harness` runs it several times on either a `SerialReactor` or a
+//! `ReactorPool` and summarizes how many stagings were realized per second,
+//! in the manner of criterion:
+//!
+//!     deep-lookups/64      serial   time: [1.021 ms 1.048 ms 1.310 ms]
+//!                                   thrpt: 1002384 realizations/s
+
+use cpaws;
+
+use object::ObjectRef;
+
+use nuketype::{Thing, Execution};
+
+use machine::Machine;
+use machine::reactor::{Reactor, SerialReactor, ReactorPool};
+
+use time;
+
+use std::fmt;
+use std::uint;
+
+#[cfg(test)]
+mod tests;
+
+/// How many times `DeepLookups` and `WideNamespace` go through all of their
+/// lookups, so that even the small sizes take long enough to measure.
+pub static ROUNDS: uint = 16;
+
+/// Something for a reactor to do.
+#[deriving(Clone, PartialEq, Eq)]
+pub enum Workload {
+  /// Follows a chain of `n` objects from `root`, each found in the last as
+  /// `next`.
+  DeepLookups(uint),
+
+  /// Looks up every one of `n` names in `ns`, an object with `n` pairs.
+  WideNamespace(uint),
+
+  /// Runs a binary tree of Executions `n` levels deep, each of which stages
+  /// branches of its `left` and `right` children, so that there are `2^n`
+  /// leaves that could all be running at once.
+  BranchHeavy(uint)
+}
+
+impl Workload {
+  /// The name of the kind of workload, without its size.
+  pub fn name(&self) -> &'static str {
+    match *self {
+      DeepLookups(_)   => "deep-lookups",
+      WideNamespace(_) => "wide-namespace",
+      BranchHeavy(_)   => "branch-heavy"
+    }
+  }
+
+  /// How big the workload is. See the variants for what that means.
+  pub fn size(&self) -> uint {
+    match *self {
+      DeepLookups(n) | WideNamespace(n) | BranchHeavy(n) => n
+    }
+  }
+
+  /// Creates the Execution to stage for this workload on `machine`.
+  pub fn prepare(&self, machine: &Machine) -> ObjectRef {
+    match *self {
+      DeepLookups(depth) => {
+        let root = Thing::empty();
+
+        let mut last = root.clone();
+
+        for _ in range(0, depth) {
+          let next = Thing::empty();
+
+          add_pair(machine, &last, "next", next.clone());
+
+          last = next;
+        }
+
+        let source    = deep_lookups_source(depth);
+        let execution = compile(machine, source.as_slice());
+
+        add_local(machine, &execution, "root", root);
+
+        execution
+      },
+
+      WideNamespace(width) => {
+        let ns = Thing::empty();
+
+        for index in range(0, width) {
+          add_pair(machine, &ns, format!("k{}", index).as_slice(),
+                   Thing::empty());
+        }
+
+        let source    = wide_namespace_source(width);
+        let execution = compile(machine, source.as_slice());
+
+        add_local(machine, &execution, "ns", ns);
+
+        execution
+      },
+
+      BranchHeavy(depth) => branch(machine, depth)
+    }
+  }
+}
+
+impl fmt::Show for Workload {
+  fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+    write!(out, "{}/{}", self.name(), self.size())
+  }
+}
+
+/// What to run a `Workload` on.
+#[deriving(Clone, PartialEq, Eq)]
+pub enum Reactors {
+  /// A single `SerialReactor`.
+  Serial,
+
+  /// A `ReactorPool` of this many reactors.
+  Pool(uint)
+}
+
+impl fmt::Show for Reactors {
+  fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      Serial        => write!(out, "serial"),
+      Pool(threads) => write!(out, "pool/{}", threads)
+    }
+  }
+}
+
+/// The workloads run by `benches/`, at a few sizes each.
+pub fn workloads() -> Vec<Workload> {
+  vec![DeepLookups(16),   DeepLookups(64),     DeepLookups(256),
+       WideNamespace(16), WideNamespace(256),  WideNamespace(1024),
+       BranchHeavy(4),    BranchHeavy(8),      BranchHeavy(12)]
+}
+
+/// The reactor setups run by `benches/`.
+pub fn reactor_setups() -> Vec<Reactors> {
+  vec![Serial, Pool(2), Pool(4)]
+}
+
+/// The result of running a `Workload` once.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct Measurement {
+  /// How many stagings were realized before the reactors stalled.
+  pub realizations: u64,
+
+  /// How long that took, from staging the workload to the stall.
+  pub elapsed_ns:   u64
+}
+
+impl Measurement {
+  /// Realizations per second. Zero if no time passed at all.
+  pub fn per_second(&self) -> f64 {
+    if self.elapsed_ns == 0 {
+      0.0
+    } else {
+      self.realizations as f64 / (self.elapsed_ns as f64 / 1e9)
+    }
+  }
+}
+
+/// Runs `workload` once on a fresh `Machine` until the reactors stall.
+pub fn measure(workload: Workload, reactors: Reactors) -> Measurement {
+  let machine   = Machine::new();
+  let execution = workload.prepare(&machine);
+
+  match reactors {
+    Serial => {
+      let mut reactor = SerialReactor::new(machine);
+
+      let start_ns = time::precise_time_ns();
+
+      reactor.stage(execution.clone(), execution);
+      reactor.run_for(uint::MAX);
+
+      Measurement {
+        realizations: reactor.stats().steps,
+        elapsed_ns:   time::precise_time_ns() - start_ns
+      }
+    },
+
+    Pool(threads) => {
+      let mut pool = ReactorPool::spawn(machine, threads);
+
+      let (stall_tx, stall_rx) = channel();
+
+      let start_ns = time::precise_time_ns();
+
+      // Both of these happen in the same message, so the pool can't stall
+      // before the handler is there to hear about it.
+      pool.on_reactor(proc (reactor) {
+        reactor.on_stall(proc (_) stall_tx.send(()));
+        reactor.stage(execution.clone(), execution);
+      });
+
+      stall_rx.recv();
+
+      let measurement = Measurement {
+        realizations: pool.stats().steps,
+        elapsed_ns:   time::precise_time_ns() - start_ns
+      };
+
+      pool.stop();
+      pool.wait();
+
+      measurement
+    }
+  }
+}
+
+/// Runs workloads repeatedly and summarizes the results.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct Harness {
+  /// How many runs to throw away first, so that allocators and the like have
+  /// settled down.
+  pub warmup:  uint,
+
+  /// How many runs to keep.
+  pub samples: uint
+}
+
+impl Harness {
+  /// A harness with one warmup run and ten samples.
+  pub fn new() -> Harness {
+    Harness {
+      warmup:  1,
+      samples: 10
+    }
+  }
+
+  /// Sets `warmup`.
+  pub fn with_warmup(self, warmup: uint) -> Harness {
+    Harness { warmup: warmup, ..self }
+  }
+
+  /// Sets `samples`.
+  pub fn with_samples(self, samples: uint) -> Harness {
+    Harness { samples: samples, ..self }
+  }
+
+  /// Measures `workload` on `reactors` `warmup + samples` times.
+  pub fn run(&self, workload: Workload, reactors: Reactors) -> Summary {
+    for _ in range(0, self.warmup) {
+      measure(workload, reactors);
+    }
+
+    Summary {
+      workload:     workload,
+      reactors:     reactors,
+      measurements: range(0, self.samples)
+                      .map(|_| measure(workload, reactors))
+                      .collect()
+    }
+  }
+}
+
+/// The measurements a `Harness` kept for one workload on one reactor setup.
+/// `Show` formats it as a report, like the module documentation's example.
+#[deriving(Clone)]
+pub struct Summary {
+  /// What was run.
+  pub workload:     Workload,
+
+  /// What it was run on.
+  pub reactors:     Reactors,
+
+  /// The samples, in the order they were taken.
+  pub measurements: Vec<Measurement>
+}
+
+impl Summary {
+  /// The fastest, median and slowest times, in nanoseconds, or `None` if there
+  /// were no samples.
+  pub fn times_ns(&self) -> Option<(u64, u64, u64)> {
+    let mut times: Vec<u64> =
+      self.measurements.iter().map(|m| m.elapsed_ns).collect();
+
+    times.sort();
+
+    if times.is_empty() {
+      None
+    } else {
+      Some((times[0], times[times.len() / 2], times[times.len() - 1]))
+    }
+  }
+
+  /// The median of the samples' realizations per second, or `None` if there
+  /// were no samples.
+  pub fn median_per_second(&self) -> Option<f64> {
+    let mut rates: Vec<f64> =
+      self.measurements.iter().map(|m| m.per_second()).collect();
+
+    rates.sort_by(|a, b| a.partial_cmp(b).expect("rate was NaN"));
+
+    if rates.is_empty() {
+      None
+    } else {
+      Some(rates[rates.len() / 2])
+    }
+  }
+}
+
+impl fmt::Show for Summary {
+  fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+    let name = format!("{}", self.workload);
+
+    try!(write!(out, "{:<20} {:<8} ", name, format!("{}", self.reactors)));
+
+    match (self.times_ns(), self.median_per_second()) {
+      (Some((low, median, high)), Some(rate)) => {
+        try!(write!(out, "time: [{} {} {}]\n",
+                    format_ns(low), format_ns(median), format_ns(high)));
+
+        write!(out, "{:30}thrpt: {:.0f} realizations/s", "", rate)
+      },
+
+      _ => write!(out, "no samples")
+    }
+  }
+}
+
+fn format_ns(ns: u64) -> String {
+  format!("{:.3f} ms", ns as f64 / 1e6)
+}
+
+/// `root next next ...`, `ROUNDS` times over.
+fn deep_lookups_source(depth: uint) -> String {
+  let mut chain = String::from_str("root");
+
+  for _ in range(0, depth) {
+    chain.push_str(" next");
+  }
+
+  Vec::from_elem(ROUNDS, chain).connect("; ")
+}
+
+/// `ns k0; ns k1; ...`, `ROUNDS` times over.
+fn wide_namespace_source(width: uint) -> String {
+  let lookups: Vec<String> =
+    range(0, width).map(|index| format!("ns k{}", index)).collect();
+
+  Vec::from_elem(ROUNDS, lookups.connect("; ")).connect("; ")
+}
+
+/// An Execution that stages branches of `left` and `right` from its locals,
+/// each of which is another of these `depth - 1` levels deep. The leaves just
+/// look up `locals`.
+fn branch(machine: &Machine, depth: uint) -> ObjectRef {
+  if depth == 0 {
+    compile(machine, "locals")
+  } else {
+    let execution = compile(machine, concat!(
+      "infrastructure execution stage[]",
+      " [infrastructure execution branch[] [left]] [];",
+      " infrastructure execution stage[]",
+      " [infrastructure execution branch[] [right]] []"));
+
+    machine.expose_system_to(&execution);
+
+    add_local(machine, &execution, "left",  branch(machine, depth - 1));
+    add_local(machine, &execution, "right", branch(machine, depth - 1));
+
+    execution
+  }
+}
+
+fn compile(machine: &Machine, source: &str) -> ObjectRef {
+  let (nodes, spans) = cpaws::parse_nodes_with_spans(source, "<bench>")
+                         .ok().expect("bench workload failed to parse!");
+
+  let (script, spans) =
+    cpaws::build_fused_script_with_spans(machine, nodes.as_slice(),
+                                         spans.as_slice());
+
+  Execution::create_with_spans(machine, script, spans)
+}
+
+fn add_pair(machine: &Machine, object: &ObjectRef, key: &str,
+            value: ObjectRef) {
+  object.lock().meta_mut().members.push_pair(machine.symbol(key), value);
+}
+
+fn add_local(machine: &Machine, execution: &ObjectRef, key: &str,
+             value: ObjectRef) {
+  let locals = execution.lock().meta().members
+                 .lookup_pair(&machine.locals_sym)
+                 .expect("Execution is missing locals!");
+
+  add_pair(machine, &locals, key, value);
+}
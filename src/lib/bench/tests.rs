@@ -0,0 +1,92 @@
+use super::{DeepLookups, WideNamespace, BranchHeavy, Serial, Pool};
+use super::{Harness, Summary, Measurement, ROUNDS, measure};
+use super::{deep_lookups_source, wide_namespace_source};
+
+#[test]
+fn workloads_show_name_and_size() {
+  assert_eq!(format!("{}", DeepLookups(64)).as_slice(),  "deep-lookups/64");
+  assert_eq!(format!("{}", BranchHeavy(8)).as_slice(),   "branch-heavy/8");
+  assert_eq!(format!("{}", Pool(4)).as_slice(),          "pool/4");
+}
+
+#[test]
+fn sources_repeat_for_each_round() {
+  let deep = deep_lookups_source(2);
+  let wide = wide_namespace_source(2);
+
+  assert!(deep.as_slice().starts_with("root next next; root next next;"));
+  assert!(wide.as_slice().starts_with("ns k0; ns k1; ns k0;"));
+
+  assert_eq!(deep.as_slice().split(';').count(), ROUNDS);
+  assert_eq!(wide.as_slice().split(';').count(), ROUNDS * 2);
+}
+
+#[test]
+fn measure_deep_lookups() {
+  // The Execution itself, then each `next`. The lookups of `root` are done
+  // without staging.
+  let measurement = measure(DeepLookups(4), Serial);
+
+  assert_eq!(measurement.realizations, 1 + ROUNDS as u64 * 4);
+}
+
+#[test]
+fn measure_wide_namespace() {
+  let measurement = measure(WideNamespace(8), Serial);
+
+  assert_eq!(measurement.realizations, 1 + ROUNDS as u64 * 8);
+}
+
+#[test]
+fn measure_branch_heavy_on_a_pool() {
+  let serial = measure(BranchHeavy(3), Serial);
+  let pool   = measure(BranchHeavy(3), Pool(2));
+
+  assert!(serial.realizations > measure(BranchHeavy(2), Serial).realizations);
+  assert_eq!(pool.realizations, serial.realizations);
+}
+
+#[test]
+fn harness_keeps_samples() {
+  let summary = Harness::new().with_warmup(0).with_samples(3)
+                  .run(DeepLookups(1), Serial);
+
+  assert_eq!(summary.measurements.len(), 3);
+}
+
+#[test]
+fn summary_times_and_rate() {
+  let summary = Summary {
+    workload:     DeepLookups(1),
+    reactors:     Serial,
+    measurements: vec![
+      Measurement { realizations: 100, elapsed_ns: 3000 },
+      Measurement { realizations: 100, elapsed_ns: 1000 },
+      Measurement { realizations: 100, elapsed_ns: 2000 }
+    ]
+  };
+
+  assert_eq!(summary.times_ns(), Some((1000, 2000, 3000)));
+
+  let rate = summary.median_per_second().unwrap();
+
+  assert!((rate - 5e7).abs() < 1.0);
+
+  let report = format!("{}", summary);
+
+  assert!(report.as_slice()
+            .starts_with("deep-lookups/1       serial   time: ["));
+  assert!(report.as_slice().ends_with("thrpt: 50000000 realizations/s"));
+}
+
+#[test]
+fn summary_without_samples() {
+  let summary = Summary {
+    workload:     WideNamespace(1),
+    reactors:     Pool(2),
+    measurements: vec![]
+  };
+
+  assert_eq!(summary.times_ns(), None);
+  assert!(format!("{}", summary).as_slice().ends_with("no samples"));
+}
@@ -1,13 +1,22 @@
 //! Implements parsing and compilation of cPaws ('canonical paws').
+//!
+//! Anywhere a node could begin, `##` starts a comment that runs to the end of
+//! the line, and `#|` starts a block comment that runs to the matching `|#`.
+//! Block comments nest. A lone `#` is still an ordinary bare symbol, as is any
+//! `#` in the middle of one.
 
 use script::*;
 
+use object::{ObjectRef, NativeReceiver};
+use object::lookup_receiver;
+
 use nuketype::Execution;
 
 use machine::Machine;
 
 use std::str::Chars;
 use std::char::is_whitespace;
+use std::sync::Arc;
 
 #[cfg(test)]
 mod tests;
@@ -34,16 +43,79 @@ pub enum Node {
 /// Holds the state of the parser, including character iterator and position.
 struct ParserState<'r> {
   chars:    &'r mut Chars<'r>,
+  text:     &'r str,
   filename: &'r str,
   line:     int,
-  column:   int
+  column:   int,
+
+  /// The span of each node, in the order the nodes were started (depth-first,
+  /// pre-order). `None` if spans aren't being recorded.
+  spans:    Option<(Arc<String>, Vec<Span>)>
 }
 
 impl<'r> ParserState<'r> {
   /// Formats an error string based on the current parser state and puts it in
   /// a `Result` as an `Err`
+  ///
+  /// The first line of the message is `filename:line:column: message`; after
+  /// that comes the offending line of source and a caret under the column.
   fn error<T>(&self, message: String) -> Result<T, String> {
-    Err(format!("{}:{}:{}: {}", self.filename, self.line, self.column, message))
+    self.error_at(self.line, self.column, message)
+  }
+
+  /// Like `error()`, but for a position other than the current one.
+  fn error_at<T>(&self, line: int, column: int, message: String)
+                 -> Result<T, String> {
+    Err(format!("{}:{}:{}: {}\n{}",
+                self.filename, line, column, message,
+                self.excerpt(line, column)))
+  }
+
+  /// Produces the given line of the source, followed by a line with a caret
+  /// pointing at the given column.
+  ///
+  /// Characters before the column are replaced with as many spaces as they are
+  /// wide when displayed, except for tabs, which are kept so that the caret
+  /// lines up no matter how wide the terminal thinks a tab is.
+  fn excerpt(&self, line: int, column: int) -> String {
+    let source = self.text.lines_any().nth((line - 1) as uint).unwrap_or("");
+
+    let mut caret = String::new();
+
+    for c in source.chars().take((column - 1) as uint) {
+      match c {
+        '\t' => caret.push_char('\t'),
+
+        _ => for _ in range(0, c.width(false).unwrap_or(0)) {
+          caret.push_char(' ');
+        }
+      }
+    }
+
+    caret.push_char('^');
+
+    format!("{}\n{}", source, caret)
+  }
+
+  /// Returns the next character without consuming it.
+  fn peek(&self) -> Option<char> {
+    self.chars.clone().next()
+  }
+
+  /// Records the current position as the start of a new node.
+  fn start_node(&mut self) {
+    let (line, column) = (self.line as uint, self.column as uint);
+
+    match self.spans {
+      Some((ref filename, ref mut spans)) =>
+        spans.push(Span {
+          filename: filename.clone(),
+          line:     line,
+          column:   column
+        }),
+
+      None => ()
+    }
   }
 }
 
@@ -57,14 +129,109 @@ pub fn parse_nodes(text: &str, filename: &str) -> Result<Vec<Node>, String> {
 
   let mut state = ParserState {
     chars:    &mut chars,
+    text:     text,
     filename: filename,
     line:     1,
-    column:   1
+    column:   1,
+    spans:    None
   };
 
   parse_nodes_until(&mut state, None)
 }
 
+/// Like `parse_nodes()`, but also returns the span each node started at.
+///
+/// The spans are in the order that a depth-first, pre-order traversal of the
+/// nodes would visit them, which is the order `build_script_with_spans()`
+/// expects them in.
+pub fn parse_nodes_with_spans(text: &str, filename: &str)
+                              -> Result<(Vec<Node>, Vec<Span>), String> {
+  let mut chars = text.chars();
+
+  let mut state = ParserState {
+    chars:    &mut chars,
+    text:     text,
+    filename: filename,
+    line:     1,
+    column:   1,
+    spans:    Some((Arc::new(filename.to_string()), Vec::new()))
+  };
+
+  let nodes = try!(parse_nodes_until(&mut state, None));
+
+  let (_, spans) = state.spans.take().unwrap();
+
+  Ok((nodes, spans))
+}
+
+/// Checks whether `text` stops partway through an expression, execution,
+/// quoted symbol, or block comment, such that more input could make it parse.
+/// Text that can't parse no matter what follows (because of an unexpected
+/// terminator) isn't incomplete; `parse_nodes()` will report the error.
+pub fn is_incomplete(text: &str) -> bool {
+  let mut chars = text.chars();
+
+  // The terminators we're still waiting for, innermost last
+  let mut open = Vec::new();
+
+  // Whether a node (and so a comment) could begin at the next character
+  let mut boundary = true;
+
+  loop {
+    let c = match chars.next() {
+      None    => return !open.is_empty(),
+      Some(c) => c
+    };
+
+    match c {
+      '"' => if !chars.any(|c| c == '"') { return true },
+      '“' => if !chars.any(|c| c == '”') { return true },
+
+      '[' => open.push(']'),
+      '{' => open.push('}'),
+
+      ']' | '}' => if open.pop() != Some(c) { return false },
+
+      '”' => return false,
+
+      '#' if boundary && chars.clone().next() == Some('#') => {
+        if !chars.any(|c| c == '\n') { return !open.is_empty() }
+        continue;
+      },
+
+      '#' if boundary && chars.clone().next() == Some('|') => {
+        chars.next();
+
+        let mut depth = 1u;
+
+        while depth > 0 {
+          match chars.next() {
+            None => return true,
+
+            Some('|') if chars.clone().next() == Some('#') => {
+              chars.next();
+              depth -= 1;
+            },
+
+            Some('#') if chars.clone().next() == Some('|') => {
+              chars.next();
+              depth += 1;
+            },
+
+            Some(_) => ()
+          }
+        }
+
+        continue;
+      },
+
+      _ => ()
+    }
+
+    boundary = is_whitespace(c) || "[]{}\"“”;".contains_char(c);
+  }
+}
+
 /// Parses nodes until, if a terminator is given, the terminator appears, or if
 /// no terminator is given, the end of the `chars` iterator is reached.
 ///
@@ -74,6 +241,9 @@ pub fn parse_nodes(text: &str, filename: &str) -> Result<Vec<Node>, String> {
 fn parse_nodes_until(state: &mut ParserState, terminator: Option<char>)
                      -> Result<Vec<Node>, String> {
 
+  // Where the opening delimiter was, if there was one
+  let (start_line, start_column) = (state.line, state.column - 1);
+
   let mut nodes = Vec::new();
 
   loop {
@@ -83,7 +253,8 @@ fn parse_nodes_until(state: &mut ParserState, terminator: Option<char>)
         match terminator {
           // If we had a terminator we were expecting first, throw an error
           Some(c) =>
-            return state.error(format!("expected '{}' before end-of-input", c)),
+            return state.error_at(start_line, start_column, format!(
+              "expected '{}' before end-of-input", c)),
 
           // Else cleanly return
           None => break
@@ -104,29 +275,52 @@ fn parse_nodes_until(state: &mut ParserState, terminator: Option<char>)
       // Skip whitespace
       Some(c) if is_whitespace(c) => (),
 
+      // ## comment to end of line
+      Some('#') if state.peek() == Some('#') => skip_line_comment(state),
+
+      // #| block comment |#
+      Some('#') if state.peek() == Some('|') => {
+        state.column += 1;
+        try!(skip_block_comment(state))
+      },
+
       // Semicolon (discard)
-      Some(';') =>
-        nodes.push(Semicolon),
+      Some(';') => {
+        state.start_node();
+        nodes.push(Semicolon)
+      },
 
       // [expression]
-      Some('[') =>
+      Some('[') => {
+        state.start_node();
+        state.column += 1;
         nodes.push(Expression(
-          try!(parse_nodes_until(state, Some(']'))))),
+          try!(parse_nodes_until(state, Some(']')))))
+      },
 
       // {execution}
-      Some('{') =>
+      Some('{') => {
+        state.start_node();
+        state.column += 1;
         nodes.push(Execution(
-          try!(parse_nodes_until(state, Some('}'))))),
+          try!(parse_nodes_until(state, Some('}')))))
+      },
 
       // "symbol"
-      Some('"') =>
+      Some('"') => {
+        state.start_node();
+        state.column += 1;
         nodes.push(Symbol(
-          try!(parse_string_until(state, '"')))),
+          try!(parse_string_until(state, '"'))))
+      },
 
       // “symbol”
-      Some('“') =>
+      Some('“') => {
+        state.start_node();
+        state.column += 1;
         nodes.push(Symbol(
-          try!(parse_string_until(state, '”')))),
+          try!(parse_string_until(state, '”'))))
+      },
 
       // If we get any terminators that we *weren't* expecting, those are
       // errors.
@@ -134,9 +328,11 @@ fn parse_nodes_until(state: &mut ParserState, terminator: Option<char>)
         return state.error(format!("unexpected terminator '{}'", c)),
 
       // Any other character is the start of a bare symbol
-      Some(c) =>
+      Some(c) => {
+        state.start_node();
         nodes.push(Symbol(
           parse_bare_symbol(state, c)))
+      }
     }
 
     state.column += 1;
@@ -154,13 +350,16 @@ fn parse_nodes_until(state: &mut ParserState, terminator: Option<char>)
 fn parse_string_until(state: &mut ParserState, terminator: char)
                       -> Result<String, String> {
 
+  // Where the opening quote was
+  let (start_line, start_column) = (state.line, state.column - 1);
+
   let mut string = String::new();
 
   loop {
     match state.chars.next() {
       // End-of-input is always an error here, since the terminator is required
       None =>
-        return state.error(format!(
+        return state.error_at(start_line, start_column, format!(
           "expected '{}' before end-of-input", terminator)),
 
       // Return cleanly if we get our terminator
@@ -187,6 +386,76 @@ fn parse_string_until(state: &mut ParserState, terminator: char)
   Ok(string)
 }
 
+/// Skips the rest of a `##` comment, leaving the newline that ends it (if any)
+/// for `parse_nodes_until` to count.
+fn skip_line_comment(state: &mut ParserState) {
+  loop {
+    match state.peek() {
+      None | Some('\n') => break,
+
+      Some(_) => {
+        state.chars.next();
+        state.column += 1;
+      }
+    }
+  }
+}
+
+/// Skips a `#|` block comment, including any block comments nested within it,
+/// up to and including the `|#` that closes it.
+///
+/// # Returns
+///
+/// `Err(message)` if end-of-input was reached before the comment was closed;
+/// `Ok(())` otherwise.
+fn skip_block_comment(state: &mut ParserState) -> Result<(), String> {
+
+  // Where the opening '#' was
+  let (start_line, start_column) = (state.line, state.column - 1);
+
+  // The opening '|'
+  state.chars.next();
+  state.column += 1;
+
+  let mut depth = 1u;
+
+  loop {
+    match state.chars.next() {
+      None =>
+        return state.error_at(start_line, start_column, format!(
+          "expected '|#' before end-of-input")),
+
+      Some('\n') => {
+        state.line += 1;
+        state.column = 1;
+        continue;
+      },
+
+      Some('|') if state.peek() == Some('#') => {
+        state.chars.next();
+        state.column += 1;
+
+        depth -= 1;
+
+        if depth == 0 { break }
+      },
+
+      Some('#') if state.peek() == Some('|') => {
+        depth += 1;
+
+        state.chars.next();
+        state.column += 1;
+      },
+
+      Some(_) => ()
+    }
+
+    state.column += 1;
+  }
+
+  Ok(())
+}
+
 /// Like 'parse_string_until`, but specialized for the rules of a bare symbol
 /// without quotes.
 ///
@@ -225,56 +494,311 @@ fn parse_bare_symbol(state: &mut ParserState, first_char: char) -> String {
 
 /// Converts a slice of cPaws nodes into a Paws Script.
 pub fn build_script(machine: &Machine, nodes: &[Node]) -> Script {
-  Script({
+  let (script, _) = Compiler::new(machine, None, false, false).build(nodes);
+
+  script
+}
+
+/// Converts a slice of cPaws nodes into a Paws Script, as well as a table
+/// mapping its instructions to the given spans, as returned by
+/// `parse_nodes_with_spans()`.
+///
+/// Executions nested within the script are also given span tables.
+pub fn build_script_with_spans(machine: &Machine,
+                               nodes:   &[Node],
+                               spans:   &[Span])
+                               -> (Script, SpanTable) {
+  Compiler::new(machine, Some(spans), false, false).build(nodes)
+}
+
+/// Like `build_script_with_spans()`, but with common instruction sequences
+/// fused into superinstructions by `script::fuse()`, including in nested
+/// Executions.
+pub fn build_fused_script_with_spans(machine: &Machine,
+                                     nodes:   &[Node],
+                                     spans:   &[Span])
+                                     -> (Script, SpanTable) {
+  Compiler::new(machine, Some(spans), false, true).build(nodes)
+}
+
+/// Like `build_fused_script_with_spans()`, but with instructions that have no
+/// effect removed by `optimize_with_spans()` before fusing, including in nested
+/// Executions.
+pub fn build_optimized_script_with_spans(machine: &Machine,
+                                         nodes:   &[Node],
+                                         spans:   &[Span])
+                                         -> (Script, SpanTable) {
+  Compiler::new(machine, Some(spans), true, true).build(nodes)
+}
+
+/// Removes instructions that have no effect from a Script, and carries out
+/// lookups that can't change. See `optimize_with_spans()`.
+pub fn optimize(script: Script) -> Script {
+  let (script, _) = optimize_with_spans(script, SpanTable(vec![]));
+
+  script
+}
+
+/// Removes pushes that are immediately discarded, along with the `Discard`:
+///
+/// * `PushLocals, Discard`, `PushSelf, Discard` and `Push(x), Discard` go away
+///   entirely
+/// * `PushPair(key, value), Discard` becomes `Push(key)`
+///
+/// It also pre-resolves lookups of constant Symbols on frozen objects with
+/// the default receiver, which always get the same answer:
+///
+/// * `Push(object), Push(symbol), Combine` and `Push(object),
+///   LookupCombine(symbol)` become `Push(value)` if `symbol` is found in the
+///   frozen `object`
+///
+/// Since this is done as the instructions are read, a chain of pushes followed
+/// by as many `Discard`s goes away as a whole, as does the `Discard,
+/// PushLocals` left by each of several semicolons in a row, except the last.
+/// Likewise, a chain of lookups through frozen namespaces is resolved as far
+/// as it goes.
+///
+/// Scripts have no jumps, so this never changes what a Script does, only how
+/// many steps it takes. The span table is kept in step with the instructions.
+pub fn optimize_with_spans(script: Script, spans: SpanTable)
+                           -> (Script, SpanTable) {
+  let Script(instructions) = script;
+  let SpanTable(mut spans) = spans;
+
+  // Make sure there's exactly one span per instruction.
+  spans.truncate(instructions.len());
+
+  while spans.len() < instructions.len() {
+    spans.push(None);
+  }
+
+  let mut optimized_instructions = Vec::with_capacity(instructions.len());
+  let mut optimized_spans        = Vec::with_capacity(instructions.len());
+
+  for (instruction, span) in instructions.move_iter().zip(spans.move_iter()) {
+    if instruction == Discard {
+      // What's left of the push the Discard would drop, if it was one.
+      let remainder = match optimized_instructions.last() {
+        Some(&PushLocals) | Some(&PushSelf) | Some(&Push(_)) =>
+          Some(None),
+
+        Some(&PushPair(ref key, _)) =>
+          Some(Some(Push(key.clone()))),
+
+        _ => None
+      };
+
+      match remainder {
+        Some(remainder) => {
+          optimized_instructions.pop();
+
+          let push_span = optimized_spans.pop().unwrap();
+
+          match remainder {
+            Some(push) => {
+              optimized_instructions.push(push);
+              optimized_spans.push(push_span);
+            },
+
+            None => ()
+          }
+
+          continue;
+        },
+
+        None => ()
+      }
+    }
+
+    // How many pushes a lookup would consume, and what it would find.
+    let resolved = {
+      let len = optimized_instructions.len();
+
+      match instruction {
+        Combine if len >= 2 =>
+          match (&optimized_instructions[len - 2],
+                 &optimized_instructions[len - 1]) {
+            (&Push(ref subject), &Push(ref message)) =>
+              pre_resolve(subject, message).map(|value| (2u, value)),
+
+            _ => None
+          },
+
+        LookupCombine(ref message) =>
+          match optimized_instructions.last() {
+            Some(&Push(ref subject)) =>
+              pre_resolve(subject, message).map(|value| (1u, value)),
+
+            _ => None
+          },
+
+        _ => None
+      }
+    };
+
+    match resolved {
+      Some((pushes, value)) => {
+        for _ in range(0, pushes) {
+          optimized_instructions.pop();
+          optimized_spans.pop();
+        }
+
+        optimized_instructions.push(Push(value));
+        optimized_spans.push(span);
+
+        continue;
+      },
+
+      None => ()
+    }
+
+    optimized_instructions.push(instruction);
+    optimized_spans.push(span);
+  }
+
+  (Script(optimized_instructions), SpanTable(optimized_spans))
+}
+
+/// What combining `subject` with `message` would respond with, if that's sure
+/// not to change: `subject` is frozen, with the default receiver, and
+/// `message` is a Symbol it has a pair for.
+fn pre_resolve(subject: &ObjectRef, message: &ObjectRef) -> Option<ObjectRef> {
+  if !subject.is_frozen() { return None }
+
+  let symbol = match message.symbol_ref() {
+    Some(symbol) => symbol.clone(),
+    None         => return None
+  };
+
+  match subject.lock().meta().receiver {
+    NativeReceiver(function)
+      if function as *const () == lookup_receiver as *const () => (),
+
+    _ => return None
+  }
+
+  subject.frozen_lookup(&symbol)
+}
+
+/// Compiles nodes, keeping track of which span corresponds to the next node.
+struct Compiler<'a> {
+  machine:   &'a Machine,
+  spans:     Option<&'a [Span]>,
+  next_span: uint,
+  optimize:  bool,
+  fuse:      bool
+}
+
+impl<'a> Compiler<'a> {
+  fn new(machine:  &'a Machine,
+         spans:    Option<&'a [Span]>,
+         optimize: bool,
+         fuse:     bool)
+         -> Compiler<'a> {
+    Compiler {
+      machine:   machine,
+      spans:     spans,
+      next_span: 0,
+      optimize:  optimize,
+      fuse:      fuse
+    }
+  }
+
+  /// Takes the span for the next node in pre-order.
+  fn take_span(&mut self) -> Option<Span> {
+    let span = self.spans.and_then(|spans|
+      if self.next_span < spans.len() {
+        Some(spans[self.next_span].clone())
+      } else {
+        None
+      });
+
+    self.next_span += 1;
+
+    span
+  }
+
+  fn build(&mut self, nodes: &[Node]) -> (Script, SpanTable) {
     let mut instructions = vec![Discard, PushLocals]; // pristine
+    let mut spans        = vec![None, None];
 
     for node in nodes.iter() {
-      compile(machine, &mut instructions, node);
+      self.compile(&mut instructions, &mut spans, node);
     }
 
     debug!("build_script instructions: {}", instructions);
 
-    instructions
-  })
-}
-
-/// Compiles a `Node` into instructions and places them on a vector.
-fn compile(machine:      &Machine,
-           instructions: &mut Vec<Instruction>,
-           node:         &Node) {
-  match *node {
-    Symbol(ref string) => {
-      instructions.push(Push(machine.symbol(string.as_slice())));
-      instructions.push(Combine);
-    },
-
-    Expression(ref nodes) => {
-      if nodes.is_empty() {
-        // Empty expression special case = "self"
-        instructions.push(PushSelf);
-        instructions.push(Combine);
+    let (script, spans) =
+      if self.optimize {
+        optimize_with_spans(Script(instructions), SpanTable(spans))
       } else {
-        instructions.push(PushLocals);
+        (Script(instructions), SpanTable(spans))
+      };
 
-        for node in nodes.iter() {
-          compile(machine, instructions, node);
+    if self.fuse {
+      fuse(script, spans)
+    } else {
+      (script, spans)
+    }
+  }
+
+  /// Compiles a `Node` into instructions and places them on a vector, placing
+  /// the node's span alongside each instruction.
+  fn compile(&mut self,
+             instructions: &mut Vec<Instruction>,
+             spans:        &mut Vec<Option<Span>>,
+             node:         &Node) {
+
+    let span = self.take_span();
+
+    let emit = |instructions: &mut Vec<Instruction>,
+                spans:        &mut Vec<Option<Span>>,
+                instruction:  Instruction| {
+      instructions.push(instruction);
+      spans.push(span.clone());
+    };
+
+    match *node {
+      Symbol(ref string) => {
+        emit(instructions, spans, Push(self.machine.symbol(string.as_slice())));
+        emit(instructions, spans, Combine);
+      },
+
+      Expression(ref nodes) => {
+        if nodes.is_empty() {
+          // Empty expression special case = "self"
+          emit(instructions, spans, PushSelf);
+          emit(instructions, spans, Combine);
+        } else {
+          emit(instructions, spans, PushLocals);
+
+          for node in nodes.iter() {
+            self.compile(instructions, spans, node);
+          }
+
+          emit(instructions, spans, Combine);
         }
+      },
 
-        instructions.push(Combine);
-      }
-    },
+      Execution(ref nodes) => {
+        let (script, script_spans) = self.build(nodes.as_slice());
 
-    Execution(ref nodes) => {
-      let execution = Execution::create(
-        machine, build_script(machine, nodes.as_slice()));
+        let execution =
+          match self.spans {
+            Some(_) =>
+              Execution::create_with_spans(self.machine, script, script_spans),
+            None =>
+              Execution::create(self.machine, script)
+          };
 
-      instructions.push(Push(execution));
-      instructions.push(Combine);
-    },
+        emit(instructions, spans, Push(execution));
+        emit(instructions, spans, Combine);
+      },
 
-    Semicolon => {
-      instructions.push(Discard);
-      instructions.push(PushLocals);
+      Semicolon => {
+        emit(instructions, spans, Discard);
+        emit(instructions, spans, PushLocals);
+      }
     }
   }
 }
@@ -1,12 +1,16 @@
 use super::{parse_nodes, build_script};
+use super::{parse_nodes_with_spans, build_script_with_spans};
+use super::{build_fused_script_with_spans, is_incomplete};
+use super::{build_optimized_script_with_spans, optimize};
 use super::{Node, Symbol, Expression, Execution, Semicolon};
 
 use script::*;
 
 use machine::Machine;
-use object::ObjectRef;
+use object::{ObjectRef, Meta};
 
 use nuketype;
+use nuketype::Thing;
 
 fn test_parse_nodes(test_case: &str,
                     expected_result: Result<Vec<Node>, String>) {
@@ -82,23 +86,76 @@ fn parse_nodes_semicolon() {
   )
 }
 
+#[test]
+fn parse_nodes_nested_semicolons() {
+  test_parse_nodes(
+    "[a;b]{c;}",
+    Ok(vec![
+      Expression(vec![
+        Symbol("a".to_string()),
+        Semicolon,
+        Symbol("b".to_string())]),
+      Execution(vec![
+        Symbol("c".to_string()),
+        Semicolon])])
+  )
+}
+
+#[test]
+fn parse_nodes_comments() {
+  test_parse_nodes(
+    "a ## b [c\n{d #| e } #| f |# \n g |# h}",
+    Ok(vec![
+      Symbol("a".to_string()),
+      Execution(vec![
+        Symbol("d".to_string()),
+        Symbol("h".to_string())])])
+  );
+  test_parse_nodes(
+    "# a#b #[c]",
+    Ok(vec![
+      Symbol("#".to_string()),
+      Symbol("a#b".to_string()),
+      Symbol("#".to_string()),
+      Expression(vec![
+        Symbol("c".to_string())])])
+  );
+}
+
+#[test]
+fn parse_nodes_comment_positions() {
+  test_parse_nodes(
+    "a ## ] x\n ]",
+    Err("<test_case>:2:2: unexpected terminator ']'\n ]\n ^".to_string())
+  );
+  test_parse_nodes(
+    "#| x\n |# ]",
+    Err("<test_case>:2:5: unexpected terminator ']'\n |# ]\n    ^".to_string())
+  );
+  test_parse_nodes(
+    "a #| #| |#",
+    Err("<test_case>:1:3: expected '|#' before end-of-input\na #| #| |#\n  ^"
+          .to_string())
+  );
+}
+
 #[test]
 fn parse_nodes_missing_terminators() {
   test_parse_nodes(
     "\"",
-    Err("<test_case>:1:1: expected '\"' before end-of-input".to_string())
+    Err("<test_case>:1:1: expected '\"' before end-of-input\n\"\n^".to_string())
   );
   test_parse_nodes(
     "“",
-    Err("<test_case>:1:1: expected '”' before end-of-input".to_string())
+    Err("<test_case>:1:1: expected '”' before end-of-input\n“\n^".to_string())
   );
   test_parse_nodes(
     "[",
-    Err("<test_case>:1:1: expected ']' before end-of-input".to_string())
+    Err("<test_case>:1:1: expected ']' before end-of-input\n[\n^".to_string())
   );
   test_parse_nodes(
     "{",
-    Err("<test_case>:1:1: expected '}' before end-of-input".to_string())
+    Err("<test_case>:1:1: expected '}' before end-of-input\n{\n^".to_string())
   );
 }
 
@@ -106,21 +163,77 @@ fn parse_nodes_missing_terminators() {
 fn parse_nodes_unexpected_terminators() {
   test_parse_nodes(
     "”",
-    Err("<test_case>:1:1: unexpected terminator '”'".to_string())
+    Err("<test_case>:1:1: unexpected terminator '”'\n”\n^".to_string())
   );
   test_parse_nodes(
     "]",
-    Err("<test_case>:1:1: unexpected terminator ']'".to_string())
+    Err("<test_case>:1:1: unexpected terminator ']'\n]\n^".to_string())
   );
   test_parse_nodes(
     "}",
-    Err("<test_case>:1:1: unexpected terminator '}'".to_string())
+    Err("<test_case>:1:1: unexpected terminator '}'\n}\n^".to_string())
   );
 }
 
+#[test]
+fn parse_nodes_error_excerpt() {
+  test_parse_nodes(
+    "a b\n  c ]",
+    Err("<test_case>:2:5: unexpected terminator ']'\n  c ]\n    ^".to_string())
+  );
+  test_parse_nodes(
+    "\t“x” }",
+    Err("<test_case>:1:6: unexpected terminator '}'\n\t“x” }\n\t    ^"
+          .to_string())
+  );
+  test_parse_nodes(
+    "日本 ]",
+    Err("<test_case>:1:4: unexpected terminator ']'\n日本 ]\n     ^"
+          .to_string())
+  );
+  test_parse_nodes(
+    "a\n  [b\n c",
+    Err("<test_case>:2:3: expected ']' before end-of-input\n  [b\n  ^"
+          .to_string())
+  );
+}
+
+#[test]
+fn is_incomplete_while_delimiters_are_open() {
+  assert!(is_incomplete("a {b"));
+  assert!(is_incomplete("a [b {c}"));
+  assert!(is_incomplete("\"hello"));
+  assert!(is_incomplete("“hello\n"));
+  assert!(is_incomplete("{ \"}\" "));
+
+  assert!(!is_incomplete(""));
+  assert!(!is_incomplete("a {b} [c]"));
+  assert!(!is_incomplete("“[”"));
+}
+
+#[test]
+fn is_incomplete_with_comments() {
+  assert!(is_incomplete("a #| b"));
+  assert!(is_incomplete("#| #| |#"));
+  assert!(is_incomplete("[a ## ]"));
+
+  assert!(!is_incomplete("a ## [b"));
+  assert!(!is_incomplete("#| { |# a"));
+  assert!(!is_incomplete("a#| b"));
+}
+
+#[test]
+fn is_incomplete_not_for_errors() {
+  assert!(!is_incomplete("a ]"));
+  assert!(!is_incomplete("{a ] b"));
+  assert!(!is_incomplete("” {"));
+}
+
 enum InstructionExpectation<'a> {
   ExpectInstruction(Instruction),
   ExpectPushSymbol(&'a str),
+  ExpectLookupCombineSymbol(&'a str),
+  ExpectLookupLocalSymbol(&'a str),
   ExpectPush(|&ObjectRef|:'a)
 }
 
@@ -149,6 +262,26 @@ fn expect_instructions<'a>(
           _ =>
             fail!("expected Push symbol \"{}\", got {}", s, instruction)
         },
+      ExpectLookupCombineSymbol(s) =>
+        match *instruction {
+          LookupCombine(ref object)
+            if object.symbol_ref().expect("not a Symbol")
+                 .as_slice() == s => (),
+
+          _ =>
+            fail!("expected LookupCombine symbol \"{}\", got {}",
+                  s, instruction)
+        },
+      ExpectLookupLocalSymbol(s) =>
+        match *instruction {
+          LookupLocal(ref object)
+            if object.symbol_ref().expect("not a Symbol")
+                 .as_slice() == s => (),
+
+          _ =>
+            fail!("expected LookupLocal symbol \"{}\", got {}",
+                  s, instruction)
+        },
       ExpectPush(block) =>
         match *instruction {
           Push(ref object) =>
@@ -273,3 +406,196 @@ fn build_script_semicolons() {
       ExpectInstruction(Combine)
     ]);
 }
+
+#[test]
+fn parse_nodes_with_spans_records_node_starts() {
+  let (nodes, spans) =
+    parse_nodes_with_spans("a [b]\n  {c}", "<test_case>")
+      .ok().expect("parse failed");
+
+  assert_eq!(3, nodes.len());
+
+  let positions: Vec<(uint, uint)> =
+    spans.iter().map(|span| (span.line, span.column)).collect();
+
+  // a, [b], b, {c}, c
+  assert_eq!(vec![(1, 1), (1, 3), (1, 4), (2, 3), (2, 4)], positions);
+
+  assert!(spans.iter().all(|span|
+    span.filename.as_slice() == "<test_case>"));
+}
+
+#[test]
+fn build_script_with_spans_maps_instructions() {
+  let machine = Machine::new();
+
+  let (nodes, spans) =
+    parse_nodes_with_spans("hello world", "<test_case>")
+      .ok().expect("parse failed");
+
+  let (Script(instructions), table) =
+    build_script_with_spans(&machine, nodes.as_slice(), spans.as_slice());
+
+  let SpanTable(ref entries) = table;
+
+  assert_eq!(instructions.len(), entries.len());
+
+  // Discard, PushLocals
+  assert!(table.get(0).is_none());
+  assert!(table.get(1).is_none());
+
+  // hello
+  assert_eq!(Some((1, 1)), table.get(2).map(|s| (s.line, s.column)));
+  assert_eq!(Some((1, 1)), table.get(3).map(|s| (s.line, s.column)));
+
+  // world
+  assert_eq!(Some((1, 7)), table.get(4).map(|s| (s.line, s.column)));
+  assert_eq!(Some((1, 7)), table.get(5).map(|s| (s.line, s.column)));
+}
+
+#[test]
+fn build_fused_script_with_spans_fuses_nested_executions() {
+  let machine = Machine::new();
+
+  let (nodes, spans) =
+    parse_nodes_with_spans("a b {c}", "<test_case>")
+      .ok().expect("parse failed");
+
+  let (Script(instructions), SpanTable(entries)) =
+    build_fused_script_with_spans(&machine, nodes.as_slice(), spans.as_slice());
+
+  assert_eq!(instructions.len(), entries.len());
+
+  expect_instructions(
+    instructions.as_slice(),
+    vec![
+      ExpectInstruction(Discard),
+      ExpectLookupLocalSymbol("a"),
+      ExpectLookupCombineSymbol("b"),
+      ExpectPush(|o| {
+        let execution =
+          o.lock().try_cast::<nuketype::Execution>()
+            .ok().expect("expected Execution");
+
+        let Script(ref instructions) = *execution.deref().root();
+
+        expect_instructions(
+          instructions.as_slice(),
+          vec![
+            ExpectInstruction(Discard),
+            ExpectLookupLocalSymbol("c")
+          ]);
+      }),
+      ExpectInstruction(Combine)
+    ]);
+}
+
+#[test]
+fn optimize_removes_discarded_pushes() {
+  let machine = Machine::new();
+
+  let Script(instructions) = optimize(Script(vec![
+    Discard,
+    PushLocals,
+    Discard,
+    PushLocals,
+    Push(machine.symbol("a")),
+    Push(machine.symbol("b")),
+    Discard,
+    Discard,
+    PushSelf,
+    Discard,
+    PushPair(machine.symbol("c"), machine.symbol("d")),
+    Discard,
+    Combine]));
+
+  expect_instructions(
+    instructions.as_slice(),
+    vec![
+      ExpectInstruction(Discard),
+      ExpectInstruction(PushLocals),
+      ExpectPushSymbol("c"),
+      ExpectInstruction(Combine)
+    ]);
+}
+
+#[test]
+fn optimize_pre_resolves_frozen_lookups() {
+  let machine = Machine::new();
+
+  let value = machine.symbol("value");
+
+  let mut inner = Meta::new();
+
+  inner.members.push_pair(machine.symbol("b"), value.clone());
+
+  let inner = Thing::frozen(inner, "inner");
+
+  let mut outer = Meta::new();
+
+  outer.members.push_pair(machine.symbol("a"), inner);
+
+  let outer = Thing::frozen(outer, "outer");
+
+  // Not frozen, so it could have changed by the time it's looked up in.
+  let thawed = Thing::from_fn(|meta| {
+    meta.members.push_pair(machine.symbol("c"), value.clone())
+  });
+
+  let Script(instructions) = optimize(Script(vec![
+    Push(outer.clone()),
+    Push(machine.symbol("a")),
+    Combine,
+    LookupCombine(machine.symbol("b")),
+    Discard,
+    Push(outer.clone()),
+    LookupCombine(machine.symbol("missing")),
+    Push(thawed.clone()),
+    LookupCombine(machine.symbol("c"))]));
+
+  expect_instructions(
+    instructions.as_slice(),
+    vec![
+      ExpectPush(|o| assert!(*o == outer)),
+      ExpectLookupCombineSymbol("missing"),
+      ExpectPush(|o| assert!(*o == thawed)),
+      ExpectLookupCombineSymbol("c")
+    ]);
+}
+
+#[test]
+fn build_optimized_script_with_spans_optimizes_nested_executions() {
+  let machine = Machine::new();
+
+  let (nodes, spans) =
+    parse_nodes_with_spans("a {;;b}", "<test_case>")
+      .ok().expect("parse failed");
+
+  let (Script(instructions), SpanTable(entries)) =
+    build_optimized_script_with_spans(&machine, nodes.as_slice(),
+                                      spans.as_slice());
+
+  assert_eq!(instructions.len(), entries.len());
+
+  expect_instructions(
+    instructions.as_slice(),
+    vec![
+      ExpectInstruction(Discard),
+      ExpectLookupLocalSymbol("a"),
+      ExpectPush(|o| {
+        let execution =
+          o.lock().try_cast::<nuketype::Execution>()
+            .ok().expect("expected Execution");
+
+        let Script(ref instructions) = *execution.deref().root();
+
+        expect_instructions(
+          instructions.as_slice(),
+          vec![
+            ExpectInstruction(Discard),
+            ExpectLookupLocalSymbol("b")
+          ]);
+      }),
+      ExpectInstruction(Combine)
+    ]);
+}
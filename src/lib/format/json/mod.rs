@@ -0,0 +1,191 @@
+//! Converting trees of plain data objects to and from ordinary JSON, for
+//! exchanging data with the outside world. Unlike `util::serialize`, this
+//! doesn't try to preserve object graphs, only the data in them:
+//!
+//! * Symbols are strings.
+//! * Numbers are integers.
+//! * Things made of pairs with Symbol keys (like those built by
+//!   `Members::push_pair()`) are objects. When a key appears more than once,
+//!   the last pair wins, as with `Members::lookup_pair()`.
+//! * Any other Things are lists of their members, with holes as `null`. The
+//!   noughty (0th) member is never looked at.
+//!
+//! Going the other way, objects and lists become Things with child
+//! relationships to their pairs and members, and `true`, `false` and `null`
+//! become Symbols of the same names. Numbers that don't fit a Number become
+//! Symbols of their decimal representation. So data doesn't always come back
+//! the same: an empty object becomes an empty list, for instance.
+//!
+//! Objects of other nuketypes, and cycles, can't be converted to JSON.
+
+use object::{ObjectRef, Meta};
+use nuketype::{Thing, Number};
+use machine::Machine;
+
+use serialize::json;
+use serialize::json::Json;
+
+use std::any::AnyRefExt;
+use std::collections::{HashSet, TreeMap};
+
+#[cfg(test)]
+mod tests;
+
+/// Converts `root` and everything under it to a JSON string. Fails with a
+/// description of the problem if it contains something that can't be
+/// converted.
+pub fn to_json(root: &ObjectRef) -> Result<String, String> {
+  to_json_value(root).map(|value| value.to_string())
+}
+
+/// Like `to_json()`, but produces the JSON value itself.
+pub fn to_json_value(root: &ObjectRef) -> Result<Json, String> {
+  convert(root, &mut HashSet::new())
+}
+
+fn convert(object: &ObjectRef, visiting: &mut HashSet<ObjectRef>)
+           -> Result<Json, String> {
+
+  match object.symbol_ref() {
+    Some(string) => return Ok(json::String(string.as_slice().to_string())),
+    None         => ()
+  }
+
+  match Number::of(object) {
+    Some(value) => return Ok(json::I64(value)),
+    None        => ()
+  }
+
+  // Take what we need and let go, so that nothing stays locked while we look
+  // at the members.
+  let members: Vec<Option<ObjectRef>> = {
+    let guard = object.lock();
+
+    if !guard.nuketype().is::<Thing>() {
+      return Err(format!("{} can't be converted to JSON", object));
+    }
+
+    guard.meta().members.iter().skip(1)
+      .map(|member| member.as_ref().map(|r| r.to().clone()))
+      .collect()
+  };
+
+  if !visiting.insert(object.clone()) {
+    return Err(format!("{} contains itself", object));
+  }
+
+  let result = match pairs_of(members.as_slice()) {
+    Some(pairs) => {
+      let mut map = TreeMap::new();
+
+      for (key, value) in pairs.move_iter() {
+        map.insert(key, try!(convert(&value, visiting)));
+      }
+
+      Ok(json::Object(map))
+    },
+
+    None => {
+      let mut list = Vec::with_capacity(members.len());
+
+      for member in members.iter() {
+        list.push(match *member {
+          Some(ref member) => try!(convert(member, visiting)),
+          None             => json::Null
+        });
+      }
+
+      Ok(json::List(list))
+    }
+  };
+
+  visiting.remove(object);
+
+  result
+}
+
+/// If every one of `members` is a pair with a Symbol key, returns the keys and
+/// values in order.
+fn pairs_of(members: &[Option<ObjectRef>]) -> Option<Vec<(String, ObjectRef)>> {
+  if members.is_empty() { return None }
+
+  let mut pairs = Vec::with_capacity(members.len());
+
+  for member in members.iter() {
+    let pair = match *member {
+      Some(ref pair) => pair.lock(),
+      None           => return None
+    };
+
+    if !pair.nuketype().is::<Thing>() { return None }
+
+    let members = &pair.meta().members;
+
+    match (members.get(1), members.get(2), members.get(3)) {
+      (Some(key), Some(value), None) => match key.to().symbol_ref() {
+        Some(key) =>
+          pairs.push((key.as_slice().to_string(), value.to().clone())),
+
+        None => return None
+      },
+
+      _ => return None
+    }
+  }
+
+  Some(pairs)
+}
+
+/// Parses `input` as JSON and builds objects out of it within `machine`.
+/// Fails with a description of the problem if it isn't valid JSON.
+pub fn from_json(machine: &Machine, input: &str) -> Result<ObjectRef, String> {
+  match json::from_str(input) {
+    Ok(value) => Ok(from_json_value(machine, &value)),
+    Err(e)    => Err(format!("invalid JSON: {}", e))
+  }
+}
+
+/// Like `from_json()`, but from a JSON value that's already been parsed.
+pub fn from_json_value(machine: &Machine, value: &Json) -> ObjectRef {
+  match *value {
+    json::String(ref string) => machine.symbol(string.as_slice()),
+
+    json::I64(n) => Number::create(n),
+
+    json::U64(n) if n <= ::std::i64::MAX as u64 => Number::create(n as i64),
+
+    json::F64(n) if n.fract() == 0.0 &&
+                    n >= ::std::i64::MIN as f64 &&
+                    n <  ::std::i64::MAX as f64 => Number::create(n as i64),
+
+    json::U64(n) => machine.symbol(n.to_string().as_slice()),
+    json::F64(n) => machine.symbol(n.to_string().as_slice()),
+
+    json::Boolean(true)  => machine.symbol("true"),
+    json::Boolean(false) => machine.symbol("false"),
+    json::Null           => machine.symbol("null"),
+
+    json::List(ref list) => {
+      let mut meta = Meta::new();
+
+      meta.members.expand_to(1);
+
+      for value in list.iter() {
+        meta.members.push_child(from_json_value(machine, value));
+      }
+
+      Thing::create(meta)
+    },
+
+    json::Object(ref map) => {
+      let mut meta = Meta::new();
+
+      for (key, value) in map.iter() {
+        meta.members.push_pair_to_child(machine.symbol(key.as_slice()),
+                                        from_json_value(machine, value));
+      }
+
+      Thing::create(meta)
+    }
+  }
+}
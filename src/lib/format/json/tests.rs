@@ -0,0 +1,75 @@
+use super::{to_json, from_json};
+
+use object::Meta;
+
+use nuketype::{Thing, Number};
+
+use machine::Machine;
+
+#[test]
+fn pairs_become_objects() {
+  let machine = Machine::new();
+
+  let mut meta = Meta::new();
+
+  meta.members.push_pair(machine.symbol("name"),  machine.symbol("paws"));
+  meta.members.push_pair(machine.symbol("count"), Number::create(3));
+  meta.members.push_pair(machine.symbol("name"),  machine.symbol("nucleus"));
+
+  assert_eq!(to_json(&Thing::create(meta)),
+             Ok("{\"count\":3,\"name\":\"nucleus\"}".to_string()));
+}
+
+#[test]
+fn other_things_become_lists() {
+  let machine = Machine::new();
+
+  let mut meta = Meta::new();
+
+  meta.members.push(machine.symbol("a"));
+  meta.members.set(3, Thing::empty());
+
+  assert_eq!(to_json(&Thing::create(meta)),
+             Ok("[\"a\",null,[]]".to_string()));
+}
+
+#[test]
+fn cycles_are_refused() {
+  let thing = Thing::empty();
+
+  thing.lock().meta_mut().members.push(thing.clone());
+
+  assert!(to_json(&thing).is_err());
+}
+
+#[test]
+fn round_trip() {
+  let machine = Machine::new();
+
+  let input = "{\"list\":[1,\"two\",{\"three\":3}],\"name\":\"paws\"}";
+
+  let object = from_json(&machine, input).ok().expect("parse failed");
+
+  assert_eq!(to_json(&object), Ok(input.to_string()));
+
+  let list = object.lock().meta().members
+               .lookup_pair(&machine.symbol("list")).expect("list missing");
+
+  assert!(list.lock().meta().members.get(1).unwrap().is_child());
+}
+
+#[test]
+fn constants_and_fractions_become_symbols() {
+  let machine = Machine::new();
+
+  let object = from_json(&machine, "[true,null,1.5,2.0]")
+                 .ok().expect("parse failed");
+
+  assert_eq!(to_json(&object),
+             Ok("[\"true\",\"null\",\"1.5\",2]".to_string()));
+}
+
+#[test]
+fn invalid_json_is_an_error() {
+  assert!(from_json(&Machine::new(), "{").is_err());
+}
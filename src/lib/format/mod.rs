@@ -0,0 +1,3 @@
+//! Conversions between Paws objects and other data formats.
+
+pub mod json;
@@ -0,0 +1,630 @@
+//! A small line editor for the REPL, with history.
+//!
+//! When stdin is a terminal, it's put into raw mode while a line is being read,
+//! so that the editor can handle keys itself:
+//!
+//! * Left and right (or Ctrl-B and Ctrl-F), Home and End (or Ctrl-A and
+//!   Ctrl-E) move the cursor.
+//! * Backspace and Delete remove characters.
+//! * Up and down (or Ctrl-P and Ctrl-N) step through the history.
+//! * Ctrl-R searches backwards through the history for lines containing what's
+//!   typed after it. Enter runs the match; any other key edits it.
+//! * Ctrl-C abandons the line (see `Interrupted`), and Ctrl-D on an empty line
+//!   ends input.
+//!
+//! Raw mode is only supported on Linux. Elsewhere, or when stdin isn't a
+//! terminal (for example, when a file is piped in), lines are read as they
+//! are, without any editing.
+
+use term::Terminal;
+
+use std::io::{mod, IoResult, EndOfFile, File, Append, Write};
+use std::io::{BufferedReader, StdReader};
+
+#[cfg(test)]
+mod tests;
+
+/// The most history entries that `History::load()` keeps.
+pub static HISTORY_LIMIT: uint = 1000;
+
+/// What `Editor::read_line()` read.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum Input {
+  /// A line, without its line terminator.
+  Line(String),
+
+  /// Ctrl-C was pressed, abandoning the line, along with anything else the
+  /// caller had been collecting.
+  Interrupted,
+
+  /// The end of input.
+  Ended
+}
+
+/// Reads lines from stdin, with editing if possible.
+pub struct Editor {
+  input:   BufferedReader<StdReader>,
+  history: History
+}
+
+impl Editor {
+  /// Creates an editor reading from stdin, with the given history. The editor
+  /// doesn't add to the history itself; see `history_mut()`.
+  pub fn new(history: History) -> Editor {
+    Editor {
+      input:   io::stdin(),
+      history: history
+    }
+  }
+
+  /// The history that the up and down keys and Ctrl-R go through.
+  pub fn history<'a>(&'a self) -> &'a History {
+    &self.history
+  }
+
+  /// A mutable reference to the history, for adding lines to it.
+  pub fn history_mut<'a>(&'a mut self) -> &'a mut History {
+    &mut self.history
+  }
+
+  /// Reads a line. `prompt` is called to draw the prompt in front of it,
+  /// possibly more than once.
+  pub fn read_line<T: Writer>(&mut self,
+                              out:    &mut Terminal<T>,
+                              prompt: |&mut Terminal<T>| -> IoResult<()>)
+                              -> IoResult<Input> {
+
+    try!(prompt(out));
+    try!(out.flush());
+
+    match raw::enter() {
+      // Raw mode ends when `_raw_mode` is dropped.
+      Some(_raw_mode) => self.edit(out, prompt),
+      None            => self.read_plain()
+    }
+  }
+
+  fn read_plain(&mut self) -> IoResult<Input> {
+    match self.input.read_line() {
+      Ok(mut line) => {
+        if line.as_slice().ends_with("\n") {
+          line.pop_char();
+        }
+
+        Ok(Line(line))
+      },
+
+      Err(ref error) if error.kind == EndOfFile => Ok(Ended),
+
+      Err(error) => Err(error)
+    }
+  }
+
+  fn edit<T: Writer>(&mut self,
+                     out:    &mut Terminal<T>,
+                     prompt: |&mut Terminal<T>| -> IoResult<()>)
+                     -> IoResult<Input> {
+
+    let mut line = LineBuffer::new();
+
+    // Where we are in the history. `history.len()` is the line being edited,
+    // which is kept in `draft` while we look at older ones.
+    let mut position = self.history.len();
+    let mut draft    = String::new();
+
+    loop {
+      let key = match read_key(&mut self.input) {
+        Ok(key) => key,
+
+        Err(ref error) if error.kind == EndOfFile => EndOfInput,
+
+        Err(error) => return Err(error)
+      };
+
+      match key {
+        Enter => {
+          try!(out.write_str("\r\n"));
+          return Ok(Line(line.to_string()))
+        },
+
+        Interrupt => {
+          try!(out.write_str("^C\r\n"));
+          return Ok(Interrupted)
+        },
+
+        EndOfInput if line.is_empty() => {
+          try!(out.write_str("\r\n"));
+          return Ok(Ended)
+        },
+
+        EndOfInput => line.delete(),
+
+        Char(c)    => line.insert(c),
+        Backspace  => line.backspace(),
+        Delete     => line.delete(),
+        Left       => line.left(),
+        Right      => line.right(),
+        Home       => line.home(),
+        End        => line.end(),
+
+        Up => if position > 0 {
+          if position == self.history.len() {
+            draft = line.to_string();
+          }
+
+          position -= 1;
+
+          line = LineBuffer::from_str(self.history.get(position).unwrap());
+        },
+
+        Down => if position < self.history.len() {
+          position += 1;
+
+          line = match self.history.get(position) {
+            Some(entry) => LineBuffer::from_str(entry),
+            None        => LineBuffer::from_str(draft.as_slice())
+          };
+        },
+
+        Search => {
+          let (found, submit) = try!(self.search(out, &line));
+
+          line = found;
+
+          if submit {
+            try!(out.write_str("\r\x1b[K"));
+            try!(prompt(out));
+            try!(write!(out, "{}\r\n", line.to_string()));
+
+            return Ok(Line(line.to_string()))
+          }
+        },
+
+        Ignored => ()
+      }
+
+      // Redraw the whole line, and then put the cursor back where it belongs.
+      try!(out.write_str("\r\x1b[K"));
+      try!(prompt(out));
+      try!(out.write_str(line.to_string().as_slice()));
+
+      if line.after_cursor() > 0 {
+        try!(write!(out, "\x1b[{}D", line.after_cursor()));
+      }
+
+      try!(out.flush());
+    }
+  }
+
+  /// Searches backwards through the history as the user types. Returns the line
+  /// to continue with, and whether it should be run straight away.
+  fn search<T: Writer>(&mut self,
+                       out:      &mut Terminal<T>,
+                       original: &LineBuffer)
+                       -> IoResult<(LineBuffer, bool)> {
+
+    let mut query = String::new();
+    let mut found = None;
+
+    loop {
+      let matched = found.and_then(|index| self.history.get(index))
+                         .unwrap_or("").to_string();
+
+      try!(write!(out, "\r\x1b[K(reverse-i-search)`{}': {}",
+                  query, matched));
+      try!(out.flush());
+
+      let key = match read_key(&mut self.input) {
+        Ok(key) => key,
+
+        Err(ref error) if error.kind == EndOfFile => Interrupt,
+
+        Err(error) => return Err(error)
+      };
+
+      match key {
+        Char(c) => {
+          query.push_char(c);
+
+          // The current match may still match.
+          let before = found.map(|index| index + 1)
+                            .unwrap_or(self.history.len());
+
+          found = self.history.search_back(query.as_slice(), before);
+        },
+
+        Backspace => {
+          query.pop_char();
+
+          found = self.history.search_back(query.as_slice(),
+                                           self.history.len());
+        },
+
+        Search => {
+          let before = found.unwrap_or(self.history.len());
+
+          // Stay on the current match if there are no older ones.
+          match self.history.search_back(query.as_slice(), before) {
+            Some(index) => found = Some(index),
+            None        => ()
+          }
+        },
+
+        Interrupt =>
+          return Ok((original.clone(), false)),
+
+        Enter if found.is_some() =>
+          return Ok((LineBuffer::from_str(matched.as_slice()), true)),
+
+        _ =>
+          return Ok(match found {
+            Some(_) => (LineBuffer::from_str(matched.as_slice()), false),
+            None    => (original.clone(), false)
+          })
+      }
+    }
+  }
+}
+
+/// Whether stdin is a terminal that the editor can put into raw mode.
+pub fn is_terminal() -> bool {
+  raw::is_terminal()
+}
+
+/// Lines that have been entered before, oldest first, optionally kept in a
+/// file between sessions.
+pub struct History {
+  entries: Vec<String>,
+  file:    Option<Path>
+}
+
+impl History {
+  /// Creates an empty history that isn't saved anywhere.
+  pub fn new() -> History {
+    History {
+      entries: Vec::new(),
+      file:    None
+    }
+  }
+
+  /// Loads the most recent `HISTORY_LIMIT` entries from `path`, if it exists.
+  /// Entries pushed afterwards are appended to it.
+  pub fn load(path: Path) -> IoResult<History> {
+    let mut entries = Vec::new();
+
+    if path.exists() {
+      let contents = try!(File::open(&path).read_to_string());
+
+      for line in contents.as_slice().lines() {
+        if !line.is_empty() {
+          entries.push(decode(line));
+        }
+      }
+
+      if entries.len() > HISTORY_LIMIT {
+        let excess = entries.len() - HISTORY_LIMIT;
+
+        entries = entries.move_iter().skip(excess).collect();
+      }
+    }
+
+    Ok(History {
+      entries: entries,
+      file:    Some(path)
+    })
+  }
+
+  /// The number of entries.
+  pub fn len(&self) -> uint {
+    self.entries.len()
+  }
+
+  /// Gets the entry at `index`, where 0 is the oldest.
+  pub fn get<'a>(&'a self, index: uint) -> Option<&'a str> {
+    if index < self.entries.len() {
+      Some(self.entries[index].as_slice())
+    } else {
+      None
+    }
+  }
+
+  /// Adds an entry, unless it's empty or the same as the last one. The entry
+  /// may contain line breaks.
+  pub fn push(&mut self, entry: &str) -> IoResult<()> {
+    if entry.is_empty() ||
+       self.entries.last().map_or(false, |last| last.as_slice() == entry) {
+      return Ok(())
+    }
+
+    self.entries.push(entry.to_string());
+
+    if self.entries.len() > HISTORY_LIMIT {
+      self.entries.remove(0);
+    }
+
+    match self.file {
+      Some(ref path) => {
+        let mut file = try!(File::open_mode(path, Append, Write));
+
+        file.write_line(encode(entry).as_slice())
+      },
+
+      None => Ok(())
+    }
+  }
+
+  /// Finds the newest entry before `before` that contains `query`.
+  fn search_back(&self, query: &str, before: uint) -> Option<uint> {
+    if query.is_empty() { return None }
+
+    range(0, before).rev().find(|&index|
+      self.entries[index].as_slice().contains(query))
+  }
+}
+
+/// Escapes line breaks and backslashes, so that each entry takes one line in
+/// the history file.
+fn encode(entry: &str) -> String {
+  let mut encoded = String::with_capacity(entry.len());
+
+  for c in entry.chars() {
+    match c {
+      '\\' => encoded.push_str("\\\\"),
+      '\n' => encoded.push_str("\\n"),
+      c    => encoded.push_char(c)
+    }
+  }
+
+  encoded
+}
+
+/// Reverses `encode()`.
+fn decode(line: &str) -> String {
+  let mut decoded = String::with_capacity(line.len());
+  let mut chars   = line.chars();
+
+  loop {
+    match chars.next() {
+      Some('\\') =>
+        match chars.next() {
+          Some('n') => decoded.push_char('\n'),
+          Some(c)   => decoded.push_char(c),
+          None      => decoded.push_char('\\')
+        },
+
+      Some(c) => decoded.push_char(c),
+
+      None => return decoded
+    }
+  }
+}
+
+/// The line being edited, and where the cursor is within it.
+#[deriving(Clone, PartialEq, Show)]
+struct LineBuffer {
+  chars:  Vec<char>,
+  cursor: uint
+}
+
+impl LineBuffer {
+  fn new() -> LineBuffer {
+    LineBuffer { chars: Vec::new(), cursor: 0 }
+  }
+
+  /// Starts with `text`, with the cursor at the end.
+  fn from_str(text: &str) -> LineBuffer {
+    let chars: Vec<char> = text.chars().collect();
+
+    LineBuffer { cursor: chars.len(), chars: chars }
+  }
+
+  fn is_empty(&self) -> bool {
+    self.chars.is_empty()
+  }
+
+  /// How many characters there are after the cursor.
+  fn after_cursor(&self) -> uint {
+    self.chars.len() - self.cursor
+  }
+
+  fn insert(&mut self, c: char) {
+    self.chars.insert(self.cursor, c);
+    self.cursor += 1;
+  }
+
+  fn backspace(&mut self) {
+    if self.cursor > 0 {
+      self.cursor -= 1;
+      self.chars.remove(self.cursor);
+    }
+  }
+
+  fn delete(&mut self) {
+    if self.cursor < self.chars.len() {
+      self.chars.remove(self.cursor);
+    }
+  }
+
+  fn left(&mut self) {
+    if self.cursor > 0 { self.cursor -= 1 }
+  }
+
+  fn right(&mut self) {
+    if self.cursor < self.chars.len() { self.cursor += 1 }
+  }
+
+  fn home(&mut self) {
+    self.cursor = 0;
+  }
+
+  fn end(&mut self) {
+    self.cursor = self.chars.len();
+  }
+
+  fn to_string(&self) -> String {
+    String::from_chars(self.chars.as_slice())
+  }
+}
+
+/// What a key press (or escape sequence) means to the editor.
+#[deriving(Clone, PartialEq, Show)]
+enum Key {
+  Char(char),
+  Enter,
+  Backspace,
+  Delete,
+  Left,
+  Right,
+  Up,
+  Down,
+  Home,
+  End,
+  Search,
+  Interrupt,
+  EndOfInput,
+  Ignored
+}
+
+/// Reads a single key from a terminal in raw mode.
+fn read_key<B: Buffer>(input: &mut B) -> IoResult<Key> {
+  Ok(match try!(input.read_char()) {
+    '\r'   | '\n'   => Enter,
+    '\x7f' | '\x08' => Backspace,
+
+    '\x01' => Home,
+    '\x02' => Left,
+    '\x03' => Interrupt,
+    '\x04' => EndOfInput,
+    '\x05' => End,
+    '\x06' => Right,
+    '\x0e' => Down,
+    '\x10' => Up,
+    '\x12' => Search,
+
+    '\x1b' => try!(read_escape(input)),
+
+    c if c < ' ' => Ignored,
+
+    c => Char(c)
+  })
+}
+
+/// Reads the rest of an escape sequence, after the `ESC`.
+fn read_escape<B: Buffer>(input: &mut B) -> IoResult<Key> {
+  match try!(input.read_char()) {
+    '[' | 'O' => (),
+    _         => return Ok(Ignored)
+  }
+
+  let mut parameters = String::new();
+
+  loop {
+    match try!(input.read_char()) {
+      c @ '0'..'9' | c @ ';' => parameters.push_char(c),
+
+      terminator => return Ok(match (parameters.as_slice(), terminator) {
+        ("",  'A') => Up,
+        ("",  'B') => Down,
+        ("",  'C') => Right,
+        ("",  'D') => Left,
+        ("",  'H') => Home,
+        ("",  'F') => End,
+        ("1", '~') => Home,
+        ("7", '~') => Home,
+        ("4", '~') => End,
+        ("8", '~') => End,
+        ("3", '~') => Delete,
+        _          => Ignored
+      })
+    }
+  }
+}
+
+#[cfg(target_os = "linux")]
+mod raw {
+  use libc::{c_int, c_uint, c_uchar};
+
+  use std::mem;
+
+  #[repr(C)]
+  struct Termios {
+    c_iflag:  c_uint,
+    c_oflag:  c_uint,
+    c_cflag:  c_uint,
+    c_lflag:  c_uint,
+    c_line:   c_uchar,
+    c_cc:     [c_uchar, ..32],
+    c_ispeed: c_uint,
+    c_ospeed: c_uint
+  }
+
+  extern {
+    fn isatty(fd: c_int) -> c_int;
+    fn tcgetattr(fd: c_int, termios: *mut Termios) -> c_int;
+    fn tcsetattr(fd: c_int, action: c_int, termios: *const Termios) -> c_int;
+  }
+
+  static STDIN:     c_int  = 0;
+  static TCSADRAIN: c_int  = 1;
+
+  static ICRNL:     c_uint = 0o400;
+  static IXON:      c_uint = 0o2000;
+  static ISIG:      c_uint = 0o1;
+  static ICANON:    c_uint = 0o2;
+  static ECHO:      c_uint = 0o10;
+  static IEXTEN:    c_uint = 0o100000;
+
+  static VTIME:     uint   = 5;
+  static VMIN:      uint   = 6;
+
+  /// Restores the terminal's original settings when dropped.
+  pub struct RawMode {
+    original: Termios
+  }
+
+  impl Drop for RawMode {
+    fn drop(&mut self) {
+      unsafe { tcsetattr(STDIN, TCSADRAIN, &self.original); }
+    }
+  }
+
+  pub fn is_terminal() -> bool {
+    unsafe { isatty(STDIN) == 1 }
+  }
+
+  /// Puts stdin into raw mode, if it's a terminal.
+  pub fn enter() -> Option<RawMode> {
+    if !is_terminal() { return None }
+
+    unsafe {
+      let mut original: Termios = mem::zeroed();
+
+      if tcgetattr(STDIN, &mut original) != 0 { return None }
+
+      let mut raw = original;
+
+      raw.c_iflag &= !(ICRNL | IXON);
+      raw.c_lflag &= !(ISIG | ICANON | ECHO | IEXTEN);
+
+      raw.c_cc[VTIME] = 0;
+      raw.c_cc[VMIN]  = 1;
+
+      if tcsetattr(STDIN, TCSADRAIN, &raw) != 0 { return None }
+
+      Some(RawMode { original: original })
+    }
+  }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod raw {
+  pub struct RawMode;
+
+  pub fn is_terminal() -> bool {
+    false
+  }
+
+  pub fn enter() -> Option<RawMode> {
+    None
+  }
+}
@@ -0,0 +1,129 @@
+use super::{History, HISTORY_LIMIT, LineBuffer, encode, decode, read_key};
+use super::{Char, Enter, Backspace, Delete, Left, Right, Up, Down, Home, End};
+use super::{Search, Interrupt, EndOfInput, Ignored};
+
+use std::io::{MemReader, TempDir, File};
+
+#[test]
+fn line_buffer_editing() {
+  let mut line = LineBuffer::from_str("helo");
+
+  line.left();
+  line.insert('l');
+
+  assert_eq!("hello".to_string(), line.to_string());
+  assert_eq!(1, line.after_cursor());
+
+  line.home();
+  line.delete();
+  line.backspace();
+
+  assert_eq!("ello".to_string(), line.to_string());
+
+  line.end();
+  line.right();
+  line.backspace();
+
+  assert_eq!("ell".to_string(), line.to_string());
+  assert_eq!(0, line.after_cursor());
+}
+
+#[test]
+fn keys_and_escape_sequences() {
+  let mut input = MemReader::new(
+    "a→\r\x7f\x03\x04\x12\x01\x05\x1b[A\x1b[B\x1b[C\x1b[D\x1bOH\x1b[4~\x1b[3~\
+     \x1b[1;5C\x1b\x07".as_bytes().to_vec());
+
+  let expected = [Char('a'), Char('→'), Enter, Backspace, Interrupt,
+                  EndOfInput, Search, Home, End, Up, Down, Right, Left, Home,
+                  End, Delete, Ignored, Ignored];
+
+  for key in expected.iter() {
+    assert_eq!(*key, read_key(&mut input).unwrap());
+  }
+
+  assert!(read_key(&mut input).is_err());
+}
+
+#[test]
+fn history_skips_empty_and_repeated_entries() {
+  let mut history = History::new();
+
+  history.push("a").unwrap();
+  history.push("a").unwrap();
+  history.push("").unwrap();
+  history.push("b").unwrap();
+  history.push("a").unwrap();
+
+  assert_eq!(3, history.len());
+  assert_eq!(Some("a"), history.get(2));
+  assert_eq!(None,      history.get(3));
+}
+
+#[test]
+fn history_search_back() {
+  let mut history = History::new();
+
+  for entry in ["say hello", "say goodbye", "other", "say hello again"].iter() {
+    history.push(*entry).unwrap();
+  }
+
+  assert_eq!(Some(3), history.search_back("hello", 4));
+  assert_eq!(Some(0), history.search_back("hello", 3));
+  assert_eq!(None,    history.search_back("hello", 0));
+  assert_eq!(Some(1), history.search_back("bye", 4));
+  assert_eq!(None,    history.search_back("", 4));
+}
+
+#[test]
+fn history_persists_between_sessions() {
+  let dir  = TempDir::new("paws-editor").unwrap();
+  let path = dir.path().join("history");
+
+  {
+    let mut history = History::load(path.clone()).unwrap();
+
+    assert_eq!(0, history.len());
+
+    history.push("first").unwrap();
+    history.push("two\nlines \\ slash").unwrap();
+  }
+
+  let history = History::load(path.clone()).unwrap();
+
+  assert_eq!(2, history.len());
+  assert_eq!(Some("first"), history.get(0));
+  assert_eq!(Some("two\nlines \\ slash"), history.get(1));
+
+  assert_eq!(2, File::open(&path).read_to_string().unwrap()
+                  .as_slice().lines().count());
+}
+
+#[test]
+fn history_load_keeps_the_most_recent_entries() {
+  let dir  = TempDir::new("paws-editor").unwrap();
+  let path = dir.path().join("history");
+
+  {
+    let mut file = File::create(&path).unwrap();
+
+    for index in range(0, HISTORY_LIMIT + 5) {
+      file.write_line(index.to_string().as_slice()).unwrap();
+    }
+  }
+
+  let history = History::load(path).unwrap();
+
+  assert_eq!(HISTORY_LIMIT, history.len());
+  assert_eq!(Some("5"), history.get(0));
+}
+
+#[test]
+fn encode_decode_round_trip() {
+  for entry in ["plain", "a\nb", "back\\slash", "\\n literally", ""].iter() {
+    let encoded = encode(*entry);
+
+    assert!(!encoded.as_slice().contains("\n"));
+    assert_eq!(entry.to_string(), decode(encoded.as_slice()));
+  }
+}
@@ -5,7 +5,7 @@ use script::*;
 use cpaws;
 
 use machine::Machine;
-use machine::reactor::{Reactor, SerialReactor};
+use machine::reactor::{Reactor, SerialReactor, ReactorPool, ReactorStats};
 
 use object::{ObjectRef, TypedRefGuard};
 
@@ -13,8 +13,21 @@ use nuketype::{Alien, Execution};
 
 use term::{mod, Terminal};
 
+use time;
+
+use util::transfer;
+use util::serialize;
+use util::pretty::PrettyPrinter;
+
+use self::editor::{Editor, History, Line, Interrupted, Ended};
+
 use std::any::AnyRefExt;
-use std::io::{mod, IoResult};
+use std::io::{mod, IoResult, File};
+use std::os;
+use std::mem::replace;
+use std::uint;
+
+pub mod editor;
 
 /// Start a new REPL in the default environment. This consists of:
 ///
@@ -24,20 +37,112 @@ use std::io::{mod, IoResult};
 ///   * `paws::system` (`infrastructure` and `implementation`)
 pub fn start() {
   let machine  = Machine::new();
-  let template = Execution::create(&machine, Script(vec![]));
+  let template = default_template(&machine);
 
-  machine.expose_system_to(&template);
+  start_with(machine, Serial(proc(machine) SerialReactor::new(machine)),
+             template, Settings::new());
+}
+
+/// Like `start()`, but with a `ReactorPool` of `reactors` reactors instead of a
+/// serial reactor.
+pub fn start_parallel(reactors: uint) {
+  let machine  = Machine::new();
+  let template = default_template(&machine);
+
+  start_with(machine,
+             Parallel(proc(machine, count) ReactorPool::spawn(machine, count),
+                      reactors),
+             template, Settings::new());
+}
+
+/// How a REPL makes the reactors it evaluates entries with.
+///
+/// The procedure is only called for the first machine. Reactors for the
+/// machines made by `:reset` are made the default way, with
+/// `SerialReactor::new()` or `ReactorPool::spawn()`.
+pub enum ReactorKind {
+  /// A single `SerialReactor`, made by the given procedure.
+  Serial(proc (Machine): Send -> SerialReactor),
+
+  /// A `ReactorPool`, made by the given procedure with the given number of
+  /// reactors (as `ReactorPool::spawn()` would be called).
+  Parallel(proc (Machine, uint): Send -> ReactorPool, uint)
+}
+
+/// Options for how a REPL shows things.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct Settings {
+  /// How many levels of members to show below each result. Pairs are shown as
+  /// `key → value`, so at depth 1, a Thing of pairs shows its keys and the
+  /// references of their values.
+  pub preview_depth:   uint,
+
+  /// How many members to show of any one object in a result.
+  pub preview_members: uint
+}
+
+impl Settings {
+  /// Creates new `Settings` that show one level of up to 32 members.
+  pub fn new() -> Settings {
+    Settings {
+      preview_depth:   1,
+      preview_members: 32
+    }
+  }
+
+  /// Sets `preview_depth`.
+  pub fn with_preview_depth(self, preview_depth: uint) -> Settings {
+    Settings { preview_depth: preview_depth, ..self }
+  }
+
+  /// Sets `preview_members`.
+  pub fn with_preview_members(self, preview_members: uint) -> Settings {
+    Settings { preview_members: preview_members, ..self }
+  }
 
-  start_with(machine, proc(machine) SerialReactor::new(machine), template);
+  /// The `PrettyPrinter` that results are shown with.
+  fn printer(&self) -> PrettyPrinter {
+    PrettyPrinter::new()
+      .with_max_depth(self.preview_depth)
+      .with_max_members(self.preview_members)
+      .with_pairs(true)
+  }
 }
 
 /// Start a new REPL in a custom environment.
 ///
-/// Only serial reactors are supported at the moment. The `template`'s metadata
-/// is used to create the first Execution.
-pub fn start_with(    machine:      Machine,
-                      make_reactor: proc (Machine): Send -> SerialReactor,
-                  mut template:     ObjectRef) {
+/// The `template`'s metadata is used to create the first Execution. The
+/// reactors are made according to `reactors`, and made again (the default way)
+/// whenever the machine is reset (`:reset`). Either way, each entry is
+/// evaluated until the reactors stall, so a pool's reactors are all idle
+/// between entries.
+///
+/// Lines starting with `:` are REPL commands rather than cPaws:
+///
+/// * `:reset [name ...]` replaces the machine and locals with fresh ones,
+///   copying over the named locals (see `util::transfer::copy_graph()`).
+/// * `:export name file` writes the graph reachable from the named local to
+///   `file` as JSON (see `util::serialize::write_json()`).
+/// * `:import name file` reads a graph written by `:export` from `file`, and
+///   adds it to the locals as `name`.
+/// * `:inspect name [key ...]` shows the named local, or the value of a key
+///   within it (and so on), as a full tree without staging anything.
+/// * `:locals` lists the locals as `key → value`.
+/// * `:load file` parses `file` and stages it against the locals, like an
+///   entry whose result isn't shown.
+///
+/// An entry that stops partway through an expression, execution, or quoted
+/// symbol continues onto the next line (see `cpaws::is_incomplete()`).
+///
+/// Results that are Symbols are shown as they are; anything else is shown as a
+/// tree (see `util::pretty`) as deep as `settings` allows.
+///
+/// Lines are read through `interact::editor`. When stdin is a terminal, they
+/// can be edited, and are saved to `~/.paws_history` between sessions.
+pub fn start_with(machine:  Machine,
+                  reactors: ReactorKind,
+                  template: ObjectRef,
+                  settings: Settings) {
 
   let mut stdout = term::stdout().expect("failed to open stdout!");
 
@@ -54,6 +159,17 @@ pub fn start_with(    machine:      Machine,
     stdout.flush()
   }
 
+  fn continuation<T: Writer>(stdout: &mut Terminal<T>) -> IoResult<()> {
+
+    try!(stdout.fg(term::color::GREEN));
+
+    try!(stdout.write_str("   ⋮ ← "));
+
+    try!(stdout.reset());
+
+    stdout.flush()
+  }
+
   fn error<T: Writer>(message: &str, stdout: &mut Terminal<T>) -> IoResult<()> {
 
     try!(stdout.fg(term::color::RED));
@@ -65,41 +181,344 @@ pub fn start_with(    machine:      Machine,
     stdout.reset()
   }
 
-  let (reactor_tx, reactor_rx) = sync_channel(0);
+  let mut session = Session::new(machine, reactors, template);
 
-  let machine2 = machine.clone();
+  let mut editor = Editor::new(default_history());
 
-  spawn(proc() reactor_loop(make_reactor(machine2), reactor_rx));
+  // The lines of an entry that isn't complete yet
+  let mut pending = String::new();
 
-  prompt(line, stdout).unwrap();
+  loop {
+    let input = editor.read_line(stdout, |out| {
+      if pending.is_empty() { prompt(line, out) } else { continuation(out) }
+    });
 
-  for line_str in io::stdin().lines() {
-    let mut line_str = line_str.unwrap();
+    let line_str = match input {
+      Ok(Line(line_str)) => line_str,
 
-    line_str.pop_char(); // drop the \n
+      // Start over, with nothing left of an incomplete entry.
+      Ok(Interrupted) => {
+        pending = String::new();
+        continue
+      },
 
-    if !line_str.is_empty() {
-      match parse(&machine, line, line_str.as_slice()) {
-        Ok(execution) => {
-          template = ObjectRef::store_with_tag(
-            box execution, template.lock().meta().clone(),
-            format!("interact {:u}", line));
+      Ok(Ended) => break,
 
-          reactor_tx.send(Some(template.clone()));
-          reactor_tx.send(None); // wait for the reactor to be ready
-        },
+      Err(e) => fail!("failed to read input: {}", e)
+    };
+
+    if pending.is_empty() && line_str.as_slice().starts_with(":") {
+      save_history(&mut editor, line_str.as_slice(), stdout);
+
+      match session.command(line_str.as_slice().slice_from(1)) {
+        Ok(())       => (),
         Err(message) => error(message.as_slice(), stdout).unwrap()
       }
 
+      continue
+    }
+
+    if !pending.is_empty() {
+      pending.push_char('\n');
+    }
+
+    pending.push_str(line_str.as_slice());
+
+    // Keep reading lines until the entry can be parsed.
+    if cpaws::is_incomplete(pending.as_slice()) { continue }
+
+    let entry = replace(&mut pending, String::new());
+
+    save_history(&mut editor, entry.as_slice(), stdout);
+
+    if !entry.as_slice().trim().is_empty() {
+      match parse(&session.machine, line, entry.as_slice(), &settings) {
+        Ok(execution) =>
+          session.eval(execution, format!("interact {:u}", line)),
+
+        Err(message) =>
+          error(message.as_slice(), stdout).unwrap()
+      }
+
       line += 1
     }
+  }
+}
+
+/// Adds an entry to the editor's history, complaining if it can't be saved.
+fn save_history<T: Writer>(editor: &mut Editor, entry: &str,
+                           stdout: &mut Terminal<T>) {
+
+  match editor.history_mut().push(entry) {
+    Ok(()) => (),
+
+    Err(e) => {
+      stdout.fg(term::color::RED).unwrap();
+      (write!(stdout, "couldn't save history: {}\n\n", e)).unwrap();
+      stdout.reset().unwrap();
+    }
+  }
+}
+
+/// A history kept in `~/.paws_history` if stdin is a terminal, or one that
+/// isn't kept anywhere otherwise.
+fn default_history() -> History {
+  if !editor::is_terminal() { return History::new() }
+
+  match os::homedir() {
+    Some(home) =>
+      History::load(home.join(".paws_history")).unwrap_or(History::new()),
+
+    None =>
+      History::new()
+  }
+}
+
+/// Creates an empty Execution with the system interface exposed.
+fn default_template(machine: &Machine) -> ObjectRef {
+  let template = Execution::create(machine, Script(vec![]));
+
+  machine.expose_system_to(&template);
+
+  template
+}
+
+/// The state of a REPL, which `:reset` replaces.
+struct Session {
+  machine:    Machine,
+  template:   ObjectRef,
+  reactor_tx: SyncSender<Option<ObjectRef>>,
+
+  /// How many reactors to make for the next machine if it uses a pool, or
+  /// `None` for a serial reactor.
+  pool_size:  Option<uint>
+}
+
+impl Session {
+  fn new(machine:  Machine,
+         reactors: ReactorKind,
+         template: ObjectRef)
+         -> Session {
+
+    let pool_size = match reactors {
+      Serial(_)          => None,
+      Parallel(_, count) => Some(count)
+    };
+
+    Session {
+      reactor_tx: Session::spawn_reactor(machine.clone(), reactors),
+      machine:    machine,
+      template:   template,
+      pool_size:  pool_size
+    }
+  }
+
+  fn spawn_reactor(machine:  Machine,
+                   reactors: ReactorKind)
+                   -> SyncSender<Option<ObjectRef>> {
+
+    let (reactor_tx, reactor_rx) = sync_channel(0);
+
+    match reactors {
+      Serial(make_reactor) =>
+        spawn(proc() reactor_loop(make_reactor(machine), reactor_rx)),
+
+      Parallel(make_pool, count) =>
+        spawn(proc() pool_loop(make_pool(machine, count), reactor_rx))
+    }
+
+    reactor_tx
+  }
+
+  fn eval(&mut self, execution: Execution, tag: String) {
+    self.template = ObjectRef::store_with_tag(
+      box execution, self.template.lock().meta().clone(), tag);
+
+    self.reactor_tx.send(Some(self.template.clone()));
+    self.reactor_tx.send(None); // wait for the reactor to be ready
+  }
+
+  fn command(&mut self, command: &str) -> Result<(), String> {
+    let words: Vec<&str> = command.words().collect();
+
+    match words.as_slice() {
+      ["reset", ..names] =>
+        self.reset(names),
+
+      ["export", name, file] =>
+        self.export(name, file),
+
+      ["export", ..] =>
+        Err("usage: :export name file".to_string()),
+
+      ["import", name, file] =>
+        self.import(name, file),
+
+      ["import", ..] =>
+        Err("usage: :import name file".to_string()),
+
+      ["inspect", name, ..keys] =>
+        self.inspect(name, keys),
+
+      ["inspect"] =>
+        Err("usage: :inspect name [key ...]".to_string()),
+
+      ["locals"] =>
+        self.show_locals(),
+
+      ["load", file] =>
+        self.load(file),
+
+      ["load", ..] =>
+        Err("usage: :load file".to_string()),
+
+      _ =>
+        Err(format!("unknown command :{}", command))
+    }
+  }
+
+  /// The template's locals.
+  fn locals(&self) -> ObjectRef {
+    self.template.lock().meta().members
+      .lookup_pair(&self.machine.locals_sym)
+      .expect("Execution is missing locals!")
+  }
+
+  /// Looks up a name in the template's locals.
+  fn local(&self, name: &str) -> Result<ObjectRef, String> {
+    let locals = self.locals();
+
+    let value = locals.lock().meta().members
+                  .lookup_pair(&self.machine.symbol(name));
+
+    value.ok_or_else(|| format!("no local named {}", name))
+  }
+
+  fn reset(&mut self, preserve: &[&str]) -> Result<(), String> {
+    let mut values = Vec::new();
+
+    for &name in preserve.iter() {
+      values.push((name, try!(self.local(name))));
+    }
+
+    let machine  = Machine::new();
+    let template = default_template(&machine);
+
+    {
+      let locals_ref = template.lock().meta().members
+                         .lookup_pair(&machine.locals_sym)
+                         .expect("Execution is missing locals!");
+      let mut locals_obj = locals_ref.lock();
+      let     locals     = &mut locals_obj.meta_mut().members;
+
+      for &(name, ref value) in values.iter() {
+        locals.push_pair(machine.symbol(name),
+                         transfer::copy_graph(&self.machine, &machine, value));
+      }
+    }
+
+    let reactors = match self.pool_size {
+      None =>
+        Serial(proc(machine) SerialReactor::new(machine)),
+
+      Some(count) =>
+        Parallel(proc(machine, count) ReactorPool::spawn(machine, count), count)
+    };
+
+    // Dropping the old sender stops the old reactors once they're idle.
+    *self = Session::new(machine, reactors, template);
+
+    Ok(())
+  }
+
+  fn inspect(&self, name: &str, keys: &[&str]) -> Result<(), String> {
+    let mut value = try!(self.local(name));
+
+    for &key in keys.iter() {
+      let member = value.lock().meta().members
+                     .lookup_pair(&self.machine.symbol(key));
+
+      value = match member {
+        Some(member) => member,
+        None         => return Err(format!("{} has no key {}", value, key))
+      };
+    }
+
+    show(&PrettyPrinter::new().with_pairs(true), &value)
+  }
+
+  fn show_locals(&self) -> Result<(), String> {
+    let printer = PrettyPrinter::new()
+                    .with_max_depth(1)
+                    .with_max_members(uint::MAX)
+                    .with_pairs(true);
+
+    show(&printer, &self.locals())
+  }
+
+  fn load(&mut self, file: &str) -> Result<(), String> {
+    let source = try!(File::open(&Path::new(file)).read_to_string()
+                        .map_err(|e| format!("{}: {}", file, e)));
+
+    let (nodes, spans) =
+      try!(cpaws::parse_nodes_with_spans(source.as_slice(), file));
+
+    let (script, spans) =
+      cpaws::build_fused_script_with_spans(&self.machine, nodes.as_slice(),
+                                           spans.as_slice());
+
+    self.eval(Execution::with_spans(script, spans),
+              format!("interact {}", file));
 
-    prompt(line, stdout).unwrap();
+    Ok(())
   }
+
+  fn export(&self, name: &str, file: &str) -> Result<(), String> {
+    let value = try!(self.local(name));
+
+    let mut file = try!(File::create(&Path::new(file))
+                          .map_err(|e| e.to_string()));
+
+    serialize::write_json(&self.machine, &value, &mut file)
+      .map_err(|e| e.to_string())
+  }
+
+  fn import(&self, name: &str, file: &str) -> Result<(), String> {
+    let mut file = try!(File::open(&Path::new(file))
+                          .map_err(|e| e.to_string()));
+
+    let value = try!(serialize::read_json(&self.machine, &mut file));
+
+    let locals = self.locals();
+
+    locals.lock().meta_mut().members
+      .push_pair(self.machine.symbol(name), value);
+
+    Ok(())
+  }
+}
+
+/// Writes `object` to stdout with `printer`, followed by a blank line.
+fn show(printer: &PrettyPrinter, object: &ObjectRef) -> Result<(), String> {
+  let mut stdout = io::stdout();
+
+  printer.write(&mut stdout, object)
+    .and_then(|_| stdout.write_char('\n'))
+    .map_err(|e| e.to_string())
 }
 
 fn reactor_loop(mut reactor:  SerialReactor,
                     rx:       Receiver<Option<ObjectRef>>) {
+
+  // The start time and stats of the evaluation in progress, if any.
+  let mut evaluating: Option<(u64, ReactorStats)> = None;
+
+  let start = |reactor: &mut SerialReactor, execution: ObjectRef| {
+    reactor.stage(execution.clone(), execution);
+
+    Some((time::precise_time_ns(), reactor.stats()))
+  };
+
   loop {
     let mut break_after = 1000u;
 
@@ -110,7 +529,7 @@ fn reactor_loop(mut reactor:  SerialReactor,
     if reactor.step() {
       match rx.try_recv().ok() {
         Some(Some(execution)) =>
-          reactor.stage(execution.clone(), execution),
+          evaluating = start(&mut reactor, execution),
 
         _ => ()
       }
@@ -118,9 +537,16 @@ fn reactor_loop(mut reactor:  SerialReactor,
       reactor.stall();
 
       if !reactor.step() {
+        match evaluating.take() {
+          Some((start_ns, start_stats)) =>
+            report(time::precise_time_ns() - start_ns,
+                   &start_stats, &reactor.stats()).unwrap(),
+          None => ()
+        }
+
         match rx.recv_opt().ok() {
           Some(Some(execution)) =>
-            reactor.stage(execution.clone(), execution),
+            evaluating = start(&mut reactor, execution),
 
           Some(None) => (),
 
@@ -131,20 +557,93 @@ fn reactor_loop(mut reactor:  SerialReactor,
   }
 }
 
+/// Like `reactor_loop()`, but for a `ReactorPool`. Each execution is staged
+/// from within one of the pool's reactors, along with a stall handler that lets
+/// us know when the whole pool has run out of work, so that we don't take
+/// another one until then.
+fn pool_loop(mut pool: ReactorPool, rx: Receiver<Option<ObjectRef>>) {
+  loop {
+    match rx.recv_opt() {
+      Ok(Some(execution)) => {
+        let start_ns    = time::precise_time_ns();
+        let start_stats = pool.stats();
+
+        let (stall_tx, stall_rx) = channel();
+
+        // Both of these happen in the same message, so the pool can't stall
+        // before the handler is there to hear about it.
+        pool.on_reactor(proc (reactor) {
+          reactor.on_stall(proc (_) stall_tx.send(()));
+          reactor.stage(execution.clone(), execution);
+        });
+
+        stall_rx.recv();
+
+        report(time::precise_time_ns() - start_ns,
+               &start_stats, &pool.stats()).unwrap();
+      },
+
+      Ok(None) => (),
+
+      Err(_) => break
+    }
+  }
+
+  pool.stop();
+  pool.wait();
+}
+
+/// Shows how long an evaluation took to stall, how many stagings were
+/// realized in the meantime, and how often the cache was hit while doing so,
+/// going by the stats from before (`start`) and after (`end`).
+fn report(elapsed_ns: u64, start: &ReactorStats, end: &ReactorStats)
+          -> IoResult<()> {
+
+  let mut cache = end.cache.clone();
+
+  cache.sym_lookup_hits   -= start.cache.sym_lookup_hits;
+  cache.sym_lookup_misses -= start.cache.sym_lookup_misses;
+  cache.receiver_hits     -= start.cache.receiver_hits;
+  cache.receiver_misses   -= start.cache.receiver_misses;
+
+  let mut stdout = term::stdout().expect("failed to open stdout!");
+
+  try!(stdout.fg(term::color::BRIGHT_BLACK));
+
+  try!(write!(stdout,
+              "       ({:.3f} ms, {} stagings, lookups {}, receivers {})\n\n",
+              elapsed_ns as f64 / 1e6, end.steps - start.steps,
+              format_rate(cache.sym_lookup_hit_rate()),
+              format_rate(cache.receiver_hit_rate())));
+
+  stdout.reset()
+}
+
+fn format_rate(rate: Option<f64>) -> String {
+  match rate {
+    Some(rate) => format!("{:.1}% hit", rate * 100.0),
+    None       => "unused".to_string()
+  }
+}
+
 fn parse(machine:  &Machine,
          line:     u64,
-         line_str: &str)
+         line_str: &str,
+         settings: &Settings)
          -> Result<Execution, String> {
 
-  cpaws::parse_nodes(line_str, (format!("<interact {:u}>", line)).as_slice())
-    .map(|nodes| {
-      let Script(mut instructions) =
-        cpaws::build_script(machine, nodes.as_slice());
+  cpaws::parse_nodes_with_spans(line_str,
+                                (format!("<interact {:u}>", line)).as_slice())
+    .map(|(nodes, spans)| {
+      let (Script(mut instructions), SpanTable(mut spans)) =
+        cpaws::build_fused_script_with_spans(machine, nodes.as_slice(),
+                                             spans.as_slice());
 
       // Inject a little wrapper into the Script in order to print out the
       // result.
       //
-      // A normal pristine script looks like this:
+      // A normal pristine script looks like this (though the PushLocals may
+      // have been fused into a LookupLocal):
       //
       //     [Discard, PushLocals, ...]
       //
@@ -154,24 +653,27 @@ fn parse(machine:  &Machine,
 
       assert!(instructions[0] == Discard);
 
-      instructions.insert(1, Push(print(line)));
+      instructions.insert(1, Push(print(line, settings.printer())));
+      spans.insert(1, None);
 
       instructions.push(Combine);
+      spans.push(None);
 
-      Execution::new(Script(instructions))
+      Execution::with_spans(Script(instructions), SpanTable(spans))
     })
 }
 
-fn print(line: u64) -> ObjectRef {
+fn print(line: u64, printer: PrettyPrinter) -> ObjectRef {
   #[deriving(Clone)]
-  struct PrintData(u64);
+  struct PrintData(u64, PrettyPrinter);
 
   fn routine<'a>(
              mut alien: TypedRefGuard<'a, Alien>,
              _reactor:  &mut Reactor,
              response:  ObjectRef) {
 
-    let &PrintData(line) = alien.data.downcast_ref::<PrintData>().unwrap();
+    let &PrintData(line, ref printer) =
+      alien.data.downcast_ref::<PrintData>().unwrap();
 
     let mut stdout = term::stdout().expect("failed to open stdout!");
 
@@ -181,11 +683,17 @@ fn print(line: u64) -> ObjectRef {
 
     stdout.fg(term::color::WHITE).unwrap();
 
-    (write!(stdout, "{}\n\n", response)).unwrap();
+    let shown = match response.symbol_ref() {
+      Some(string) => string.as_slice().to_string(),
+      None         => printer.to_string(&response)
+    };
+
+    // Line the rest of the tree up under the first line.
+    (write!(stdout, "{}\n", shown.replace("\n", "\n       "))).unwrap();
 
     stdout.reset().unwrap();
   }
 
   Alien::create(format!("interact {} print", line),
-                routine, box PrintData(line))
+                routine, box PrintData(line, printer))
 }
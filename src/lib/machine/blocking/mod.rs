@@ -0,0 +1,79 @@
+//! A pool of tasks for work that would block a reactor, like file I/O.
+//!
+//! Jobs don't get a reactor, so the usual way to get a result back into Paws
+//! is to take a `Remote` (see `machine::reactor::remote`) before running the
+//! job, and stage through it when done.
+
+use std::sync::{Arc, Mutex};
+use std::task::TaskBuilder;
+
+#[cfg(test)]
+mod tests;
+
+/// A job to be run on the pool.
+pub type Job = proc(): Send;
+
+/// Runs jobs on a fixed number of tasks, which are only spawned once the first
+/// job comes in. Clones share the same tasks.
+///
+/// The tasks exit once every clone of the pool has been dropped and they've
+/// finished the jobs they were given.
+#[deriving(Clone)]
+pub struct BlockingPool {
+  size:   uint,
+  sender: Arc<Mutex<Option<Sender<Job>>>>
+}
+
+impl BlockingPool {
+  /// Creates a pool that will run up to `size` jobs at once.
+  pub fn new(size: uint) -> BlockingPool {
+    assert!(size > 0, "a BlockingPool needs at least one task");
+
+    BlockingPool {
+      size:   size,
+      sender: Arc::new(Mutex::new(None))
+    }
+  }
+
+  /// The number of jobs that can run at once.
+  pub fn size(&self) -> uint {
+    self.size
+  }
+
+  /// Queues `job` to be run on one of the pool's tasks, spawning them if this
+  /// is the first job.
+  pub fn run(&self, job: Job) {
+    let mut sender = self.sender.lock();
+
+    if sender.is_none() {
+      *sender = Some(self.spawn());
+    }
+
+    // The workers only exit once the sender is dropped, so this can't fail.
+    sender.as_ref().unwrap().send(job);
+  }
+
+  fn spawn(&self) -> Sender<Job> {
+    let (sender, receiver) = channel::<Job>();
+
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    for index in range(0, self.size) {
+      let receiver = receiver.clone();
+
+      TaskBuilder::new().named(format!("blocking #{}", index)).spawn(proc() {
+        loop {
+          // Only hold the lock while waiting, not while running the job.
+          let job = receiver.lock().recv_opt();
+
+          match job {
+            Ok(job) => job(),
+            Err(_)  => break
+          }
+        }
+      });
+    }
+
+    sender
+  }
+}
@@ -0,0 +1,62 @@
+use super::BlockingPool;
+
+use std::sync::{Arc, Barrier};
+
+#[test]
+fn runs_jobs() {
+  let pool = BlockingPool::new(2);
+
+  let (tx, rx) = channel();
+
+  for n in range(0u, 10) {
+    let tx = tx.clone();
+
+    pool.run(proc() tx.send(n));
+  }
+
+  let mut results: Vec<uint> = range(0u, 10).map(|_| rx.recv()).collect();
+
+  results.sort();
+
+  assert_eq!(range(0u, 10).collect::<Vec<uint>>(), results);
+}
+
+#[test]
+fn runs_jobs_concurrently() {
+  let pool    = BlockingPool::new(3);
+  let barrier = Arc::new(Barrier::new(3));
+
+  let (tx, rx) = channel();
+
+  // None of these can finish unless all three are running at once.
+  for _ in range(0u, 3) {
+    let barrier = barrier.clone();
+    let tx      = tx.clone();
+
+    pool.run(proc() {
+      barrier.wait();
+      tx.send(());
+    });
+  }
+
+  for _ in range(0u, 3) {
+    rx.recv();
+  }
+}
+
+#[test]
+fn clones_share_tasks() {
+  let pool  = BlockingPool::new(1);
+  let clone = pool.clone();
+
+  let (tx, rx) = channel();
+
+  pool.run(proc() tx.send(::std::task::name()));
+
+  let (tx2, rx2) = channel();
+
+  clone.run(proc() tx2.send(::std::task::name()));
+
+  assert_eq!(Some("blocking #0".to_string()), rx.recv());
+  assert_eq!(Some("blocking #0".to_string()), rx2.recv());
+}
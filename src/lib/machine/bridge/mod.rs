@@ -0,0 +1,275 @@
+//! Connecting Machines in the same process, so that they can stage work on
+//! each other.
+//!
+//! Objects can't be shared between Machines (see `util::transfer`), so what
+//! passes over a bridge is a message of plain data: Symbols, Numbers, and
+//! Things made of them, converted as by `format::json`. Each Machine has a
+//! single `Bridges`, which knows its peers by name and has an entry Execution
+//! (or Alien) for messages coming in. Once it's listening, each message that
+//! arrives is rebuilt within the Machine and a clone of the entry is staged
+//! with it, much like `infrastructure execution branch`.
+//!
+//! A Machine can also advertise other entries by name. Messages for those
+//! come from other processes, through `machine::remote`, which passes them to
+//! `post()`.
+//!
+//! From Paws, `implementation remote stage` sends messages, and
+//! `implementation remote entry` sets the entry and starts listening.
+//!
+//! A listening Machine counts the next message as outstanding work (see
+//! `machine::reactor::remote`), so it won't stall while waiting for one. Call
+//! `close()` to stop.
+
+use object::{ObjectRef, TypedRefGuard};
+
+use nuketype::Alien;
+
+use machine::{Machine, Reactor};
+
+use format::json::{to_json_value, from_json_value};
+
+use util::clone;
+
+use serialize::json::Json;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::task::TaskBuilder;
+
+#[cfg(test)]
+mod tests;
+
+/// What goes through a Machine's inbox.
+enum Letter {
+  /// A message from a peer, for the advertised entry of that name, or for the
+  /// entry if there's no name.
+  Message(Option<String>, Json),
+
+  /// Sent by `close()` to the Machine's own inbox, to stop listening.
+  Closed
+}
+
+/// A Machine's connections to its peers. Clones share the same state. See the
+/// module documentation.
+#[deriving(Clone)]
+pub struct Bridges {
+  state: Arc<Mutex<State>>,
+  inbox: Arc<Mutex<Receiver<Letter>>>
+}
+
+struct State {
+  /// Given to peers, so that they can send to us.
+  inbox_tx:  Sender<Letter>,
+
+  /// Our peers' inboxes, by the names we know them by.
+  peers:     HashMap<String, Sender<Letter>>,
+
+  /// What to stage with incoming messages.
+  entry:     Option<ObjectRef>,
+
+  /// What to stage with messages posted for a name.
+  entries:   HashMap<String, ObjectRef>,
+
+  /// Whether we're waiting on the inbox.
+  listening: bool
+}
+
+/// Connects `a` and `b`, so that `a` knows `b` as `b_name`, and `b` knows `a`
+/// as `a_name`. Replaces any peers they already had by those names.
+pub fn connect(a: &Machine, a_name: &str, b: &Machine, b_name: &str) {
+  a.bridges.add_peer(b_name, &b.bridges);
+  b.bridges.add_peer(a_name, &a.bridges);
+}
+
+impl Bridges {
+  /// Creates a `Bridges` with no peers and no entry.
+  pub fn new() -> Bridges {
+    let (inbox_tx, inbox_rx) = channel();
+
+    Bridges {
+      state: Arc::new(Mutex::new(State {
+        inbox_tx:  inbox_tx,
+        peers:     HashMap::new(),
+        entry:     None,
+        entries:   HashMap::new(),
+        listening: false
+      })),
+      inbox: Arc::new(Mutex::new(inbox_rx))
+    }
+  }
+
+  /// Lets messages be sent to `peer` as `name`. Only one way; see
+  /// `connect()`.
+  pub fn add_peer(&self, name: &str, peer: &Bridges) {
+    let peer_inbox = peer.state.lock().inbox_tx.clone();
+
+    self.state.lock().peers.insert(name.to_string(), peer_inbox);
+  }
+
+  /// The names of the peers that messages can be sent to.
+  pub fn peers(&self) -> Vec<String> {
+    self.state.lock().peers.keys().map(|name| name.clone()).collect()
+  }
+
+  /// Converts `message` and sends it to the peer known as `peer`. Fails with
+  /// a description of the problem if there's no such peer, if it has gone
+  /// away, or if the message isn't plain data.
+  pub fn send(&self, peer: &str, message: &ObjectRef) -> Result<(), String> {
+    let json = try!(to_json_value(message));
+
+    let state = self.state.lock();
+
+    match state.peers.find_equiv(&peer) {
+      Some(sender) =>
+        sender.send_opt(Message(None, json))
+          .map_err(|_| format!("peer {} has gone away", peer)),
+
+      None =>
+        Err(format!("no peer named {}", peer))
+    }
+  }
+
+  /// Sets what to stage with incoming messages, replacing any earlier entry.
+  /// It's cloned for each message, so it should be an Execution or an Alien.
+  pub fn set_entry(&self, entry: ObjectRef) {
+    self.state.lock().entry = Some(entry);
+  }
+
+  /// What's staged with incoming messages, if anything.
+  pub fn entry(&self) -> Option<ObjectRef> {
+    self.state.lock().entry.clone()
+  }
+
+  /// Sets what to stage with messages posted for `name`, replacing any earlier
+  /// entry by that name. Like the entry, it's cloned for each message.
+  pub fn advertise(&self, name: &str, entry: ObjectRef) {
+    self.state.lock().entries.insert(name.to_string(), entry);
+  }
+
+  /// The names of the advertised entries, in order.
+  pub fn advertised(&self) -> Vec<String> {
+    let mut names: Vec<String> =
+      self.state.lock().entries.keys().map(|name| name.clone()).collect();
+
+    names.sort();
+    names
+  }
+
+  /// What's staged with messages posted for `name`, if anything.
+  pub fn advertised_entry(&self, name: &str) -> Option<ObjectRef> {
+    self.state.lock().entries.find_equiv(&name).map(|entry| entry.clone())
+  }
+
+  /// Queues a message for the entry advertised as `entry`, as though a peer
+  /// had sent it. Fails if nothing is advertised by that name.
+  pub fn post(&self, entry: &str, message: Json) -> Result<(), String> {
+    let state = self.state.lock();
+
+    if state.entries.find_equiv(&entry).is_none() {
+      return Err(format!("no entry named {}", entry))
+    }
+
+    // We hold on to the receiving end, so this can't fail.
+    state.inbox_tx.send(Message(Some(entry.to_string()), message));
+    Ok(())
+  }
+
+  /// Whether incoming messages are being waited on.
+  pub fn is_listening(&self) -> bool {
+    self.state.lock().listening
+  }
+
+  /// Starts staging the entry with incoming messages on `reactor`'s Machine,
+  /// if that isn't happening already. Messages that arrived before now are
+  /// staged first.
+  pub fn listen(&self, reactor: &mut Reactor) {
+    {
+      let mut state = self.state.lock();
+
+      if state.listening { return }
+
+      state.listening = true;
+    }
+
+    self.wait_for_next(reactor);
+  }
+
+  /// Forgets every peer, and stops listening. Peers that know this Machine
+  /// can still send to it, but nothing will be staged until `listen()` is
+  /// called again.
+  pub fn close(&self) {
+    let mut state = self.state.lock();
+
+    state.peers.clear();
+
+    if state.listening {
+      // There's a task waiting on the inbox.
+      let _ = state.inbox_tx.send_opt(Closed);
+    }
+  }
+
+  /// Waits on a task of its own for the next letter, and stages a relay with
+  /// it if it's a message. The relay stages the entry and waits again.
+  fn wait_for_next(&self, reactor: &mut Reactor) {
+    let remote  = reactor.remote();
+    let machine = reactor.machine().clone();
+    let bridges = self.clone();
+
+    TaskBuilder::new().named("bridge").spawn(proc() {
+      let letter = bridges.inbox.lock().recv_opt();
+
+      match letter {
+        Ok(Message(name, json)) => {
+          let message  = from_json_value(&machine, &json);
+          let relay_to = Relay { bridges: bridges, entry: name };
+
+          remote.stage(Alien::create("bridge relay", relay, box relay_to),
+                       message)
+        },
+
+        // Dropping the remote lets the reactor know not to wait for it.
+        _ => bridges.state.lock().listening = false
+      }
+    });
+  }
+}
+
+/// Data for a relay: where the message came in, and the name of the entry it
+/// was sent to, if any.
+#[deriving(Clone)]
+struct Relay {
+  bridges: Bridges,
+  entry:   Option<String>
+}
+
+/// Stages a clone of the entry with a message that just arrived, and waits for
+/// the next one.
+fn relay<'a>(alien:    TypedRefGuard<'a, Alien>,
+             reactor:  &mut Reactor,
+             message:  ObjectRef) {
+
+  let Relay { bridges, entry } = match alien.data.downcast_ref::<Relay>() {
+    Some(relay) => relay.clone(),
+    None        => fail!("relay called on a non-relay Alien!")
+  };
+
+  drop(alien);
+
+  let entry = match entry {
+    Some(name) => bridges.advertised_entry(name.as_slice()),
+    None       => bridges.entry()
+  };
+
+  let clone = entry
+                .and_then(|entry| clone::stageable(&entry, reactor.machine()));
+
+  match clone {
+    Some(clone) => reactor.stage(clone, message),
+
+    None =>
+      machine_warn!(reactor.machine(), "bridge",
+                    "dropped message {}: no entry to stage", message)
+  }
+
+  bridges.wait_for_next(reactor);
+}
@@ -0,0 +1,189 @@
+use super::{Bridges, connect};
+
+use object::{ObjectRef, Meta, TypedRefGuard};
+
+use nuketype::{Thing, Alien};
+
+use machine::{Machine, Reactor};
+use machine::reactor::{MockReactor, SerialReactor};
+
+use format::json::to_json;
+
+use serialize::json;
+
+use util;
+
+use std::sync::{Arc, Mutex};
+
+/// Data for an Alien that sends the JSON of whatever it's realized with, and
+/// then closes its Machine's bridges.
+#[deriving(Clone)]
+struct Report(Arc<Mutex<Sender<String>>>);
+
+fn report<'a>(alien:    TypedRefGuard<'a, Alien>,
+              reactor:  &mut Reactor,
+              response: ObjectRef) {
+
+  match alien.data.downcast_ref::<Report>() {
+    Some(&Report(ref tx)) => tx.lock().send(to_json(&response).unwrap()),
+    None                  => fail!("wrong data")
+  }
+
+  reactor.machine().bridges.close();
+}
+
+#[test]
+fn send_needs_a_peer() {
+  let machine = Machine::new();
+
+  assert!(machine.bridges.send("nobody", &machine.symbol("hi")).is_err());
+}
+
+#[test]
+fn send_needs_plain_data() {
+  let a = Machine::new();
+  let b = Machine::new();
+
+  connect(&a, "a", &b, "b");
+
+  assert_eq!(vec!["b".to_string()], a.bridges.peers());
+
+  let (tx, _rx) = channel();
+
+  let alien = Alien::create("report", report,
+                            box Report(Arc::new(Mutex::new(tx))));
+
+  assert!(a.bridges.send("b", &alien).is_err());
+  assert!(a.bridges.send("b", &a.symbol("hi")).is_ok());
+}
+
+#[test]
+fn messages_stage_the_entry_on_the_peer() {
+  util::timeout(1000, proc() {
+    let a = Machine::new();
+    let b = Machine::new();
+
+    connect(&a, "a", &b, "b");
+
+    let (tx, rx) = channel();
+
+    b.bridges.set_entry(Alien::create("report", report,
+                                      box Report(Arc::new(Mutex::new(tx)))));
+
+    let mut reactor = SerialReactor::new(b.clone());
+
+    reactor.on_stall(proc (reactor) reactor.stop());
+
+    b.bridges.listen(&mut reactor);
+
+    let mut meta = Meta::new();
+
+    meta.members.push_pair(a.symbol("hello"), a.symbol("world"));
+
+    a.bridges.send("b", &Thing::create(meta)).unwrap();
+
+    // Ends once the entry closes the bridges.
+    reactor.run();
+
+    assert_eq!(rx.recv().as_slice(), r#"{"hello":"world"}"#);
+    assert!(!b.bridges.is_listening());
+  })
+}
+
+#[test]
+fn messages_wait_for_listen() {
+  let a = Machine::new();
+  let b = Machine::new();
+
+  connect(&a, "a", &b, "b");
+
+  a.bridges.send("b", &a.symbol("early")).unwrap();
+
+  let mut reactor = MockReactor::new(b.clone());
+
+  b.bridges.listen(&mut reactor);
+
+  assert!(reactor.receive_remote());
+
+  let (_, message) = reactor.stagings.remove(0).unwrap();
+
+  assert!(message.eq_as_symbol(&b.symbol("early")));
+}
+
+#[test]
+fn close_stops_listening() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  machine.bridges.listen(&mut reactor);
+
+  assert!(machine.bridges.is_listening());
+
+  machine.bridges.close();
+
+  // The remote is dropped without staging anything.
+  assert!(!reactor.receive_remote());
+  assert_eq!(0, reactor.outstanding);
+}
+
+#[test]
+fn relay_without_entry_keeps_listening() {
+  let a = Machine::new();
+  let b = Machine::new();
+
+  connect(&a, "a", &b, "b");
+
+  let mut reactor = MockReactor::new(b.clone());
+
+  b.bridges.listen(&mut reactor);
+
+  a.bridges.send("b", &a.symbol("lost")).unwrap();
+
+  assert!(reactor.receive_remote());
+  assert_eq!(1, reactor.run(1));
+
+  assert!(reactor.stagings.is_empty());
+  assert_eq!(1, reactor.outstanding);
+
+  b.bridges.close();
+
+  assert!(!reactor.receive_remote());
+}
+
+#[test]
+fn post_needs_an_advertised_entry() {
+  let machine = Machine::new();
+
+  assert!(machine.bridges.post("job", json::String("hi".to_string()))
+            .is_err());
+
+  machine.bridges.advertise("job", Thing::empty());
+
+  assert_eq!(vec!["job".to_string()], machine.bridges.advertised());
+  assert!(machine.bridges.post("job", json::String("hi".to_string()))
+            .is_ok());
+}
+
+#[test]
+fn posted_messages_stage_the_advertised_entry() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let (tx, rx) = channel();
+
+  let job = Alien::create("report", report,
+                          box Report(Arc::new(Mutex::new(tx))));
+
+  machine.bridges.set_entry(Thing::empty());
+  machine.bridges.advertise("job", job);
+
+  machine.bridges.post("job", json::String("hi".to_string())).unwrap();
+  machine.bridges.listen(&mut reactor);
+
+  assert!(reactor.receive_remote());
+
+  // The relay, then the clone of `job` it stages.
+  assert_eq!(2, reactor.run(2));
+
+  assert_eq!(rx.recv().as_slice(), r#""hi""#);
+}
@@ -0,0 +1,78 @@
+//! Notifying interested parties when an Execution completes.
+//!
+//! An Execution is complete once it has evaluated its last combination and is
+//! staged again, with that combination's response (its final response, so to
+//! speak). At that point, `realize()` stages everything registered for it
+//! with that response, once. See `infrastructure execution on-complete`.
+
+use object::ObjectRef;
+
+use std::collections::HashMap;
+use std::mem::replace;
+use std::sync::{Arc, Mutex};
+use std::sync::atomics::{AtomicUint, SeqCst};
+
+#[cfg(test)]
+mod tests;
+
+/// The objects waiting for each Execution to complete. Clones share the same
+/// state.
+///
+/// Holds on to both the Executions and the objects waiting for them until the
+/// Executions complete, so an Execution that never does keeps them alive.
+#[deriving(Clone)]
+pub struct Completions {
+  count: Arc<AtomicUint>,
+  hooks: Arc<Mutex<HashMap<ObjectRef, Vec<ObjectRef>>>>
+}
+
+impl Completions {
+  /// Creates an empty list.
+  pub fn new() -> Completions {
+    Completions {
+      count: Arc::new(AtomicUint::new(0)),
+      hooks: Arc::new(Mutex::new(HashMap::new()))
+    }
+  }
+
+  /// Arranges for `hook` to be staged with `execution`'s final response once
+  /// it completes. Hooks are staged in the order they were registered.
+  pub fn register(&self, execution: ObjectRef, hook: ObjectRef) {
+    let mut hooks = self.hooks.lock();
+
+    hooks.find_or_insert_with(execution, |_| Vec::new()).push(hook);
+
+    self.count.fetch_add(1, SeqCst);
+  }
+
+  /// Takes the hooks registered for `execution`, which has just completed.
+  /// Doesn't lock anything if there aren't any hooks at all, which is the
+  /// usual case.
+  pub fn take(&self, execution: &ObjectRef) -> Vec<ObjectRef> {
+    if self.count.load(SeqCst) == 0 { return Vec::new() }
+
+    let mut hooks = self.hooks.lock();
+
+    match hooks.pop(execution) {
+      Some(taken) => {
+        self.count.fetch_sub(taken.len(), SeqCst);
+        taken
+      },
+      None => Vec::new()
+    }
+  }
+
+  /// The number of hooks waiting, across every Execution.
+  pub fn len(&self) -> uint {
+    self.count.load(SeqCst)
+  }
+
+  /// Forgets every hook, returning them with their Executions.
+  pub fn clear(&self) -> Vec<(ObjectRef, Vec<ObjectRef>)> {
+    let mut hooks = self.hooks.lock();
+
+    self.count.store(0, SeqCst);
+
+    replace(&mut *hooks, HashMap::new()).move_iter().collect()
+  }
+}
@@ -0,0 +1,28 @@
+use super::Completions;
+
+use nuketype::Thing;
+
+#[test]
+fn hooks_are_taken_once_in_order() {
+  let completions = Completions::new();
+
+  let execution = Thing::empty();
+  let other     = Thing::empty();
+
+  let first  = Thing::empty();
+  let second = Thing::empty();
+
+  completions.register(execution.clone(), first.clone());
+  completions.register(execution.clone(), second.clone());
+  completions.register(other.clone(), Thing::empty());
+
+  assert_eq!(3, completions.len());
+
+  assert!(completions.take(&execution) == vec![first, second]);
+  assert!(completions.take(&execution).is_empty());
+
+  assert_eq!(1, completions.len());
+
+  assert_eq!(1, completions.clear().len());
+  assert_eq!(0, completions.len());
+}
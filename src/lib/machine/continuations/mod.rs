@@ -0,0 +1,80 @@
+//! Detection of dropped continuations.
+//!
+//! A common failure mode in Paws is a caller that never gets re-staged: a
+//! lookup misses, a comparison doesn't match, or an alien just doesn't respond,
+//! and the program quietly stops making progress. When enabled, `Continuations`
+//! remembers each Execution that has been handed to a receiver as a caller,
+//! forgets it once it's realized again, and reports whatever is left over when
+//! the reactor stalls.
+
+use object::ObjectRef;
+
+use std::collections::HashMap;
+use std::mem::replace;
+use std::sync::{Arc, Mutex};
+use std::sync::atomics::{AtomicBool, Relaxed};
+
+#[cfg(test)]
+mod tests;
+
+/// Tracks callers that are waiting to be re-staged. Disabled by default, since
+/// it costs a lock on every combination. Clones share the same state.
+#[deriving(Clone)]
+pub struct Continuations {
+  enabled: Arc<AtomicBool>,
+  waiting: Arc<Mutex<HashMap<ObjectRef, String>>>
+}
+
+impl Continuations {
+  /// Creates a new, disabled tracker.
+  pub fn new() -> Continuations {
+    Continuations {
+      enabled: Arc::new(AtomicBool::new(false)),
+      waiting: Arc::new(Mutex::new(HashMap::new()))
+    }
+  }
+
+  /// Starts tracking callers.
+  pub fn enable(&self) {
+    self.enabled.store(true, Relaxed);
+  }
+
+  /// Returns true if callers are being tracked.
+  pub fn is_enabled(&self) -> bool {
+    self.enabled.load(Relaxed)
+  }
+
+  /// Records that `caller` has been handed off and is waiting to be re-staged.
+  /// `site` describes where, for the report.
+  pub fn waiting(&self, caller: &ObjectRef, site: String) {
+    if self.is_enabled() {
+      self.waiting.lock().insert(caller.clone(), site);
+    }
+  }
+
+  /// Records that `execution` has been realized, and so isn't waiting anymore.
+  pub fn resumed(&self, execution: &ObjectRef) {
+    if self.is_enabled() {
+      self.waiting.lock().pop(execution);
+    }
+  }
+
+  /// Takes every caller that is still waiting, along with the site that
+  /// dropped it.
+  pub fn take_dropped(&self) -> Vec<(ObjectRef, String)> {
+    let mut waiting = self.waiting.lock();
+
+    replace(&mut *waiting, HashMap::new()).move_iter().collect()
+  }
+
+  /// Warns about every caller that is still waiting. Meant to be called when a
+  /// reactor stalls, at which point nothing else is going to re-stage them.
+  pub fn report(&self) {
+    if !self.is_enabled() { return }
+
+    for (caller, site) in self.take_dropped().move_iter() {
+      warn!("dropped continuation: {} was never re-staged after {}",
+            caller, site);
+    }
+  }
+}
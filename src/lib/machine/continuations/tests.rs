@@ -0,0 +1,38 @@
+use super::Continuations;
+
+use nuketype::Thing;
+
+#[test]
+fn disabled_by_default() {
+  let continuations = Continuations::new();
+
+  continuations.waiting(&Thing::empty(), "site".to_string());
+
+  assert!(continuations.take_dropped().is_empty());
+}
+
+#[test]
+fn resumed_callers_are_not_dropped() {
+  let continuations = Continuations::new();
+
+  continuations.enable();
+
+  let resumed = Thing::empty();
+  let dropped = Thing::empty();
+
+  continuations.waiting(&resumed, "a".to_string());
+  continuations.waiting(&dropped, "b".to_string());
+
+  continuations.resumed(&resumed);
+
+  match continuations.take_dropped().as_slice() {
+    [(ref caller, ref site)] => {
+      assert!(caller == &dropped);
+      assert_eq!("b", site.as_slice());
+    },
+    _ => fail!("expected exactly one dropped continuation")
+  }
+
+  // Taking clears the list.
+  assert!(continuations.take_dropped().is_empty());
+}
@@ -0,0 +1,64 @@
+//! Procedures to run when a Machine's reactors shut down gracefully.
+//!
+//! Anything holding on to something that should be let go of properly before
+//! the process ends, like buffered output or an open connection, can register
+//! a finalizer here. `Reactor::shutdown()` runs them, in the order they were
+//! registered, once the reactors are done draining their queues and before
+//! they stop. Each one is only run once, even by a pool. Stopping a reactor
+//! with `stop()` doesn't run them at all.
+
+use machine::Reactor;
+
+use std::mem::replace;
+use std::sync::{Arc, Mutex};
+
+#[cfg(test)]
+mod tests;
+
+/// A procedure to run on shutdown. It's given the reactor that's shutting
+/// down, which will stop as soon as the finalizers are done.
+pub type Finalizer = proc (&mut Reactor): Send;
+
+/// The finalizers registered on a Machine. Clones share the same list.
+#[deriving(Clone)]
+pub struct Finalizers {
+  finalizers: Arc<Mutex<Vec<Finalizer>>>
+}
+
+impl Finalizers {
+  /// Creates an empty list.
+  pub fn new() -> Finalizers {
+    Finalizers {
+      finalizers: Arc::new(Mutex::new(Vec::new()))
+    }
+  }
+
+  /// Arranges for `finalizer` to be run on shutdown, after those already
+  /// registered.
+  pub fn register(&self, finalizer: Finalizer) {
+    self.finalizers.lock().push(finalizer);
+  }
+
+  /// The number of finalizers that haven't been run yet.
+  pub fn len(&self) -> uint {
+    self.finalizers.lock().len()
+  }
+
+  /// Takes every registered finalizer, leaving none.
+  pub fn take(&self) -> Vec<Finalizer> {
+    replace(&mut *self.finalizers.lock(), Vec::new())
+  }
+
+  /// Takes every registered finalizer and runs them on `reactor`, in order.
+  /// Returns how many were run.
+  pub fn run(&self, reactor: &mut Reactor) -> uint {
+    let finalizers = self.take();
+    let count      = finalizers.len();
+
+    for finalizer in finalizers.move_iter() {
+      finalizer(&mut *reactor);
+    }
+
+    count
+  }
+}
@@ -0,0 +1,40 @@
+use super::Finalizers;
+
+use machine::Machine;
+use machine::reactor::MockReactor;
+
+#[test]
+fn finalizers_run_once_in_order() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let finalizers = Finalizers::new();
+
+  let (tx, rx) = channel();
+
+  let first_tx = tx.clone();
+
+  finalizers.register(proc (_) first_tx.send(1u));
+  finalizers.register(proc (_) tx.send(2u));
+
+  assert_eq!(2, finalizers.len());
+
+  assert_eq!(2, finalizers.run(&mut reactor));
+  assert_eq!(0, finalizers.run(&mut reactor));
+
+  assert_eq!(0, finalizers.len());
+
+  assert_eq!(vec![1u, 2], rx.iter().collect());
+}
+
+#[test]
+fn finalizers_are_given_the_reactor() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  machine.finalizers.register(proc (reactor) reactor.stop());
+
+  machine.finalizers.run(&mut reactor);
+
+  assert_eq!(1, reactor.stops);
+}
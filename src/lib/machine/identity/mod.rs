@@ -0,0 +1,112 @@
+//! Stable numeric identities for objects.
+//!
+//! Object references only mean something within a running process, so
+//! anything saved for a later run (like an `ExecutionSnapshot`) refers to
+//! objects by ID instead. An embedder that wants to restore such a thing gives
+//! the objects the same IDs in the new process, with `register()`, before
+//! restoring it. Symbols don't need IDs, since their names identify them.
+
+use object::ObjectRef;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[cfg(test)]
+mod tests;
+
+/// Maps objects to IDs and back. Clones share the same table.
+///
+/// Holds on to every object that has an ID, until it's `forget()`-ed.
+#[deriving(Clone)]
+pub struct Identities {
+  state: Arc<Mutex<State>>
+}
+
+struct State {
+  next:    u64,
+  ids:     HashMap<ObjectRef, u64>,
+  objects: HashMap<u64, ObjectRef>
+}
+
+impl Identities {
+  /// Creates an empty table. IDs start at 1.
+  pub fn new() -> Identities {
+    Identities {
+      state: Arc::new(Mutex::new(State {
+        next:    1,
+        ids:     HashMap::new(),
+        objects: HashMap::new()
+      }))
+    }
+  }
+
+  /// Gets the ID of `object`, giving it a new one if it doesn't have one yet.
+  pub fn id_of(&self, object: &ObjectRef) -> u64 {
+    let mut state = self.state.lock();
+
+    match state.ids.find(object) {
+      Some(&id) => return id,
+      None      => ()
+    }
+
+    let id = state.next;
+
+    state.next += 1;
+
+    state.ids.insert(object.clone(), id);
+    state.objects.insert(id, object.clone());
+
+    id
+  }
+
+  /// Gets the object with the given ID, if there is one.
+  pub fn object_of(&self, id: u64) -> Option<ObjectRef> {
+    self.state.lock().objects.find(&id).map(|object| object.clone())
+  }
+
+  /// Gives `object` the ID `id`, replacing whatever either had before. New
+  /// IDs handed out by `id_of()` are always greater than any registered.
+  pub fn register(&self, id: u64, object: ObjectRef) {
+    let mut state = self.state.lock();
+
+    let old_object = state.objects.pop(&id);
+    let old_id     = state.ids.pop(&object);
+
+    match old_object {
+      Some(old_object) => { state.ids.pop(&old_object); },
+      None             => ()
+    }
+
+    match old_id {
+      Some(old_id) => { state.objects.pop(&old_id); },
+      None         => ()
+    }
+
+    if id >= state.next {
+      state.next = id + 1;
+    }
+
+    state.ids.insert(object.clone(), id);
+    state.objects.insert(id, object);
+  }
+
+  /// Forgets the object with the given ID, letting go of it. Returns it, if
+  /// there was one.
+  pub fn forget(&self, id: u64) -> Option<ObjectRef> {
+    let mut state = self.state.lock();
+
+    let object = state.objects.pop(&id);
+
+    match object {
+      Some(ref object) => { state.ids.pop(object); },
+      None             => ()
+    }
+
+    object
+  }
+
+  /// The number of objects with IDs.
+  pub fn len(&self) -> uint {
+    self.state.lock().objects.len()
+  }
+}
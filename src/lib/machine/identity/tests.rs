@@ -0,0 +1,53 @@
+use super::Identities;
+
+use nuketype::Thing;
+
+#[test]
+fn ids_are_stable() {
+  let identities = Identities::new();
+
+  let a = Thing::empty();
+  let b = Thing::empty();
+
+  let id_a = identities.id_of(&a);
+  let id_b = identities.id_of(&b);
+
+  assert!(id_a != id_b);
+  assert_eq!(id_a, identities.id_of(&a));
+
+  assert!(identities.object_of(id_b) == Some(b));
+  assert!(identities.object_of(id_b + 1).is_none());
+}
+
+#[test]
+fn register_replaces_and_skips_ahead() {
+  let identities = Identities::new();
+
+  let a = Thing::empty();
+  let b = Thing::empty();
+
+  identities.id_of(&a);
+  identities.register(10, a.clone());
+
+  assert_eq!(10, identities.id_of(&a));
+  assert_eq!(1,  identities.len());
+
+  assert_eq!(11, identities.id_of(&b));
+
+  identities.register(10, b.clone());
+
+  assert!(identities.object_of(10) == Some(b));
+  assert_eq!(1, identities.len());
+}
+
+#[test]
+fn forget_lets_go() {
+  let identities = Identities::new();
+
+  let a  = Thing::empty();
+  let id = identities.id_of(&a);
+
+  assert!(identities.forget(id) == Some(a.clone()));
+  assert!(identities.object_of(id).is_none());
+  assert!(identities.id_of(&a) != id);
+}
@@ -0,0 +1,271 @@
+//! Reports on the objects reachable within a machine.
+//!
+//! `inspect()` takes a `util::graph::Graph` snapshot of everything reachable
+//! from a set of roots, and counts up what it found: objects by nuketype and by
+//! tag, and the member relationships between them. `roots()` gives the usual
+//! set of roots: the system namespaces, plus whatever else is of interest, such
+//! as the Executions in a `SerialReactor`'s queue (see
+//! `SerialReactor::queued()`).
+//!
+//! Paws programs can get the same report with `implementation inspect
+//! graph[]`.
+//!
+//! `to_dot()` draws the objects reachable from a set of roots instead, as a
+//! GraphViz graph.
+
+use object::ObjectRef;
+
+use machine::Machine;
+
+use util::graph::{Graph, Node, NodeKind, ObjectNodeReceiver};
+use util::graph::{SymbolNode, ThingNode, LocalsNode, ExecutionNode};
+use util::graph::{AlienNode, OtherNode};
+
+use std::collections::{TreeMap, HashSet, RingBuf, Deque};
+use std::io::IoResult;
+
+#[cfg(test)]
+mod tests;
+
+/// What `inspect()` found.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct Report {
+  /// The snapshot the report was made from.
+  pub graph:    Graph,
+
+  /// The number of objects of each nuketype, by name (`symbol`, `thing`,
+  /// `locals`, `execution`, `alien`, or the name other nuketypes display
+  /// themselves with), in order of name.
+  pub kinds:    Vec<(String, uint)>,
+
+  /// The number of objects with each tag, in order of tag. Untagged objects
+  /// aren't counted.
+  pub tags:     Vec<(String, uint)>,
+
+  /// The number of member relationships, not counting holes.
+  pub members:  uint,
+
+  /// How many of those are child relationships.
+  pub children: uint
+}
+
+impl Report {
+  /// The number of objects found.
+  pub fn objects(&self) -> uint {
+    self.graph.nodes.len()
+  }
+
+  /// Writes a summary of the report, one count per line:
+  ///
+  ///     objects 12
+  ///     members 20 (3 child)
+  ///     kind symbol 8
+  ///     kind thing 4
+  ///     tag "example" 1
+  pub fn write_text(&self, writer: &mut Writer) -> IoResult<()> {
+    try!(writeln!(writer, "objects {}", self.objects()));
+    try!(writeln!(writer, "members {} ({} child)",
+                  self.members, self.children));
+
+    for &(ref kind, count) in self.kinds.iter() {
+      try!(writeln!(writer, "kind {} {}", kind, count));
+    }
+
+    for &(ref tag, count) in self.tags.iter() {
+      try!(writeln!(writer, "tag \"{}\" {}", tag.escape_default(), count));
+    }
+
+    Ok(())
+  }
+}
+
+/// The system namespaces of `machine`, followed by `extra`.
+pub fn roots(machine: &Machine, extra: &[ObjectRef]) -> Vec<ObjectRef> {
+  let mut roots = vec![machine.infrastructure(), machine.implementation()];
+
+  roots.push_all(extra);
+
+  roots
+}
+
+/// Reports on everything reachable from `roots`, through members and object
+/// receivers.
+///
+/// # Failure
+///
+/// Fails if `roots` is empty.
+pub fn inspect(roots: &[ObjectRef]) -> Report {
+  let graph = Graph::snapshot_all(roots);
+
+  let mut kinds:    TreeMap<String, uint> = TreeMap::new();
+  let mut tags:     TreeMap<String, uint> = TreeMap::new();
+  let mut members:  uint                  = 0;
+  let mut children: uint                  = 0;
+
+  for node in graph.nodes.iter() {
+    increment(&mut kinds, kind_name(&node.kind));
+
+    match node.tag {
+      Some(ref tag) => increment(&mut tags, tag.clone()),
+      None          => ()
+    }
+
+    for edge in node.members.iter().filter_map(|member| member.as_ref()) {
+      members += 1;
+
+      if edge.child {
+        children += 1;
+      }
+    }
+  }
+
+  Report {
+    graph:    graph,
+    kinds:    kinds.move_iter().collect(),
+    tags:     tags.move_iter().collect(),
+    members:  members,
+    children: children
+  }
+}
+
+fn increment(counts: &mut TreeMap<String, uint>, key: String) {
+  let count = counts.find(&key).map(|&count| count).unwrap_or(0);
+
+  counts.insert(key, count + 1);
+}
+
+/// Names a node's nuketype. Other nuketypes are named by what they display
+/// before any `[`, so `FileHandle[notes.txt]` is a `FileHandle`.
+fn kind_name(kind: &NodeKind) -> String {
+  match *kind {
+    SymbolNode(_)    => "symbol".to_string(),
+    ThingNode        => "thing".to_string(),
+    LocalsNode       => "locals".to_string(),
+    ExecutionNode(_) => "execution".to_string(),
+    AlienNode        => "alien".to_string(),
+
+    OtherNode(ref description) =>
+      description.as_slice().split('[').next().unwrap_or("").to_string()
+  }
+}
+
+/// Writes the objects reachable from `roots` as a GraphViz `digraph`.
+///
+/// Objects are labelled with their tag if they have one, and otherwise with
+/// their string (for Symbols) or nuketype. Members are drawn as edges labelled
+/// with their index: solid for child relationships, and dashed otherwise.
+/// Object receivers are drawn as blue edges labelled `receiver`.
+///
+/// `machine`'s system namespaces are drawn as boxes, but not what's inside
+/// them, since every Execution with the system exposed to it can reach them
+/// and they would take over the graph.
+///
+/// # Failure
+///
+/// Fails if `roots` is empty.
+pub fn to_dot(machine: &Machine, roots: &[ObjectRef], writer: &mut Writer)
+              -> IoResult<()> {
+
+  let graph = Graph::snapshot_all(roots);
+
+  let system_tags: Vec<String> =
+    [machine.infrastructure(), machine.implementation()].iter()
+      .filter_map(|namespace| namespace.tag())
+      .map(|tag| tag.as_slice().to_string())
+      .collect();
+
+  let is_system = |node: &Node| {
+    node.tag.as_ref().map_or(false, |tag| system_tags.contains(tag))
+  };
+
+  // The roots come first in the graph, without duplicates.
+  let mut distinct = HashSet::new();
+
+  for root in roots.iter() {
+    distinct.insert(root.clone());
+  }
+
+  let mut seen:  HashSet<uint> = range(0, distinct.len()).collect();
+  let mut queue: RingBuf<uint> = range(0, distinct.len()).collect();
+
+  try!(writeln!(writer, "digraph paws {{"));
+
+  loop {
+    let index = match queue.pop_front() {
+      Some(index) => index,
+      None        => break
+    };
+
+    let node = &graph.nodes[index];
+
+    if is_system(node) {
+      try!(writeln!(writer, "  n{} [label=\"{}\", shape=box];",
+                    index, escape(label(node).as_slice())));
+      continue
+    }
+
+    try!(writeln!(writer, "  n{} [label=\"{}\"];",
+                  index, escape(label(node).as_slice())));
+
+    for (member, edge) in node.members.iter().enumerate() {
+      let edge = match *edge {
+        Some(ref edge) => edge,
+        None           => continue
+      };
+
+      try!(writeln!(writer, "  n{} -> n{} [label=\"{}\"{}];",
+                    index, edge.to, member,
+                    if edge.child { "" } else { ", style=dashed" }));
+
+      if seen.insert(edge.to) {
+        queue.push_back(edge.to);
+      }
+    }
+
+    match node.receiver {
+      ObjectNodeReceiver(to) => {
+        try!(writeln!(writer,
+                      "  n{} -> n{} [label=\"receiver\", color=blue];",
+                      index, to));
+
+        if seen.insert(to) {
+          queue.push_back(to);
+        }
+      },
+
+      _ => ()
+    }
+  }
+
+  writeln!(writer, "}}")
+}
+
+/// What to call a node in `to_dot()`.
+fn label(node: &Node) -> String {
+  match node.tag {
+    Some(ref tag) => return tag.clone(),
+    None          => ()
+  }
+
+  match node.kind {
+    SymbolNode(ref string)         => format!("\"{}\"", string),
+    ExecutionNode(ref description) => description.clone(),
+    ref kind                       => kind_name(kind)
+  }
+}
+
+/// Escapes a label for a double-quoted GraphViz string.
+fn escape(label: &str) -> String {
+  let mut escaped = String::with_capacity(label.len());
+
+  for c in label.chars() {
+    match c {
+      '"'  => escaped.push_str("\\\""),
+      '\\' => escaped.push_str("\\\\"),
+      '\n' => escaped.push_str("\\n"),
+      c    => escaped.push_char(c)
+    }
+  }
+
+  escaped
+}
@@ -0,0 +1,119 @@
+use super::{inspect, roots, to_dot};
+
+use object::{Meta, ObjectReceiver};
+
+use nuketype::{Thing, Number};
+
+use machine::Machine;
+
+use std::io::MemWriter;
+
+#[test]
+fn inspect_counts_kinds_tags_and_members() {
+  let machine = Machine::new();
+
+  let child = Thing::tagged(Meta::new(), "child");
+
+  let root = Thing::tagged(Meta::new(), "root");
+
+  {
+    let mut root_obj = root.lock();
+    let     members  = &mut root_obj.meta_mut().members;
+
+    members.push_child(child.clone());
+    members.push(machine.symbol("answer"));
+    members.push(Number::create(42));
+  }
+
+  child.lock().meta_mut().members.push(root.clone());
+
+  let report = inspect([root.clone(), child.clone()]);
+
+  assert_eq!(4, report.objects());
+
+  assert_eq!(vec![("Number".to_string(), 1),
+                  ("symbol".to_string(), 1),
+                  ("thing".to_string(),  2)],
+             report.kinds);
+
+  assert_eq!(vec![("child".to_string(), 1), ("root".to_string(), 1)],
+             report.tags);
+
+  // root → child, root → "answer", root → 42, child → root
+  assert_eq!(4, report.members);
+  assert_eq!(1, report.children);
+}
+
+#[test]
+fn roots_include_system_namespaces() {
+  let machine = Machine::new();
+  let extra   = Thing::empty();
+
+  let roots = roots(&machine, [extra.clone()]);
+
+  assert!(roots == vec![machine.infrastructure(), machine.implementation(),
+                        extra]);
+
+  let report = inspect(roots.as_slice());
+
+  assert!(report.tags.iter().any(|&(ref tag, _)|
+    tag.as_slice() == "(impl. inspect)"));
+}
+
+#[test]
+fn write_text_summarizes() {
+  let root = Thing::tagged(Meta::new(), "root");
+
+  let mut writer = MemWriter::new();
+
+  inspect([root]).write_text(&mut writer).unwrap();
+
+  assert_eq!("objects 1\nmembers 0 (0 child)\nkind thing 1\ntag \"root\" 1\n",
+             String::from_utf8(writer.unwrap()).unwrap().as_slice());
+}
+
+#[test]
+fn to_dot_draws_members_and_receivers() {
+  let machine = Machine::new();
+
+  let child = Thing::empty();
+
+  let root = Thing::tagged(Meta::new(), "root \"quoted\"");
+
+  {
+    let mut root_obj = root.lock();
+    let     meta     = root_obj.meta_mut();
+
+    meta.members.push_child(child.clone());
+    meta.members.push(machine.symbol("hello"));
+    meta.members.push(machine.infrastructure());
+    meta.receiver = ObjectReceiver(child.clone());
+  }
+
+  let mut writer = MemWriter::new();
+
+  to_dot(&machine, [root], &mut writer).unwrap();
+
+  let dot = String::from_utf8(writer.unwrap()).unwrap();
+
+  let lines: Vec<&str> = dot.as_slice().lines().collect();
+
+  assert_eq!(Some(&"digraph paws {"), lines.head());
+  assert_eq!(Some(&"}"),              lines.last());
+
+  for expected in [
+      "  n0 [label=\"root \\\"quoted\\\"\"];",
+      "  n0 -> n1 [label=\"1\"];",
+      "  n0 -> n2 [label=\"2\", style=dashed];",
+      "  n0 -> n3 [label=\"3\", style=dashed];",
+      "  n0 -> n1 [label=\"receiver\", color=blue];",
+      "  n1 [label=\"thing\"];",
+      "  n2 [label=\"\\\"hello\\\"\"];",
+      "  n3 [label=\"(infrastructure)\", shape=box];"].iter() {
+
+    assert!(lines.contains(expected), "missing {} in:\n{}", expected, dot);
+  }
+
+  // Nothing inside the system namespace is drawn.
+  assert!(!lines.iter().any(|line| line.starts_with("  n3 ->")));
+}
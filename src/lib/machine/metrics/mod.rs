@@ -0,0 +1,133 @@
+//! Statistics for every reactor running on a Machine, gathered in one place.
+//!
+//! Each reactor registers itself when it's created, and reports its
+//! `ReactorStats` when it stalls and when it stops (and `implementation
+//! metrics` reports the calling reactor's before looking). A `MetricsSnapshot`
+//! adds them up and works out rates over the time since the Machine was
+//! created.
+
+use machine::ReactorStats;
+
+use std::io::IoResult;
+use std::sync::{Arc, Mutex};
+
+use time;
+
+#[cfg(test)]
+mod tests;
+
+/// Collects the last reported statistics of each reactor. Clones share the
+/// same reports.
+#[deriving(Clone)]
+pub struct Metrics {
+  started:  u64,
+  reactors: Arc<Mutex<Vec<(String, ReactorStats)>>>
+}
+
+impl Metrics {
+  /// Creates an empty `Metrics`, starting the clock now.
+  pub fn new() -> Metrics {
+    Metrics {
+      started:  time::precise_time_ns(),
+      reactors: Arc::new(Mutex::new(Vec::new()))
+    }
+  }
+
+  /// Adds a reactor called `name`, with all of its counters at zero. Returns
+  /// the id to give to `report()`.
+  pub fn register(&self, name: &str) -> uint {
+    let mut reactors = self.reactors.lock();
+
+    reactors.push((name.to_string(), ReactorStats::new()));
+
+    reactors.len() - 1
+  }
+
+  /// Replaces the statistics of the reactor with the given id.
+  pub fn report(&self, id: uint, stats: ReactorStats) {
+    *self.reactors.lock().get_mut(id).mut1() = stats;
+  }
+
+  /// Takes a copy of the statistics reported so far.
+  pub fn snapshot(&self) -> MetricsSnapshot {
+    MetricsSnapshot {
+      elapsed_ns: time::precise_time_ns() - self.started,
+      reactors:   self.reactors.lock().clone()
+    }
+  }
+}
+
+/// What `Metrics::snapshot()` found.
+#[deriving(Clone, Show)]
+pub struct MetricsSnapshot {
+  /// How long the Machine had been around, in nanoseconds.
+  pub elapsed_ns: u64,
+
+  /// The name and last reported statistics of each reactor, in the order they
+  /// registered.
+  pub reactors:   Vec<(String, ReactorStats)>
+}
+
+impl MetricsSnapshot {
+  /// The statistics of all of the reactors, added up.
+  pub fn total(&self) -> ReactorStats {
+    let mut total = ReactorStats::new();
+
+    for &(_, ref stats) in self.reactors.iter() {
+      total.add(stats);
+    }
+
+    total
+  }
+
+  /// How many stagings were realized per second, across all of the reactors.
+  /// `None` if no time has passed.
+  pub fn realized_per_second(&self) -> Option<f64> {
+    if self.elapsed_ns == 0 {
+      None
+    } else {
+      Some(self.total().steps as f64 / (self.elapsed_ns as f64 / 1e9))
+    }
+  }
+
+  /// Writes a human-readable report: a section for each reactor, and then the
+  /// totals.
+  pub fn write_text(&self, writer: &mut Writer) -> IoResult<()> {
+    for &(ref name, ref stats) in self.reactors.iter() {
+      try!(writeln!(writer, "-- {}:", name));
+      try!(dump_stats(writer, stats));
+    }
+
+    try!(writeln!(writer, "-- all reactors, over {:.3} s:",
+                  self.elapsed_ns as f64 / 1e9));
+    try!(dump_stats(writer, &self.total()));
+
+    match self.realized_per_second() {
+      Some(rate) => writeln!(writer, "realized/second:   {:.1}", rate),
+      None       => Ok(())
+    }
+  }
+}
+
+fn dump_stats(writer: &mut Writer, stats: &ReactorStats) -> IoResult<()> {
+  try!(writeln!(writer, "stagings made:     {} ({} queued)",
+                stats.stagings, stats.queue_depth));
+  try!(writeln!(writer, "stagings realized: {} ({} executions, {} aliens)",
+                stats.steps, stats.executions, stats.aliens));
+  try!(writeln!(writer, "stalls handled:    {}", stats.stalls));
+  try!(writeln!(writer, "symbol lookups:    {} hits, {} misses ({})",
+                stats.cache.sym_lookup_hits,
+                stats.cache.sym_lookup_misses,
+                format_rate(stats.cache.sym_lookup_hit_rate())));
+  writeln!(writer, "receivers:         {} hits, {} misses ({})",
+           stats.cache.receiver_hits,
+           stats.cache.receiver_misses,
+           format_rate(stats.cache.receiver_hit_rate()))
+}
+
+fn format_rate(rate: Option<f64>) -> String {
+  match rate {
+    Some(rate) => format!("{:.1}% hit", rate * 100.0),
+    None       => "unused".to_string()
+  }
+}
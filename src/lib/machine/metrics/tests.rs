@@ -0,0 +1,59 @@
+use super::Metrics;
+
+use machine::ReactorStats;
+
+use std::io::MemWriter;
+
+#[test]
+fn snapshot_adds_up_the_reports() {
+  let metrics = Metrics::new();
+
+  let a = metrics.register("a");
+  let b = metrics.register("b");
+
+  metrics.report(a, ReactorStats { steps: 3, stagings: 4,
+                                   ..ReactorStats::new() });
+  metrics.report(b, ReactorStats { steps: 5, stagings: 1,
+                                   ..ReactorStats::new() });
+
+  // Only the latest report counts.
+  metrics.report(a, ReactorStats { steps: 2, ..ReactorStats::new() });
+
+  let snapshot = metrics.snapshot();
+
+  assert_eq!(2, snapshot.reactors.len());
+  assert!(snapshot.reactors[0].ref0().as_slice() == "a");
+
+  let total = snapshot.total();
+
+  assert_eq!(7, total.steps);
+  assert_eq!(1, total.stagings);
+}
+
+#[test]
+fn clones_share_reports() {
+  let metrics = Metrics::new();
+  let clone   = metrics.clone();
+
+  let id = clone.register("clone");
+
+  clone.report(id, ReactorStats { steps: 1, ..ReactorStats::new() });
+
+  assert_eq!(1, metrics.snapshot().total().steps);
+}
+
+#[test]
+fn write_text_names_each_reactor() {
+  let metrics = Metrics::new();
+
+  metrics.register("SerialReactor");
+
+  let mut writer = MemWriter::new();
+
+  metrics.snapshot().write_text(&mut writer).unwrap();
+
+  let text = String::from_utf8(writer.unwrap()).unwrap();
+
+  assert!(text.as_slice().contains("-- SerialReactor:"));
+  assert!(text.as_slice().contains("-- all reactors"));
+}
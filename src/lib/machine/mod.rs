@@ -3,9 +3,10 @@
 //! They may have `Reactor`s operating within their context, which are the
 //! evaluation cores of Paws.
 
-use object::ObjectRef;
+use object::{ObjectRef, Meta, CacheConfig, SharedCache};
 
 use nuketype::symbol::{Symbol, SymbolMap};
+use nuketype::{Number, Thing};
 
 use system::implementation;
 use system::infrastructure;
@@ -14,8 +15,34 @@ use std::sync::{Arc, Mutex};
 
 pub use self::reactor::Reactor;
 pub use self::reactor::Combination;
+pub use self::reactor::ReactorStats;
+pub use self::reactor::Responsibility;
+pub use self::warnings::{Warnings, WarningPolicy};
+pub use self::continuations::Continuations;
+pub use self::trace::{Trace, TraceFormat, TraceSink};
+pub use self::blocking::BlockingPool;
+pub use self::timer::Timer;
+pub use self::metrics::{Metrics, MetricsSnapshot};
+pub use self::replay::Replay;
+pub use self::identity::Identities;
+pub use self::completions::Completions;
+pub use self::finalizers::Finalizers;
+pub use self::bridge::Bridges;
 
 pub mod reactor;
+pub mod warnings;
+pub mod continuations;
+pub mod trace;
+pub mod blocking;
+pub mod timer;
+pub mod metrics;
+pub mod replay;
+pub mod inspect;
+pub mod identity;
+pub mod completions;
+pub mod finalizers;
+pub mod bridge;
+pub mod remote;
 
 #[cfg(test)]
 mod tests;
@@ -31,6 +58,76 @@ pub struct Machine {
   /// the symbol map; not strictly necessary.
   pub locals_sym:     ObjectRef,
 
+  /// Deduplicates warnings emitted through `machine_warn!`. Shared between
+  /// clones of the Machine.
+  pub warnings:       Warnings,
+
+  /// Tracks callers that never get re-staged, if enabled. See
+  /// `machine::continuations`.
+  pub continuations:  Continuations,
+
+  /// Makes Executions take responsibility for the objects they combine
+  /// against, if enabled. See `machine::reactor::responsibility`.
+  pub responsibility: Responsibility,
+
+  /// Writes every combination to a structured log, if enabled. See
+  /// `machine::trace`.
+  pub trace:          Trace,
+
+  /// The cache sizes for reactors created with this Machine. Changing it only
+  /// affects reactors created afterward.
+  pub cache_config:   CacheConfig,
+
+  /// A receiver cache shared by all of the reactors in a pool, behind their
+  /// own. Disabled by default. See `object::cache::shared`.
+  pub shared_cache:   SharedCache,
+
+  /// The most instructions an Execution may evaluate in one realization
+  /// before it's put at the back of its reactor's queue to let other work
+  /// through, or `None` for no limit (the default). Changing it only affects
+  /// reactors created afterward. See `Execution::advance_within()`.
+  pub preempt_after:  Option<uint>,
+
+  /// Staged with an Execution that was preempted, to have it carry on. Any
+  /// other response it gets before this one is staged again behind it.
+  pub resume_marker:  ObjectRef,
+
+  /// Runs work that would otherwise block a reactor, like file I/O. See
+  /// `machine::blocking`.
+  pub blocking:       BlockingPool,
+
+  /// Runs things after a delay, like `implementation timer after`. See
+  /// `machine::timer`.
+  pub timer:          Timer,
+
+  /// The last reported statistics of every reactor on this Machine. See
+  /// `machine::metrics`.
+  pub metrics:        Metrics,
+
+  /// Records the order stagings are realized in, or replays it, if enabled.
+  /// See `machine::replay`.
+  pub replay:         Replay,
+
+  /// Gives objects IDs that mean something outside of this process, for
+  /// snapshots. See `machine::identity`.
+  pub identities:     Identities,
+
+  /// What to stage when Executions complete. See `machine::completions`.
+  pub completions:    Completions,
+
+  /// What to run when the reactors shut down gracefully. See
+  /// `machine::finalizers`.
+  pub finalizers:     Finalizers,
+
+  /// Connections to other Machines in the same process. See
+  /// `machine::bridge`.
+  pub bridges:        Bridges,
+
+  /// The arguments given to the program, like those after the script file on
+  /// the command line. Available as `implementation arguments`, so changing
+  /// it has no effect once the system interface has been generated.
+  pub arguments:      Vec<String>,
+
   /// The system interface. See `paws::system`. Lazily generated, because many
   /// tests don't need it.
       system:         Arc<Mutex<Option<System>>>,
@@ -45,6 +142,23 @@ impl Machine {
     Machine {
       symbol_map:     Arc::new(Mutex::new(symbol_map)),
       locals_sym:     locals_sym,
+      warnings:       Warnings::new(),
+      continuations:  Continuations::new(),
+      responsibility: Responsibility::new(),
+      trace:          Trace::new(),
+      cache_config:   CacheConfig::new(),
+      shared_cache:   SharedCache::new(),
+      preempt_after:  None,
+      resume_marker:  Thing::tagged(Meta::new(), "(resume)"),
+      blocking:       BlockingPool::new(4),
+      timer:          Timer::new(),
+      metrics:        Metrics::new(),
+      replay:         Replay::new(),
+      identities:     Identities::new(),
+      completions:    Completions::new(),
+      finalizers:     Finalizers::new(),
+      bridges:        Bridges::new(),
+      arguments:      vec![],
       system:         Arc::new(Mutex::new(None))
     }
   }
@@ -57,6 +171,20 @@ impl Machine {
     Symbol::create(self.symbol_map.lock().intern(string))
   }
 
+  /// Starts tracing every combination and staging on this Machine's reactors
+  /// to `sink`. The same as `trace.enable_sink()`; see `machine::trace`.
+  pub fn set_tracer(&self, sink: TraceSink, format: TraceFormat) {
+    self.trace.enable_sink(sink, format)
+  }
+
+  /// Creates a `Number` object with the given value.
+  ///
+  /// Numbers don't need anything from the Machine; this is here so that
+  /// creating one reads the same as creating a Symbol.
+  pub fn number(&self, value: i64) -> ObjectRef {
+    Number::create(value)
+  }
+
   /// Exposes the system interface (`infrastructure` and `implementation`) as
   /// members of the locals of the given Execution.
   pub fn expose_system_to(&self, execution: &ObjectRef) {
@@ -75,6 +203,16 @@ impl Machine {
     locals.push_pair(self.symbol("implementation"), implementation);
   }
 
+  /// The `infrastructure` namespace exposed by `expose_system_to()`.
+  pub fn infrastructure(&self) -> ObjectRef {
+    self.system().infrastructure
+  }
+
+  /// The `implementation` namespace exposed by `expose_system_to()`.
+  pub fn implementation(&self) -> ObjectRef {
+    self.system().implementation
+  }
+
   /// Lazy-get the system interface.
   fn system(&self) -> System {
     let mut lazy_system = self.system.lock();
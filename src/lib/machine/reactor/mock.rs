@@ -1,11 +1,26 @@
-use super::Reactor;
+use super::{Reactor, ReactorStats};
+use super::{RunStatus, Completed, BudgetExhausted, Waiting, Stalled};
+use super::{Remote, RemoteSink};
+use super::realize;
 
 use machine::Machine;
 
 use object::{ObjectRef, Cache};
 
+use nuketype::{Execution, Alien};
+
+use std::any::AnyRefExt;
+use std::mem::replace;
+use std::time::duration::Duration;
+
+use time;
+
 /// A fake reactor that, instead of actually reacting anything, instead simply
 /// accumulates state from the calls made to it.
+///
+/// Tests can check what happened with the `expect_` methods, which consume the
+/// logged stagings in order and fail with a description of what was there
+/// instead, or drive the reactor for a few steps with `run()`.
 pub struct MockReactor {
   /// Indicates whether the reactor is alive. This is `true` when created, but
   /// `false` as soon as `stop()` is called.
@@ -16,29 +31,218 @@ pub struct MockReactor {
   /// A log of all `stage()` calls made while the reactor was alive.
   pub stagings:       Vec<(ObjectRef, ObjectRef)>,
 
-  /// A log of all `on_stall()` calls made while the reactor was alive.
+  /// A log of all `on_stall()` calls made while the reactor was alive. See
+  /// `stall()`.
   pub stall_handlers: Vec<proc (&mut Reactor)>,
 
+  /// How many times `stop()` has been called.
+  pub stops:          uint,
+
+  /// The `limit` given to the first `shutdown()` call, if any. Nothing is
+  /// drained or finalized; tests can do that themselves with `run()` and
+  /// `machine.finalizers.run()`.
+  pub shutdown_limit: Option<uint>,
+
   /// The machine associated with the reactor.
   pub machine:        Machine,
 
   /// The reactor's cache.
-  pub cache:          Cache
+  pub cache:          Cache,
+
+  /// How many `Remote`s have been handed out but haven't delivered yet. See
+  /// `receive_remote()`.
+  pub outstanding:    uint,
+
+  remote_tx:          Sender<Option<(ObjectRef, ObjectRef)>>,
+  remote_rx:          Receiver<Option<(ObjectRef, ObjectRef)>>
 }
 
 impl MockReactor {
   /// Creates a new `MockReactor` for the given `Machine`.
   pub fn new(machine: Machine) -> MockReactor {
+    let (remote_tx, remote_rx) = channel();
+
+    let cache = Cache::new_serial_with(&machine.cache_config);
+
     MockReactor {
       alive:          true,
       stagings:       Vec::new(),
       stall_handlers: Vec::new(),
+      stops:          0,
+      shutdown_limit: None,
       machine:        machine,
-      cache:          Cache::new_serial()
+      cache:          cache,
+      outstanding:    0,
+      remote_tx:      remote_tx,
+      remote_rx:      remote_rx
+    }
+  }
+
+  /// Waits for one of the `Remote`s handed out by `remote()` to deliver, and
+  /// logs its staging like `stage()` would.
+  ///
+  /// Returns false if it was dropped without staging anything.
+  pub fn receive_remote(&mut self) -> bool {
+    let delivery = self.remote_rx.recv();
+
+    self.outstanding -= 1;
+
+    match delivery {
+      Some((execution, response)) => {
+        self.stage(execution, response);
+        true
+      },
+      None => false
+    }
+  }
+
+  /// Realizes logged stagings of Executions and Aliens, oldest first, until
+  /// there are none left, the reactor is stopped, or `steps` have been
+  /// realized. Anything they stage is logged and may be realized in turn.
+  /// Stagings of anything else are left in the log for the `expect_` methods.
+  ///
+  /// Returns the number of stagings realized.
+  pub fn run(&mut self, steps: uint) -> uint {
+    let mut realized = 0;
+
+    while realized < steps && self.alive {
+      let next = self.stagings.iter()
+        .position(|&(ref execution, _)| is_stageable(execution));
+
+      match next {
+        Some(index) => {
+          let (execution, response) = self.stagings.remove(index).unwrap();
+
+          realize(self, execution, response);
+
+          realized += 1;
+        },
+
+        None => break
+      }
+    }
+
+    realized
+  }
+
+  /// Calls and removes the logged stall handlers, in the order they were
+  /// registered, as a real reactor would when it ran out of work.
+  ///
+  /// Returns the number of handlers called.
+  pub fn stall(&mut self) -> uint {
+    let handlers = replace(&mut self.stall_handlers, Vec::new());
+    let count    = handlers.len();
+
+    for handler in handlers.move_iter() {
+      handler(self);
+    }
+
+    count
+  }
+
+  /// Removes the oldest logged staging, which must be of `execution` with
+  /// `response`.
+  ///
+  /// # Failure
+  ///
+  /// Fails if the oldest staging is anything else, or there are none.
+  pub fn expect_stage(&mut self, execution: &ObjectRef, response: &ObjectRef) {
+    let (staged, staged_response) = self.expect_staged(execution);
+
+    if &staged_response != response {
+      fail!("expected {} to be staged with {}, but it was staged with {}",
+            staged, response, staged_response);
+    }
+  }
+
+  /// Removes the oldest logged staging, which must be of `execution`, and
+  /// returns it along with whatever it was staged with.
+  ///
+  /// # Failure
+  ///
+  /// Fails if the oldest staging is of anything else, or there are none.
+  pub fn expect_staged(&mut self, execution: &ObjectRef)
+                       -> (ObjectRef, ObjectRef) {
+    if self.stagings.is_empty() {
+      fail!("expected {} to be staged, but nothing was", execution);
+    }
+
+    let (staged, response) = self.stagings.remove(0).unwrap();
+
+    if &staged != execution {
+      fail!("expected {} to be staged next, but {} was (with {})",
+            execution, staged, response);
+    }
+
+    (staged, response)
+  }
+
+  /// Removes the oldest logged staging of `execution` with `response`, no
+  /// matter what was staged before it.
+  ///
+  /// # Failure
+  ///
+  /// Fails if there's no such staging.
+  pub fn expect_stage_anywhere(&mut self,
+                               execution: &ObjectRef,
+                               response:  &ObjectRef) {
+
+    let index = self.stagings.iter()
+      .position(|&(ref staged, ref staged_response)|
+        staged == execution && staged_response == response);
+
+    match index {
+      Some(index) => { self.stagings.remove(index); },
+
+      None => fail!("expected {} to be staged with {}, but it wasn't (got {})",
+                    execution, response, self.stagings)
+    }
+  }
+
+  /// Checks that every logged staging has been expected.
+  ///
+  /// # Failure
+  ///
+  /// Fails, listing them, if there are stagings left.
+  pub fn expect_no_stagings(&self) {
+    if !self.stagings.is_empty() {
+      fail!("expected nothing more to be staged, but got {}", self.stagings);
+    }
+  }
+
+  /// Checks that `stop()` has been called.
+  ///
+  /// # Failure
+  ///
+  /// Fails if it hasn't.
+  pub fn expect_stopped(&self) {
+    if self.stops == 0 {
+      fail!("expected the reactor to be stopped");
+    }
+  }
+
+  /// Why `run_for()` or `run_for_duration()` stopped, judging by what's left.
+  fn status(&self) -> RunStatus {
+    if !self.alive {
+      Completed
+    } else if self.stagings.iter().any(|&(ref execution, _)|
+                                        is_stageable(execution)) {
+      BudgetExhausted
+    } else if self.outstanding > 0 {
+      Waiting
+    } else {
+      Stalled
     }
   }
 }
 
+/// Whether `realize()` would do anything with `object`.
+fn is_stageable(object: &ObjectRef) -> bool {
+  let guard = object.lock();
+
+  guard.nuketype().is::<Execution>() || guard.nuketype().is::<Alien>()
+}
+
 impl Reactor for MockReactor {
   fn stage(&mut self, execution: ObjectRef, response: ObjectRef) {
     if self.alive {
@@ -53,7 +257,14 @@ impl Reactor for MockReactor {
   }
 
   fn stop(&mut self) {
-    self.alive = false;
+    self.alive  = false;
+    self.stops += 1;
+  }
+
+  fn shutdown(&mut self, limit: uint) {
+    if self.shutdown_limit.is_none() {
+      self.shutdown_limit = Some(limit);
+    }
   }
 
   fn machine(&self) -> &Machine {
@@ -63,4 +274,52 @@ impl Reactor for MockReactor {
   fn cache(&mut self) -> &mut Cache {
     &mut self.cache
   }
+
+  /// Mock reactors never realize anything, so the counters are always zero,
+  /// and `queue_depth` is the number of logged stagings.
+  fn stats(&self) -> ReactorStats {
+    ReactorStats {
+      cache:       self.cache.stats().clone(),
+      queue_depth: self.stagings.len(),
+      ..ReactorStats::new()
+    }
+  }
+
+  fn remote(&mut self) -> Remote {
+    self.outstanding += 1;
+
+    Remote::new(box MockRemote(self.remote_tx.clone()))
+  }
+
+  /// Like `run()`. Stall handlers are never called (that's up to `stall()`),
+  /// so this is `Stalled` as soon as there's nothing left to realize and no
+  /// `Remote`s are outstanding. Deliveries from `Remote`s aren't waited for
+  /// either; see `receive_remote()`.
+  fn run_for(&mut self, steps: uint) -> RunStatus {
+    self.run(steps);
+    self.status()
+  }
+
+  /// Like `run_for()`, but realizes stagings one at a time until `duration`
+  /// has passed.
+  fn run_for_duration(&mut self, duration: Duration) -> RunStatus {
+    let start = time::precise_time_ns();
+    let limit = duration.num_nanoseconds().unwrap_or(::std::i64::MAX);
+
+    while ((time::precise_time_ns() - start) as i64) < limit {
+      if self.run(1) == 0 { break }
+    }
+
+    self.status()
+  }
+}
+
+struct MockRemote(Sender<Option<(ObjectRef, ObjectRef)>>);
+
+impl RemoteSink for MockRemote {
+  fn deliver(&mut self, staging: Option<(ObjectRef, ObjectRef)>) {
+    let MockRemote(ref sender) = *self;
+
+    let _ = sender.send_opt(staging);
+  }
 }
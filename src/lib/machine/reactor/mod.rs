@@ -4,22 +4,38 @@
 //! different purposes, including a `MockReactor` intended for testing.
 
 use machine::Machine;
+use machine::trace::Trace;
 
 use object::ObjectRef;
 use object::{ObjectReceiver, NativeReceiver};
-use object::{Meta, Params, Cache};
+use object::{Params, Cache, CacheStats};
+use object::lookup_receiver;
 
-use nuketype::{Thing, Execution, Alien};
+use nuketype::{Execution, Alien, Locals};
+use nuketype::locals::locals_receiver;
+
+use script::Span;
 
 use util::clone;
 
+use std::time::duration::Duration;
+
 pub use self::mock::MockReactor;
 pub use self::serial::SerialReactor;
 pub use self::parallel::{ReactorPool, ParallelReactor};
+pub use self::scheduler::{SchedulerPolicy, SchedulerView};
+pub use self::scheduler::{RoundRobin, LeastLoaded, Affinity, LastRealized};
+pub use self::responsibility::{Responsibility, Blocked};
+pub use self::responsibility::{BlockedCombination, BlockedAdoption};
+pub use self::remote::{Remote, RemoteSink};
 
 mod mock;
 mod serial;
 mod parallel;
+mod scheduler;
+
+pub mod responsibility;
+pub mod remote;
 
 #[cfg(test)]
 mod tests;
@@ -27,8 +43,8 @@ mod tests;
 /// A single Paws reactor.
 ///
 /// Responsible for a single Machine's Unit. In the future, Machines will be
-/// split so that they can have multiple Units and also talk to other Machines,
-/// possibly on a network.
+/// split so that they can have multiple Units. Machines in the same process can
+/// already talk to each other through `machine::bridge`.
 ///
 /// May be part of a pool, in which the reactors are expected to communicate
 /// with each other transparently.
@@ -53,11 +69,173 @@ pub trait Reactor {
   /// well.
   fn stop(&mut self);
 
+  /// Stops gracefully: work from outside of the reactor, like `Remote`s, is
+  /// refused from now on, at most `limit` more stagings are realized (whether
+  /// they were already queued or are made along the way), the Machine's
+  /// finalizers are run (see `machine::finalizers`), and then the reactor
+  /// stops as if by `stop()`. Stall handlers aren't called while draining;
+  /// running out of work just means it's done sooner.
+  ///
+  /// If the reactor is part of a pool, the whole pool shuts down. Calling it
+  /// again once shutting down has no effect.
+  fn shutdown(&mut self, limit: uint);
+
   /// Gets a reference to the machine this reactor is associated with.
   fn machine(&self) -> &Machine;
 
   /// Gets a mutable reference to this reactor's cache.
   fn cache(&mut self) -> &mut Cache;
+
+  /// Takes a snapshot of this reactor's performance-related numbers.
+  ///
+  /// Only covers this reactor, even if it's part of a pool.
+  fn stats(&self) -> ReactorStats;
+
+  /// Promises a staging that will be made later from another task, and
+  /// returns the `Remote` to make it with. The reactor (or pool) won't stall
+  /// until the `Remote` has been used or dropped. See
+  /// `machine::reactor::remote`.
+  fn remote(&mut self) -> Remote;
+
+  /// Realizes at most `steps` stagings, and returns instead of hanging when
+  /// there's nothing to do. Useful for interleaving Paws with some other event
+  /// loop. See `RunStatus` for why it returned.
+  fn run_for(&mut self, steps: uint) -> RunStatus;
+
+  /// Like `run_for()`, but keeps realizing stagings until `duration` has
+  /// passed. A staging that's already started isn't interrupted, so this may
+  /// run over by however long that takes.
+  fn run_for_duration(&mut self, duration: Duration) -> RunStatus;
+
+  /// Reports `stats()` to the Machine's metrics, if this reactor registered
+  /// with them. See `machine::metrics`.
+  fn report_metrics(&self) {
+  }
+
+  /// The trace that this reactor's combinations (and, for `SerialReactor` and
+  /// `ParallelReactor`, stagings) are recorded in. See `machine::trace`.
+  fn tracer(&self) -> &Trace {
+    &self.machine().trace
+  }
+}
+
+/// A snapshot of performance-related information for a single `Reactor`. See
+/// `Reactor::stats()`.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct ReactorStats {
+  /// The reactor's cache statistics.
+  pub cache:       CacheStats,
+
+  /// The number of stagings waiting in the reactor's queue.
+  pub queue_depth: uint,
+
+  /// The number of stagings made on the reactor since it was created.
+  pub stagings:    u64,
+
+  /// The number of stagings the reactor has realized since it was created.
+  pub steps:       u64,
+
+  /// How many of those stagings realized an Execution.
+  pub executions:  u64,
+
+  /// How many of those stagings realized an Alien.
+  pub aliens:      u64,
+
+  /// The number of times the reactor has handled a stall.
+  pub stalls:      u64,
+
+  /// How many stagings the reactor took from other reactors' queues. Only
+  /// `ParallelReactor` steals work.
+  pub steals:      u64,
+
+  /// How many stagings the reactor took from its own work-stealing queue,
+  /// rather than stealing them. Only counted by `ParallelReactor`.
+  pub local_hits:  u64
+}
+
+impl ReactorStats {
+  /// Creates a new `ReactorStats` with all of the counters at zero.
+  pub fn new() -> ReactorStats {
+    ReactorStats {
+      cache:       CacheStats::new(),
+      queue_depth: 0,
+      stagings:    0,
+      steps:       0,
+      executions:  0,
+      aliens:      0,
+      stalls:      0,
+      steals:      0,
+      local_hits:  0
+    }
+  }
+
+  /// Adds the counters from `other` to these, for aggregating the statistics
+  /// of several reactors.
+  pub fn add(&mut self, other: &ReactorStats) {
+    self.cache.add(&other.cache);
+
+    self.queue_depth += other.queue_depth;
+    self.stagings    += other.stagings;
+    self.steps       += other.steps;
+    self.executions  += other.executions;
+    self.aliens      += other.aliens;
+    self.stalls      += other.stalls;
+    self.steals      += other.steals;
+    self.local_hits  += other.local_hits;
+  }
+
+  /// The fraction of dequeued stagings that were stolen from other reactors
+  /// rather than taken locally, from 0 to 1. A rough measure of how unbalanced
+  /// the work was. `None` if nothing was dequeued, as in a `SerialReactor`.
+  pub fn steal_rate(&self) -> Option<f64> {
+    if self.steals + self.local_hits == 0 {
+      None
+    } else {
+      Some(self.steals as f64 / (self.steals + self.local_hits) as f64)
+    }
+  }
+
+  /// Counts a staging that was realized, as returned by `realize()`.
+  pub fn count(&mut self, realized: Realized) {
+    self.steps += 1;
+
+    match realized {
+      RealizedExecution => self.executions += 1,
+      RealizedAlien     => self.aliens     += 1,
+      NotRealized       => ()
+    }
+  }
+}
+
+/// What `realize()` found to realize.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum Realized {
+  /// An Execution was advanced.
+  RealizedExecution,
+
+  /// An Alien's routine was called.
+  RealizedAlien,
+
+  /// It was neither, so nothing happened.
+  NotRealized
+}
+
+/// Why `Reactor::run_for()` or `run_for_duration()` returned.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum RunStatus {
+  /// The reactor was stopped, so there's nothing more it will do.
+  Completed,
+
+  /// The budget ran out while there was still work to do.
+  BudgetExhausted,
+
+  /// There's no work to do right now, but `Remote`s are still outstanding (or,
+  /// for a `ParallelReactor`, the rest of its pool may make more), so there
+  /// may be more later.
+  Waiting,
+
+  /// The reactor ran out of work, and its stall handlers didn't make any more.
+  Stalled
 }
 
 /// Describes the different kinds of arguments available for combination.
@@ -140,6 +318,15 @@ pub fn combine<R: Reactor>(
     (map(combination.subject), map(combination.message))
   };
 
+  // Take responsibility for the subject, if that's being enforced. If someone
+  // else has it, the combination will be tried again once they're done.
+  let responsibility = reactor.machine().responsibility.clone();
+
+  if responsibility.is_enabled() &&
+     !responsibility.acquire(&caller, &subject, &message) {
+    return
+  }
+
   // Perform the receiver-finding algorithm, using `use_receiver_of` to
   // iterate through until we find the receiver we want to use.
   let mut use_receiver_of = subject.clone();
@@ -149,30 +336,57 @@ pub fn combine<R: Reactor>(
     match receiver {
       // If the receiver is a NativeReceiver, then call the function it
       // contains.
-      NativeReceiver(function) =>
+      NativeReceiver(function) => {
+        // Looking up a Symbol with the default receiver is by far the most
+        // common combination (every bare symbol in cPaws), so do it directly
+        // rather than going through `lookup_receiver()`. Same result.
+        if function as *const () == lookup_receiver as *const () {
+          match message.symbol_ref() {
+            Some(symbol) => {
+              trace(reactor, &caller, &subject, &message,
+                    || "lookup".to_string());
+
+              let result =
+                reactor.cache().sym_lookup(subject.clone(), symbol.clone());
+
+              match result {
+                Some(value) => reactor.stage(caller, value),
+                None        => ()
+              }
+
+              return
+            },
+            None => ()
+          }
+        }
+
+        trace(reactor, &caller, &subject, &message,
+              || format!("native {:p}", function as *const ()));
+
         return function(reactor, Params {
           caller:  caller,
           subject: subject,
           message: message
-        }),
+        })
+      },
 
       // Otherwise, we need to check if this receiver is stageable (Execution
       // or Alien) or not.
       ObjectReceiver(receiver) =>
         match clone::stageable(&receiver, reactor.machine()) {
           Some(clone) => {
+            trace(reactor, &caller, &subject, &message,
+                  || receiver.to_string());
+
             // If it is, we construct a params object `[, caller, subject,
-            // message]` and `React` a clone of the receiver with the params
-            // object as the response.
+            // message]` (reusing an old one if we can; see `Cache::params()`)
+            // and `React` a clone of the receiver with the params object as
+            // the response.
             //
             // TODO: Find a way to not have to clone it all the time.
-            let mut params = Meta::new();
+            let params = reactor.cache().params(caller, subject, message);
 
-            params.members.set(1, caller);
-            params.members.set(2, subject);
-            params.members.set(3, message);
-
-            return reactor.stage(clone, Thing::create(params))
+            return reactor.stage(clone, params)
           },
 
           None => {
@@ -185,6 +399,48 @@ pub fn combine<R: Reactor>(
   }
 }
 
+/// Records a combination in the machine's trace, if it's enabled. `receiver`
+/// is only called to describe the receiver if it is.
+fn trace<R: Reactor>(
+         reactor:  &R,
+         caller:   &ObjectRef,
+         subject:  &ObjectRef,
+         message:  &ObjectRef,
+         receiver: || -> String) {
+
+  let trace = reactor.tracer();
+
+  if trace.is_enabled() {
+    trace.record(caller, subject, message, receiver().as_slice());
+  }
+}
+
+local_data_key!(current_span_key: Span)
+
+/// Returns the span of the source that the combination currently being carried
+/// out on this task came from, if known.
+///
+/// Set by `realize()` for Executions, to the span of the combination they just
+/// produced, and by call-pattern Aliens, to the span of the combination their
+/// caller is waiting on. Used to point warnings and failures at the source.
+pub fn current_span() -> Option<Span> {
+  current_span_key.get().map(|span| (*span).clone())
+}
+
+/// Sets the span returned by `current_span()` for this task.
+pub fn set_current_span(span: Option<Span>) {
+  current_span_key.replace(span);
+}
+
+/// Formats `current_span()` to be appended to a message, as `" (at
+/// file:line:column)"`, or nothing if it isn't known.
+pub fn at_current_span() -> String {
+  match current_span() {
+    Some(span) => format!(" (at {})", span),
+    None       => String::new()
+  }
+}
+
 /// Realizes an Execution (or Alien) with the given response.
 ///
 /// In the case of Executions, this causes the Execution to be advanced with
@@ -195,10 +451,26 @@ pub fn combine<R: Reactor>(
 pub fn realize<R: Reactor>(
                reactor:       &mut R,
                execution_ref: ObjectRef,
-               response_ref:  ObjectRef) {
+               response_ref:  ObjectRef)
+               -> Realized {
+
+  let replay = reactor.machine().replay.clone();
+
+  if replay.is_enabled() {
+    replay.begin(&execution_ref, &response_ref);
+  }
+
+  // Anything waiting on an Execution that has since `abandon[]`ed some of
+  // its responsibility should get its chance first.
+  let responsibility = reactor.machine().responsibility.clone();
+
+  if responsibility.is_enabled() {
+    retry(reactor, responsibility.take_ready());
+  }
+
   // Detect whether `execution_ref` is an Execution, an Alien, or
   // something else, and handle those cases separately.
-  match execution_ref.lock().try_cast::<Execution>() {
+  let realized = match execution_ref.lock().try_cast::<Execution>() {
     Ok(mut execution) => {
       // For an Execution, we just want to advance() it and have the
       // Machine process the combination if there was one.
@@ -206,15 +478,120 @@ pub fn realize<R: Reactor>(
       debug!("realize execution {} \t<-- {}",
         execution_ref, response_ref);
 
-      match execution.advance(response_ref) {
-        Some(combination) =>
-          // Calls the receiver and all that jazz.
-          combine(reactor, execution.unlock().clone(), combination),
+      let continuations = reactor.machine().continuations.clone();
+
+      continuations.resumed(&execution_ref);
+
+      let budget        = reactor.machine().preempt_after;
+      let resume_marker = reactor.machine().resume_marker.clone();
+
+      // Lookups in the Execution's own locals can be carried out right here,
+      // unless every combination has to be accounted for on its own.
+      let inline = !responsibility.is_enabled() && !replay.is_enabled();
+
+      let mut response_ref = response_ref;
+
+      // What's left of the budget, shared by every pass through the loop.
+      let mut remaining = budget;
+
+      loop {
+        if execution.is_preempted() && response_ref != resume_marker {
+          // This got here before the marker (on a pool, for instance), so
+          // it's for after the Execution has carried on.
+          drop(execution);
+
+          reactor.stage(execution_ref.clone(), response_ref);
+          break
+        }
+
+        let start = execution.pc();
+
+        match execution.advance_within(response_ref.clone(), remaining) {
+          Some(combination) => {
+            let complete = execution.is_complete();
+            let span     = execution.last_span();
+            let spent    = execution.pc() - start;
+
+            drop(execution);
+
+            set_current_span(span.clone());
+
+            // Carry on with the result rather than staging it, if we can.
+            if inline {
+              match lookup_local(reactor, &execution_ref, &combination) {
+                Some(value) => {
+                  if complete {
+                    release(reactor, &execution_ref);
+                  }
+
+                  remaining = remaining.map(|left| left.saturating_sub(spent));
+
+                  // Out of budget, so it carries on with the value after
+                  // everything else has had a turn.
+                  if remaining == Some(0) {
+                    reactor.stage(execution_ref.clone(), value);
+                    break
+                  }
+
+                  execution = execution_ref.lock().try_cast::<Execution>()
+                                .ok().expect("Execution stopped being one!");
+                  response_ref = value;
+                  continue
+                },
+
+                None => ()
+              }
+            }
 
-        None =>
-          // This execution is already complete, so we can't do anything.
-          debug!("execution {} complete", execution_ref)
+            if continuations.is_enabled() {
+              let site = match span {
+                Some(span) => format!("{} at {}", combination, span),
+                None       => format!("{}", combination)
+              };
+
+              continuations.waiting(&execution_ref, site);
+            }
+
+            // Calls the receiver and all that jazz.
+            combine(reactor, execution_ref.clone(), combination);
+
+            // That was the last combination, so the execution is done with
+            // everything it was responsible for.
+            if complete {
+              release(reactor, &execution_ref);
+            }
+          },
+
+          None if execution.is_preempted() => {
+            // It ran out of budget, so everything else gets a turn before it
+            // carries on with the resume marker.
+            debug!("execution {} preempted", execution_ref);
+
+            drop(execution);
+
+            reactor.stage(execution_ref.clone(), resume_marker.clone())
+          },
+
+          None => {
+            // This execution is already complete, so we can't do anything,
+            // but anything waiting for that can carry on with its final
+            // response.
+            debug!("execution {} complete", execution_ref);
+
+            drop(execution);
+
+            let hooks = reactor.machine().completions.take(&execution_ref);
+
+            for hook in hooks.move_iter() {
+              reactor.stage(hook, response_ref.clone());
+            }
+          }
+        }
+
+        break
       }
+
+      RealizedExecution
     },
 
     Err(execution_ish) =>
@@ -226,14 +603,119 @@ pub fn realize<R: Reactor>(
           debug!("realize alien     {} \t<-- {}",
             execution_ref, response_ref);
 
-          Alien::realize(alien, reactor, response_ref)
+          // Only call-pattern Aliens know where they were called from; see
+          // `current_span()`.
+          set_current_span(None);
+
+          Alien::realize(alien, reactor, response_ref);
+
+          RealizedAlien
         },
 
-        Err(_) =>
+        Err(_) => {
           // Finally, if it was neither an Execution nor an Alien, it
           // really shouldn't have been given to us and we'll just pretend it
           // wasn't.
-          warn!("tried to realize non-stageable {}!", execution_ref)
+          machine_warn!(reactor.machine(), "reactor",
+                        "tried to realize non-stageable {}!", execution_ref);
+
+          NotRealized
+        }
+      }
+  };
+
+  if replay.is_enabled() {
+    replay.end();
+  }
+
+  realized
+}
+
+/// Carries out a combination right away if it looks up a Symbol in `caller`'s
+/// locals, and those locals still have `locals_receiver` (or
+/// `lookup_receiver`), returning what the receiver would have staged `caller`
+/// with. This is what every statement starting with a bare symbol does (see
+/// `LookupLocal`), so skipping the trip through the queue adds up.
+///
+/// `None` if it's any other combination, or if nothing was found, in which case
+/// `combine()` should carry it out as usual.
+fn lookup_local<R: Reactor>(
+                reactor:     &mut R,
+                caller:      &ObjectRef,
+                combination: &Combination)
+                -> Option<ObjectRef> {
+
+  let message = match *combination {
+    Combination { subject: FromLocals, message: From(ref message) } => message,
+    _ => return None
+  };
+
+  let symbol = match message.symbol_ref() {
+    Some(symbol) => symbol.clone(),
+    None         => return None
+  };
+
+  let locals_sym = reactor.machine().locals_sym.symbol_ref().unwrap().clone();
+
+  let locals = match reactor.cache().sym_lookup(caller.clone(), locals_sym) {
+    Some(locals) => locals,
+    None         => return None
+  };
+
+  let result = match reactor.cache().receiver(locals.clone()) {
+    NativeReceiver(function)
+      if function as *const () == locals_receiver as *const () => {
+
+      let is_name = match locals.lock().try_cast::<Locals>() {
+        Ok(guard) => message.eq_as_symbol(guard.deref().name()),
+        Err(_)    => false
+      };
+
+      if is_name {
+        Some(locals.clone())
+      } else {
+        reactor.cache().sym_lookup(locals.clone(), symbol)
       }
+    },
+
+    NativeReceiver(function)
+      if function as *const () == lookup_receiver as *const () =>
+        reactor.cache().sym_lookup(locals.clone(), symbol),
+
+    _ => None
+  };
+
+  if result.is_some() {
+    trace(reactor, caller, &locals, message, || "lookup".to_string());
+  }
+
+  result
+}
+
+/// Releases everything `execution` is responsible for, and retries whatever
+/// was waiting on it. See `responsibility`.
+fn release<R: Reactor>(reactor: &mut R, execution: &ObjectRef) {
+  let responsibility = reactor.machine().responsibility.clone();
+
+  if !responsibility.is_enabled() { return }
+
+  retry(reactor, responsibility.release(execution));
+}
+
+/// Tries again what was waiting on an Execution's responsibility. Anything
+/// still blocked just goes back to waiting.
+fn retry<R: Reactor>(reactor: &mut R, blocked: Vec<Blocked>) {
+  let responsibility = reactor.machine().responsibility.clone();
+
+  for blocked in blocked.move_iter() {
+    match blocked {
+      BlockedCombination(caller, combination) =>
+        combine(reactor, caller, combination),
+
+      BlockedAdoption(caller, object) =>
+        if responsibility.adopt(&caller, &object) {
+          reactor.stage(caller, object);
+        }
+    }
   }
 }
@@ -1,29 +1,79 @@
-use super::Reactor;
+use super::{Reactor, ReactorStats};
+use super::{RunStatus, Completed, BudgetExhausted, Waiting};
+use super::{Remote, RemoteSink};
 use super::realize;
+use super::{SchedulerPolicy, SchedulerView, RoundRobin};
 
 use machine::Machine;
 
 use object::{ObjectRef, Cache};
 
-use std::collections::{Deque, RingBuf};
+use std::collections::{Deque, RingBuf, HashMap};
 use std::mem::replace;
+use std::uint;
 use std::vec::unzip;
 use std::sync::{Arc, Mutex};
 use std::sync::atomics::{AtomicBool, AtomicUint, SeqCst};
-use std::task;
+use std::sync::deque::{BufferPool, Worker, Stealer, Data, Empty, Abort};
+use std::task::TaskBuilder;
+use std::time::duration::Duration;
+
+use time;
+
+/// An execution and the response to realize it with.
+type Staging = (ObjectRef, ObjectRef);
+
+/// How many stagings a reactor realizes before checking its messages again.
+static BATCH_SIZE: uint = 64;
 
 enum ReactorMessage {
   Do(proc (&mut ParallelReactor): Send),
   Stage(ObjectRef, ObjectRef),
+  Wake,
   Stall,
+  Pause(uint),
   Stop
 }
 
+/// Shared state for `ReactorPool::with_world_stopped()`.
+struct WorldStop {
+  /// Whether someone is stopping the world right now. Only one caller may do
+  /// so at a time.
+  stopped:    bool,
+
+  /// How many reactors have paused for the current stop.
+  paused:     uint,
+
+  /// Bumped every time the world is let go, so that paused reactors (and
+  /// `Pause` messages that arrive too late) can tell.
+  generation: uint
+}
+
 /// A pool of `ParallelReactor`s running in parallel.
 ///
 /// The number of reactors must be configured at creation and can not be
 /// dynamically configured.
 ///
+/// Each general reactor keeps the stagings it makes in its own work-stealing
+/// deque and works through them itself, while reactors that run out of work
+/// steal from the others. The owner takes its newest stagings first, which
+/// tend to have their objects still in its cache, while thieves take the
+/// oldest ones from the other end. Channels are only used for control
+/// messages (like stalls and stopping), to wake sleeping reactors when there's
+/// work to steal, and for routed work.
+///
+/// Besides the general reactors, which share normal work between them, a pool
+/// may contain specialized reactors, each with a name (its specialty), such as
+/// one reactor dedicated to I/O. Executions can be routed to a specialty with
+/// `route()`, after which any staging of them lands on that reactor. A
+/// specialized reactor never picks up unrouted work; it passes it on to the
+/// general reactors.
+///
+/// Which general reactor gets work that's sent to it from outside (rather than
+/// staged by a general reactor onto its own deque) is up to the pool's
+/// `SchedulerPolicy`, which is `RoundRobin` unless given to
+/// `spawn_with_scheduler()`.
+///
 /// # Warning
 ///
 /// `ParallelReactor` is in an early stage of development and may not comply
@@ -34,17 +84,41 @@ pub struct ReactorPool {
   /// The machine context the pool operates within.
   machine:        Machine,
 
-  /// The next channel to use within this instance. Incremented automatically
-  /// for round robin distribution.
-  next:           uint,
+  /// Chooses which general reactor gets work sent to it.
+  scheduler:      Arc<Box<SchedulerPolicy+Send+Sync>>,
+
+  /// How many stagings have been sent to each general reactor that it hasn't
+  /// picked up yet. See `SchedulerView::backlog()`.
+  backlog:        Arc<Vec<AtomicUint>>,
 
   /// The index on `channels` of the reactor that owns this `ReactorPool`
   /// instance, if any.
   me:             Option<uint>,
 
   /// Senders to all reactors in the pool, including this one (if owned).
+  ///
+  /// The general reactors come first, followed by one for each specialty.
   channels:       Vec<Sender<ReactorMessage>>,
 
+  /// Handles to steal stagings from the deques of each of the general
+  /// reactors, in the same order as `channels`.
+  stealers:       Vec<Stealer<Staging>>,
+
+  /// General reactors that ran out of work and are waiting for a message, to be
+  /// woken up when there's something to steal.
+  sleepers:       Arc<Mutex<Vec<uint>>>,
+
+  /// The number of general reactors in the pool.
+  general:        uint,
+
+  /// The names of the specialized reactors, in the order they appear in
+  /// `channels` after the general ones.
+  specialties:    Arc<Vec<String>>,
+
+  /// Executions that have been routed to a specialized reactor, with the index
+  /// of that reactor within `channels`.
+  routes:         Arc<Mutex<HashMap<ObjectRef, uint>>>,
+
   /// Keeps a count of all reactors that are waiting for messages within the
   /// pool. It being equal to the total number of reactors in the pool is a
   /// condition for stall detection.
@@ -60,13 +134,30 @@ pub struct ReactorPool {
   pending:        Arc<AtomicUint>,
 
   /// Decides whether to notify other reactors if a stall is detected. Set to
-  /// true every time the `stagings` queue within any instance is found to be
-  /// non-empty. Set to false once a `Stall` message is sent out.
+  /// true every time any instance finds work to do. Set to false once a `Stall`
+  /// message is sent out.
   notify_stall:   Arc<AtomicBool>,
 
+  /// Set by `shutdown()`. Work from outside of the pool is refused from then
+  /// on, and stalls finish the shutdown instead of calling stall handlers.
+  shutting_down:  Arc<AtomicBool>,
+
+  /// How many more stagings may be realized once shutting down.
+  drain_left:     Arc<AtomicUint>,
+
+  /// Set by whichever reactor finishes the shutdown, so that the finalizers
+  /// are only run once.
+  finishing:      Arc<AtomicBool>,
+
   /// Determines how many reactors have yet to exit. The condition variable is
   /// used to wait/signal.
-  stop_sig:       Arc<Mutex<uint>>
+  stop_sig:       Arc<Mutex<uint>>,
+
+  /// The final statistics of each reactor that has exited.
+  finished:       Arc<Mutex<Vec<ReactorStats>>>,
+
+  /// Serializes `with_world_stopped()` and lets the paused reactors go.
+  world:          Arc<Mutex<WorldStop>>
 }
 
 impl ReactorPool {
@@ -75,33 +166,91 @@ impl ReactorPool {
   ///
   /// The number of reactors can not be changed after the pool is spawned.
   pub fn spawn(machine: Machine, reactors: uint) -> ReactorPool {
+    ReactorPool::spawn_with_specialties(machine, reactors, [])
+  }
+
+  /// Creates a new `ReactorPool` with `general` general reactors and one
+  /// specialized reactor for each of `specialties`.
+  ///
+  /// As with `spawn()`, the reactors can not be changed after the pool is
+  /// spawned.
+  pub fn spawn_with_specialties(machine:     Machine,
+                                general:     uint,
+                                specialties: &[&str])
+                                -> ReactorPool {
+
+    ReactorPool::spawn_with_scheduler(machine, general, specialties,
+                                      box RoundRobin::new())
+  }
+
+  /// Like `spawn_with_specialties()`, but uses `scheduler` to decide which
+  /// general reactor gets work sent to it.
+  pub fn spawn_with_scheduler(machine:     Machine,
+                              general:     uint,
+                              specialties: &[&str],
+                              scheduler:   Box<SchedulerPolicy+Send+Sync>)
+                              -> ReactorPool {
+
+    let reactors = general + specialties.len();
+
     if reactors < 2 {
       fail!("must spawn at least two ParallelReactors!");
     }
 
+    if general < 1 {
+      fail!("must spawn at least one general ParallelReactor!");
+    }
+
     let (senders, receivers) =
       unzip(range(0, reactors).map(|_| channel::<ReactorMessage>()));
 
+    let buffers: BufferPool<Staging> = BufferPool::new();
+
+    let (workers, stealers): (Vec<Worker<Staging>>, Vec<Stealer<Staging>>) =
+      unzip(range(0, general).map(|_| buffers.deque()));
+
     let pool = ReactorPool {
       machine:  machine,
 
-      next:         0,
+      scheduler:    Arc::new(scheduler),
+      backlog:      Arc::new(range(0, general).map(|_| AtomicUint::new(0))
+                                              .collect()),
       me:           None,
       channels:     senders,
+      stealers:     stealers,
+      sleepers:     Arc::new(Mutex::new(Vec::new())),
+
+      general:      general,
+      specialties:  Arc::new(specialties.iter().map(|s| s.to_string())
+                                          .collect()),
+      routes:       Arc::new(Mutex::new(HashMap::new())),
 
       waiting:      Arc::new(AtomicUint::new(0)),
       pending:      Arc::new(AtomicUint::new(0)),
       notify_stall: Arc::new(AtomicBool::new(true)),
 
-      stop_sig:     Arc::new(Mutex::new(reactors))
+      shutting_down: Arc::new(AtomicBool::new(false)),
+      drain_left:    Arc::new(AtomicUint::new(uint::MAX)),
+      finishing:     Arc::new(AtomicBool::new(false)),
+
+      stop_sig:     Arc::new(Mutex::new(reactors)),
+      finished:     Arc::new(Mutex::new(Vec::new())),
+      world:        Arc::new(Mutex::new(WorldStop {
+                      stopped:    false,
+                      paused:     0,
+                      generation: 0
+                    }))
     };
 
+    let mut workers = workers.move_iter();
+
     for (index, receiver) in receivers.move_iter().enumerate() {
       let mut pool = pool.clone();
 
       pool.me = Some(index);
 
-      ParallelReactor::spawn(receiver, pool)
+      // Only the general reactors get deques.
+      ParallelReactor::spawn(receiver, workers.next(), pool)
     }
 
     pool
@@ -116,6 +265,69 @@ impl ReactorPool {
     }
   }
 
+  /// Asks each of the reactors in the pool for its current statistics, and
+  /// waits for them to answer.
+  ///
+  /// Reactors that have stopped can't answer, so they're left out. If called
+  /// on a reactor's own `ReactorPool`, that reactor is left out too, since it
+  /// can't answer while it's waiting.
+  pub fn reactor_stats(&self) -> Vec<ReactorStats> {
+    let (tx, rx) = channel();
+
+    for (index, channel) in self.channels.iter().enumerate() {
+      if Some(index) == self.me { continue }
+
+      let tx = tx.clone();
+
+      self.pending.fetch_add(1, SeqCst);
+
+      let ask = Do(proc (reactor) tx.send(reactor.stats()));
+
+      if channel.send_opt(ask).is_err() {
+        self.pending.fetch_sub(1, SeqCst);
+      }
+    }
+
+    // Any reactor that stops before answering drops its sender, so this ends
+    // once everyone has either answered or stopped.
+    drop(tx);
+
+    let mut stats = Vec::new();
+
+    loop {
+      match rx.recv_opt() {
+        Ok(reactor_stats) => stats.push(reactor_stats),
+        Err(_)            => break
+      }
+    }
+
+    stats
+  }
+
+  /// Statistics for the pool as a whole: the sum of `reactor_stats()`, or once
+  /// the reactors have all stopped, of `finished_stats()`.
+  pub fn stats(&self) -> ReactorStats {
+    let mut stats = self.reactor_stats();
+
+    if stats.is_empty() {
+      stats = self.finished_stats();
+    }
+
+    let mut total = ReactorStats::new();
+
+    for reactor_stats in stats.iter() {
+      total.add(reactor_stats);
+    }
+
+    total
+  }
+
+  /// Gets the final statistics of each of the reactors that have exited, in the
+  /// order they exited. After `wait()`, that's all of them.
+  pub fn finished_stats(&self) -> Vec<ReactorStats> {
+    self.finished.lock().clone()
+  }
+
   /// Tell all reactors to stop.
   pub fn stop(&self) {
     self.pending.fetch_add(self.len(), SeqCst);
@@ -125,32 +337,289 @@ impl ReactorPool {
     }
   }
 
-  /// Run a procedure on one of the reactors in this pool.
+  /// Shuts the pool down gracefully. See `Reactor::shutdown()`: `stage()` and
+  /// `Remote`s are refused from now on, at most `limit` more stagings are
+  /// realized across the whole pool, and then one of the reactors runs the
+  /// Machine's finalizers before they all stop. Use `wait()` to wait for all
+  /// of that to happen.
+  pub fn shutdown(&self, limit: uint) {
+    // `drain_left` starts out at its maximum, so a reactor that sees the flag
+    // before this has been stored doesn't finish early.
+    if self.shutting_down.swap(true, SeqCst) { return }
+
+    self.drain_left.store(limit, SeqCst);
+
+    // A pool that has already run out of work has to be woken up to notice.
+    self.notify_stall.store(true, SeqCst);
+
+    self.pending.fetch_add(self.len(), SeqCst);
+
+    for channel in self.channels.iter() {
+      let _ = channel.send_opt(Wake);
+    }
+  }
+
+  /// Pauses every reactor in the pool at a safe point (between stagings), runs
+  /// `closure` while none of them are doing anything, and then lets them go.
   ///
-  /// Which reactor is chosen is not defined; it could be any of them.
+  /// Nothing in the object graph changes while `closure` runs, except by
+  /// `closure` itself, which makes it possible to take consistent snapshots,
+  /// count objects, or rearrange the graph without racing the reactors. The
+  /// pool's own queues are left alone.
+  ///
+  /// If this is called on a reactor's own `ReactorPool`, that reactor is
+  /// already at a safe point (it's running this), so only the others are
+  /// paused.
+  ///
+  /// Concurrent calls take turns. A reactor waiting for its turn counts as
+  /// paused for whoever's turn it is, so two reactors stopping the world at
+  /// once don't wait on each other forever.
+  pub fn with_world_stopped<T>(&self, closure: || -> T) -> T {
+    let generation = {
+      let mut state = self.world.lock();
+
+      while state.stopped {
+        let generation = state.generation;
+
+        if self.me.is_some() {
+          state.paused += 1;
+          state.cond.broadcast();
+        }
+
+        while state.generation == generation {
+          state.cond.wait();
+        }
+      }
+
+      state.stopped = true;
+      state.generation
+    };
+
+    let mut targets = 0u;
+
+    for (index, channel) in self.channels.iter().enumerate() {
+      if Some(index) == self.me { continue }
+
+      self.pending.fetch_add(1, SeqCst);
+
+      if channel.send_opt(Pause(generation)).is_ok() {
+        targets += 1;
+      } else {
+        // That reactor is gone, so it can't do anything anyway.
+        self.pending.fetch_sub(1, SeqCst);
+      }
+    }
+
+    {
+      let state = self.world.lock();
+
+      while state.paused < targets {
+        state.cond.wait();
+      }
+    }
+
+    let result = closure();
+
+    {
+      let mut state = self.world.lock();
+
+      // Paused reactors don't count themselves out again, so that the next
+      // stop can start counting right away.
+      state.stopped     = false;
+      state.paused      = 0;
+      state.generation += 1;
+
+      state.cond.broadcast();
+    }
+
+    result
+  }
+
+  /// Stages `execution` with `response` on one of the reactors in this pool:
+  /// the one it's routed to, if any, or otherwise a general reactor chosen by
+  /// the pool's `SchedulerPolicy` (preferring one that's asleep).
+  ///
+  /// Does nothing once the pool is shutting down.
+  pub fn stage(&self, execution: ObjectRef, response: ObjectRef) {
+    if self.shutting_down.load(SeqCst) { return }
+
+    let index = match self.route_for(&execution) {
+      Some(index) => index,
+      None        => self.idle_index(&execution)
+    };
+
+    self.send_stage(index, execution, response);
+  }
+
+  /// Run a procedure on one of the general reactors in this pool.
+  ///
+  /// Which reactor is chosen is up to the pool's `SchedulerPolicy`.
   pub fn on_reactor(&mut self, block: proc (&mut ParallelReactor): Send) {
+    let index = self.choose(None);
+
     self.pending.fetch_add(1, SeqCst);
 
-    let _ = self.next_channel().send_opt(Do(block));
+    let _ = self.channels[index].send_opt(Do(block));
   }
 
-  /// Get the next channel in round robin order.
+  /// Run a procedure on the reactor with the given specialty.
   ///
-  /// If owned, skips the reactor that owns this `ReactorPool` instance.
-  fn next_channel(&mut self) -> &Sender<ReactorMessage> {
-    let next;
+  /// Fails if there is no such specialty in this pool.
+  pub fn on_special(&self,
+                    specialty: &str,
+                    block:     proc (&mut ParallelReactor): Send) {
 
-    if Some(self.next) == self.me {
-      next = (self.next + 1) % self.len();
+    let index = self.specialty_index(specialty);
 
-      self.next = (self.next + 2) % self.len();
+    self.pending.fetch_add(1, SeqCst);
+
+    let _ = self.channels[index].send_opt(Do(block));
+  }
+
+  /// Routes all future stagings of `execution` to the reactor with the given
+  /// specialty, replacing any previous route for it.
+  ///
+  /// Fails if there is no such specialty in this pool.
+  pub fn route(&self, execution: ObjectRef, specialty: &str) {
+    let index = self.specialty_index(specialty);
+
+    self.routes.lock().insert(execution, index);
+  }
+
+  /// Removes any route for `execution`, so that its stagings are balanced
+  /// across the general reactors again.
+  pub fn unroute(&self, execution: &ObjectRef) {
+    self.routes.lock().pop(execution);
+  }
+
+  /// The names of the specialized reactors in this pool.
+  pub fn specialties(&self) -> &[String] {
+    self.specialties.as_slice()
+  }
+
+  /// The specialty of the reactor that owns this instance, if it's owned by a
+  /// specialized reactor.
+  fn specialty(&self) -> Option<&str> {
+    match self.me {
+      Some(index) if index >= self.general =>
+        Some(self.specialties[index - self.general].as_slice()),
+
+      _ => None
+    }
+  }
+
+  /// Gets the index within `channels` of the reactor with the given specialty.
+  fn specialty_index(&self, specialty: &str) -> uint {
+    match self.specialties.iter().position(|s| s.as_slice() == specialty) {
+      Some(position) => self.general + position,
+      None           => fail!("no reactor with specialty {}", specialty)
+    }
+  }
+
+  /// Gets the index of the reactor `execution` has been routed to, if any.
+  fn route_for(&self, execution: &ObjectRef) -> Option<uint> {
+    if self.specialties.is_empty() {
+      None
     } else {
-      next = self.next;
+      self.routes.lock().find(execution).map(|index| *index)
+    }
+  }
+
+  /// Wakes up one of the sleeping general reactors, if there are any, so that
+  /// it can steal some work.
+  fn wake_one(&self) {
+    // Avoid taking the lock if no one could possibly be sleeping.
+    if self.waiting.load(SeqCst) == 0 { return }
+
+    let sleeper = self.sleepers.lock().pop();
+
+    match sleeper {
+      Some(index) => {
+        self.pending.fetch_add(1, SeqCst);
+
+        let _ = self.channels[index].send_opt(Wake);
+      },
+
+      None => ()
+    }
+  }
+
+  /// Get the index of one of the sleeping general reactors, if there are any,
+  /// or otherwise whichever one the scheduler chooses for `execution`.
+  ///
+  /// Better than `choose()` for sending work, since a sleeping reactor can
+  /// start on it right away, rather than it waiting in a busy reactor's deque
+  /// to be stolen.
+  fn idle_index(&self, execution: &ObjectRef) -> uint {
+    // Avoid taking the lock if no one could possibly be sleeping.
+    let sleeper =
+      if self.waiting.load(SeqCst) == 0 {
+        None
+      } else {
+        self.sleepers.lock().pop()
+      };
+
+    match sleeper {
+      Some(index) => index,
+      None        => self.choose(Some(execution))
+    }
+  }
 
-      self.next = (self.next + 1) % self.len();
+  /// Sends a staging to the reactor at `index` within `channels`, counting it
+  /// as pending (and as backlog, for a general reactor).
+  fn send_stage(&self, index: uint, execution: ObjectRef, response: ObjectRef) {
+    self.pending.fetch_add(1, SeqCst);
+
+    if index < self.general {
+      self.backlog[index].fetch_add(1, SeqCst);
+    }
+
+    // We don't really care whether this succeeds or not -- if it doesn't, the
+    // reactors are stopping so it wouldn't matter.
+    let _ = self.channels[index].send_opt(Stage(execution, response));
+  }
+
+  /// Whether another staging may be realized: always, unless the pool is
+  /// shutting down, in which case it uses up one of those `shutdown()` allows.
+  fn may_realize(&self) -> bool {
+    if !self.shutting_down.load(SeqCst) { return true }
+
+    loop {
+      let left = self.drain_left.load(SeqCst);
+
+      if left == 0 { return false }
+
+      if self.drain_left.compare_and_swap(left, left - 1, SeqCst) == left {
+        return true
+      }
     }
+  }
 
-    &self.channels[next]
+  /// Whether this instance is owned by a general reactor (or not owned at all).
+  fn is_general(&self) -> bool {
+    match self.me {
+      Some(index) => index < self.general,
+      None        => true
+    }
+  }
+
+  /// Asks the scheduler for the index of a general reactor to send work for
+  /// `execution` (or a procedure) to.
+  fn choose(&self, execution: Option<&ObjectRef>) -> uint {
+    let me = if self.is_general() { self.me } else { None };
+
+    let view = SchedulerView::new(self.general, me, self.backlog.as_slice());
+
+    self.scheduler.choose(&view, execution) % self.general
+  }
+
+  /// Asks the scheduler whether work for `execution` staged by this general
+  /// reactor should go to another general reactor instead of its own deque.
+  fn redirect(&self, execution: &ObjectRef) -> Option<uint> {
+    let view = SchedulerView::new(self.general, self.me,
+                                  self.backlog.as_slice());
+
+    self.scheduler.redirect(&view, execution).map(|index| index % self.general)
   }
 }
 
@@ -177,31 +646,68 @@ pub struct ParallelReactor {
   /// The pool the reactor belongs to.
   pool:           ReactorPool,
 
-  /// A queue of `stage()` calls assigned to the reactor.
-  stagings:       RingBuf<(ObjectRef, ObjectRef)>,
+  /// The reactor's work-stealing deque of stagings, if it's a general reactor.
+  /// Other reactors may steal from the other end at any time.
+  worker:         Option<Worker<Staging>>,
+
+  /// Stagings that were routed to this reactor and so can't be stolen.
+  pinned:         RingBuf<Staging>,
 
   /// Procedures to be called in the event the pool encounters a stall.
   stall_handlers: Vec<proc (&mut Reactor)>,
 
   /// Our local cache.
-  cache:          Cache
+  cache:          Cache,
+
+  /// The counters from `stats()`, kept up to date as we go. `cache` and
+  /// `queue_depth` are filled in when they're asked for.
+  counts:         ReactorStats,
+
+  /// The general reactor we last stole from, which is where we look first next
+  /// time, since a reactor that's producing a lot of work probably still is.
+  last_victim:    Option<uint>,
+
+  /// Our id in the Machine's metrics. See `machine::metrics`.
+  metrics_id:     uint
 }
 
 impl ParallelReactor {
-  fn spawn(receiver: Receiver<ReactorMessage>, pool: ReactorPool) {
-    task::spawn(proc () {
+  fn spawn(receiver: Receiver<ReactorMessage>,
+           worker:   Option<Worker<Staging>>,
+           pool:     ReactorPool) {
+
+    let name = match pool.specialty() {
+      Some(specialty) => format!("ParallelReactor ({})", specialty),
+      None            => format!("ParallelReactor #{}", pool.me.unwrap())
+    };
+
+    let metrics_id = pool.machine.metrics.register(name.as_slice());
+
+    TaskBuilder::new().named(name).spawn(proc () {
+      let cache = Cache::new_parallel_with(&pool.machine.cache_config)
+                    .with_shared(pool.machine.shared_cache.clone());
+
       let mut reactor = ParallelReactor {
         receiver:       receiver,
         pool:           pool,
-        stagings:       RingBuf::new(),
+        worker:         worker,
+        pinned:         RingBuf::new(),
         stall_handlers: Vec::new(),
-        cache:          Cache::new_parallel()
+        cache:          cache,
+        counts:         ReactorStats::new(),
+        last_victim:    None,
+        metrics_id:     metrics_id
       };
 
       reactor.run()
     })
   }
 
+  /// The specialty of this reactor, or `None` if it's a general reactor.
+  pub fn specialty(&self) -> Option<&str> {
+    self.pool.specialty()
+  }
+
   fn run(&mut self) {
     debug!("ParallelReactor started");
 
@@ -221,57 +727,103 @@ impl ParallelReactor {
         }
       }
 
-      // If we have work to do, do it. Otherwise check to see if all reactors
+      // If we have work to do (or can steal some), do a batch of it before
+      // checking for messages again. Otherwise check to see if all reactors
       // are stalled, and if so try to notify; if not, wait for a message.
-      if !self.stagings.is_empty() {
-        // Since we have work, set notify_stall to true so that stall
-        // notifications will happen if we find ourselves without work.
-        self.pool.notify_stall.store(true, SeqCst);
+      match self.next_staging() {
+        Some(staging) => {
+          // Since we have work, set notify_stall to true so that stall
+          // notifications will happen if we find ourselves without work.
+          self.pool.notify_stall.store(true, SeqCst);
+
+          let mut next  = Some(staging);
+          let mut count = 0u;
+
+          loop {
+            match next {
+              // Shutting down, and that's all we were allowed to do. The
+              // staging is dropped along with everything else.
+              Some(_) if !self.pool.may_realize() => {
+                self.finish();
+                break
+              },
+
+              Some((execution, response)) => {
+                if self.pool.is_general() {
+                  self.pool.scheduler.realizing(&execution,
+                                                self.pool.me.unwrap());
+                }
+
+                let realized = realize(self, execution, response);
+
+                self.counts.count(realized);
+              },
+
+              None => break
+            }
 
-        // Iterate through the work, being careful to only take how many there
-        // were initially.
-        let stagings_len = self.stagings.len();
+            count += 1;
 
-        for _ in range(0, stagings_len) {
-          let (execution, response) = self.stagings.pop_front().unwrap();
+            if count == BATCH_SIZE { break }
 
-          realize(self, execution, response)
-        }
-      } else {
-        let waiting = self.pool.waiting.fetch_add(1, SeqCst) + 1;
-        let pending = self.pool.pending.load(SeqCst);
+            next = self.next_staging();
+          }
+        },
+
+        None => {
+          let me = self.pool.me.unwrap();
 
-        debug!("waiting: {}/{}, pending: {}",
-               waiting, self.pool.len(), pending);
+          if self.pool.is_general() {
+            self.pool.sleepers.lock().push(me);
+          }
+
+          let waiting = self.pool.waiting.fetch_add(1, SeqCst) + 1;
+          let pending = self.pool.pending.load(SeqCst);
+
+          debug!("waiting: {}/{}, pending: {}",
+                 waiting, self.pool.len(), pending);
 
-        // Only attempt to notify the reactors if they are all waiting *and* all
-        // of the channels are empty (represented by `pending == 0`).
-        //
-        // If both of these are true, then nothing else could possibly change,
-        // so this is a safe assumption.
-        if waiting == self.pool.len() && pending == 0 {
+          // Only attempt to notify the reactors if they are all waiting *and*
+          // all of the channels are empty (represented by `pending == 0`).
+          //
+          // A general reactor only waits once its own deque is empty, and
+          // nothing can be added to it while it waits, so if both of these are
+          // true, nothing else could possibly change, and this is a safe
+          // assumption.
+          if waiting == self.pool.len() && pending == 0 {
 
-          // Only notify if no one else has notified yet.
-          if self.pool.notify_stall.swap(false, SeqCst) {
-            self.pool.pending.fetch_add(self.pool.len(), SeqCst);
+            // Only notify if no one else has notified yet.
+            if self.pool.notify_stall.swap(false, SeqCst) {
+              self.pool.pending.fetch_add(self.pool.len(), SeqCst);
 
-            for channel in self.pool.channels.iter() {
-              let _ = channel.send_opt(Stall);
+              for channel in self.pool.channels.iter() {
+                let _ = channel.send_opt(Stall);
+              }
             }
           }
-        }
 
-        let message = self.receiver.recv();
+          let message = self.receiver.recv();
 
-        self.pool.pending.fetch_sub(1, SeqCst);
-        self.pool.waiting.fetch_sub(1, SeqCst);
+          self.pool.pending.fetch_sub(1, SeqCst);
+          self.pool.waiting.fetch_sub(1, SeqCst);
 
-        if !self.handle_message(message) { break 'stop }
+          // We might have been woken by something other than a `Wake`, in
+          // which case we're still on the list.
+          if self.pool.is_general() {
+            self.pool.sleepers.lock().retain(|&index| index != me);
+          }
+
+          if !self.handle_message(message) { break 'stop }
+        }
       }
     }
 
     debug!("ParallelReactor stopped");
 
+    self.pool.finished.lock().push(self.stats());
+
+    self.report_metrics();
+
     let mut stop_sig = self.pool.stop_sig.lock();
     
     *stop_sig -= 1;
@@ -279,16 +831,112 @@ impl ParallelReactor {
     stop_sig.cond.broadcast();
   }
 
+  /// Gets the next staging to realize: routed work first, then our own deque,
+  /// and then whatever we can steal from the other general reactors.
+  fn next_staging(&mut self) -> Option<Staging> {
+    match self.pinned.pop_front() {
+      Some(staging) => return Some(staging),
+      None          => ()
+    }
+
+    match self.worker {
+      Some(ref worker) =>
+        match worker.pop() {
+          Some(staging) => {
+            self.counts.local_hits += 1;
+            return Some(staging)
+          },
+          None => ()
+        },
+
+      // Specialized reactors only do routed work.
+      None => return None
+    }
+
+    let stolen = self.steal();
+
+    if stolen.is_some() {
+      self.counts.steals += 1;
+    }
+
+    stolen
+  }
+
+  /// Tries to steal a staging from each of the other general reactors in turn,
+  /// starting with the last one we stole from.
+  fn steal(&mut self) -> Option<Staging> {
+    let me    = self.pool.me.unwrap();
+    let count = self.pool.stealers.len();
+    let start = self.last_victim.unwrap_or((me + 1) % count);
+
+    for offset in range(0, count) {
+      let victim = (start + offset) % count;
+
+      if victim == me { continue }
+
+      loop {
+        let stolen = self.pool.stealers[victim].steal();
+
+        match stolen {
+          Data(staging) => {
+            self.last_victim = Some(victim);
+            return Some(staging)
+          },
+
+          Empty => break,
+
+          // Lost a race with another thief or the owner; try again.
+          Abort => continue
+        }
+      }
+    }
+
+    self.last_victim = None;
+
+    None
+  }
+
   fn handle_message(&mut self, message: ReactorMessage) -> bool {
     match message {
       Do(block) =>
         block(self),
 
       Stage(execution, response) =>
-        self.stagings.push((execution, response)),
+        match self.worker {
+          Some(ref worker) => {
+            self.pool.backlog[self.pool.me.unwrap()].fetch_sub(1, SeqCst);
+
+            worker.push((execution, response));
+            self.pool.wake_one();
+          },
+
+          None => self.pinned.push((execution, response))
+        },
+
+      // Just here to get us to look for work.
+      Wake => (),
 
       Stall =>
-        self.stall(),
+        if self.pool.shutting_down.load(SeqCst) {
+          self.finish()
+        } else {
+          self.stall()
+        },
+
+      Pause(generation) => {
+        let mut state = self.pool.world.lock();
+
+        // Otherwise we already counted ourselves while waiting for our own
+        // turn, and that stop is over.
+        if state.generation == generation {
+          state.paused += 1;
+          state.cond.broadcast();
+
+          while state.generation == generation {
+            state.cond.wait();
+          }
+        }
+      },
 
       Stop =>
         return false
@@ -297,25 +945,120 @@ impl ParallelReactor {
     true
   }
 
+  /// Runs the Machine's finalizers and stops the pool, once it has drained
+  /// everything it's going to while shutting down. Only the first reactor to
+  /// get here does anything.
+  fn finish(&mut self) {
+    if self.pool.finishing.swap(true, SeqCst) { return }
+
+    let finalizers = self.pool.machine.finalizers.clone();
+
+    finalizers.run(self);
+
+    self.pool.stop();
+  }
+
   fn stall(&mut self) {
+    self.counts.stalls += 1;
+
+    self.pool.machine.continuations.report();
+
+    self.report_metrics();
+
     let stall_handlers = replace(&mut self.stall_handlers, Vec::new());
 
     for handler in stall_handlers.move_iter() {
       handler(&mut *self)
     }
   }
+
+  /// Realizes stagings from our own queues (and whatever we can steal) until
+  /// `budget` returns false or there are none left. `budget` is asked before
+  /// each staging.
+  ///
+  /// This never stalls or waits: stalls are detected by the pool as a whole,
+  /// and messages (like stagings routed here by other reactors) are left for
+  /// our own loop, which is running this. So with nothing left here, it's
+  /// `Waiting`, since the rest of the pool may make more work.
+  fn run_budgeted(&mut self, budget: || -> bool) -> RunStatus {
+    loop {
+      let (execution, response) = match self.next_staging() {
+        Some(staging) => staging,
+        None          => return Waiting
+      };
+
+      if !budget() {
+        // It's first in line once we get back to our own loop.
+        self.pinned.push_front((execution, response));
+
+        return BudgetExhausted
+      }
+
+      // Shutting down, and that's all we were allowed to do.
+      if !self.pool.may_realize() {
+        self.finish();
+
+        return Completed
+      }
+
+      if self.pool.is_general() {
+        self.pool.scheduler.realizing(&execution, self.pool.me.unwrap());
+      }
+
+      let realized = realize(self, execution, response);
+
+      self.counts.count(realized);
+    }
+  }
 }
 
 impl Reactor for ParallelReactor {
   fn stage(&mut self, execution: ObjectRef, response: ObjectRef) {
-    if self.stagings.is_empty() {
-      self.stagings.push((execution, response));
-    } else {
-      self.pool.pending.fetch_add(1, SeqCst);
+    self.pool.machine.trace.record_staging(&execution, &response);
+    self.pool.machine.replay.staged(&execution, &response);
+
+    self.counts.stagings += 1;
+
+    match self.pool.route_for(&execution) {
+      // Routed to us, so it has to be done here.
+      Some(index) if Some(index) == self.pool.me =>
+        return self.pinned.push((execution, response)),
+
+      // Routed to some other reactor.
+      Some(index) => {
+        self.pool.pending.fetch_add(1, SeqCst);
+
+        let _ = self.pool.channels[index].send_opt(
+          Stage(execution, response));
+
+        return
+      },
+
+      None => ()
+    }
 
-      // We don't really care whether this succeeds or not -- if it doesn't, the
-      // reactors are stopping so it wouldn't matter.
-      let _ = self.pool.next_channel().send_opt(Stage(execution, response));
+    // The scheduler might know of a better home for it.
+    if self.worker.is_some() {
+      match self.pool.redirect(&execution) {
+        Some(index) => return self.pool.send_stage(index, execution, response),
+        None        => ()
+      }
+    }
+
+    match self.worker {
+      // Keep it for ourselves, but let someone else steal it if they're idle.
+      Some(ref worker) => {
+        worker.push((execution, response));
+
+        self.pool.wake_one();
+      },
+
+      // We're specialized, so it belongs to the general reactors.
+      None => {
+        let index = self.pool.idle_index(&execution);
+
+        self.pool.send_stage(index, execution, response);
+      }
     }
   }
 
@@ -327,6 +1070,10 @@ impl Reactor for ParallelReactor {
     self.pool.stop()
   }
 
+  fn shutdown(&mut self, limit: uint) {
+    self.pool.shutdown(limit)
+  }
+
   fn machine(&self) -> &Machine {
     &self.pool.machine
   }
@@ -334,4 +1081,85 @@ impl Reactor for ParallelReactor {
   fn cache(&mut self) -> &mut Cache {
     &mut self.cache
   }
+
+  fn stats(&self) -> ReactorStats {
+    ReactorStats {
+      cache:       self.cache.stats().clone(),
+      // Stagings in the deque could be stolen at any moment, so only the
+      // routed ones are counted.
+      queue_depth: self.pinned.len(),
+      ..self.counts.clone()
+    }
+  }
+
+  fn remote(&mut self) -> Remote {
+    // Counting the delivery as pending from now on keeps the pool from
+    // stalling until it arrives.
+    self.pool.pending.fetch_add(1, SeqCst);
+
+    let index = self.pool.choose(None);
+
+    Remote::new(box PoolRemote {
+      channel:       self.pool.channels[index].clone(),
+      backlog:       self.pool.backlog.clone(),
+      shutting_down: self.pool.shutting_down.clone(),
+      index:         index
+    })
+  }
+
+  /// See `run_budgeted()`: this is for a reactor's own task, like from an
+  /// Alien, and it's never `Stalled`.
+  fn run_for(&mut self, steps: uint) -> RunStatus {
+    let mut remaining = steps;
+
+    self.run_budgeted(|| {
+      if remaining > 0 {
+        remaining -= 1;
+        true
+      } else {
+        false
+      }
+    })
+  }
+
+  /// See `run_for()`.
+  fn run_for_duration(&mut self, duration: Duration) -> RunStatus {
+    let start = time::precise_time_ns();
+    let limit = duration.num_nanoseconds().unwrap_or(::std::i64::MAX);
+
+    self.run_budgeted(|| {
+      ((time::precise_time_ns() - start) as i64) < limit
+    })
+  }
+
+  fn report_metrics(&self) {
+    self.pool.machine.metrics.report(self.metrics_id, self.stats());
+  }
+}
+
+/// Delivers to one of the general reactors of a pool, as a message that has
+/// already been counted as pending.
+struct PoolRemote {
+  channel:       Sender<ReactorMessage>,
+  backlog:       Arc<Vec<AtomicUint>>,
+  shutting_down: Arc<AtomicBool>,
+  index:         uint
+}
+
+impl RemoteSink for PoolRemote {
+  fn deliver(&mut self, staging: Option<(ObjectRef, ObjectRef)>) {
+    // Either way, the reactor needs to receive something to count the pending
+    // message off, and to look for work (or a stall) again. If the pool has
+    // stopped, it doesn't matter. Once it's shutting down, the staging itself
+    // is refused.
+    let _ = self.channel.send_opt(match staging {
+      Some((execution, response)) if !self.shutting_down.load(SeqCst) => {
+        self.backlog[self.index].fetch_add(1, SeqCst);
+
+        Stage(execution, response)
+      },
+
+      _ => Wake
+    });
+  }
 }
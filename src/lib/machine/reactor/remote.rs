@@ -0,0 +1,48 @@
+//! Staging from outside of a reactor.
+//!
+//! Reactors aren't `Send`, so anything running on another task (like blocking
+//! I/O; see `machine::blocking`) can't stage directly. Instead, it takes a
+//! `Remote` from the reactor beforehand with `Reactor::remote()`. A `Remote`
+//! stands for exactly one staging that hasn't happened yet: until it's used
+//! (or dropped), the reactor counts it as outstanding work, and won't consider
+//! itself stalled.
+
+use object::ObjectRef;
+
+/// Delivers the staging promised by a `Remote` to its reactor. `None` means the
+/// `Remote` was dropped without staging anything, which the reactor still needs
+/// to hear about to stop waiting for it.
+///
+/// Only reactors need to implement this.
+pub trait RemoteSink: Send {
+  /// Delivers the staging, or the lack of one. Called exactly once.
+  fn deliver(&mut self, staging: Option<(ObjectRef, ObjectRef)>);
+}
+
+/// A promise to stage something on a reactor from another task. See the module
+/// documentation.
+pub struct Remote {
+  sink: Option<Box<RemoteSink+Send>>
+}
+
+impl Remote {
+  /// Wraps a reactor-specific sink. Only reactors need to do this.
+  pub fn new(sink: Box<RemoteSink+Send>) -> Remote {
+    Remote { sink: Some(sink) }
+  }
+
+  /// Stages `execution` with `response` on the reactor this came from,
+  /// fulfilling the promise.
+  pub fn stage(mut self, execution: ObjectRef, response: ObjectRef) {
+    self.sink.take().unwrap().deliver(Some((execution, response)));
+  }
+}
+
+impl Drop for Remote {
+  fn drop(&mut self) {
+    match self.sink.take() {
+      Some(mut sink) => sink.deliver(None),
+      None           => ()
+    }
+  }
+}
@@ -0,0 +1,268 @@
+//! Responsibility: exclusive access to objects for the Executions that combine
+//! against them.
+//!
+//! From the spec, an Execution must be *responsible* for an object before it
+//! may act on it, and responsibility for an object extends to everything it
+//! holds by child relationship (see `Relationship::is_child()`). When enabled,
+//! `combine()` acquires responsibility for the subject of each combination on
+//! behalf of the caller. If another Execution already holds it, the
+//! combination is set aside until that Execution completes and releases
+//! everything it was responsible for.
+//!
+//! Executions can also take responsibility explicitly, with `infrastructure
+//! execution adopt[]`, and give it up early with `abandon[]`.
+//!
+//! Symbols and frozen objects can't be modified, so they're exempt.
+
+use machine::reactor::{Combination, From};
+
+use object::ObjectRef;
+
+use std::collections::{HashMap, HashSet};
+use std::mem::replace;
+use std::sync::{Arc, Mutex};
+use std::sync::atomics::{AtomicBool, Relaxed};
+
+/// Tracks which Executions are responsible for which objects. Disabled by
+/// default, since it costs a walk of the subject's children and a lock on
+/// every combination. Clones share the same state.
+#[deriving(Clone)]
+pub struct Responsibility {
+  enabled: Arc<AtomicBool>,
+  state:   Arc<Mutex<ResponsibilityState>>
+}
+
+struct ResponsibilityState {
+  /// The Execution responsible for each object.
+  owners:  HashMap<ObjectRef, ObjectRef>,
+
+  /// The objects each Execution is responsible for.
+  held:    HashMap<ObjectRef, Vec<ObjectRef>>,
+
+  /// What's waiting for each Execution to release its responsibility, in the
+  /// order it was blocked.
+  blocked: HashMap<ObjectRef, Vec<Blocked>>,
+
+  /// What was waiting for an Execution that has since abandoned some of its
+  /// responsibility, to be tried again by the next reactor to look. See
+  /// `take_ready()`.
+  ready:   Vec<Blocked>
+}
+
+/// Something waiting for another Execution to release its responsibility.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum Blocked {
+  /// A combination, with its caller, to be carried out.
+  BlockedCombination(ObjectRef, Combination),
+
+  /// An adoption: the Execution, and the object it wants to be responsible
+  /// for. The Execution should be staged with the object once it is.
+  BlockedAdoption(ObjectRef, ObjectRef)
+}
+
+impl Responsibility {
+  /// Creates a new, disabled tracker.
+  pub fn new() -> Responsibility {
+    Responsibility {
+      enabled: Arc::new(AtomicBool::new(false)),
+      state:   Arc::new(Mutex::new(ResponsibilityState {
+        owners:  HashMap::new(),
+        held:    HashMap::new(),
+        blocked: HashMap::new(),
+        ready:   Vec::new()
+      }))
+    }
+  }
+
+  /// Starts enforcing responsibility.
+  pub fn enable(&self) {
+    self.enabled.store(true, Relaxed);
+  }
+
+  /// Returns true if responsibility is being enforced.
+  pub fn is_enabled(&self) -> bool {
+    self.enabled.load(Relaxed)
+  }
+
+  /// Makes `caller` responsible for `subject` and everything it holds by child
+  /// relationship, so that `caller` can combine `message` against it.
+  ///
+  /// Returns false if some other Execution is already responsible for any of
+  /// those objects, in which case nothing is acquired, and the combination is
+  /// kept to be handed back by `release()` once that Execution is done.
+  pub fn acquire(&self,
+                 caller:  &ObjectRef,
+                 subject: &ObjectRef,
+                 message: &ObjectRef)
+                 -> bool {
+
+    self.try_acquire(caller, subject, || {
+      BlockedCombination(caller.clone(), Combination {
+        subject: From(subject.clone()),
+        message: From(message.clone())
+      })
+    })
+  }
+
+  /// Makes `caller` responsible for `object` and everything it holds by child
+  /// relationship, like `acquire()`, but on its own request rather than for a
+  /// combination.
+  ///
+  /// Returns false if some other Execution is already responsible for any of
+  /// those objects, in which case nothing is acquired, and the adoption is kept
+  /// to be handed back once that Execution is done.
+  pub fn adopt(&self, caller: &ObjectRef, object: &ObjectRef) -> bool {
+    self.try_acquire(caller, object, || {
+      BlockedAdoption(caller.clone(), object.clone())
+    })
+  }
+
+  fn try_acquire(&self, caller: &ObjectRef, subject: &ObjectRef,
+                 blocked: || -> Blocked)
+                 -> bool {
+
+    // Walk the children before taking our lock, since it means locking each
+    // of the objects.
+    let objects = family(subject);
+
+    let mut state = self.state.lock();
+
+    let mut blocker = None;
+
+    for object in objects.iter() {
+      match state.owners.find(object) {
+        Some(owner) if owner != caller => {
+          blocker = Some(owner.clone());
+          break
+        },
+
+        _ => ()
+      }
+    }
+
+    match blocker {
+      Some(owner) => {
+        debug!("{} blocked on {} (responsible for {})", caller, owner, subject);
+
+        state.blocked.find_or_insert(owner, Vec::new()).push(blocked());
+
+        false
+      },
+
+      None => {
+        for object in objects.move_iter() {
+          if state.owners.find(&object).is_none() {
+            state.owners.insert(object.clone(), caller.clone());
+
+            state.held.find_or_insert(caller.clone(), Vec::new()).push(object);
+          }
+        }
+
+        true
+      }
+    }
+  }
+
+  /// Releases everything `execution` is responsible for, and returns what was
+  /// waiting on it, to be tried again.
+  pub fn release(&self, execution: &ObjectRef) -> Vec<Blocked> {
+
+    let mut state = self.state.lock();
+
+    match state.held.pop(execution) {
+      Some(objects) =>
+        for object in objects.iter() {
+          state.owners.pop(object);
+        },
+
+      None => ()
+    }
+
+    state.blocked.pop(execution).unwrap_or(Vec::new())
+  }
+
+  /// Releases `execution`'s responsibility for `object` and everything it
+  /// holds by child relationship, keeping the rest. Whatever was waiting on
+  /// `execution` becomes ready to be tried again (see `take_ready()`), since
+  /// some of it may have been waiting on those objects.
+  ///
+  /// Returns false if `execution` wasn't responsible for `object`.
+  pub fn abandon(&self, execution: &ObjectRef, object: &ObjectRef) -> bool {
+    let objects = family(object);
+
+    let mut state = self.state.lock();
+
+    if state.owners.find(object) != Some(execution) { return false }
+
+    let mut released = Vec::new();
+
+    for object in objects.move_iter() {
+      if state.owners.find(&object) == Some(execution) {
+        state.owners.pop(&object);
+        released.push(object);
+      }
+    }
+
+    match state.held.find_mut(execution) {
+      Some(held) => held.retain(|object| !released.contains(object)),
+      None       => ()
+    }
+
+    match state.blocked.pop(execution) {
+      Some(blocked) => state.ready.push_all_move(blocked),
+      None          => ()
+    }
+
+    true
+  }
+
+  /// Takes whatever has become ready to be tried again since the last call,
+  /// because of `abandon()`.
+  pub fn take_ready(&self) -> Vec<Blocked> {
+    let mut state = self.state.lock();
+
+    if state.ready.is_empty() {
+      Vec::new()
+    } else {
+      replace(&mut state.ready, Vec::new())
+    }
+  }
+
+  /// Returns the Execution responsible for `object`, if there is one.
+  pub fn owner(&self, object: &ObjectRef) -> Option<ObjectRef> {
+    self.state.lock().owners.find(object).map(|owner| owner.clone())
+  }
+}
+
+/// Collects `object` and everything it holds by child relationship,
+/// recursively, skipping anything that doesn't need responsibility.
+fn family(object: &ObjectRef) -> Vec<ObjectRef> {
+  let mut seen    = HashSet::new();
+  let mut family  = Vec::new();
+  let mut pending = vec![object.clone()];
+
+  while !pending.is_empty() {
+    let object = pending.pop().unwrap();
+
+    if object.symbol_ref().is_some() || object.is_frozen() { continue }
+
+    if !seen.insert(object.clone()) { continue }
+
+    {
+      let guard = object.lock();
+
+      for member in guard.meta().members.vec.iter() {
+        match *member {
+          Some(ref relationship) if relationship.is_child() =>
+            pending.push(relationship.to().clone()),
+
+          _ => ()
+        }
+      }
+    }
+
+    family.push(object);
+  }
+
+  family
+}
@@ -0,0 +1,224 @@
+//! Policies for deciding which of a `ReactorPool`'s general reactors gets work
+//! that's sent to it, rather than staged by a general reactor onto its own
+//! deque: stagings from outside the pool or from specialized reactors, remote
+//! deliveries, and `on_reactor()` procedures.
+
+use object::{ObjectRef, WeakObjectRef};
+
+use std::hash;
+use std::sync::Mutex;
+use std::sync::atomics::{AtomicUint, SeqCst};
+use std::collections::LruCache;
+
+/// Chooses a general reactor for new work. Shared by every reactor in the
+/// pool, so it may be asked from several tasks at once.
+pub trait SchedulerPolicy {
+  /// Returns the index of the general reactor (below `view.general()`) that
+  /// should get work for `execution`, or a procedure to run if `None`. Indices
+  /// out of range are wrapped around.
+  fn choose(&self, view: &SchedulerView, execution: Option<&ObjectRef>)
+            -> uint;
+
+  /// Returns the general reactor that a general reactor (`view.me()`) should
+  /// send work for `execution` to, rather than keeping it on its own deque.
+  /// The default is to keep it.
+  fn redirect(&self, _view: &SchedulerView, _execution: &ObjectRef)
+              -> Option<uint> {
+    None
+  }
+
+  /// Called by the general reactor at `index` just before it realizes
+  /// `execution`. Does nothing by default.
+  fn realizing(&self, _execution: &ObjectRef, _index: uint) {
+  }
+}
+
+/// What a `SchedulerPolicy` gets to know about the pool when choosing.
+pub struct SchedulerView<'a> {
+  general: uint,
+  me:      Option<uint>,
+  backlog: &'a [AtomicUint]
+}
+
+impl<'a> SchedulerView<'a> {
+  /// Describes a pool with `general` general reactors, asked from the general
+  /// reactor `me` if any. `backlog` has one count per general reactor.
+  pub fn new(general: uint, me: Option<uint>, backlog: &'a [AtomicUint])
+             -> SchedulerView<'a> {
+    SchedulerView { general: general, me: me, backlog: backlog }
+  }
+
+  /// The number of general reactors.
+  pub fn general(&self) -> uint {
+    self.general
+  }
+
+  /// The general reactor doing the asking, if it's a general reactor. It
+  /// already has its own work, so it's usually better to choose another.
+  pub fn me(&self) -> Option<uint> {
+    self.me
+  }
+
+  /// How many stagings have been sent to the general reactor at `index` that
+  /// it hasn't picked up yet.
+  pub fn backlog(&self, index: uint) -> uint {
+    self.backlog[index].load(SeqCst)
+  }
+}
+
+/// Takes each general reactor in turn, skipping the one asking. The default.
+pub struct RoundRobin {
+  next: AtomicUint
+}
+
+impl RoundRobin {
+  /// Creates a new `RoundRobin`, starting with the first general reactor.
+  pub fn new() -> RoundRobin {
+    RoundRobin { next: AtomicUint::new(0) }
+  }
+}
+
+impl SchedulerPolicy for RoundRobin {
+  fn choose(&self, view: &SchedulerView, _: Option<&ObjectRef>) -> uint {
+    let index = self.next.fetch_add(1, SeqCst) % view.general();
+
+    if Some(index) == view.me() {
+      self.next.fetch_add(1, SeqCst) % view.general()
+    } else {
+      index
+    }
+  }
+}
+
+/// Takes the general reactor with the smallest backlog, skipping the one
+/// asking. Ties go round robin, so that idle reactors share the work.
+pub struct LeastLoaded {
+  start: AtomicUint
+}
+
+impl LeastLoaded {
+  /// Creates a new `LeastLoaded`.
+  pub fn new() -> LeastLoaded {
+    LeastLoaded { start: AtomicUint::new(0) }
+  }
+}
+
+impl SchedulerPolicy for LeastLoaded {
+  fn choose(&self, view: &SchedulerView, _: Option<&ObjectRef>) -> uint {
+    let start = self.start.fetch_add(1, SeqCst);
+
+    let mut best: Option<(uint, uint)> = None;
+
+    for offset in range(0, view.general()) {
+      let index = (start + offset) % view.general();
+
+      if Some(index) == view.me() && view.general() > 1 { continue }
+
+      let backlog = view.backlog(index);
+
+      match best {
+        Some((_, least)) if least <= backlog => (),
+        _ => best = Some((index, backlog))
+      }
+    }
+
+    best.map(|(index, _)| index).unwrap_or(0)
+  }
+}
+
+/// Always takes the same general reactor for the same Execution, so that it
+/// keeps finding its objects in that reactor's cache. Procedures go round
+/// robin.
+pub struct Affinity {
+  fallback: RoundRobin
+}
+
+impl Affinity {
+  /// Creates a new `Affinity`.
+  pub fn new() -> Affinity {
+    Affinity { fallback: RoundRobin::new() }
+  }
+}
+
+impl SchedulerPolicy for Affinity {
+  fn choose(&self, view: &SchedulerView, execution: Option<&ObjectRef>)
+            -> uint {
+    match execution {
+      Some(execution) =>
+        (hash::hash(execution) % view.general() as u64) as uint,
+
+      None =>
+        self.fallback.choose(view, None)
+    }
+  }
+}
+
+/// The most general reactors' backlogs can be before `LastRealized` stops
+/// sending them more work.
+static LAST_REALIZED_MAX_BACKLOG: uint = 16;
+
+/// Sends work for an Execution back to the general reactor that last realized
+/// it, so that it keeps finding its objects (its clone, receivers, and
+/// lookups) in that reactor's cache, unless that reactor is overloaded. Unlike
+/// `Affinity`, this applies to work that general reactors stage too, and
+/// follows Executions around when they're stolen.
+///
+/// Other work goes to the least loaded general reactor. Remembers a limited
+/// number of Executions, and costs a lock for every realization. Executions
+/// are only held weakly, so being remembered doesn't keep them alive.
+pub struct LastRealized {
+  last:     Mutex<LruCache<u64, (WeakObjectRef, uint)>>,
+  fallback: LeastLoaded
+}
+
+impl LastRealized {
+  /// Creates a new `LastRealized` that remembers up to `capacity` Executions.
+  pub fn new(capacity: uint) -> LastRealized {
+    LastRealized {
+      last:     Mutex::new(LruCache::new(capacity)),
+      fallback: LeastLoaded::new()
+    }
+  }
+
+  /// The general reactor that last realized `execution`, if it isn't
+  /// overloaded.
+  fn preferred(&self, view: &SchedulerView, execution: &ObjectRef)
+               -> Option<uint> {
+    let index = {
+      let mut last = self.last.lock();
+
+      match last.get(&hash::hash(execution)) {
+        // The same hash might belong to some other Execution.
+        Some(&(ref weak, index))
+          if weak.upgrade().as_ref() == Some(execution) => index,
+
+        _ => return None
+      }
+    };
+
+    if index < view.general() &&
+       view.backlog(index) < LAST_REALIZED_MAX_BACKLOG {
+      Some(index)
+    } else {
+      None
+    }
+  }
+}
+
+impl SchedulerPolicy for LastRealized {
+  fn choose(&self, view: &SchedulerView, execution: Option<&ObjectRef>)
+            -> uint {
+    execution.and_then(|execution| self.preferred(view, execution))
+      .unwrap_or_else(|| self.fallback.choose(view, execution))
+  }
+
+  fn redirect(&self, view: &SchedulerView, execution: &ObjectRef)
+              -> Option<uint> {
+    self.preferred(view, execution).and_then(|index|
+      if Some(index) == view.me() { None } else { Some(index) })
+  }
+
+  fn realizing(&self, execution: &ObjectRef, index: uint) {
+    self.last.lock().put(hash::hash(execution), (execution.downgrade(), index));
+  }
+}
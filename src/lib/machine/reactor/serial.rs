@@ -1,4 +1,6 @@
-use super::Reactor;
+use super::{Reactor, ReactorStats};
+use super::{RunStatus, Completed, BudgetExhausted, Waiting, Stalled};
+use super::{Remote, RemoteSink};
 use super::realize;
 
 use machine::Machine;
@@ -8,6 +10,10 @@ use object::{ObjectRef, Cache};
 use std::collections::{Deque, RingBuf};
 use std::sync::Semaphore;
 use std::mem::replace;
+use std::time::duration::Duration;
+
+use time;
+use time::Timespec;
 
 /// A reactor that executes without attempting any parallelism whatsoever.
 ///
@@ -19,19 +25,49 @@ pub struct SerialReactor {
   stagings:       RingBuf<(ObjectRef, ObjectRef)>,
   stall_handlers: Vec<proc (&mut Reactor)>,
   machine:        Machine,
-  cache:          Cache
+  cache:          Cache,
+
+  /// The counters from `stats()`, kept up to date as we go. `cache` and
+  /// `queue_depth` are filled in when they're asked for.
+  counts:         ReactorStats,
+
+  /// Where `Remote`s deliver to. See `Reactor::remote()`.
+  remote_tx:      Sender<Option<(ObjectRef, ObjectRef)>>,
+  remote_rx:      Receiver<Option<(ObjectRef, ObjectRef)>>,
+
+  /// How many `Remote`s haven't delivered yet.
+  outstanding:    uint,
+
+  /// How many more stagings may be realized, once `shutdown()` has been
+  /// called.
+  draining:       Option<uint>,
+
+  /// Our id in the Machine's metrics. See `machine::metrics`.
+  metrics_id:     uint
 }
 
 impl SerialReactor {
   /// Creates a new SerialReactor with an empty queue and no stall handlers for
   /// the given Machine.
   pub fn new(machine: Machine) -> SerialReactor {
+    let (remote_tx, remote_rx) = channel();
+
+    let cache = Cache::new_serial_with(&machine.cache_config);
+
+    let metrics_id = machine.metrics.register("SerialReactor");
+
     SerialReactor {
       alive:          true,
       stagings:       RingBuf::new(),
       stall_handlers: Vec::new(),
       machine:        machine,
-      cache:          Cache::new_serial()
+      cache:          cache,
+      counts:         ReactorStats::new(),
+      remote_tx:      remote_tx,
+      remote_rx:      remote_rx,
+      outstanding:    0,
+      draining:       None,
+      metrics_id:     metrics_id
     }
   }
 
@@ -40,26 +76,143 @@ impl SerialReactor {
     self.alive
   }
 
+  /// The Executions (or other objects) waiting in the queue to be realized, in
+  /// the order they will be. Useful as roots for `machine::inspect`.
+  pub fn queued(&self) -> Vec<ObjectRef> {
+    self.stagings.iter().map(|&(ref execution, _)| execution.clone()).collect()
+  }
+
   /// Takes a single staging off the internal queue and reacts it, realizing the
   /// execution and response.
   ///
   /// Returns `false` if the reactor is no longer alive, or the queue is empty.
+  ///
+  /// Once `shutdown()` has been called, this is also where the reactor
+  /// finishes, when it runs out of either work or stagings to drain.
   pub fn step(&mut self) -> bool {
     if self.alive {
-      match self.stagings.pop_front() {
+      if self.draining == Some(0) {
+        self.finish();
+        return false
+      }
+
+      if self.stagings.is_empty() && self.outstanding > 0 {
+        self.receive_remotes(false);
+      }
+
+      match self.next_staging() {
         Some((execution, response)) => {
-          realize(self, execution, response);
+          self.draining = self.draining.map(|left| left - 1);
+
+          let realized = realize(self, execution, response);
+
+          self.counts.count(realized);
           true
         },
-        None => false
+        None => {
+          if self.draining.is_some() {
+            self.finish();
+          }
+
+          false
+        }
       }
     } else {
       false
     }
   }
 
+  /// Runs the Machine's finalizers and stops, once `shutdown()` has drained
+  /// everything it's going to.
+  fn finish(&mut self) {
+    self.draining = None;
+
+    let finalizers = self.machine.finalizers.clone();
+
+    finalizers.run(self);
+
+    self.stop();
+  }
+
+  /// Takes the next staging to realize off the queue: the one the Machine's
+  /// replay says comes next, if it's replaying (see `machine::replay`), or
+  /// otherwise the one at the front.
+  fn next_staging(&mut self) -> Option<(ObjectRef, ObjectRef)> {
+    let replay = self.machine.replay.clone();
+
+    if replay.is_replaying() {
+      // It might be coming from a `Remote`.
+      if replay.next().is_none() && self.outstanding > 0 {
+        self.receive_remotes(true);
+      }
+
+      let position = replay.next().and_then(|(execution, response)|
+        self.stagings.iter().position(|&(ref e, ref r)|
+          *e == execution && *r == response));
+
+      match position {
+        Some(index) => {
+          let staging = self.stagings.get(index).clone();
+
+          let stagings = replace(&mut self.stagings, RingBuf::new());
+
+          for (other_index, other) in stagings.iter().enumerate() {
+            if other_index != index { self.stagings.push(other.clone()) }
+          }
+
+          return Some(staging)
+        },
+
+        None => {
+          machine_warn!(self.machine, "replay",
+                        "couldn't find staging {} to replay; going back to \
+                         the usual order", replay.next_id().unwrap());
+
+          replay.stop();
+        }
+      }
+    }
+
+    self.stagings.pop_front()
+  }
+
+  /// Queues whatever `Remote`s have delivered. If `block` is true, waits for
+  /// at least one of them to deliver first, if any are outstanding.
+  fn receive_remotes(&mut self, block: bool) {
+    if block && self.outstanding > 0 {
+      let delivery = self.remote_rx.recv();
+
+      self.receive_remote(delivery);
+    }
+
+    loop {
+      match self.remote_rx.try_recv() {
+        Ok(delivery) => self.receive_remote(delivery),
+        Err(_)       => break
+      }
+    }
+  }
+
+  /// Queues what a `Remote` delivered, unless we're shutting down.
+  fn receive_remote(&mut self, delivery: Option<(ObjectRef, ObjectRef)>) {
+    self.outstanding -= 1;
+
+    match delivery {
+      Some((execution, response)) if self.draining.is_none() =>
+        self.stage(execution, response),
+
+      _ => ()
+    }
+  }
+
   /// Immediately invokes the reactor's stall handlers.
   pub fn stall(&mut self) {
+    self.counts.stalls += 1;
+
+    self.machine.continuations.report();
+
+    self.report_metrics();
+
     let stall_handlers = replace(&mut self.stall_handlers, Vec::new());
 
     for handler in stall_handlers.move_iter() {
@@ -80,13 +233,20 @@ impl SerialReactor {
       // If we are no longer alive, we have to stop.
       if !self.alive { break }
 
+      // Work that's been promised from elsewhere means we aren't stalled yet;
+      // wait for it instead.
+      if self.outstanding > 0 {
+        self.receive_remotes(true);
+        continue
+      }
+
       // Otherwise, try to call stall handlers to hopefully get more work or
       // stop.
       self.stall();
 
-      // If our stall handlers didn't produce any work, or stopped us, we have
-      // to exit the loop.
-      if !self.alive || !self.step() { break }
+      // If our stall handlers didn't produce any work (or promise any), or
+      // stopped us, we have to exit the loop.
+      if !self.alive || (!self.step() && self.outstanding == 0) { break }
     }
 
     // If we're still alive, we should hang: there's nothing more to be done,
@@ -96,11 +256,60 @@ impl SerialReactor {
       Semaphore::new(0).acquire();
     }
   }
+
+  /// Like `run_for_duration()` (see `Reactor`), but keeps realizing stagings
+  /// until the system clock reaches `deadline`, for embedders that schedule
+  /// by wall-clock time.
+  pub fn run_until(&mut self, deadline: Timespec) -> RunStatus {
+    self.run_budgeted(|| time::get_time() < deadline)
+  }
+
+  /// Runs until stopped, stalled, or `budget` returns false. `budget` is asked
+  /// before each step.
+  fn run_budgeted(&mut self, budget: || -> bool) -> RunStatus {
+    loop {
+      if !self.alive { return Completed }
+
+      if self.stagings.is_empty() {
+        self.receive_remotes(false);
+      }
+
+      if self.stagings.is_empty() {
+        // Once shutting down, there's nothing left to wait for, since any
+        // work promised from elsewhere would be refused.
+        if self.draining.is_some() {
+          self.finish();
+          return Completed
+        }
+
+        // Don't stall while work has been promised, but don't wait for it
+        // either.
+        if self.outstanding > 0 { return Waiting }
+
+        self.stall();
+
+        if !self.alive { return Completed }
+
+        if self.stagings.is_empty() && self.outstanding == 0 {
+          return Stalled
+        }
+      } else {
+        if !budget() { return BudgetExhausted }
+
+        self.step();
+      }
+    }
+  }
 }
 
 impl Reactor for SerialReactor {
   fn stage(&mut self, execution: ObjectRef, response: ObjectRef) {
     if self.alive {
+      self.machine.trace.record_staging(&execution, &response);
+      self.machine.replay.staged(&execution, &response);
+
+      self.counts.stagings += 1;
+
       self.stagings.push((execution, response));
     }
   }
@@ -119,6 +328,14 @@ impl Reactor for SerialReactor {
 
     // Drop the stall handlers
     self.stall_handlers.truncate(0);
+
+    self.report_metrics();
+  }
+
+  fn shutdown(&mut self, limit: uint) {
+    if self.alive && self.draining.is_none() {
+      self.draining = Some(limit);
+    }
   }
 
   fn machine(&self) -> &Machine {
@@ -128,4 +345,56 @@ impl Reactor for SerialReactor {
   fn cache(&mut self) -> &mut Cache {
     &mut self.cache
   }
+
+  fn stats(&self) -> ReactorStats {
+    ReactorStats {
+      cache:       self.cache.stats().clone(),
+      queue_depth: self.stagings.len(),
+      ..self.counts.clone()
+    }
+  }
+
+  fn remote(&mut self) -> Remote {
+    self.outstanding += 1;
+
+    Remote::new(box SerialRemote(self.remote_tx.clone()))
+  }
+
+  fn run_for(&mut self, steps: uint) -> RunStatus {
+    let mut remaining = steps;
+
+    self.run_budgeted(|| {
+      if remaining > 0 {
+        remaining -= 1;
+        true
+      } else {
+        false
+      }
+    })
+  }
+
+  fn run_for_duration(&mut self, duration: Duration) -> RunStatus {
+    let start = time::precise_time_ns();
+    let limit = duration.num_nanoseconds().unwrap_or(::std::i64::MAX);
+
+    self.run_budgeted(|| {
+      ((time::precise_time_ns() - start) as i64) < limit
+    })
+  }
+
+  fn report_metrics(&self) {
+    self.machine.metrics.report(self.metrics_id, self.stats());
+  }
+}
+
+/// Delivers to a `SerialReactor`'s `remote_rx`.
+struct SerialRemote(Sender<Option<(ObjectRef, ObjectRef)>>);
+
+impl RemoteSink for SerialRemote {
+  fn deliver(&mut self, staging: Option<(ObjectRef, ObjectRef)>) {
+    let SerialRemote(ref sender) = *self;
+
+    // If the reactor is gone, there's nobody to tell.
+    let _ = sender.send_opt(staging);
+  }
 }
@@ -1,5 +1,11 @@
-use super::{MockReactor, SerialReactor, ReactorPool};
-use super::{Reactor, Combination, From, FromLocals, combine};
+use super::{MockReactor, SerialReactor, ReactorPool, Responsibility};
+use super::{BlockedCombination, BlockedAdoption};
+use super::ReactorStats;
+use super::{SchedulerPolicy, SchedulerView, RoundRobin, LeastLoaded, Affinity};
+use super::LastRealized;
+use super::{Completed, BudgetExhausted, Waiting, Stalled};
+use super::{Reactor, Combination, From, FromLocals, combine, realize};
+use super::{current_span, set_current_span, at_current_span};
 
 use script::*;
 
@@ -10,9 +16,22 @@ use nuketype::{Alien, Thing, Execution};
 
 use machine::Machine;
 
+use system::implementation;
+
+use cpaws;
+
+use time;
+use time::Timespec;
+
 use util;
 
 use std::any::AnyRefExt;
+use std::sync::{Arc, Mutex};
+use std::sync::atomics::{AtomicUint, SeqCst};
+use std::task;
+use std::io::timer;
+use std::io::ChanWriter;
+use std::time::duration::Duration;
 
 #[test]
 fn combine_via_direct_default_receiver() {
@@ -74,6 +93,61 @@ fn combine_via_indirect_default_receiver() {
   assert!(reactor.stagings.shift() == Some((caller_ref, value_ref)));
 }
 
+#[test]
+fn combine_is_traced() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let (tx, rx) = channel();
+
+  machine.trace.enable(box ChanWriter::new(tx));
+
+  let caller_ref = Execution::create(&machine, Script(vec![]));
+  let other_ref  = Thing::empty();
+  let key_ref    = machine.symbol("key");
+
+  other_ref.lock().meta_mut().members.push_pair(
+    key_ref.clone(), Thing::empty());
+
+  combine(&mut reactor, caller_ref.clone(), Combination {
+    subject: From(other_ref.clone()),
+    message: From(key_ref.clone())
+  });
+
+  other_ref.lock().meta_mut().receiver =
+    ObjectReceiver(Execution::create(&machine, Script(vec![])));
+
+  combine(&mut reactor, caller_ref.clone(), Combination {
+    subject: From(other_ref.clone()),
+    message: From(key_ref.clone())
+  });
+
+  let mut bytes = vec![];
+
+  // The trace (and with it, the sender) is still alive, so don't block.
+  loop {
+    match rx.try_recv() {
+      Ok(chunk) => bytes.push_all(chunk.as_slice()),
+      Err(_)    => break
+    }
+  }
+
+  let log   = String::from_utf8(bytes).ok().expect("trace isn't UTF-8");
+  let lines: Vec<&str> = log.as_slice().lines().collect();
+
+  assert_eq!(2, lines.len());
+
+  let fields = format!("\"caller\":\"{}\",\"subject\":\"{}\",\
+                        \"message\":\"[:key]\"", caller_ref, other_ref);
+
+  for line in lines.iter() {
+    assert!(line.contains(fields.as_slice()), "{}", line);
+  }
+
+  assert!(lines[0].ends_with(",\"receiver\":\"lookup\"}"));
+  assert!(!lines[1].contains("\"receiver\":\"lookup\""));
+}
+
 #[test]
 fn combine_via_executionish_receiver() {
   let     machine = Machine::new();
@@ -250,6 +324,70 @@ fn test_reactor_react_stop_call(machine: &Machine) -> ReactorTest {
   }
 }
 
+fn test_reactor_remote() -> ReactorTest {
+  let (realized_tx, realized_rx) = channel();
+  let (stalled_tx,  stalled_rx)  = channel::<bool>();
+
+  let alien = Alien::create("report", report_task,
+                            box ReportTask(Arc::new(Mutex::new(realized_tx)),
+                                           0));
+
+  ReactorTest {
+    init: proc(reactor) {
+      reactor.on_stall(proc (reactor) {
+        // The remote staging has to have happened by now.
+        stalled_tx.send(realized_rx.try_recv().is_ok());
+        reactor.stop();
+      });
+
+      let remote = reactor.remote();
+
+      spawn(proc() {
+        timer::sleep(Duration::milliseconds(50));
+        remote.stage(alien, Thing::empty());
+      });
+    },
+    fini: proc() {
+      assert_eq!(Ok(true), stalled_rx.try_recv());
+    }
+  }
+}
+
+fn test_reactor_shutdown() -> ReactorTest {
+  let (realized_tx,  realized_rx)  = channel();
+  let (finalized_tx, finalized_rx) = channel::<()>();
+
+  let alien = Alien::create("report", report_task,
+                            box ReportTask(Arc::new(Mutex::new(realized_tx)),
+                                           0));
+
+  ReactorTest {
+    init: proc(reactor) {
+      let remote = reactor.remote();
+
+      reactor.machine().finalizers.register(proc (_) finalized_tx.send(()));
+
+      reactor.shutdown(2);
+
+      // Only two of these will be realized. Stagings made by the reactor
+      // itself are still accepted...
+      for _ in range(0u, 3) {
+        reactor.stage(alien.clone(), Thing::empty());
+      }
+
+      // ...but not work from outside.
+      remote.stage(alien, Thing::empty());
+    },
+    fini: proc() {
+      assert!(realized_rx.try_recv().is_ok());
+      assert!(realized_rx.try_recv().is_ok());
+      assert!(realized_rx.try_recv().is_err());
+
+      assert_eq!(Ok(()), finalized_rx.try_recv());
+    }
+  }
+}
+
 #[test]
 fn serial_reactor_stall_handlers() {
   util::timeout(1000, proc() {
@@ -281,45 +419,1197 @@ fn serial_reactor_react_stop_call() {
   })
 }
 
-static PARALLEL_CONFIGS: [uint, ..3] = [2, 4, 8];
+#[test]
+fn serial_reactor_remote() {
+  util::timeout(1000, proc() {
+    let mut reactor = SerialReactor::new(Machine::new());
+
+    let test = test_reactor_remote();
+
+    (test.init)(&mut reactor);
+
+    reactor.run();
+
+    (test.fini)();
+  })
+}
 
 #[test]
-fn parallel_reactor_stall_handlers() {
-  for &reactors in PARALLEL_CONFIGS.iter() {
-    util::timeout(1000, proc() {
-      let mut pool = ReactorPool::spawn(Machine::new(), reactors);
+fn serial_reactor_shutdown() {
+  util::timeout(1000, proc() {
+    let mut reactor = SerialReactor::new(Machine::new());
 
-      let ReactorTest { init, fini } =
-        test_reactor_stall_handlers();
+    let test = test_reactor_shutdown();
 
-      pool.on_reactor(proc(reactor) {
-        init(reactor)
-      });
+    (test.init)(&mut reactor);
 
-      pool.wait();
+    reactor.run();
 
-      fini();
-    })
+    (test.fini)();
+  })
+}
+
+#[test]
+fn mock_reactor_remote() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine);
+
+  let execution = Thing::empty();
+  let response  = Thing::empty();
+
+  let remote  = reactor.remote();
+  let dropped = reactor.remote();
+
+  assert_eq!(2, reactor.outstanding);
+
+  {
+    let execution = execution.clone();
+    let response  = response.clone();
+
+    spawn(proc() {
+      drop(dropped);
+      remote.stage(execution, response);
+    });
   }
+
+  assert!(!reactor.receive_remote());
+  assert!(reactor.receive_remote());
+
+  assert_eq!(0, reactor.outstanding);
+
+  assert!(reactor.stagings == vec![(execution, response)]);
 }
 
 #[test]
-fn parallel_reactor_react_stop_call() {
-  for &reactors in PARALLEL_CONFIGS.iter() {
-    util::timeout(1000, proc() {
-      let     machine = Machine::new();
-      let mut pool    = ReactorPool::spawn(machine.clone(), reactors);
+fn mock_reactor_expectations() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine);
 
-      let ReactorTest { init, fini } =
-        test_reactor_react_stop_call(&machine);
+  let a = Thing::empty();
+  let b = Thing::empty();
+  let c = Thing::empty();
 
-      pool.on_reactor(proc(reactor) {
-        init(reactor)
-      });
+  reactor.stage(a.clone(), b.clone());
+  reactor.stage(b.clone(), c.clone());
+  reactor.stage(c.clone(), a.clone());
 
-      pool.wait();
+  reactor.expect_stage(&a, &b);
+  reactor.expect_stage_anywhere(&c, &a);
 
-      fini();
-    })
+  let (_, response) = reactor.expect_staged(&b);
+
+  assert!(response == c);
+
+  reactor.expect_no_stagings();
+
+  reactor.stop();
+  reactor.expect_stopped();
+}
+
+#[test]
+#[should_fail]
+fn mock_reactor_expectations_are_ordered() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine);
+
+  let a = Thing::empty();
+  let b = Thing::empty();
+
+  reactor.stage(a.clone(), b.clone());
+  reactor.stage(b.clone(), a.clone());
+
+  reactor.expect_stage(&b, &a);
+}
+
+#[test]
+fn mock_reactor_run() {
+  fn echo(reactor: &mut Reactor, response: ObjectRef) {
+    reactor.stage(response.clone(), response);
+  }
+
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine);
+
+  let first  = Thing::empty();
+  let second = Thing::empty();
+
+  reactor.stage(Alien::oneshot("echo", echo), first.clone());
+  reactor.stage(Alien::oneshot("echo", echo), second.clone());
+
+  // Only one step allowed, so the second echo is still waiting, ahead of what
+  // the first one staged.
+  assert_eq!(1, reactor.run(1));
+
+  assert_eq!(1, reactor.run(16));
+
+  reactor.expect_stage(&first,  &first);
+  reactor.expect_stage(&second, &second);
+  reactor.expect_no_stagings();
+}
+
+#[test]
+fn mock_reactor_stall() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine);
+
+  let execution = Thing::empty();
+  let response  = Thing::empty();
+
+  {
+    let execution = execution.clone();
+    let response  = response.clone();
+
+    reactor.on_stall(proc(reactor) reactor.stage(execution, response));
+  }
+
+  reactor.expect_no_stagings();
+
+  assert_eq!(1, reactor.stall());
+  assert_eq!(0, reactor.stall());
+
+  reactor.expect_stage(&execution, &response);
+}
+
+#[test]
+fn serial_reactor_preempts_long_executions() {
+  let mut machine = Machine::new();
+
+  machine.preempt_after = Some(2);
+
+  let mut reactor = SerialReactor::new(machine.clone());
+
+  let long  = Execution::create(&machine, Script(Vec::from_elem(6, Discard)));
+  let short = Execution::create(&machine, Script(vec![Discard]));
+
+  reactor.stage(long,  Thing::empty());
+  reactor.stage(short, Thing::empty());
+
+  // The long one is realized first, but only gets through two instructions
+  // before the short one gets its turn.
+  while reactor.step() { }
+
+  let stats = reactor.stats();
+
+  assert_eq!(4, stats.executions);
+  assert_eq!(4, stats.stagings);
+}
+
+#[test]
+fn inline_lookups_share_one_budget() {
+  let mut machine = Machine::new();
+
+  machine.preempt_after = Some(2);
+
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let a     = machine.symbol("a");
+  let value = Thing::empty();
+
+  let execution = Execution::create(&machine, Script(vec![
+    LookupLocal(a.clone()), LookupLocal(a.clone()), LookupLocal(a.clone())]));
+
+  {
+    let caller     = execution.lock();
+
+    let locals_ref = caller.meta().members
+                           .lookup_pair(&machine.symbol("locals"))
+                           .expect("locals not found on created Execution!");
+
+    locals_ref.lock().meta_mut().members.push_pair_to_child(
+      a.clone(), value.clone());
+  }
+
+  realize(&mut reactor, execution.clone(), Thing::empty());
+
+  // Two lookups are carried out inline, and then the budget is spent.
+  reactor.expect_stage(&execution, &value);
+  reactor.expect_no_stagings();
+}
+
+#[test]
+fn preempted_executions_keep_responses_until_resumed() {
+  let mut machine = Machine::new();
+
+  machine.preempt_after = Some(1);
+
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let execution = Execution::create(&machine, Script(vec![Discard, Discard]));
+  let response  = machine.symbol("early");
+
+  realize(&mut reactor, execution.clone(), Thing::empty());
+
+  reactor.expect_stage(&execution, &machine.resume_marker);
+
+  // Something else gets to it before the marker does.
+  realize(&mut reactor, execution.clone(), response.clone());
+
+  reactor.expect_stage(&execution, &response);
+  reactor.expect_no_stagings();
+}
+
+#[test]
+fn serial_reactor_replays_recorded_order() {
+  let     machine = Machine::new();
+  let mut reactor = SerialReactor::new(machine.clone());
+
+  let (tx, rx) = channel();
+
+  let tx = Arc::new(Mutex::new(tx));
+
+  machine.replay.replay(vec![(0, 2), (0, 0), (0, 1)]);
+
+  // Made outside of any realization, so these are (0, 0), (0, 1), (0, 2).
+  for index in range(0u, 3) {
+    reactor.stage(Alien::create("order", report_order,
+                                box ReportOrder(tx.clone(), index)),
+                  Thing::empty());
+  }
+
+  while reactor.step() { }
+
+  let order: Vec<uint> = range(0u, 3).map(|_| rx.recv()).collect();
+
+  assert_eq!(vec![2u, 0, 1], order);
+}
+
+struct ReportOrder(Arc<Mutex<Sender<uint>>>, uint);
+
+fn report_order<'a>(alien:     TypedRefGuard<'a, Alien>,
+                    _reactor:  &mut Reactor,
+                    _response: ObjectRef) {
+
+  match alien.data.downcast_ref::<ReportOrder>() {
+    Some(&ReportOrder(ref tx, index)) => tx.lock().send(index),
+    None                              => fail!("wrong data")
+  }
+}
+
+#[test]
+fn serial_reactor_stats() {
+  let     machine = Machine::new();
+  let mut reactor = SerialReactor::new(machine.clone());
+
+  let execution = Execution::create(&machine, Script(vec![]));
+
+  reactor.stage(execution.clone(), Thing::empty());
+  reactor.stage(execution.clone(), Thing::empty());
+
+  let stats = reactor.stats();
+
+  assert_eq!(2, stats.queue_depth);
+  assert_eq!(0, stats.steps);
+
+  assert!(reactor.step());
+
+  let stats = reactor.stats();
+
+  assert_eq!(1, stats.queue_depth);
+  assert_eq!(1, stats.steps);
+  assert_eq!(1, stats.executions);
+  assert_eq!(0, stats.aliens);
+
+  reactor.stall();
+
+  assert_eq!(1, reactor.stats().stalls);
+}
+
+#[test]
+fn serial_reactor_queued() {
+  let     machine = Machine::new();
+  let mut reactor = SerialReactor::new(machine.clone());
+
+  let first  = Execution::create(&machine, Script(vec![]));
+  let second = Execution::create(&machine, Script(vec![]));
+
+  reactor.stage(first.clone(),  Thing::empty());
+  reactor.stage(second.clone(), Thing::empty());
+
+  assert!(reactor.queued() == vec![first, second]);
+}
+
+#[test]
+fn serial_reactor_run_for() {
+  let     machine = Machine::new();
+  let mut reactor = SerialReactor::new(machine.clone());
+
+  let execution = Execution::create(&machine, Script(vec![]));
+
+  for _ in range(0u, 3) {
+    reactor.stage(execution.clone(), Thing::empty());
   }
+
+  assert_eq!(BudgetExhausted, reactor.run_for(2));
+  assert_eq!(1, reactor.stats().queue_depth);
+
+  // No stall handlers, so nothing more to do.
+  assert_eq!(Stalled, reactor.run_for(2));
+  assert_eq!(3, reactor.stats().steps);
+  assert_eq!(1, reactor.stats().stalls);
+
+  reactor.on_stall(proc (reactor) reactor.stop());
+
+  assert_eq!(Completed, reactor.run_for(2));
+}
+
+#[test]
+fn serial_reactor_run_for_waits_on_remotes() {
+  let     machine = Machine::new();
+  let mut reactor = SerialReactor::new(machine.clone());
+
+  let remote = reactor.remote();
+
+  assert_eq!(Waiting, reactor.run_for(10));
+  assert_eq!(0, reactor.stats().stalls);
+
+  remote.stage(Execution::create(&machine, Script(vec![])), Thing::empty());
+
+  assert_eq!(Stalled, reactor.run_for(10));
+  assert_eq!(1, reactor.stats().steps);
+}
+
+#[test]
+fn serial_reactor_run_for_duration() {
+  let     machine = Machine::new();
+  let mut reactor = SerialReactor::new(machine.clone());
+
+  reactor.stage(Execution::create(&machine, Script(vec![])), Thing::empty());
+
+  assert_eq!(BudgetExhausted, reactor.run_for_duration(Duration::zero()));
+  assert_eq!(0, reactor.stats().steps);
+
+  assert_eq!(Stalled, reactor.run_for_duration(Duration::seconds(1)));
+  assert_eq!(1, reactor.stats().steps);
+}
+
+#[test]
+fn serial_reactor_run_until() {
+  let     machine = Machine::new();
+  let mut reactor = SerialReactor::new(machine.clone());
+
+  reactor.stage(Execution::create(&machine, Script(vec![])), Thing::empty());
+
+  let now = time::get_time();
+
+  assert_eq!(BudgetExhausted, reactor.run_until(now));
+  assert_eq!(0, reactor.stats().steps);
+
+  let later = Timespec::new(now.sec + 1, now.nsec);
+
+  assert_eq!(Stalled, reactor.run_until(later));
+  assert_eq!(1, reactor.stats().steps);
+}
+
+#[test]
+fn serial_reactor_shutdown_with_run_for() {
+  let     machine = Machine::new();
+  let mut reactor = SerialReactor::new(machine.clone());
+
+  let execution = Execution::create(&machine, Script(vec![]));
+
+  for _ in range(0u, 3) {
+    reactor.stage(execution.clone(), Thing::empty());
+  }
+
+  reactor.shutdown(1);
+
+  assert_eq!(Completed, reactor.run_for(10));
+  assert_eq!(1, reactor.stats().steps);
+  assert_eq!(0, reactor.stats().stalls);
+  assert!(!reactor.is_alive());
+}
+
+#[test]
+fn mock_reactor_run_for() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let execution = Execution::create(&machine, Script(vec![]));
+
+  reactor.stage(execution.clone(), Thing::empty());
+  reactor.stage(execution.clone(), Thing::empty());
+
+  assert_eq!(BudgetExhausted, reactor.run_for(1));
+
+  let remote = reactor.remote();
+
+  assert_eq!(Waiting, reactor.run_for(10));
+
+  drop(remote);
+
+  assert!(!reactor.receive_remote());
+
+  assert_eq!(Stalled, reactor.run_for(10));
+
+  reactor.stop();
+
+  assert_eq!(Completed, reactor.run_for(10));
+}
+
+#[test]
+fn parallel_reactor_run_for() {
+  util::timeout(1000, proc() {
+    let     machine = Machine::new();
+    let mut pool    = ReactorPool::spawn(machine.clone(), 1);
+
+    let (tx, rx) = channel();
+
+    pool.on_reactor(proc(reactor) {
+      let execution = Execution::create(reactor.machine(), Script(vec![]));
+
+      reactor.stage(execution.clone(), Thing::empty());
+      reactor.stage(execution.clone(), Thing::empty());
+
+      tx.send(reactor.run_for(1));
+
+      // Nothing left here, but the pool could still make more.
+      tx.send(reactor.run_for(10));
+
+      reactor.stop();
+    });
+
+    pool.wait();
+
+    assert_eq!(BudgetExhausted, rx.recv());
+    assert_eq!(Waiting,         rx.recv());
+  })
+}
+
+#[test]
+fn reactor_stats_steal_rate() {
+  let mut stats = ReactorStats::new();
+
+  assert_eq!(None, stats.steal_rate());
+
+  stats.steals     = 1;
+  stats.local_hits = 3;
+
+  assert_eq!(Some(0.25), stats.steal_rate());
+}
+
+static PARALLEL_CONFIGS: [uint, ..3] = [2, 4, 8];
+
+#[test]
+fn parallel_reactor_stall_handlers() {
+  for &reactors in PARALLEL_CONFIGS.iter() {
+    util::timeout(1000, proc() {
+      let mut pool = ReactorPool::spawn(Machine::new(), reactors);
+
+      let ReactorTest { init, fini } =
+        test_reactor_stall_handlers();
+
+      pool.on_reactor(proc(reactor) {
+        init(reactor)
+      });
+
+      pool.wait();
+
+      fini();
+    })
+  }
+}
+
+#[test]
+fn parallel_reactor_react_stop_call() {
+  for &reactors in PARALLEL_CONFIGS.iter() {
+    util::timeout(1000, proc() {
+      let     machine = Machine::new();
+      let mut pool    = ReactorPool::spawn(machine.clone(), reactors);
+
+      let ReactorTest { init, fini } =
+        test_reactor_react_stop_call(&machine);
+
+      pool.on_reactor(proc(reactor) {
+        init(reactor)
+      });
+
+      pool.wait();
+
+      fini();
+    })
+  }
+}
+
+#[test]
+fn parallel_reactor_remote() {
+  for &reactors in PARALLEL_CONFIGS.iter() {
+    util::timeout(1000, proc() {
+      let mut pool = ReactorPool::spawn(Machine::new(), reactors);
+
+      let ReactorTest { init, fini } = test_reactor_remote();
+
+      pool.on_reactor(proc(reactor) {
+        init(reactor)
+      });
+
+      pool.wait();
+
+      fini();
+    })
+  }
+}
+
+#[test]
+fn parallel_reactor_shutdown() {
+  for &reactors in PARALLEL_CONFIGS.iter() {
+    util::timeout(1000, proc() {
+      let mut pool = ReactorPool::spawn(Machine::new(), reactors);
+
+      let ReactorTest { init, fini } = test_reactor_shutdown();
+
+      pool.on_reactor(proc(reactor) {
+        init(reactor)
+      });
+
+      pool.wait();
+
+      fini();
+    })
+  }
+}
+
+#[test]
+fn parallel_reactor_pool_shutdown_when_idle() {
+  for &reactors in PARALLEL_CONFIGS.iter() {
+    util::timeout(1000, proc() {
+      let machine = Machine::new();
+      let pool    = ReactorPool::spawn(machine.clone(), reactors);
+
+      let (tx, rx) = channel();
+
+      machine.finalizers.register(proc (_) tx.send(()));
+
+      pool.shutdown(10);
+      pool.wait();
+
+      assert_eq!(Ok(()), rx.try_recv());
+      assert_eq!(0, machine.finalizers.len());
+    })
+  }
+}
+
+#[test]
+fn parallel_reactor_with_world_stopped() {
+  util::timeout(1000, proc() {
+    for &n_reactors in PARALLEL_CONFIGS.iter() {
+      let machine = Machine::new();
+      let pool    = ReactorPool::spawn(machine, n_reactors);
+
+      assert_eq!(42u, pool.with_world_stopped(|| 42u));
+
+      // The reactors must still be running afterward.
+      let (tx, rx) = channel();
+
+      let mut pool2 = pool.clone();
+
+      pool2.on_reactor(proc (_) tx.send(()));
+
+      rx.recv();
+
+      pool.stop();
+      pool.wait();
+    }
+  })
+}
+
+#[test]
+fn parallel_reactor_with_world_stopped_concurrently() {
+  util::timeout(1000, proc() {
+    for &n_reactors in PARALLEL_CONFIGS.iter() {
+      let machine = Machine::new();
+      let pool    = ReactorPool::spawn(machine, n_reactors);
+
+      let (tx, rx) = channel();
+
+      for _ in range(0u, 2) {
+        let pool = pool.clone();
+        let tx   = tx.clone();
+
+        spawn(proc() {
+          for _ in range(0u, 100) {
+            pool.with_world_stopped(|| ());
+          }
+
+          tx.send(());
+        });
+      }
+
+      rx.recv();
+      rx.recv();
+
+      pool.stop();
+      pool.wait();
+    }
+  })
+}
+
+/// Data for an Alien that sends the name of the task that realized it.
+#[deriving(Clone)]
+struct ReportTask(Arc<Mutex<Sender<Option<String>>>>, u64);
+
+fn report_task<'a>(alien:     TypedRefGuard<'a, Alien>,
+                   _reactor:  &mut Reactor,
+                   _response: ObjectRef) {
+
+  match alien.data.downcast_ref::<ReportTask>() {
+    Some(&ReportTask(ref tx, delay)) => {
+      if delay > 0 { timer::sleep(Duration::milliseconds(delay as i32)) }
+
+      tx.lock().send(task::name())
+    },
+
+    None => fail!("wrong data")
+  }
+}
+
+#[test]
+fn parallel_reactor_specialties() {
+  util::timeout(1000, proc() {
+    let     machine = Machine::new();
+    let mut pool    = ReactorPool::spawn_with_specialties(machine, 2, ["io"]);
+
+    let (tx, rx) = channel();
+
+    let alien = Alien::create("report", report_task,
+                              box ReportTask(Arc::new(Mutex::new(tx)), 0));
+
+    pool.route(alien.clone(), "io");
+
+    pool.on_reactor(proc (reactor) {
+      for _ in range(0u, 8) {
+        reactor.stage(alien.clone(), Thing::empty());
+      }
+    });
+
+    for _ in range(0u, 8) {
+      assert_eq!(Some("ParallelReactor (io)".to_string()), rx.recv());
+    }
+
+    let (tx, rx) = channel();
+
+    pool.on_special("io", proc (reactor) {
+      tx.send(reactor.specialty().map(|s| s.to_string()))
+    });
+
+    assert_eq!(Some("io".to_string()), rx.recv());
+
+    pool.stop();
+    pool.wait();
+  })
+}
+
+#[test]
+fn parallel_reactor_specialty_hands_work_to_general_reactors() {
+  util::timeout(1000, proc() {
+    let     machine = Machine::new();
+    let mut pool    = ReactorPool::spawn_with_specialties(machine, 2, ["io"]);
+
+    let (tx, rx) = channel();
+
+    let alien = Alien::create("report", report_task,
+                              box ReportTask(Arc::new(Mutex::new(tx)), 0));
+
+    // Not routed, so it's general work, even when staged by the specialty.
+    pool.on_special("io", proc (reactor) {
+      for _ in range(0u, 8) {
+        reactor.stage(alien.clone(), Thing::empty());
+      }
+    });
+
+    for _ in range(0u, 8) {
+      let name = rx.recv().expect("task has no name");
+
+      assert!(name.as_slice().starts_with("ParallelReactor #"), "{}", name);
+    }
+
+    pool.stop();
+    pool.wait();
+  })
+}
+
+#[test]
+fn parallel_reactor_work_stealing() {
+  util::timeout(1000, proc() {
+    let     machine = Machine::new();
+    let mut pool    = ReactorPool::spawn(machine, 4);
+
+    let (tx, rx) = channel();
+
+    let alien = Alien::create("report", report_task,
+                              box ReportTask(Arc::new(Mutex::new(tx)), 10));
+
+    // All of the work starts out on one reactor, but it's slow enough that the
+    // others should steal some of it.
+    pool.on_reactor(proc (reactor) {
+      for _ in range(0u, 32) {
+        let clone = util::clone::stageable(&alien, reactor.machine()).unwrap();
+
+        reactor.stage(clone, Thing::empty());
+      }
+    });
+
+    let mut names: Vec<Option<String>> =
+      range(0u, 32).map(|_| rx.recv()).collect();
+
+    names.sort();
+    names.dedup();
+
+    assert!(names.len() > 1, "no work was stolen");
+
+    pool.stop();
+    pool.wait();
+
+    let stats = pool.stats();
+
+    assert!(stats.steals     > 0);
+    assert!(stats.local_hits > 0);
+    assert!(stats.steals + stats.local_hits <= stats.steps);
+  })
+}
+
+#[test]
+fn parallel_reactor_stall_after_stealing() {
+  util::timeout(1000, proc() {
+    let     machine = Machine::new();
+    let mut pool    = ReactorPool::spawn(machine, 4);
+
+    let (tx, rx)                 = channel();
+    let (stalled_tx, stalled_rx) = channel();
+
+    let alien = Alien::create("report", report_task,
+                              box ReportTask(Arc::new(Mutex::new(tx)), 5));
+
+    // The stall must only be detected once all of the work is done, including
+    // whatever was stolen.
+    pool.on_reactor(proc (reactor) {
+      for _ in range(0u, 16) {
+        let clone = util::clone::stageable(&alien, reactor.machine()).unwrap();
+
+        reactor.stage(clone, Thing::empty());
+      }
+
+      reactor.on_stall(proc (reactor) {
+        stalled_tx.send(());
+        reactor.stop();
+      });
+    });
+
+    pool.wait();
+
+    assert!(stalled_rx.try_recv().is_ok());
+
+    for _ in range(0u, 16) {
+      assert!(rx.try_recv().is_ok());
+    }
+  })
+}
+
+#[test]
+fn responsibility_covers_children() {
+  let responsibility = Responsibility::new();
+
+  let a       = Thing::empty();
+  let b       = Thing::empty();
+  let message = Thing::empty();
+  let child   = Thing::empty();
+  let other   = Thing::empty();
+  let subject = Thing::empty();
+
+  {
+    let mut subject = subject.lock();
+
+    subject.meta_mut().members.push_child(child.clone());
+    subject.meta_mut().members.push(other.clone());
+  }
+
+  assert!(responsibility.acquire(&a, &subject, &message));
+
+  assert!(responsibility.owner(&subject) == Some(a.clone()));
+  assert!(responsibility.owner(&child)   == Some(a.clone()));
+  assert!(responsibility.owner(&other)   == None);
+
+  // Acquiring again as the same Execution is fine.
+  assert!(responsibility.acquire(&a, &child, &message));
+
+  // But not as another one.
+  assert!(!responsibility.acquire(&b, &child, &message));
+  assert!( responsibility.acquire(&b, &other, &message));
+
+  let blocked = responsibility.release(&a);
+
+  assert_eq!(1, blocked.len());
+
+  match blocked[0] {
+    BlockedCombination(ref caller, ref combination) => {
+      assert!(caller == &b);
+      assert!(combination.subject == From(child.clone()));
+    },
+
+    ref other => fail!("expected a combination, got {}", other)
+  }
+
+  assert!(responsibility.owner(&child) == None);
+  assert!(responsibility.acquire(&b, &child, &message));
+}
+
+#[test]
+fn combine_waits_for_responsibility() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  machine.responsibility.enable();
+
+  let a       = Execution::create(&machine, Script(vec![]));
+  let b       = Execution::create(&machine, Script(vec![]));
+  let subject = Thing::empty();
+  let message = Thing::empty();
+
+  assert!(machine.responsibility.acquire(&a, &subject, &message));
+
+  combine(&mut reactor, b.clone(), Combination {
+    subject: From(subject.clone()),
+    message: From(message.clone())
+  });
+
+  // Blocked, so nothing should have happened.
+  assert!(reactor.stagings.is_empty());
+
+  super::release(&mut reactor, &a);
+
+  // The default receiver for a Thing doesn't stage anything when the lookup
+  // misses, but `b` should now be responsible for the subject.
+  assert!(machine.responsibility.owner(&subject) == Some(b.clone()));
+}
+
+#[test]
+fn responsibility_adopt_and_abandon() {
+  let responsibility = Responsibility::new();
+
+  let a       = Thing::empty();
+  let b       = Thing::empty();
+  let child   = Thing::empty();
+  let subject = Thing::empty();
+
+  subject.lock().meta_mut().members.push_child(child.clone());
+
+  assert!( responsibility.adopt(&a, &subject));
+  assert!(!responsibility.adopt(&b, &child));
+
+  // Only the owner can abandon.
+  assert!(!responsibility.abandon(&b, &subject));
+  assert!( responsibility.abandon(&a, &subject));
+
+  assert!(responsibility.owner(&subject) == None);
+  assert!(responsibility.owner(&child)   == None);
+
+  // Nothing left for `a` to release, but `b`'s adoption is ready to retry.
+  assert!(responsibility.release(&a).is_empty());
+
+  let ready = responsibility.take_ready();
+
+  assert_eq!(1, ready.len());
+  assert!(ready[0] == BlockedAdoption(b.clone(), child.clone()));
+
+  assert!(responsibility.take_ready().is_empty());
+}
+
+#[test]
+fn realize_retries_abandoned_adoptions() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  machine.responsibility.enable();
+
+  let a       = Execution::create(&machine, Script(vec![]));
+  let b       = Execution::create(&machine, Script(vec![]));
+  let subject = Thing::empty();
+
+  assert!( machine.responsibility.adopt(&a, &subject));
+  assert!(!machine.responsibility.adopt(&b, &subject));
+  assert!( machine.responsibility.abandon(&a, &subject));
+
+  // The next realization picks up `b`'s adoption, and stages it with the
+  // subject now that it's responsible.
+  realize(&mut reactor, a.clone(), Thing::empty());
+
+  assert!(machine.responsibility.owner(&subject) == Some(b.clone()));
+
+  assert_eq!(1, reactor.stagings.len());
+  assert!(reactor.stagings[0] == (b.clone(), subject.clone()));
+}
+
+#[test]
+fn parallel_reactor_pool_stats() {
+  util::timeout(1000, proc() {
+    for &n_reactors in PARALLEL_CONFIGS.iter() {
+      let mut pool = ReactorPool::spawn(Machine::new(), n_reactors);
+
+      let (tx, rx) = channel();
+
+      let alien = Alien::create("report", report_task,
+                                box ReportTask(Arc::new(Mutex::new(tx)), 0));
+
+      pool.on_reactor(proc (reactor) {
+        reactor.stage(alien, Thing::empty());
+      });
+
+      // Wait for it to be realized.
+      rx.recv();
+
+      let live = pool.reactor_stats();
+
+      assert_eq!(n_reactors, live.len());
+      assert_eq!(1, live.iter().map(|stats| stats.aliens).sum());
+
+      pool.stop();
+      pool.wait();
+
+      assert_eq!(0, pool.reactor_stats().len());
+      assert_eq!(n_reactors, pool.finished_stats().len());
+
+      let stats = pool.stats();
+
+      assert_eq!(1, stats.steps);
+      assert_eq!(1, stats.aliens);
+    }
+  })
+}
+
+#[test]
+fn round_robin_skips_me() {
+  let backlog: Vec<AtomicUint> = range(0u, 3).map(|_| AtomicUint::new(0))
+                                             .collect();
+
+  let policy = RoundRobin::new();
+  let view   = SchedulerView::new(3, Some(1), backlog.as_slice());
+
+  let chosen: Vec<uint> = range(0u, 4).map(|_| policy.choose(&view, None))
+                                      .collect();
+
+  assert_eq!(vec![0, 2, 0, 2], chosen);
+}
+
+#[test]
+fn least_loaded_takes_smallest_backlog() {
+  let backlog: Vec<AtomicUint> = [3u, 1, 0, 2].iter()
+    .map(|&n| AtomicUint::new(n)).collect();
+
+  let policy = LeastLoaded::new();
+
+  assert_eq!(2, policy.choose(&SchedulerView::new(4, None, backlog.as_slice()),
+                              None));
+
+  // Unless it's the one asking.
+  assert_eq!(1, policy.choose(&SchedulerView::new(4, Some(2),
+                                                  backlog.as_slice()),
+                              None));
+}
+
+#[test]
+fn affinity_is_consistent() {
+  let backlog: Vec<AtomicUint> = range(0u, 4).map(|_| AtomicUint::new(0))
+                                             .collect();
+
+  let policy    = Affinity::new();
+  let view      = SchedulerView::new(4, None, backlog.as_slice());
+  let execution = Thing::empty();
+
+  let first = policy.choose(&view, Some(&execution));
+
+  assert!(first < 4);
+
+  for _ in range(0u, 8) {
+    assert_eq!(first, policy.choose(&view, Some(&execution)));
+  }
+}
+
+#[test]
+fn last_realized_prefers_last_reactor_unless_overloaded() {
+  let backlog: Vec<AtomicUint> = range(0u, 4).map(|_| AtomicUint::new(0))
+                                             .collect();
+
+  let policy    = LastRealized::new(8);
+  let execution = Thing::empty();
+
+  policy.realizing(&execution, 2);
+
+  {
+    let view = SchedulerView::new(4, None, backlog.as_slice());
+
+    assert_eq!(policy.choose(&view, Some(&execution)), 2);
+    assert_eq!(policy.redirect(&view, &execution), Some(2));
+  }
+
+  {
+    // Already there.
+    let view = SchedulerView::new(4, Some(2), backlog.as_slice());
+
+    assert_eq!(policy.redirect(&view, &execution), None);
+  }
+
+  backlog[2].store(100, SeqCst);
+
+  {
+    let view = SchedulerView::new(4, None, backlog.as_slice());
+
+    assert!(policy.choose(&view, Some(&execution)) != 2);
+    assert_eq!(policy.redirect(&view, &execution), None);
+  }
+}
+
+#[test]
+fn last_realized_does_not_keep_executions_alive() {
+  let backlog: Vec<AtomicUint> = range(0u, 4).map(|_| AtomicUint::new(0))
+                                             .collect();
+
+  let policy    = LastRealized::new(8);
+  let execution = Thing::empty();
+
+  policy.realizing(&execution, 2);
+
+  let weak = execution.downgrade();
+
+  drop(execution);
+
+  assert!(weak.upgrade().is_none());
+
+  // Something new in its place isn't mistaken for it.
+  let other = Thing::empty();
+  let view  = SchedulerView::new(4, None, backlog.as_slice());
+
+  assert_eq!(policy.redirect(&view, &other), None);
+}
+
+#[test]
+fn parallel_reactor_pool_stage_with_scheduler() {
+  util::timeout(1000, proc() {
+    let pool = ReactorPool::spawn_with_scheduler(Machine::new(), 4, [],
+                                                 box LeastLoaded::new());
+
+    let (tx, rx) = channel();
+
+    let tx = Arc::new(Mutex::new(tx));
+
+    for _ in range(0u, 8) {
+      pool.stage(Alien::create("report", report_task,
+                               box ReportTask(tx.clone(), 0)),
+                 Thing::empty());
+    }
+
+    for _ in range(0u, 8) {
+      rx.recv();
+    }
+
+    pool.stop();
+    pool.wait();
+
+    assert_eq!(8, pool.stats().aliens);
+  })
+}
+
+fn execution_with_spans(machine: &Machine, source: &str) -> ObjectRef {
+  let (nodes, spans) =
+    cpaws::parse_nodes_with_spans(source, "<test_case>")
+      .ok().expect("parse failed");
+
+  let (script, table) =
+    cpaws::build_fused_script_with_spans(machine, nodes.as_slice(),
+                                         spans.as_slice());
+
+  Execution::create_with_spans(machine, script, table)
+}
+
+#[test]
+fn realize_sets_current_span() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let execution = execution_with_spans(&machine, "\n  a b");
+
+  realize(&mut reactor, execution.clone(), Thing::empty());
+
+  assert_eq!(Some((2, 3)), current_span().map(|s| (s.line, s.column)));
+  assert_eq!(" (at <test_case>:2:3)".to_string(), at_current_span());
+
+  // Aliens don't know where they came from.
+  realize(&mut reactor, implementation::void(&machine), Thing::empty());
+
+  assert!(current_span().is_none());
+  assert_eq!("".to_string(), at_current_span());
+}
+
+#[test]
+fn call_pattern_aliens_set_current_span_from_caller() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller = execution_with_spans(&machine, "a b");
+
+  realize(&mut reactor, caller.clone(), Thing::empty());
+
+  set_current_span(None);
+
+  fn routine(_reactor: &mut Reactor, _caller: ObjectRef, _args: &[ObjectRef]) {
+  }
+
+  let alien = Alien::call_pattern("test", routine, 1);
+
+  realize(&mut reactor, alien.clone(), caller.clone());
+  realize(&mut reactor, alien.clone(), Thing::empty());
+
+  assert_eq!(Some((1, 1)), current_span().map(|s| (s.line, s.column)));
+}
+
+/// An Execution of `source` with `name` set to `value` in its locals.
+fn execution_with_local(machine: &Machine, source: &str,
+                        name: &str, value: ObjectRef) -> ObjectRef {
+  let execution = execution_with_spans(machine, source);
+
+  let locals = execution.lock().meta().members
+                 .lookup_pair(&machine.locals_sym).unwrap();
+
+  locals.lock().meta_mut().members
+    .push_pair_to_child(machine.symbol(name), value);
+
+  execution
+}
+
+#[test]
+fn realize_looks_up_locals_without_staging() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let value     = Thing::empty();
+  let hook      = Thing::empty();
+  let execution = execution_with_local(&machine, "x", "x", value.clone());
+
+  machine.completions.register(execution.clone(), hook.clone());
+
+  // The lookup is carried out within the same realization, so the Execution
+  // completes right away, with the value as its final response.
+  realize(&mut reactor, execution.clone(), Thing::empty());
+
+  reactor.expect_stage(&hook, &value);
+  reactor.expect_no_stagings();
+}
+
+#[test]
+fn realize_stages_lookups_in_locals_with_other_receivers() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let value     = Thing::empty();
+  let execution = execution_with_local(&machine, "x", "x", value.clone());
+
+  {
+    let locals = execution.lock().meta().members
+                   .lookup_pair(&machine.locals_sym).unwrap();
+
+    locals.lock().meta_mut().receiver = ObjectReceiver(Thing::empty());
+  }
+
+  realize(&mut reactor, execution.clone(), Thing::empty());
+
+  reactor.expect_stage(&execution, &value);
+  reactor.expect_no_stagings();
 }
@@ -0,0 +1,331 @@
+//! Staging messages on Machines in other processes, over TCP.
+//!
+//! This goes beyond `machine::bridge`, and is built on it: a `Listener`
+//! accepts connections for a Machine, and each message that comes in is
+//! posted to the entry its Machine advertised under the name it was sent to
+//! (see `Bridges::advertise()`). So, as with a bridge, nothing is staged
+//! until the Machine is listening. On the other end, a `Client` connects to a
+//! listener and stages messages against its entries.
+//!
+//! Messages are plain data, converted as by `format::json`: Symbols, Numbers,
+//! and Things of pairs or members made of them.
+//!
+//! # Protocol
+//!
+//! The client opens with `PAWSRU` and a protocol version byte. Everything
+//! after that, in both directions, is a frame: a big-endian `u32` length and
+//! then that many bytes of JSON.
+//!
+//! * If the listener doesn't speak that version, it replies with
+//!   `{"error": ...}` and closes the connection. Otherwise it replies with
+//!   `{"entries": [...]}`, the names of the entries it advertises.
+//! * The client then sends any number of `{"stage": name, "message": ...}`.
+//!   Each gets `{"ok": true}` once the message is queued on the Machine, or
+//!   `{"error": ...}` if it can't be (for instance, if nothing is advertised
+//!   by that name).
+
+use object::ObjectRef;
+
+use machine::{Machine, Bridges};
+
+use format::json::to_json_value;
+
+use serialize::json;
+use serialize::json::Json;
+
+use std::collections::TreeMap;
+use std::io::{IoResult, IoError, InvalidInput, OtherIoError, EndOfFile};
+use std::io::net::ip::SocketAddr;
+use std::io::net::tcp::{TcpListener, TcpAcceptor, TcpStream};
+use std::io::Acceptor;
+use std::task::TaskBuilder;
+
+#[cfg(test)]
+mod tests;
+
+/// Identifies a connection as speaking this protocol.
+static PROTOCOL_MAGIC: &'static [u8] = b"PAWSRU";
+
+/// The version of the protocol. Bumped on any incompatible change.
+pub static PROTOCOL_VERSION: u8 = 1;
+
+/// The longest frame that will be read, in bytes. Anything longer is taken
+/// to be a mistake rather than allocated for.
+pub static MAX_FRAME_LEN: uint = 16 * 1024 * 1024;
+
+/// Accepts connections for a Machine on a task of its own. See the module
+/// documentation.
+///
+/// The listener keeps accepting connections until `close()` is called, even
+/// if it's dropped.
+pub struct Listener {
+  address:  SocketAddr,
+  acceptor: TcpAcceptor
+}
+
+impl Listener {
+  /// Starts accepting connections on `host` and `port` for `machine`. Use
+  /// port 0 to have one picked; `address()` tells which.
+  pub fn bind(machine: &Machine, host: &str, port: u16)
+              -> IoResult<Listener> {
+
+    let mut acceptor = try!(TcpListener::bind(host, port).and_then(listen));
+
+    let address   = try!(acceptor.socket_name());
+    let accepting = acceptor.clone();
+    let machine   = machine.clone();
+
+    TaskBuilder::new().named("remote listener").spawn(proc() {
+      let mut accepting = accepting;
+
+      for stream in accepting.incoming() {
+        match stream {
+          Ok(stream) => {
+            let machine = machine.clone();
+
+            TaskBuilder::new().named("remote connection").spawn(proc() {
+              let mut stream = stream;
+
+              match serve(&machine.bridges, &mut stream) {
+                Ok(()) => (),
+
+                Err(error) =>
+                  machine_warn!(machine, "remote", "connection failed: {}",
+                                error)
+              }
+            });
+          },
+
+          // What accepting gives after `close()`.
+          Err(ref error) if error.kind == EndOfFile => break,
+
+          Err(error) =>
+            machine_warn!(machine, "remote", "accept failed: {}", error)
+        }
+      }
+    });
+
+    Ok(Listener {
+      address:  address,
+      acceptor: acceptor
+    })
+  }
+
+  /// The address connections are being accepted on.
+  pub fn address(&self) -> SocketAddr {
+    self.address
+  }
+
+  /// Stops accepting connections. Those already accepted stay open until the
+  /// client closes them.
+  pub fn close(&mut self) {
+    let _ = self.acceptor.close_accept();
+  }
+}
+
+/// A connection to a `Listener` in another process. See the module
+/// documentation.
+pub struct Client {
+  peer:    String,
+  stream:  TcpStream,
+  entries: Vec<String>
+}
+
+impl Client {
+  /// Connects to the listener on `host` and `port`, and learns which entries
+  /// it advertises. Fails if it doesn't speak this version of the protocol.
+  pub fn connect(host: &str, port: u16) -> IoResult<Client> {
+    let mut stream = try!(TcpStream::connect(host, port));
+
+    try!(stream.write(PROTOCOL_MAGIC));
+    try!(stream.write_u8(PROTOCOL_VERSION));
+    try!(stream.flush());
+
+    let reply = try!(read_frame(&mut stream));
+
+    match error_of(&reply) {
+      Some(problem) =>
+        return Err(IoError {
+          kind:   OtherIoError,
+          desc:   "listener refused the connection",
+          detail: Some(problem)
+        }),
+
+      None => ()
+    }
+
+    let entries = match reply.find(&"entries".to_string()) {
+      Some(&json::List(ref names)) =>
+        names.iter().filter_map(|name| name.as_string())
+          .map(|name| name.to_string()).collect(),
+
+      _ => return Err(invalid("listener didn't send its entries", None))
+    };
+
+    Ok(Client {
+      peer:    format!("{}:{}", host, port),
+      stream:  stream,
+      entries: entries
+    })
+  }
+
+  /// The listener's end of the connection, as `host:port`.
+  pub fn peer<'a>(&'a self) -> &'a str {
+    self.peer.as_slice()
+  }
+
+  /// The names of the entries the listener advertised when we connected.
+  pub fn entries<'a>(&'a self) -> &'a [String] {
+    self.entries.as_slice()
+  }
+
+  /// Converts `message` and stages it against the entry advertised as
+  /// `entry`. Fails with a description of the problem if the message isn't
+  /// plain data, or if the listener couldn't queue it.
+  pub fn stage(&mut self, entry: &str, message: &ObjectRef)
+               -> Result<(), String> {
+
+    let message = try!(to_json_value(message));
+
+    self.stage_json(entry, message)
+  }
+
+  /// Like `stage()`, but with a message that's already been converted.
+  pub fn stage_json(&mut self, entry: &str, message: Json)
+                    -> Result<(), String> {
+
+    let request = object(vec![("stage",   json::String(entry.to_string())),
+                              ("message", message)]);
+
+    let reply = try!(write_frame(&mut self.stream, &request)
+                       .and_then(|()| read_frame(&mut self.stream))
+                       .map_err(|error| format!("I/O on {} failed: {}",
+                                                self.peer, error)));
+
+    match error_of(&reply) {
+      Some(problem) => Err(problem),
+      None          => Ok(())
+    }
+  }
+}
+
+/// Writes `value` to `writer` as a frame.
+pub fn write_frame(writer: &mut Writer, value: &Json) -> IoResult<()> {
+  let text = value.to_string();
+
+  try!(writer.write_be_u32(text.len() as u32));
+  try!(writer.write_str(text.as_slice()));
+
+  writer.flush()
+}
+
+/// Reads a frame from `reader`. Frames longer than `MAX_FRAME_LEN`, and those
+/// that aren't valid JSON, are `InvalidInput` errors.
+pub fn read_frame(reader: &mut Reader) -> IoResult<Json> {
+  let len = try!(reader.read_be_u32()) as uint;
+
+  if len > MAX_FRAME_LEN {
+    return Err(invalid("frame is too long", Some(len.to_string())));
+  }
+
+  let bytes = try!(reader.read_exact(len));
+
+  let text = match String::from_utf8(bytes) {
+    Ok(text) => text,
+    Err(_)   => return Err(invalid("frame is not valid UTF-8", None))
+  };
+
+  json::from_str(text.as_slice())
+    .map_err(|error| invalid("frame is not valid JSON",
+                             Some(error.to_string())))
+}
+
+/// Speaks the listener's side of the protocol on `stream`, posting messages
+/// to `bridges`, until the client closes the connection.
+fn serve(bridges: &Bridges, stream: &mut TcpStream) -> IoResult<()> {
+  let magic = try!(stream.read_exact(PROTOCOL_MAGIC.len()));
+
+  if magic.as_slice() != PROTOCOL_MAGIC {
+    return Err(invalid("not a Paws remote client", None));
+  }
+
+  let version = try!(stream.read_u8());
+
+  if version != PROTOCOL_VERSION {
+    let problem = format!("unsupported protocol version {}", version);
+
+    try!(write_frame(stream, &error(problem.as_slice())));
+
+    return Err(invalid("unsupported protocol version",
+                       Some(version.to_string())));
+  }
+
+  let entries = bridges.advertised().move_iter()
+                  .map(|name| json::String(name)).collect();
+
+  try!(write_frame(stream, &object(vec![("entries", json::List(entries))])));
+
+  loop {
+    let request = match read_frame(stream) {
+      Ok(request) => request,
+
+      Err(ref error) if error.kind == EndOfFile => return Ok(()),
+
+      Err(error) => return Err(error)
+    };
+
+    let posted = match (request.find(&"stage".to_string()),
+                        request.find(&"message".to_string())) {
+
+      (Some(&json::String(ref entry)), Some(message)) =>
+        bridges.post(entry.as_slice(), message.clone()),
+
+      _ =>
+        Err("expected {\"stage\": name, \"message\": ...}".to_string())
+    };
+
+    let reply = match posted {
+      Ok(())       => object(vec![("ok", json::Boolean(true))]),
+      Err(problem) => error(problem.as_slice())
+    };
+
+    try!(write_frame(stream, &reply));
+  }
+}
+
+/// Starts listening. The trait isn't imported, since its name is taken.
+fn listen<L: ::std::io::Listener<TcpStream, TcpAcceptor>>(listener: L)
+          -> IoResult<TcpAcceptor> {
+  listener.listen()
+}
+
+fn object(pairs: Vec<(&str, Json)>) -> Json {
+  let mut map = TreeMap::new();
+
+  for (key, value) in pairs.move_iter() {
+    map.insert(key.to_string(), value);
+  }
+
+  json::Object(map)
+}
+
+fn error(problem: &str) -> Json {
+  object(vec![("error", json::String(problem.to_string()))])
+}
+
+/// The problem described by an `{"error": ...}` frame, if it is one.
+fn error_of(reply: &Json) -> Option<String> {
+  reply.find(&"error".to_string())
+    .map(|problem| match problem.as_string() {
+      Some(problem) => problem.to_string(),
+      None          => problem.to_string()
+    })
+}
+
+fn invalid(desc: &'static str, detail: Option<String>) -> IoError {
+  IoError {
+    kind:   InvalidInput,
+    desc:   desc,
+    detail: detail
+  }
+}
@@ -0,0 +1,139 @@
+use super::{Listener, Client, write_frame, read_frame, error_of};
+use super::{PROTOCOL_MAGIC, MAX_FRAME_LEN};
+
+use object::{ObjectRef, Meta, TypedRefGuard};
+
+use nuketype::{Thing, Alien};
+
+use machine::{Machine, Reactor};
+use machine::reactor::SerialReactor;
+
+use format::json::to_json;
+
+use util;
+
+use serialize::json;
+
+use std::io::{MemReader, MemWriter, InvalidInput};
+use std::io::net::tcp::TcpStream;
+use std::sync::{Arc, Mutex};
+
+/// Data for an Alien that sends the JSON of whatever it's realized with, and
+/// then closes its Machine's bridges.
+#[deriving(Clone)]
+struct Report(Arc<Mutex<Sender<String>>>);
+
+fn report<'a>(alien:    TypedRefGuard<'a, Alien>,
+              reactor:  &mut Reactor,
+              response: ObjectRef) {
+
+  match alien.data.downcast_ref::<Report>() {
+    Some(&Report(ref tx)) => tx.lock().send(to_json(&response).unwrap()),
+    None                  => fail!("wrong data")
+  }
+
+  reactor.machine().bridges.close();
+}
+
+#[test]
+fn frames_round_trip() {
+  let value = json::from_str(r#"{"stage":"a","message":["b",1]}"#).unwrap();
+
+  let mut writer = MemWriter::new();
+
+  write_frame(&mut writer, &value).unwrap();
+
+  let bytes = writer.unwrap();
+
+  assert_eq!(bytes.slice_to(4), &[0u8, 0, 0, 31]);
+
+  let mut reader = MemReader::new(bytes);
+
+  assert_eq!(read_frame(&mut reader).unwrap(), value);
+}
+
+#[test]
+fn read_frame_rejects_bad_frames() {
+  let mut too_long = MemWriter::new();
+
+  too_long.write_be_u32(MAX_FRAME_LEN as u32 + 1).unwrap();
+
+  let mut reader = MemReader::new(too_long.unwrap());
+
+  assert_eq!(read_frame(&mut reader).unwrap_err().kind, InvalidInput);
+
+  let mut not_json = MemWriter::new();
+
+  not_json.write_be_u32(3).unwrap();
+  not_json.write_str("{{{").unwrap();
+
+  let mut reader = MemReader::new(not_json.unwrap());
+
+  assert_eq!(read_frame(&mut reader).unwrap_err().kind, InvalidInput);
+}
+
+#[test]
+fn client_stages_against_advertised_entries() {
+  util::timeout(1000, proc() {
+    let machine = Machine::new();
+
+    let (tx, rx) = channel();
+
+    let entry = Alien::create("report", report,
+                              box Report(Arc::new(Mutex::new(tx))));
+
+    machine.bridges.advertise("greet", entry);
+
+    let mut listener = Listener::bind(&machine, "127.0.0.1", 0).unwrap();
+    let     port     = listener.address().port;
+
+    let mut client = Client::connect("127.0.0.1", port).unwrap();
+
+    assert_eq!(client.entries(), &["greet".to_string()]);
+
+    // The message is rebuilt on our side, so it can come from anywhere.
+    let other = Machine::new();
+
+    let mut meta = Meta::new();
+
+    meta.members.push_pair(other.symbol("hello"), other.symbol("world"));
+
+    assert!(client.stage("nobody", &other.symbol("lost")).is_err());
+    assert!(client.stage("greet", &Thing::create(meta)).is_ok());
+
+    let mut reactor = SerialReactor::new(machine.clone());
+
+    reactor.on_stall(proc (reactor) reactor.stop());
+
+    machine.bridges.listen(&mut reactor);
+
+    // Ends once the entry closes the bridges.
+    reactor.run();
+
+    assert_eq!(rx.recv().as_slice(), r#"{"hello":"world"}"#);
+
+    listener.close();
+  })
+}
+
+#[test]
+fn listener_refuses_other_versions() {
+  util::timeout(1000, proc() {
+    let machine = Machine::new();
+
+    let mut listener = Listener::bind(&machine, "127.0.0.1", 0).unwrap();
+    let     port     = listener.address().port;
+
+    let mut stream = TcpStream::connect("127.0.0.1", port).unwrap();
+
+    stream.write(PROTOCOL_MAGIC).unwrap();
+    stream.write_u8(255).unwrap();
+
+    let reply = read_frame(&mut stream).unwrap();
+
+    assert_eq!(error_of(&reply).unwrap().as_slice(),
+               "unsupported protocol version 255");
+
+    listener.close();
+  })
+}
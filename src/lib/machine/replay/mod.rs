@@ -0,0 +1,265 @@
+//! Recording the order stagings were realized in, and replaying it.
+//!
+//! Parallel runs realize stagings in a different order every time, which makes
+//! some bugs hard to reproduce. While recording, every staging a reactor
+//! realizes is written to a log, one line each. Feeding that log back to a
+//! `SerialReactor` makes it realize the same stagings in the same order, every
+//! time.
+//!
+//! Objects don't have names that survive from one run to the next, so each
+//! staging is identified instead by where it came from: `R S` for the `S`th
+//! staging (counting from zero) made while realizing the `R`th staging
+//! (counting from one), or made outside of any realization if `R` is zero. As
+//! long as the program doesn't depend on anything outside of Paws, replaying
+//! the same realizations in the same order makes the same stagings, and so
+//! the same identifiers.
+//!
+//! Things that happen outside of reactors, like `Remote`s delivering from
+//! other tasks, can still arrive in a different order. If the replaying
+//! reactor can't find the staging the log says comes next, it warns and goes
+//! back to its usual order.
+
+use object::ObjectRef;
+
+use std::io::{IoResult, BufferedReader};
+use std::collections::{Deque, RingBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomics::{AtomicBool, Relaxed};
+
+#[cfg(test)]
+mod tests;
+
+/// Identifies a staging across runs: the number of the realization it was made
+/// during (zero if none), and how many stagings that realization had made
+/// before it. See the module documentation.
+pub type StagingId = (u64, u64);
+
+local_data_key!(current_key: (u64, u64))
+
+enum Mode {
+  Recording(Box<Writer+Send>),
+  Replaying(RingBuf<StagingId>)
+}
+
+struct State {
+  mode:         Mode,
+
+  /// How many stagings have been realized so far.
+  realizations: u64,
+
+  /// How many stagings have been made outside of any realization.
+  outside:      u64,
+
+  /// Stagings that have been made but not realized yet.
+  pending:      Vec<(StagingId, ObjectRef, ObjectRef)>
+}
+
+/// Records or replays the order stagings are realized in, if enabled. Disabled
+/// by default. Clones share the same state.
+#[deriving(Clone)]
+pub struct Replay {
+  enabled: Arc<AtomicBool>,
+  state:   Arc<Mutex<Option<State>>>
+}
+
+impl Replay {
+  /// Creates a new, disabled `Replay`.
+  pub fn new() -> Replay {
+    Replay {
+      enabled: Arc::new(AtomicBool::new(false)),
+      state:   Arc::new(Mutex::new(None))
+    }
+  }
+
+  /// Starts writing the identifier of each staging realized to `writer`, one
+  /// per line.
+  pub fn record(&self, writer: Box<Writer+Send>) {
+    self.start(Recording(writer))
+  }
+
+  /// Starts replaying `recording`: `next()` gives the staging it says should
+  /// be realized next, until it runs out.
+  pub fn replay(&self, recording: Vec<StagingId>) {
+    self.start(Replaying(recording.move_iter().collect()))
+  }
+
+  fn start(&self, mode: Mode) {
+    *self.state.lock() = Some(State {
+      mode:         mode,
+      realizations: 0,
+      outside:      0,
+      pending:      Vec::new()
+    });
+
+    self.enabled.store(true, Relaxed);
+  }
+
+  /// Stops recording or replaying.
+  pub fn stop(&self) {
+    self.enabled.store(false, Relaxed);
+
+    let _ = self.flush();
+
+    *self.state.lock() = None;
+  }
+
+  /// Returns true if recording or replaying.
+  pub fn is_enabled(&self) -> bool {
+    self.enabled.load(Relaxed)
+  }
+
+  /// Returns true if replaying and the recording hasn't run out yet.
+  pub fn is_replaying(&self) -> bool {
+    if !self.is_enabled() { return false }
+
+    match *self.state.lock() {
+      Some(State { mode: Replaying(ref recording), .. }) =>
+        !recording.is_empty(),
+      _ =>
+        false
+    }
+  }
+
+  /// Gives a staging an identifier. Should be called by the reactor it was
+  /// made on, from the task that made it.
+  pub fn staged(&self, execution: &ObjectRef, response: &ObjectRef) {
+    if !self.is_enabled() { return }
+
+    let mut guard = self.state.lock();
+
+    let state = match *guard {
+      Some(ref mut state) => state,
+      None                => return
+    };
+
+    let id = match current_key.get().map(|current| *current) {
+      Some((realization, made)) => {
+        current_key.replace(Some((realization, made + 1)));
+
+        (realization, made)
+      },
+
+      None => {
+        state.outside += 1;
+
+        (0, state.outside - 1)
+      }
+    };
+
+    state.pending.push((id, execution.clone(), response.clone()));
+  }
+
+  /// The staging the recording says should be realized next, if it has been
+  /// made. `None` if it hasn't, or if not replaying.
+  pub fn next(&self) -> Option<(ObjectRef, ObjectRef)> {
+    match *self.state.lock() {
+      Some(State { mode: Replaying(ref recording), ref pending, .. }) =>
+        recording.front().and_then(|next|
+          pending.iter().find(|&&(id, _, _)| id == *next)
+            .map(|&(_, ref execution, ref response)|
+              (execution.clone(), response.clone()))),
+      _ =>
+        None
+    }
+  }
+
+  /// The identifier the recording says should be realized next, if any. For
+  /// describing where a replay went wrong.
+  pub fn next_id(&self) -> Option<StagingId> {
+    match *self.state.lock() {
+      Some(State { mode: Replaying(ref recording), .. }) =>
+        recording.front().map(|&id| id),
+      _ =>
+        None
+    }
+  }
+
+  /// Marks the start of realizing a staging on the current task: records it,
+  /// or moves on to the next one in the recording, and counts the stagings
+  /// made until `end()` as made during it. Called by `realize()`.
+  pub fn begin(&self, execution: &ObjectRef, response: &ObjectRef) {
+    if !self.is_enabled() { return }
+
+    let mut guard = self.state.lock();
+
+    let result = {
+      let state = match *guard {
+        Some(ref mut state) => state,
+        None                => return
+      };
+
+      state.realizations += 1;
+
+      current_key.replace(Some((state.realizations, 0)));
+
+      let position = state.pending.iter().position(|&(_, ref e, ref r)|
+        e == execution && r == response);
+
+      // Stagings made before recording started (or realized without being
+      // staged, as in tests) have no identifier, so there's nothing to write.
+      let (realization, made) = match position {
+        Some(index) => state.pending.remove(index).unwrap().val0(),
+        None        => return
+      };
+
+      match state.mode {
+        Recording(ref mut writer) =>
+          writer.write_line(format!("{} {}", realization, made).as_slice()),
+
+        Replaying(ref mut recording) => {
+          if recording.front() == Some(&(realization, made)) {
+            recording.pop_front();
+          }
+
+          Ok(())
+        }
+      }
+    };
+
+    match result {
+      Ok(()) => (),
+      Err(e) => {
+        warn!("recording stopped: {}", e);
+
+        self.enabled.store(false, Relaxed);
+        *guard = None;
+      }
+    }
+  }
+
+  /// Marks the end of realizing a staging on the current task. Called by
+  /// `realize()`.
+  pub fn end(&self) {
+    current_key.replace(None);
+  }
+
+  /// Flushes the recording, if recording. Should be called once the machine
+  /// is done.
+  pub fn flush(&self) -> IoResult<()> {
+    match *self.state.lock() {
+      Some(State { mode: Recording(ref mut writer), .. }) => writer.flush(),
+      _                                                   => Ok(())
+    }
+  }
+}
+
+/// Reads a recording written while recording, for `Replay::replay()`.
+pub fn read_recording<R: Reader>(reader: R) -> Result<Vec<StagingId>, String> {
+  let mut recording = Vec::new();
+
+  for (number, line) in BufferedReader::new(reader).lines().enumerate() {
+    let line = try!(line.map_err(|e| e.to_string()));
+
+    let fields: Vec<Option<u64>> = line.as_slice().words()
+      .map(|word| from_str::<u64>(word)).collect();
+
+    match fields.as_slice() {
+      [Some(realization), Some(made)] => recording.push((realization, made)),
+
+      _ => return Err(format!("line {} of the recording is invalid: {}",
+                              number + 1, line.as_slice().trim()))
+    }
+  }
+
+  Ok(recording)
+}
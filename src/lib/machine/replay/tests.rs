@@ -0,0 +1,70 @@
+use super::{Replay, read_recording};
+
+use nuketype::Thing;
+
+use std::io::{ChanReader, ChanWriter, MemReader};
+
+#[test]
+fn records_where_stagings_came_from() {
+  let replay = Replay::new();
+
+  let (tx, rx) = channel();
+
+  replay.record(box ChanWriter::new(tx));
+
+  let (a, b, c) = (Thing::empty(), Thing::empty(), Thing::empty());
+  let response  = Thing::empty();
+
+  replay.staged(&a, &response);
+  replay.staged(&b, &response);
+
+  replay.begin(&b, &response);
+  replay.staged(&c, &response);
+  replay.end();
+
+  replay.begin(&a, &response);
+  replay.end();
+
+  replay.begin(&c, &response);
+  replay.end();
+
+  replay.stop();
+
+  assert_eq!(Ok(vec![(0, 1), (0, 0), (1, 0)]),
+             read_recording(ChanReader::new(rx)));
+}
+
+#[test]
+fn replays_in_recorded_order() {
+  let replay = Replay::new();
+
+  replay.replay(vec![(0, 1), (0, 0)]);
+
+  let (a, b)   = (Thing::empty(), Thing::empty());
+  let response = Thing::empty();
+
+  assert!(replay.is_replaying());
+  assert!(replay.next().is_none());
+
+  replay.staged(&a, &response);
+  replay.staged(&b, &response);
+
+  assert!(replay.next() == Some((b.clone(), response.clone())));
+
+  replay.begin(&b, &response);
+  replay.end();
+
+  assert!(replay.next() == Some((a.clone(), response.clone())));
+
+  replay.begin(&a, &response);
+  replay.end();
+
+  assert!(!replay.is_replaying());
+}
+
+#[test]
+fn read_recording_rejects_invalid_lines() {
+  let reader = MemReader::new(b"0 1\nfoo\n".to_vec());
+
+  assert!(read_recording(reader).is_err());
+}
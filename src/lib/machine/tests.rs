@@ -1,5 +1,7 @@
 use super::Machine;
 
+use nuketype::Number;
+
 #[test]
 fn machine_creates_symbols_with_different_object_identity() {
   let machine = Machine::new();
@@ -20,3 +22,10 @@ fn machine_can_create_symbols_that_dont_match() {
 
   assert!(!machine.symbol("foo").eq_as_symbol(&machine.symbol("bar")));
 }
+
+#[test]
+fn machine_creates_numbers() {
+  let machine = Machine::new();
+
+  assert_eq!(Some(-7), Number::of(&machine.number(-7)));
+}
@@ -0,0 +1,150 @@
+//! A single task per Machine for running things after a delay.
+//!
+//! Jobs don't get a reactor, so the usual way to stage something later is to
+//! take a `Remote` (see `machine::reactor::remote`) when scheduling, and stage
+//! through it from the job. Until then, the reactor counts the `Remote` as
+//! outstanding work, so it doesn't stall early.
+
+use std::collections::PriorityQueue;
+use std::io::timer;
+use std::sync::{Arc, Mutex};
+use std::task::TaskBuilder;
+use std::time::duration::Duration;
+
+use time;
+
+#[cfg(test)]
+mod tests;
+
+/// A job to be run once its delay has passed. Jobs all run on the same task,
+/// so they should be quick; anything that blocks belongs on the
+/// `BlockingPool`.
+pub type Job = proc(): Send;
+
+/// Runs jobs after a delay, on a task that's only spawned once the first job
+/// comes in. Clones share the same task.
+///
+/// The task exits once every clone of the timer has been dropped and it's run
+/// every job it was given.
+#[deriving(Clone)]
+pub struct Timer {
+  sender: Arc<Mutex<Option<Sender<Entry>>>>
+}
+
+struct Entry {
+  /// When to run the job, in `time::precise_time_ns()` terms.
+  deadline: u64,
+
+  /// Breaks ties between jobs with the same deadline, so they run in the order
+  /// they were given.
+  serial:   u64,
+
+  job:      Job
+}
+
+// `PriorityQueue` is a max-heap, so these put the soonest deadline (and then
+// the lowest serial) on top.
+
+impl PartialEq for Entry {
+  fn eq(&self, other: &Entry) -> bool {
+    self.deadline == other.deadline && self.serial == other.serial
+  }
+}
+
+impl Eq for Entry { }
+
+impl PartialOrd for Entry {
+  fn partial_cmp(&self, other: &Entry) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Entry {
+  fn cmp(&self, other: &Entry) -> Ordering {
+    (other.deadline, other.serial).cmp(&(self.deadline, self.serial))
+  }
+}
+
+impl Timer {
+  /// Creates a new timer. No task is spawned until it's needed.
+  pub fn new() -> Timer {
+    Timer { sender: Arc::new(Mutex::new(None)) }
+  }
+
+  /// Runs `job` on the timer's task once `delay` has passed. Negative delays
+  /// are treated as zero.
+  pub fn after(&self, delay: Duration, job: Job) {
+    let delay    = delay.num_nanoseconds().unwrap_or(::std::i64::MAX);
+    let deadline = time::precise_time_ns() + ::std::cmp::max(delay, 0) as u64;
+
+    let mut sender = self.sender.lock();
+
+    if sender.is_none() {
+      *sender = Some(spawn());
+    }
+
+    // The task only exits once the sender is dropped, so this can't fail.
+    sender.as_ref().unwrap().send(Entry {
+      deadline: deadline,
+      serial:   0,
+      job:      job
+    });
+  }
+}
+
+fn spawn() -> Sender<Entry> {
+  let (sender, receiver) = channel::<Entry>();
+
+  TaskBuilder::new().named("timer").spawn(proc() {
+    let mut queue: PriorityQueue<Entry> = PriorityQueue::new();
+    let mut serial = 0u64;
+    let mut open   = true;
+
+    let mut clock = timer::Timer::new().unwrap();
+
+    loop {
+      // Run everything that's due.
+      let now = time::precise_time_ns();
+
+      while queue.top().map(|entry| entry.deadline <= now).unwrap_or(false) {
+        (queue.pop().unwrap().job)();
+      }
+
+      let received = match queue.top().map(|entry| entry.deadline) {
+        // Nothing to wait for but new jobs.
+        None if open => receiver.recv_opt().ok(),
+
+        None => break,
+
+        Some(deadline) if open => {
+          let wait    = (deadline - now) as i64;
+          let timeout = clock.oneshot(Duration::nanoseconds(wait));
+
+          select!(
+            entry = receiver.recv_opt() => entry.ok(),
+            ()    = timeout.recv()      => continue
+          )
+        },
+
+        // No more jobs are coming, so just sleep until the next one is due.
+        Some(deadline) => {
+          clock.sleep(Duration::nanoseconds((deadline - now) as i64));
+          continue
+        }
+      };
+
+      match received {
+        Some(mut entry) => {
+          entry.serial = serial;
+          serial += 1;
+
+          queue.push(entry);
+        },
+
+        None => open = false
+      }
+    }
+  });
+
+  sender
+}
@@ -0,0 +1,49 @@
+use super::Timer;
+
+use std::time::duration::Duration;
+
+use time;
+
+#[test]
+fn runs_jobs_in_deadline_order() {
+  let timer = Timer::new();
+
+  let (tx, rx) = channel();
+
+  for &(n, delay) in [(3u, 60i64), (1, 20), (2, 40), (0, 0)].iter() {
+    let tx = tx.clone();
+
+    timer.after(Duration::milliseconds(delay), proc() tx.send(n));
+  }
+
+  let order: Vec<uint> = range(0u, 4).map(|_| rx.recv()).collect();
+
+  assert_eq!(vec![0, 1, 2, 3], order);
+}
+
+#[test]
+fn waits_for_the_delay() {
+  let timer = Timer::new();
+  let start = time::precise_time_ns();
+
+  let (tx, rx) = channel();
+
+  timer.after(Duration::milliseconds(50), proc() tx.send(()));
+
+  rx.recv();
+
+  assert!(time::precise_time_ns() - start >= 50_000_000);
+}
+
+#[test]
+fn runs_pending_jobs_after_being_dropped() {
+  let (tx, rx) = channel();
+
+  {
+    let timer = Timer::new();
+
+    timer.after(Duration::milliseconds(20), proc() tx.send(()));
+  }
+
+  rx.recv();
+}
@@ -0,0 +1,270 @@
+//! Tracing of what reactors do, to a structured log.
+//!
+//! When enabled, every combination that `machine::reactor::combine()` carries
+//! out and every staging onto a `SerialReactor` or `ParallelReactor` is
+//! recorded, by default as one JSON object per line:
+//!
+//!     {"time":1520,"reactor":"ParallelReactor #0","event":"combination",
+//!      "caller":"[#0x...]","subject":"[#0x...]","message":"[:foo]",
+//!      "receiver":"lookup"}
+//!     {"time":1544,"reactor":"ParallelReactor #0","event":"staging",
+//!      "execution":"[#0x...]","response":"[#0x...]"}
+//!
+//! `time` is in microseconds since tracing was enabled. Objects are written in
+//! their `Show` format, which includes their tags. The receiver is `lookup`
+//! for the default symbol lookup, `native 0x...` for any other native
+//! receiver, and the receiver object itself for Executions and Aliens.
+//!
+//! The same records can be written as a plain text timeline instead, which is
+//! easier to read directly. See `TraceFormat`. They can also be sent down a
+//! channel, one line at a time, instead of written anywhere. See `TraceSink`.
+
+use object::ObjectRef;
+
+use serialize::json;
+
+use std::io::IoResult;
+use std::sync::{Arc, Mutex};
+use std::sync::atomics::{AtomicBool, Relaxed};
+use std::task;
+
+use time;
+
+#[cfg(test)]
+mod tests;
+
+/// How records are written.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum TraceFormat {
+  /// One JSON object per line, as shown in the module documentation.
+  JsonLines,
+
+  /// One line of text per record, aligned into columns.
+  Timeline
+}
+
+impl TraceFormat {
+  /// Gets a format by the name used on the command line: `json` or `text`.
+  pub fn from_name(name: &str) -> Option<TraceFormat> {
+    match name {
+      "json" => Some(JsonLines),
+      "text" => Some(Timeline),
+      _      => None
+    }
+  }
+}
+
+/// Something a reactor did.
+pub enum Event<'a> {
+  /// A combination of a caller, subject and message, with a description of the
+  /// receiver that was chosen.
+  Combination(&'a ObjectRef, &'a ObjectRef, &'a ObjectRef, &'a str),
+
+  /// An execution staged with a response.
+  Staging(&'a ObjectRef, &'a ObjectRef)
+}
+
+/// Where records go.
+pub enum TraceSink {
+  /// Written to a writer, each followed by a newline.
+  WriterSink(Box<Writer+Send>),
+
+  /// Sent as a `String` per record, without the newline.
+  ChannelSink(Sender<String>)
+}
+
+/// The open log of an enabled trace.
+struct Log {
+  sink:     TraceSink,
+  format:   TraceFormat,
+  start_ns: u64
+}
+
+/// Writes records of what reactors do, if enabled. Disabled by default, since
+/// it costs a lock and a write on every combination. Clones share the same log.
+///
+/// Usually reached through `Reactor::tracer()`.
+#[deriving(Clone)]
+pub struct Trace {
+  enabled: Arc<AtomicBool>,
+  log:     Arc<Mutex<Option<Log>>>
+}
+
+impl Trace {
+  /// Creates a new, disabled trace.
+  pub fn new() -> Trace {
+    Trace {
+      enabled: Arc::new(AtomicBool::new(false)),
+      log:     Arc::new(Mutex::new(None))
+    }
+  }
+
+  /// Starts writing JSON records to `writer`. Any previous writer is flushed
+  /// and replaced.
+  pub fn enable(&self, writer: Box<Writer+Send>) {
+    self.enable_with_format(writer, JsonLines)
+  }
+
+  /// Starts writing records to `writer` in the given format. Any previous
+  /// writer is flushed and replaced, and time starts again from zero.
+  pub fn enable_with_format(&self, writer: Box<Writer+Send>,
+                            format: TraceFormat) {
+    self.enable_sink(WriterSink(writer), format)
+  }
+
+  /// Starts sending records to `sink` in the given format. Any previous sink
+  /// is flushed and replaced, and time starts again from zero.
+  pub fn enable_sink(&self, sink: TraceSink, format: TraceFormat) {
+    let mut guard = self.log.lock();
+
+    match guard.take() {
+      Some(Log { sink: WriterSink(mut old), .. }) => { let _ = old.flush(); },
+      _                                           => ()
+    }
+
+    *guard = Some(Log {
+      sink:     sink,
+      format:   format,
+      start_ns: time::precise_time_ns()
+    });
+
+    self.enabled.store(true, Relaxed);
+  }
+
+  /// Returns true if anything is being traced.
+  pub fn is_enabled(&self) -> bool {
+    self.enabled.load(Relaxed)
+  }
+
+  /// Writes a record for a combination. `receiver` describes the receiver
+  /// that was chosen; see the module documentation.
+  pub fn record(&self,
+                caller:   &ObjectRef,
+                subject:  &ObjectRef,
+                message:  &ObjectRef,
+                receiver: &str) {
+    self.record_event(Combination(caller, subject, message, receiver))
+  }
+
+  /// Writes a record for a staging.
+  pub fn record_staging(&self, execution: &ObjectRef, response: &ObjectRef) {
+    self.record_event(Staging(execution, response))
+  }
+
+  /// Writes a record for any event, attributed to the current task.
+  ///
+  /// If writing fails (or the receiver of a `ChannelSink` hangs up), tracing is
+  /// disabled with a warning rather than bringing the reactor down.
+  pub fn record_event(&self, event: Event) {
+    if !self.is_enabled() { return }
+
+    let reactor = task::name();
+
+    let mut guard = self.log.lock();
+
+    let result = match *guard {
+      Some(ref mut log) => {
+        // Taken under the lock, so that times never go backwards in the log.
+        let time_us = (time::precise_time_ns() - log.start_ns) / 1000;
+
+        let line = format_record(reactor.as_ref().map(|s| s.as_slice()),
+                                 time_us, &event, log.format);
+
+        match log.sink {
+          WriterSink(ref mut writer) =>
+            writer.write_line(line.as_slice()).map_err(|e| e.to_string()),
+
+          ChannelSink(ref sender) =>
+            sender.send_opt(line).map_err(|_| "receiver hung up".to_string())
+        }
+      },
+
+      None => return
+    };
+
+    match result {
+      Ok(()) => (),
+      Err(e) => {
+        warn!("trace disabled: {}", e);
+
+        self.enabled.store(false, Relaxed);
+        *guard = None;
+      }
+    }
+  }
+
+  /// Flushes the log. Should be called once the machine is done.
+  pub fn flush(&self) -> IoResult<()> {
+    match *self.log.lock() {
+      Some(Log { sink: WriterSink(ref mut writer), .. }) => writer.flush(),
+      _                                                  => Ok(())
+    }
+  }
+}
+
+/// Formats a single record (without the newline). `time_us` is the time since
+/// tracing started, in microseconds.
+pub fn format_record(reactor: Option<&str>,
+                     time_us: u64,
+                     event:   &Event,
+                     format:  TraceFormat)
+                     -> String {
+  match format {
+    JsonLines => format_json(reactor, time_us, event),
+    Timeline  => format_timeline(reactor, time_us, event)
+  }
+}
+
+fn format_json(reactor: Option<&str>, time_us: u64, event: &Event) -> String {
+  let mut out = format!("{{\"time\":{},\"reactor\":", time_us);
+
+  match reactor {
+    Some(name) => push_json_string(&mut out, name),
+    None       => out.push_str("null")
+  }
+
+  match *event {
+    Combination(caller, subject, message, receiver) => {
+      out.push_str(",\"event\":\"combination\",\"caller\":");
+      push_json_string(&mut out, caller.to_string().as_slice());
+      out.push_str(",\"subject\":");
+      push_json_string(&mut out, subject.to_string().as_slice());
+      out.push_str(",\"message\":");
+      push_json_string(&mut out, message.to_string().as_slice());
+      out.push_str(",\"receiver\":");
+      push_json_string(&mut out, receiver);
+    },
+
+    Staging(execution, response) => {
+      out.push_str(",\"event\":\"staging\",\"execution\":");
+      push_json_string(&mut out, execution.to_string().as_slice());
+      out.push_str(",\"response\":");
+      push_json_string(&mut out, response.to_string().as_slice());
+    }
+  }
+
+  out.push_char('}');
+
+  out
+}
+
+fn format_timeline(reactor: Option<&str>, time_us: u64, event: &Event)
+                   -> String {
+  let time    = format!("{}.{:03}ms", time_us / 1000, time_us % 1000);
+  let reactor = reactor.unwrap_or("-");
+
+  match *event {
+    Combination(caller, subject, message, receiver) =>
+      format!("{:>12s}  {:<20s}  combine  {} <- {} {} via {}",
+              time, reactor, caller, subject, message, receiver),
+
+    Staging(execution, response) =>
+      format!("{:>12s}  {:<20s}  stage    {} <- {}",
+              time, reactor, execution, response)
+  }
+}
+
+/// Appends `string` to `out` as a quoted, escaped JSON string.
+fn push_json_string(out: &mut String, string: &str) {
+  out.push_str(json::String(string.to_string()).to_string().as_slice());
+}
@@ -0,0 +1,121 @@
+use super::{Trace, TraceFormat, JsonLines, Timeline, format_record};
+use super::{Combination, Staging};
+use super::ChannelSink;
+
+use object::Meta;
+
+use nuketype::Thing;
+
+use machine::Machine;
+
+#[test]
+fn disabled_by_default() {
+  let trace = Trace::new();
+
+  assert!(!trace.is_enabled());
+
+  // Shouldn't do anything, or fail.
+  trace.record(&Thing::empty(), &Thing::empty(), &Thing::empty(), "lookup");
+
+  assert!(trace.flush().is_ok());
+}
+
+#[test]
+fn format_record_is_json() {
+  let machine = Machine::new();
+
+  let caller  = Thing::tagged(Meta::new(), "caller");
+  let subject = Thing::tagged(Meta::new(), "subject");
+  let message = machine.symbol("say \"hi\"\n");
+
+  let line = format_record(Some("ParallelReactor #0"), 1520,
+                           &Combination(&caller, &subject, &message, "lookup"),
+                           JsonLines);
+
+  let expected = format!(
+    concat!("{{\"time\":1520,\"reactor\":\"ParallelReactor #0\",",
+            "\"event\":\"combination\",\"caller\":\"{}\",",
+            "\"subject\":\"{}\",\"message\":\"[:say \\\"hi\\\"\\n]\",",
+            "\"receiver\":\"lookup\"}}"),
+    caller, subject);
+
+  assert_eq!(expected, line);
+}
+
+#[test]
+fn format_record_without_reactor_name() {
+  let obj  = Thing::empty();
+  let line = format_record(None, 0, &Combination(&obj, &obj, &obj, "native"),
+                           JsonLines);
+
+  assert!(line.as_slice().starts_with("{\"time\":0,\"reactor\":null,"));
+}
+
+#[test]
+fn format_staging_record() {
+  let execution = Thing::tagged(Meta::new(), "execution");
+  let response  = Thing::tagged(Meta::new(), "response");
+
+  let line = format_record(None, 7, &Staging(&execution, &response),
+                           JsonLines);
+
+  let expected = format!(
+    concat!("{{\"time\":7,\"reactor\":null,\"event\":\"staging\",",
+            "\"execution\":\"{}\",\"response\":\"{}\"}}"),
+    execution, response);
+
+  assert_eq!(expected, line);
+}
+
+#[test]
+fn format_record_as_timeline() {
+  let execution = Thing::tagged(Meta::new(), "execution");
+  let response  = Thing::tagged(Meta::new(), "response");
+
+  let line = format_record(Some("SerialReactor"), 12345,
+                           &Staging(&execution, &response), Timeline);
+
+  assert_eq!(format!("    12.345ms  SerialReactor         stage    {} <- {}",
+                     execution, response),
+             line);
+}
+
+#[test]
+fn trace_formats_by_name() {
+  assert_eq!(Some(JsonLines), TraceFormat::from_name("json"));
+  assert_eq!(Some(Timeline),  TraceFormat::from_name("text"));
+  assert_eq!(None,            TraceFormat::from_name("xml"));
+}
+
+#[test]
+fn channel_sink_receives_records() {
+  let machine = Machine::new();
+
+  let (tx, rx) = channel();
+
+  machine.set_tracer(ChannelSink(tx), JsonLines);
+
+  let execution = Thing::empty();
+  let response  = Thing::empty();
+
+  machine.trace.record_staging(&execution, &response);
+
+  let line = rx.recv();
+
+  assert!(line.as_slice().contains("\"event\":\"staging\""));
+}
+
+#[test]
+fn channel_sink_disables_when_receiver_hangs_up() {
+  let trace = Trace::new();
+
+  let (tx, rx) = channel();
+
+  trace.enable_sink(ChannelSink(tx), Timeline);
+
+  drop(rx);
+
+  trace.record_staging(&Thing::empty(), &Thing::empty());
+
+  assert!(!trace.is_enabled());
+}
@@ -0,0 +1,130 @@
+//! Deduplication of warnings emitted while reacting.
+//!
+//! Paws programs tend to loop, and a malformed combination in a loop produces
+//! the same warning over and over, drowning out everything else. Warnings
+//! emitted through `machine_warn!` are tracked by category and call site, and
+//! according to the machine's `WarningPolicy`, only the first occurrence and
+//! periodic summaries are actually logged.
+
+use machine::reactor::at_current_span;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[cfg(test)]
+mod tests;
+
+/// Decides which warnings get logged.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct WarningPolicy {
+  /// If false, every warning is logged.
+  pub deduplicate:      bool,
+
+  /// When deduplicating, log a "repeated N times" summary every time a site
+  /// has warned this many more times. Zero disables summaries.
+  pub summary_interval: u64
+}
+
+impl WarningPolicy {
+  /// The default policy: deduplicate, and summarize every 1000 repeats.
+  pub fn new() -> WarningPolicy {
+    WarningPolicy {
+      deduplicate:      true,
+      summary_interval: 1000
+    }
+  }
+
+  /// A policy that logs every warning.
+  pub fn verbose() -> WarningPolicy {
+    WarningPolicy {
+      deduplicate:      false,
+      summary_interval: 0
+    }
+  }
+}
+
+/// Identifies where a warning came from: `(category, file, line)`.
+pub type WarningSite = (&'static str, &'static str, uint);
+
+/// Tracks warnings for a machine. Clones share the same state.
+#[deriving(Clone)]
+pub struct Warnings {
+  state: Arc<Mutex<WarningsState>>
+}
+
+struct WarningsState {
+  policy: WarningPolicy,
+  counts: HashMap<WarningSite, u64>
+}
+
+impl Warnings {
+  /// Creates a new warning tracker with the default policy.
+  pub fn new() -> Warnings {
+    Warnings {
+      state: Arc::new(Mutex::new(WarningsState {
+        policy: WarningPolicy::new(),
+        counts: HashMap::new()
+      }))
+    }
+  }
+
+  /// Changes the policy. Counts so far are kept.
+  pub fn set_policy(&self, policy: WarningPolicy) {
+    self.state.lock().policy = policy;
+  }
+
+  /// Gets a copy of the current policy.
+  pub fn policy(&self) -> WarningPolicy {
+    self.state.lock().policy.clone()
+  }
+
+  /// Records a warning from the given site, and logs it if the policy says to.
+  ///
+  /// Returns true if it was logged. Prefer the `machine_warn!` macro, which
+  /// fills in the site automatically.
+  pub fn warn(&self,
+              category: &'static str,
+              file:     &'static str,
+              line:     uint,
+              message:  String)
+              -> bool {
+
+    let (count, policy) = {
+      let mut state = self.state.lock();
+
+      let count = {
+        let count = state.counts.find_or_insert((category, file, line), 0);
+
+        *count += 1;
+        *count
+      };
+
+      (count, state.policy.clone())
+    };
+
+    let message = format!("{}{}", message, at_current_span());
+
+    if !policy.deduplicate || count == 1 {
+      warn!("[{}] {}", category, message);
+      true
+
+    } else if policy.summary_interval > 0 &&
+              count % policy.summary_interval == 0 {
+      warn!("[{}] {} (repeated {} times from {}:{})",
+            category, message, count, file, line);
+      true
+
+    } else {
+      false
+    }
+  }
+
+  /// Returns how many times each site has warned, for sites that have warned
+  /// more than once.
+  pub fn repeated(&self) -> Vec<(WarningSite, u64)> {
+    self.state.lock().counts.iter()
+      .filter(|&(_, &count)| count > 1)
+      .map(|(site, &count)| (*site, count))
+      .collect()
+  }
+}
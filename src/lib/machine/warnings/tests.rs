@@ -0,0 +1,29 @@
+use super::{Warnings, WarningPolicy};
+
+#[test]
+fn deduplicates_by_site() {
+  let warnings = Warnings::new();
+
+  warnings.set_policy(WarningPolicy { deduplicate: true, summary_interval: 3 });
+
+  let logged: Vec<bool> = range(0u, 7).map(|_|
+    warnings.warn("test", "a.rs", 1, "hello".to_string())).collect();
+
+  assert_eq!(vec![true, false, true, false, false, true, false], logged);
+
+  // A different site is logged independently.
+  assert!(warnings.warn("test", "a.rs", 2, "hello".to_string()));
+
+  assert_eq!(vec![(("test", "a.rs", 1), 7)], warnings.repeated());
+}
+
+#[test]
+fn verbose_logs_everything() {
+  let warnings = Warnings::new();
+
+  warnings.set_policy(WarningPolicy::verbose());
+
+  for _ in range(0u, 5) {
+    assert!(warnings.warn("test", "a.rs", 1, "hello".to_string()));
+  }
+}
@@ -3,13 +3,15 @@
 use object::{ObjectRef, TypedRefGuard};
 use object::{Meta, Params, Tag};
 
-use nuketype::Nuketype;
+use nuketype::{Nuketype, Execution};
 
 use machine::Reactor;
+use machine::reactor::set_current_span;
 
 use std::any::{Any, AnyRefExt, AnyMutRefExt};
 use std::io::IoResult;
 use std::mem::replace;
+use std::sync::{Arc, Mutex};
 
 pub use nuketype::execution::stage_receiver;
 
@@ -88,6 +90,20 @@ impl Alien {
                box NativeReceiverData(receiver))
   }
 
+  /// If `object` is an Alien made by `new_from_native_receiver()`, returns the
+  /// function inside it, which can be called directly instead.
+  pub fn native_receiver_of(object: &ObjectRef)
+                            -> Option<fn (&mut Reactor, Params)> {
+    match object.lock().try_cast::<Alien>() {
+      Ok(alien) =>
+        alien.data.downcast_ref::<NativeReceiverData>()
+          .map(|&NativeReceiverData(receiver)| receiver),
+
+      Err(_) =>
+        None
+    }
+  }
+
   /// Boxes up a new Alien with the given `routine` and `data`, and tags it with
   /// `name`.
   ///
@@ -331,6 +347,9 @@ fn call_pattern_alien_routine<'a>(
       debug!("call_pattern_alien_routine: calling {} from {} with {}",
              alien, caller, args.as_slice());
 
+      // Point anything the routine complains about at the caller's source.
+      set_current_span(Execution::last_span_of(&caller));
+
       routine(reactor, caller, args.as_slice())
     },
     None =>
@@ -420,9 +439,10 @@ fn native_receiver_alien_routine<'a>(
 
       _ => {
         // Malformed. Warn and unstage.
-        warn!(concat!("native_receiver_alien_routine received",
-                      " malformed params object {}"),
-              response);
+        machine_warn!(reactor.machine(), "alien",
+                      concat!("native_receiver_alien_routine received",
+                              " malformed params object {}"),
+                      response);
 
         return
       }
@@ -431,3 +451,79 @@ fn native_receiver_alien_routine<'a>(
 
   receiver(reactor, params)
 }
+
+/// Native logic to be run once a `then()` call has a result. Receives the
+/// result.
+pub type Continuation = proc (&mut Reactor, ObjectRef): Send;
+
+/// Calls `target` with `argument` as cPaws' `target[] argument` would, and runs
+/// `continuation` with the result, allowing call-pattern Aliens to invoke Paws
+/// code and then carry on natively without hand-rolling a continuation Alien.
+///
+/// The target is staged directly, not branched; use
+/// `util::clone::stageable()` first if it needs to stay untouched.
+///
+/// Internally, this stages `target` with a continuation Alien as the caller.
+/// The first time the continuation Alien is realized, it stages whatever it
+/// received (the target's resumption) with `argument`. The second time, it
+/// calls `continuation` with what it received. Any further realizations are
+/// ignored.
+///
+/// # Example
+///
+///     alien::then(reactor, function, argument, proc (reactor, result) {
+///       reactor.stage(caller, result)
+///     });
+pub fn then(reactor:      &mut Reactor,
+            target:       ObjectRef,
+            argument:     ObjectRef,
+            continuation: Continuation) {
+
+  let then_data = box ThenData {
+    argument:     Some(argument),
+    continuation: Arc::new(Mutex::new(Some(continuation)))
+  };
+
+  let alien = Alien::create("(then)", then_alien_routine,
+                            then_data as Box<Data+Send+Sync>);
+
+  reactor.stage(target, alien);
+}
+
+/// Internal state for `then()` continuation Aliens.
+///
+/// A `proc` can't be cloned, so clones of the Alien share the same (single)
+/// continuation.
+#[deriving(Clone)]
+struct ThenData {
+  argument:     Option<ObjectRef>,
+  continuation: Arc<Mutex<Option<Continuation>>>
+}
+
+/// Function that performs `then()` continuations.
+fn then_alien_routine<'a>(
+                      mut alien: TypedRefGuard<'a, Alien>,
+                      reactor:   &mut Reactor,
+                      response:  ObjectRef) {
+
+  let (argument, continuation) = {
+    let data = alien.data.downcast_mut::<ThenData>().unwrap();
+
+    match data.argument.take() {
+      Some(argument) => (Some(argument), None),
+      None           => (None, data.continuation.lock().take())
+    }
+  };
+
+  drop(alien);
+
+  match (argument, continuation) {
+    (Some(argument), _) =>
+      reactor.stage(response, argument),
+
+    (None, Some(continuation)) =>
+      continuation(reactor, response),
+
+    (None, None) => ()
+  }
+}
@@ -1,4 +1,4 @@
-use super::Alien;
+use super::{Alien, then};
 
 use object::{ObjectRef, Params, Meta};
 
@@ -94,15 +94,8 @@ fn call_pattern_alien() {
 
     Alien::realize(alien, reactor, send);
 
-    match reactor.stagings.as_slice() {
-      [(ref execution, ref response)] => {
-        assert!(execution == &caller_ref);
-        assert!(response  == &alien_ref);
-      },
-      _ => fail!("Unexpected reaction!")
-    }
-
-    reactor.stagings.truncate(0);
+    reactor.expect_stage(&caller_ref, &alien_ref);
+    reactor.expect_no_stagings();
   };
 
   assert_caller_and_alien(&mut reactor, caller_ref.clone());
@@ -114,15 +107,11 @@ fn call_pattern_alien() {
 
     Alien::realize(alien, &mut reactor, machine.symbol("c"));
 
-    match reactor.stagings.as_slice() {
-      [(ref execution, ref response)] => {
-        assert!(execution == &caller_ref);
-        assert!(response.symbol_ref().unwrap().as_slice() == "abc");
-      },
-      _ => fail!("Unexpected reaction!")
-    }
+    let (_, response) = reactor.expect_staged(&caller_ref);
 
-    reactor.stagings.truncate(0);
+    assert!(response.symbol_ref().unwrap().as_slice() == "abc");
+
+    reactor.expect_no_stagings();
   }
 
   {
@@ -130,7 +119,7 @@ fn call_pattern_alien() {
 
     Alien::realize(alien, &mut reactor, machine.symbol("d"));
 
-    assert!(reactor.stagings.is_empty()); // already complete
+    reactor.expect_no_stagings(); // already complete
   }
 }
 
@@ -153,17 +142,12 @@ fn oneshot_alien() {
     let alien = alien_ref.lock().try_cast::<Alien>().ok().unwrap();
 
     Alien::realize(alien, &mut reactor, caller_ref.clone());
-    
-    match reactor.stagings.as_slice() {
-      [(ref execution, ref response)] => {
-        assert!(execution == &caller_ref);
-        assert!(response.eq_as_symbol(&machine.symbol("foo")));
-      },
-
-      _ => fail!("Unexpected reaction!")
-    }
 
-    reactor.stagings.truncate(0);
+    let (_, response) = reactor.expect_staged(&caller_ref);
+
+    assert!(response.eq_as_symbol(&machine.symbol("foo")));
+
+    reactor.expect_no_stagings();
   }
 
   {
@@ -171,7 +155,7 @@ fn oneshot_alien() {
 
     Alien::realize(alien, &mut reactor, caller_ref.clone());
 
-    assert!(reactor.stagings.is_empty());
+    reactor.expect_no_stagings();
   }
 }
 
@@ -207,18 +191,60 @@ fn alien_from_native_receiver() {
 
     Alien::realize(alien, &mut reactor, params.clone());
 
-    match reactor.stagings.as_slice() {
-      [(ref execution1, ref response1), (ref execution2, ref response2)] => {
-        assert!(execution1 == &caller);
-        assert!(response1  == &message);
+    reactor.expect_stage(&caller,  &message);
+    reactor.expect_stage(&subject, &message);
+    reactor.expect_no_stagings();
+  }
+}
+
+#[test]
+fn then_calls_and_continues() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
 
-        assert!(execution2 == &subject);
-        assert!(response2  == &message);
-      },
+  let function = Thing::empty();
+  let argument = machine.symbol("argument");
+  let result   = machine.symbol("result");
+  let out      = Thing::empty();
 
-      _ => fail!("Unexpected reaction!")
-    }
+  let out_clone = out.clone();
+
+  then(&mut reactor, function.clone(), argument.clone(),
+    proc (reactor, result) { reactor.stage(out_clone, result) });
+
+  // function <- (then)
+  let (_, continuation) = reactor.expect_staged(&function);
 
-    reactor.stagings.truncate(0);
+  reactor.expect_no_stagings();
+
+  // (then) <- resumption; resumption <- argument
+  let resumption = Thing::empty();
+
+  {
+    let alien = continuation.lock().try_cast::<Alien>().ok().unwrap();
+
+    Alien::realize(alien, &mut reactor, resumption.clone());
+
+    reactor.expect_stage(&resumption, &argument);
+    reactor.expect_no_stagings();
+  }
+
+  // (then) <- result; continuation runs
+  {
+    let alien = continuation.lock().try_cast::<Alien>().ok().unwrap();
+
+    Alien::realize(alien, &mut reactor, result.clone());
+
+    reactor.expect_stage(&out, &result);
+    reactor.expect_no_stagings();
+  }
+
+  // Already complete.
+  {
+    let alien = continuation.lock().try_cast::<Alien>().ok().unwrap();
+
+    Alien::realize(alien, &mut reactor, result.clone());
+
+    assert!(reactor.stagings.is_empty());
   }
 }
@@ -23,6 +23,14 @@ use util::clone;
 
 use std::io::IoResult;
 use std::sync::Arc;
+use std::fmt::Show;
+use std::fmt;
+
+pub use self::snapshot::{ExecutionSnapshot, SnapshotCombinable};
+pub use self::snapshot::{SnapshotLocals, SnapshotSelf};
+pub use self::snapshot::{SnapshotSymbol, SnapshotObject};
+
+pub mod snapshot;
 
 #[cfg(test)]
 mod tests;
@@ -35,31 +43,66 @@ mod tests;
 /// automatically, so prefer that to `Execution::new()` if possible.
 #[deriving(Clone)]
 pub struct Execution {
-  root:     Arc<Script>,
-  pc:       uint,
-  stack:    Vec<Combinable>
+  root:      Arc<Script>,
+  spans:     Option<Arc<SpanTable>>,
+  pc:        uint,
+
+  /// Each item on the stack is paired with the span of the instruction that
+  /// pushed it, if known, for debugging purposes.
+  stack:     Vec<(Combinable, Option<Span>)>,
+
+  /// True if the last `advance_within()` ran out of budget, in which case the
+  /// next response is ignored.
+  preempted: bool
 }
 
 impl Execution {
   /// Creates a new Execution with the given Script as its root.
   pub fn new(root: Script) -> Execution {
     Execution {
-      root:     Arc::new(root),
-      pc:       0,
-      stack:    Vec::new()
+      root:      Arc::new(root),
+      spans:     None,
+      pc:        0,
+      stack:     Vec::new(),
+      preempted: false
+    }
+  }
+
+  /// Creates a new Execution with the given Script as its root, and a table
+  /// describing where in the source each of the Script's instructions came
+  /// from.
+  pub fn with_spans(root: Script, spans: SpanTable) -> Execution {
+    Execution {
+      root:      Arc::new(root),
+      spans:     Some(Arc::new(spans)),
+      pc:        0,
+      stack:     Vec::new(),
+      preempted: false
     }
   }
 
   /// Boxes up an Execution into an object with its receiver set to
   /// `stage_receiver` and a new, empty `locals` object.
   pub fn create(machine: &Machine, root: Script) -> ObjectRef {
+    Execution::create_from(machine, Execution::new(root))
+  }
+
+  /// Like `create()`, but with a span table. See `Execution::with_spans()`.
+  pub fn create_with_spans(machine: &Machine,
+                           root:    Script,
+                           spans:   SpanTable)
+                           -> ObjectRef {
+    Execution::create_from(machine, Execution::with_spans(root, spans))
+  }
+
+  fn create_from(machine: &Machine, execution: Execution) -> ObjectRef {
     let mut meta = Meta::with_receiver(stage_receiver);
 
     meta.members.push_pair_to_child(
       machine.locals_sym.clone(),
       Locals::empty(machine.locals_sym.clone()));
 
-    ObjectRef::store(box Execution::new(root), meta)
+    ObjectRef::store(box execution, meta)
   }
 
   /// Returns the "root" Script of the Execution, which the Execution's internal
@@ -68,36 +111,190 @@ impl Execution {
     &*self.root
   }
 
+  /// Returns the span table of the root Script, if the Execution was created
+  /// with one.
+  pub fn spans<'a>(&'a self) -> Option<&'a SpanTable> {
+    self.spans.as_ref().map(|spans| &**spans)
+  }
+
+  /// Recreates an Execution partway through its root Script, with `pc` as the
+  /// index of the next instruction to evaluate. Spans are not kept.
+  ///
+  /// Used to restore Executions that were saved with `pc()` and `stack()`. See
+  /// `util::serialize`, and `resume()` for restoring from a snapshot.
+  pub fn resume_at(root: Script, pc: uint, stack: Vec<Combinable>)
+                   -> Execution {
+    let stack = stack.move_iter().map(|combinable| (combinable, None)).collect();
+
+    Execution {
+      root:      Arc::new(root),
+      spans:     None,
+      pc:        pc,
+      stack:     stack,
+      preempted: false
+    }
+  }
+
+  /// Returns the index of the next instruction in the root Script to be
+  /// evaluated.
+  pub fn pc(&self) -> uint {
+    self.pc
+  }
+
+  /// Returns a copy of the Execution's stack, without spans.
+  pub fn stack(&self) -> Vec<Combinable> {
+    self.stack.iter().map(|&(ref combinable, _)| combinable.clone()).collect()
+  }
+
+  /// Returns true if every instruction in the root Script has been evaluated,
+  /// i.e. the Execution has nothing left to combine.
+  pub fn is_complete(&self) -> bool {
+    let Script(ref instructions) = *self.root;
+
+    self.pc >= instructions.len()
+  }
+
+  /// Returns the index of the next instruction to be evaluated.
+  pub fn pc(&self) -> uint {
+    self.pc
+  }
+
+  /// Returns true if the last `advance_within()` stopped because it ran out of
+  /// budget, rather than at a combination or the end.
+  pub fn is_preempted(&self) -> bool {
+    self.preempted
+  }
+
+  /// Returns the span of the most recently evaluated instruction, if known.
+  pub fn last_span(&self) -> Option<Span> {
+    if self.pc > 0 { self.span_at(self.pc - 1) } else { None }
+  }
+
+  /// Returns the span of the most recently evaluated instruction of `object`, if
+  /// it's an Execution and the span is known.
+  pub fn last_span_of(object: &ObjectRef) -> Option<Span> {
+    match object.lock().try_cast::<Execution>() {
+      Ok(execution) => execution.deref().last_span(),
+      Err(_)        => None
+    }
+  }
+
+  /// Returns the span of the instruction at `index` within the root Script, if
+  /// known.
+  fn span_at(&self, index: uint) -> Option<Span> {
+    self.spans.as_ref().and_then(|spans| spans.get(index).map(|s| s.clone()))
+  }
+
+  /// Creates a copy of the Execution at the same point in the same Script, but
+  /// with every object referenced by its Script or its stack replaced with the
+  /// result of `map`.
+  ///
+  /// Used to move Executions between Machines. See `util::transfer`.
+  pub fn map_objects(&self, map: |&ObjectRef| -> ObjectRef) -> Execution {
+    let Script(ref instructions) = *self.root;
+
+    let root = instructions.iter().map(|instruction|
+      match *instruction {
+        Push(ref object)             => Push(map(object)),
+        PushPair(ref key, ref value) => PushPair(map(key), map(value)),
+        LookupCombine(ref symbol)    => LookupCombine(map(symbol)),
+        LookupLocal(ref symbol)      => LookupLocal(map(symbol)),
+        ref other                    => other.clone()
+      }).collect();
+
+    let stack = self.stack.iter().map(|&(ref combinable, ref span)|
+      (match *combinable {
+        From(ref object) => From(map(object)),
+        FromLocals       => FromLocals,
+        FromSelf         => FromSelf
+      }, span.clone())).collect();
+
+    Execution {
+      root:      Arc::new(Script(root)),
+      spans:     self.spans.clone(),
+      pc:        self.pc,
+      stack:     stack,
+      preempted: self.preempted
+    }
+  }
+
   /// Advances the Execution, first pushing `response` onto the stack, moving
   /// its program counter forward and evaluating instructions, ending with
   /// either the execution of a Combine instruction or completion.
   pub fn advance(&mut self, response: ObjectRef) -> Option<Combination> {
+    self.advance_within(response, None)
+  }
+
+  /// Like `advance()`, but evaluates no more than `budget` instructions, if
+  /// given (and at least one). If the budget runs out before a combination,
+  /// returns `None` with `is_preempted()` true; the next call then ignores its
+  /// response and carries on from where this one stopped. Reactors only make
+  /// that call with `Machine::resume_marker`.
+  ///
+  /// Lets a reactor put a long-running Execution back in its queue, so that it
+  /// doesn't starve everything else. See `Machine::preempt_after`.
+  pub fn advance_within(&mut self, response: ObjectRef, budget: Option<uint>)
+                        -> Option<Combination> {
     let Script(ref instructions) = *self.root;
 
-    if self.pc < instructions.len() {
-      self.stack.push(From(response));
+    if self.preempted {
+      self.preempted = false;
+    } else if self.pc < instructions.len() {
+      self.stack.push((From(response), None));
     }
 
+    let mut evaluated = 0u;
+
     while self.pc < instructions.len() {
+      match budget {
+        Some(budget) if evaluated > 0 && evaluated >= budget => {
+          self.preempted = true;
+          return None
+        },
+        _ => evaluated += 1
+      }
+
       let instruction = &instructions[self.pc];
+      let span        = self.span_at(self.pc);
 
       self.pc += 1;
 
-      debug!("advance: {} on (stack: {})", instruction, self.stack);
+      debug!("advance: {} on (stack: {})",
+        instruction, StackDisplay(self.stack.as_slice()));
 
       match *instruction {
         PushLocals =>
-          self.stack.push(FromLocals),
+          self.stack.push((FromLocals, span)),
 
         PushSelf =>
-          self.stack.push(FromSelf),
+          self.stack.push((FromSelf, span)),
 
         Push(ref object) =>
-          self.stack.push(From(object.clone())),
+          self.stack.push((From(object.clone()), span)),
+
+        PushPair(ref key, ref value) => {
+          self.stack.push((From(key.clone()),   span.clone()));
+          self.stack.push((From(value.clone()), span));
+        },
+
+        LookupCombine(ref symbol) => {
+          let (subject, _) = self.stack.pop().expect("stack too small");
+
+          return Some(Combination {
+            subject: subject,
+            message: From(symbol.clone())
+          })
+        },
+
+        LookupLocal(ref symbol) =>
+          return Some(Combination {
+            subject: FromLocals,
+            message: From(symbol.clone())
+          }),
 
         Combine => {
-          let message = self.stack.pop().expect("stack too small");
-          let subject = self.stack.pop().expect("stack too small");
+          let (message, _) = self.stack.pop().expect("stack too small");
+          let (subject, _) = self.stack.pop().expect("stack too small");
 
           return Some(Combination {
             subject: subject,
@@ -118,8 +315,51 @@ impl Nuketype for Execution {
   fn fmt_paws(&self, writer: &mut Writer) -> IoResult<()> {
     let Script(ref instructions) = *self.root;
 
-    write!(writer, "Execution {{ pc: {} => {}, stack: {} }}",
-      self.pc, instructions[self.pc], self.stack)
+    let stack = StackDisplay(self.stack.as_slice());
+
+    if self.pc < instructions.len() {
+      write!(writer, "Execution {{ pc: {} => {}, stack: {} }}",
+        self.pc, instructions[self.pc], stack)
+    } else {
+      write!(writer, "Execution {{ pc: {} => (complete), stack: {} }}",
+        self.pc, stack)
+    }
+  }
+}
+
+/// Formats an Execution's stack with the source token of each item, where
+/// known, e.g. `[locals, "hello" (file:3:4)]`.
+struct StackDisplay<'a>(&'a [(Combinable, Option<Span>)]);
+
+impl<'a> Show for StackDisplay<'a> {
+  fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+    let StackDisplay(stack) = *self;
+
+    try!(write!(out, "["));
+
+    for (index, &(ref combinable, ref span)) in stack.iter().enumerate() {
+      if index > 0 {
+        try!(write!(out, ", "));
+      }
+
+      try!(match *combinable {
+        FromLocals => write!(out, "locals"),
+        FromSelf   => write!(out, "self"),
+
+        From(ref object) =>
+          match object.symbol_ref() {
+            Some(string) => write!(out, "\"{:s}\"", string.as_slice()),
+            None         => write!(out, "{}", object)
+          }
+      });
+
+      match *span {
+        Some(ref span) => try!(write!(out, " ({})", span)),
+        None           => ()
+      }
+    }
+
+    write!(out, "]")
   }
 }
 
@@ -135,8 +375,9 @@ pub fn stage_receiver(reactor: &mut Reactor, params: Params) {
     },
 
     None =>
-      warn!(concat!("stage_receiver failed: {} <-- {}, subject is neither an",
-                    " execution nor an alien"),
-            params.subject, params.message)
+      machine_warn!(reactor.machine(), "combination",
+                    concat!("stage_receiver failed: {} <-- {}, subject is",
+                            " neither an execution nor an alien"),
+                    params.subject, params.message)
   }
 }
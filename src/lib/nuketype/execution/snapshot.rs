@@ -0,0 +1,140 @@
+//! Saving an Execution's progress in a form that outlives the process.
+//!
+//! Unlike `util::serialize`, which saves everything an Execution can reach,
+//! a snapshot only saves the Execution itself: its root Script (as bytecode;
+//! see `Script::serialize()`), its program counter, and its stack. Objects on
+//! the stack are saved by name if they're Symbols, and otherwise by their ID in
+//! the Machine's `Identities` (see `machine::identity`), so an embedder that
+//! restores one in another process has to give those objects the same IDs
+//! there first.
+//!
+//! Snapshots can be encoded with any `serialize` encoder, like JSON.
+
+use script::Script;
+
+use object::ObjectRef;
+
+use nuketype::Execution;
+
+use machine::Machine;
+use machine::reactor::{Combinable, From, FromLocals, FromSelf};
+
+use std::io::{MemWriter, BufReader};
+
+/// What an Execution had done so far when `Execution::snapshot()` was taken.
+#[deriving(Clone, PartialEq, Eq, Show, Encodable, Decodable)]
+pub struct ExecutionSnapshot {
+  /// The root Script, as bytecode.
+  pub root:  Vec<u8>,
+
+  /// The index of the next instruction to be evaluated.
+  pub pc:    uint,
+
+  /// The stack, bottom first.
+  pub stack: Vec<SnapshotCombinable>
+}
+
+/// An item on a snapshot's stack. See `Combinable`.
+#[deriving(Clone, PartialEq, Eq, Show, Encodable, Decodable)]
+pub enum SnapshotCombinable {
+  /// `FromLocals`.
+  SnapshotLocals,
+
+  /// `FromSelf`.
+  SnapshotSelf,
+
+  /// `From` a Symbol, by name.
+  SnapshotSymbol(String),
+
+  /// `From` any other object, by ID.
+  SnapshotObject(u64)
+}
+
+impl Execution {
+  /// Takes a snapshot of the Execution, giving IDs to the objects on its stack
+  /// in `machine.identities`. See `nuketype::execution::snapshot`.
+  ///
+  /// Fails with a description of the problem if the root Script can't be
+  /// written as bytecode.
+  pub fn snapshot(&self, machine: &Machine)
+                  -> Result<ExecutionSnapshot, String> {
+
+    let mut root = MemWriter::new();
+
+    match self.root().serialize(&mut root) {
+      Ok(())     => (),
+      Err(error) => return Err(format!("couldn't write root script: {}",
+                                       error))
+    }
+
+    let stack = self.stack().move_iter().map(|combinable|
+      match combinable {
+        FromLocals => SnapshotLocals,
+        FromSelf   => SnapshotSelf,
+
+        From(object) => match object.symbol_ref() {
+          Some(name) => SnapshotSymbol(name.as_slice().to_string()),
+          None       => SnapshotObject(machine.identities.id_of(&object))
+        }
+      }).collect();
+
+    Ok(ExecutionSnapshot {
+      root:  root.unwrap(),
+      pc:    self.pc(),
+      stack: stack
+    })
+  }
+
+  /// Recreates an Execution from a snapshot within `machine`, where the
+  /// objects on its stack must have the IDs they had when it was taken. Spans
+  /// are not kept.
+  ///
+  /// Fails with a description of the problem if the snapshot is invalid or
+  /// refers to an object that `machine` doesn't know.
+  pub fn resume(snapshot: &ExecutionSnapshot, machine: &Machine)
+                -> Result<Execution, String> {
+
+    let mut reader = BufReader::new(snapshot.root.as_slice());
+
+    let root = match Script::deserialize(&mut reader, machine) {
+      Ok(root)   => root,
+      Err(error) => return Err(format!("couldn't read root script: {}",
+                                       error))
+    };
+
+    let len = { let Script(ref instructions) = root; instructions.len() };
+
+    if snapshot.pc > len {
+      return Err(format!("pc {} is past the end of the script", snapshot.pc));
+    }
+
+    let mut stack = Vec::with_capacity(snapshot.stack.len());
+
+    for combinable in snapshot.stack.iter() {
+      stack.push(match *combinable {
+        SnapshotLocals => FromLocals,
+        SnapshotSelf   => FromSelf,
+
+        SnapshotSymbol(ref name) => From(machine.symbol(name.as_slice())),
+
+        SnapshotObject(id) => match machine.identities.object_of(id) {
+          Some(object) => From(object),
+          None         => return Err(format!("no object with ID {}", id))
+        }
+      });
+    }
+
+    Ok(Execution::resume_at(root, snapshot.pc, stack))
+  }
+}
+
+/// Gets a snapshot of the Execution within `object`, if it is one. See
+/// `Execution::snapshot()`.
+pub fn snapshot_of(object: &ObjectRef, machine: &Machine)
+                   -> Option<Result<ExecutionSnapshot, String>> {
+
+  match object.lock().try_cast::<Execution>() {
+    Ok(execution) => Some(execution.deref().snapshot(machine)),
+    Err(_)        => None
+  }
+}
@@ -4,7 +4,10 @@ use script::*;
 
 use machine::Machine;
 use machine::reactor::{From, FromLocals, FromSelf};
-use nuketype::Thing;
+use nuketype::{Nuketype, Thing};
+
+use std::io::MemWriter;
+use std::sync::Arc;
 
 #[test]
 fn advance_push_and_combine() {
@@ -31,6 +34,73 @@ fn advance_push_and_combine() {
   assert!(combination.message == From(symbol1));
 }
 
+#[test]
+fn advance_within_preempts_and_carries_on() {
+  let machine = Machine::new();
+
+  let symbol0 = machine.symbol("hello");
+  let symbol1 = machine.symbol("world");
+
+  let execution_ref =
+    Execution::create(&machine,
+      Script( vec![Discard,
+                   Push(symbol0.clone()),
+                   Push(symbol1.clone()),
+                   Combine] ));
+
+  let mut execution = execution_ref.lock().try_cast::<Execution>()
+                        .ok().unwrap();
+
+  assert!(execution.advance_within(Thing::empty(), Some(2)).is_none());
+  assert!(execution.is_preempted());
+  assert!(!execution.is_complete());
+
+  // The response is ignored when carrying on.
+  let combination =
+    execution.advance_within(machine.symbol("ignored"), Some(2)).unwrap();
+
+  assert!(!execution.is_preempted());
+
+  assert!(combination.subject == From(symbol0));
+  assert!(combination.message == From(symbol1));
+}
+
+#[test]
+fn advance_superinstructions() {
+  let machine = Machine::new();
+
+  let symbol0 = machine.symbol("hello");
+  let symbol1 = machine.symbol("world");
+
+  let execution_ref =
+    Execution::create(&machine,
+      Script( vec![Discard,
+                   PushPair(symbol0.clone(), symbol1.clone()),
+                   Combine,
+                   LookupCombine(symbol0.clone()),
+                   LookupLocal(symbol1.clone())] ));
+
+  let mut execution = execution_ref.lock().try_cast::<Execution>()
+                        .ok().unwrap();
+
+  let combination = execution.advance(Thing::empty()).unwrap();
+
+  assert!(combination.subject == From(symbol0.clone()));
+  assert!(combination.message == From(symbol1.clone()));
+
+  let result = Thing::empty();
+
+  let combination = execution.advance(result.clone()).unwrap();
+
+  assert!(combination.subject == From(result));
+  assert!(combination.message == From(symbol0));
+
+  let combination = execution.advance(Thing::empty()).unwrap();
+
+  assert!(combination.subject == FromLocals);
+  assert!(combination.message == From(symbol1));
+}
+
 #[test]
 fn advance_combine_locals_and_self() {
   let machine = Machine::new();
@@ -127,3 +197,97 @@ fn creates_executions_that_have_unique_locals() {
 
   assert!(locals1 != locals2);
 }
+
+#[test]
+fn fmt_paws_names_stack_items_by_span() {
+  let machine = Machine::new();
+
+  let span = Span {
+    filename: Arc::new("file".to_string()),
+    line:     3,
+    column:   4
+  };
+
+  let execution_ref =
+    Execution::create_with_spans(&machine,
+      Script( vec![Discard,
+                   PushLocals,
+                   Push(machine.symbol("hello")),
+                   PushLocals,
+                   Push(machine.symbol("world")),
+                   Combine] ),
+      SpanTable( vec![None, None, Some(span), None, None, None] ));
+
+  let mut execution = execution_ref.lock().try_cast::<Execution>()
+                        .ok().unwrap();
+
+  execution.advance(Thing::empty());
+
+  let mut writer = MemWriter::new();
+
+  execution.deref().fmt_paws(&mut writer).unwrap();
+
+  let output = String::from_utf8(writer.unwrap()).unwrap();
+
+  assert!(output.as_slice().contains(
+            "stack: [locals, \"hello\" (file:3:4)]"),
+          "unexpected output: {}", output);
+}
+
+#[test]
+fn snapshot_and_resume_in_another_machine() {
+  use super::{ExecutionSnapshot, SnapshotSymbol, SnapshotSelf, SnapshotObject};
+  use serialize::json;
+
+  let machine = Machine::new();
+
+  let response = Thing::empty();
+  let object   = Thing::empty();
+
+  let mut execution = Execution::new(
+    Script(vec![Push(machine.symbol("hello")), PushSelf, Push(object.clone()),
+                PushLocals, Push(machine.symbol("world")), Combine]));
+
+  execution.advance(response.clone());
+
+  let snapshot = execution.snapshot(&machine).ok().expect("snapshot failed");
+
+  let response_id = machine.identities.id_of(&response);
+  let object_id   = machine.identities.id_of(&object);
+
+  assert_eq!(snapshot.pc, 6);
+  assert_eq!(snapshot.stack, vec![SnapshotObject(response_id),
+                                  SnapshotSymbol("hello".to_string()),
+                                  SnapshotSelf,
+                                  SnapshotObject(object_id)]);
+
+  // As if in another process.
+  let encoded = json::encode(&snapshot);
+  let decoded: ExecutionSnapshot = json::decode(encoded.as_slice()).unwrap();
+
+  let other = Machine::new();
+
+  assert!(Execution::resume(&decoded, &other).is_err());
+
+  let other_response = Thing::empty();
+  let other_object   = Thing::empty();
+
+  other.identities.register(response_id, other_response.clone());
+  other.identities.register(object_id,   other_object.clone());
+
+  let resumed = Execution::resume(&decoded, &other)
+                  .ok().expect("resume failed");
+
+  assert_eq!(resumed.pc(), 6);
+
+  let stack = resumed.stack();
+
+  assert!(stack[0] == From(other_response));
+  assert!(stack[2] == FromSelf);
+  assert!(stack[3] == From(other_object));
+
+  match stack[1] {
+    From(ref symbol) => assert!(symbol.eq_as_symbol(&other.symbol("hello"))),
+    _                => fail!("expected a Symbol")
+  }
+}
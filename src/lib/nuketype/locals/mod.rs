@@ -44,6 +44,11 @@ impl Locals {
   pub fn empty(name: ObjectRef) -> ObjectRef {
     Locals::create(name, Meta::new())
   }
+
+  /// The name that the `Locals` responds to with itself.
+  pub fn name<'a>(&'a self) -> &'a ObjectRef {
+    &self.name
+  }
 }
 
 impl Nuketype for Locals {
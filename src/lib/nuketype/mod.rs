@@ -6,6 +6,8 @@
 //! * **Label** (represented by `Symbol`)
 //! * **Execution** (represented by `Execution` and `Alien` for transparent and
 //!   opaque variants, respectively)
+//!
+//! Paws.rs adds **Number** (represented by `Number`) for arithmetic.
 
 use std::any::{Any, AnyRefExt, AnyMutRefExt};
 
@@ -16,12 +18,14 @@ pub use self::symbol::Symbol;
 pub use self::execution::Execution;
 pub use self::alien::Alien;
 pub use self::locals::Locals;
+pub use self::number::Number;
 
 pub mod thing;
 pub mod symbol;
 pub mod execution;
 pub mod alien;
 pub mod locals;
+pub mod number;
 
 /// The interface that all Nuclear types ("nuketypes") must implement.
 pub trait Nuketype: Any {
@@ -0,0 +1,63 @@
+//! Numbers are implementation-defined integers, for arithmetic that would be
+//! painful to do on Symbols.
+
+use object::{ObjectRef, Meta};
+
+use nuketype::Nuketype;
+
+use std::io::IoResult;
+
+#[cfg(test)]
+mod tests;
+
+/// A signed 64-bit integer.
+///
+/// Numbers are immutable; arithmetic always produces a new Number.
+#[deriving(Clone, PartialEq, Eq, PartialOrd, Ord, Show)]
+pub struct Number {
+  value: i64
+}
+
+impl Number {
+  /// Creates a new Number with the given value.
+  pub fn new(value: i64) -> Number {
+    Number {
+      value: value
+    }
+  }
+
+  /// Boxes up a new Number with the given value and empty metadata.
+  pub fn create(value: i64) -> ObjectRef {
+    ObjectRef::store(box Number::new(value), Meta::new())
+  }
+
+  /// Parses a Number from its decimal representation, as produced by
+  /// `to_label()`.
+  pub fn from_label(label: &str) -> Option<Number> {
+    from_str::<i64>(label).map(|value| Number::new(value))
+  }
+
+  /// Gets the value of the Number within `object`, if it is one.
+  pub fn of(object: &ObjectRef) -> Option<i64> {
+    match object.lock().try_cast::<Number>() {
+      Ok(number) => Some(number.deref().value),
+      Err(_)     => None
+    }
+  }
+
+  /// The integer that the Number represents.
+  pub fn value(&self) -> i64 {
+    self.value
+  }
+
+  /// The decimal representation of the Number.
+  pub fn to_label(&self) -> String {
+    self.value.to_string()
+  }
+}
+
+impl Nuketype for Number {
+  fn fmt_paws(&self, writer: &mut Writer) -> IoResult<()> {
+    write!(writer, "Number[{}]", self.value)
+  }
+}
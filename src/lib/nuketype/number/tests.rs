@@ -0,0 +1,26 @@
+use super::Number;
+
+use nuketype::Thing;
+
+#[test]
+fn from_label_round_trips() {
+  for &value in [0i64, 1, -1, 42, ::std::i64::MAX, ::std::i64::MIN].iter() {
+    let number = Number::new(value);
+
+    assert_eq!(Some(number.clone()),
+               Number::from_label(number.to_label().as_slice()));
+  }
+}
+
+#[test]
+fn from_label_rejects_garbage() {
+  assert!(Number::from_label("").is_none());
+  assert!(Number::from_label("4 2").is_none());
+  assert!(Number::from_label("forty-two").is_none());
+}
+
+#[test]
+fn of_reads_only_numbers() {
+  assert_eq!(Some(-7), Number::of(&Number::create(-7)));
+  assert_eq!(None,     Number::of(&Thing::empty()));
+}
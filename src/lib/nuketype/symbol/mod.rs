@@ -5,8 +5,9 @@ use object::ObjectRef;
 use nuketype::Nuketype;
 
 use std::io::IoResult;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use std::collections::HashMap;
+use std::cmp::max;
 
 #[cfg(test)]
 mod tests;
@@ -14,15 +15,25 @@ mod tests;
 /// Maps strings to Symbol objects.
 ///
 /// The most common usage is as part of a Machine.
+///
+/// Only weak references to the strings are kept, so a string is forgotten once
+/// every Symbol made from it is gone, rather than being kept for the life of
+/// the map. Forgotten entries are pruned now and then by `intern()`, or right
+/// away by `compact()`.
 #[deriving(Clone)]
 pub struct SymbolMap {
-  map: HashMap<String, Arc<String>>
+  map:      HashMap<String, Weak<String>>,
+  prune_at: uint
 }
 
+/// The fewest entries a `SymbolMap` will have before `intern()` bothers to
+/// prune it.
+static MIN_PRUNE_AT: uint = 1024;
+
 impl SymbolMap {
   /// Creates an empty SymbolMap.
   pub fn new() -> SymbolMap {
-    SymbolMap { map: HashMap::new() }
+    SymbolMap { map: HashMap::new(), prune_at: MIN_PRUNE_AT }
   }
 
   /// Returns a reference counted pointer to a string that is guaranteed to
@@ -50,25 +61,45 @@ impl SymbolMap {
   ///     // hello1 is NOT pointer-equal, however, to world1
   ///     assert!((&*hello1 as *String) != (&*world1 as *String));
   pub fn intern(&mut self, string: &str) -> Arc<String> {
-    self.map.find_equiv(&string).map(|string_ptr| {
+    match self.map.find_equiv(&string).and_then(|weak_ptr| weak_ptr.upgrade()) {
+      Some(string_ptr) => string_ptr,
+
+      None => {
+        let string_ptr = Arc::new(string.to_string());
 
-      string_ptr.clone()
+        self.map.insert(string.to_string(), string_ptr.downgrade());
 
-    }).unwrap_or_else(|| {
+        // Only prune once the map has doubled since the last time, so that
+        // interning stays cheap on average.
+        if self.map.len() >= self.prune_at {
+          self.compact();
 
-      let string_ptr = Arc::new(string.to_string());
+          self.prune_at = max(self.map.len() * 2, MIN_PRUNE_AT);
+        }
 
-      self.map.insert(string.to_string(), string_ptr.clone());
+        string_ptr
+      }
+    }
+  }
 
-      string_ptr
+  /// Removes every string that no Symbol refers to anymore.
+  pub fn compact(&mut self) {
+    let dead: Vec<String> =
+      self.map.iter()
+        .filter(|&(_, weak_ptr)| weak_ptr.upgrade().is_none())
+        .map(|(string, _)| string.clone())
+        .collect();
 
-    })
+    for string in dead.move_iter() {
+      self.map.remove(&string);
+    }
   }
 }
 
 impl Collection for SymbolMap {
   fn len(&self) -> uint {
-    // In case you want to know how many symbols have been interned.
+    // In case you want to know how many symbols have been interned. Includes
+    // forgotten strings that haven't been pruned yet.
     self.map.len()
   }
 }
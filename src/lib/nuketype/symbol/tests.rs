@@ -20,3 +20,35 @@ fn compare_symbols() {
   assert!( symbol1.eq_by_name_ptr(&symbol2));
   assert!(!symbol1.eq_by_name_ptr(&symbol3));
 }
+
+#[test]
+fn forgotten_symbols_are_compacted() {
+  let mut symbol_map = SymbolMap::new();
+
+  let kept = Symbol::new(symbol_map.intern("kept"));
+
+  {
+    let _forgotten = Symbol::new(symbol_map.intern("forgotten"));
+  }
+
+  assert_eq!(2, symbol_map.len());
+
+  symbol_map.compact();
+
+  assert_eq!(1, symbol_map.len());
+
+  let kept_again = Symbol::new(symbol_map.intern("kept"));
+
+  assert!(kept.eq_by_name_ptr(&kept_again));
+}
+
+#[test]
+fn intern_prunes_opportunistically() {
+  let mut symbol_map = SymbolMap::new();
+
+  for index in range(0u, 10000) {
+    symbol_map.intern(index.to_string().as_slice());
+  }
+
+  assert!(symbol_map.len() < 10000);
+}
@@ -38,6 +38,12 @@ impl Thing {
     ObjectRef::store_with_tag(box Thing, meta, tag)
   }
 
+  /// Boxes a new frozen Thing with the given Meta and a tag. Frozen objects can
+  /// not be modified. See `ObjectRef::store_frozen()`.
+  pub fn frozen<T: Tag>(meta: Meta, tag: T) -> ObjectRef {
+    ObjectRef::store_frozen(box Thing, meta, tag)
+  }
+
   /// Boxes a new Thing with empty Meta (`Meta::new()`).
   pub fn empty() -> ObjectRef {
     Thing::create(Meta::new())
@@ -1,24 +1,191 @@
 //! Caches for common operations on Paws objects.
 
-use object::{mod, ObjectRef, WeakObjectRef};
+use object::{mod, ObjectRef, WeakObjectRef, Meta};
+
+use nuketype::Thing;
 
 use std::sync::Arc;
 use std::collections::LruCache;
 
+pub use self::shared::SharedCache;
+
+pub mod shared;
+
 #[cfg(test)]
 mod tests;
 
-static SYM_LOOKUP_CACHE_SIZE: uint = 64;
-static RECEIVER_CACHE_SIZE: uint = 64;
+static DEFAULT_CACHE_SIZE: uint = 64;
+
+static DEFAULT_PARAMS_POOL_SIZE: uint = 16;
 
 /// Provides caching for various common operations on Paws objects.
 pub struct Cache {
   sym_lookup_cache: LruCache<SymLookupCacheKey, SymLookupCacheEntry>,
+  sym_miss_cache:   LruCache<SymLookupCacheKey, SymMissCacheEntry>,
   receiver_cache:   Option<LruCache<ReceiverCacheKey, ReceiverCacheEntry>>,
+
+  /// Params objects made by `params()`, to be reused once nothing else refers
+  /// to them.
+  params_pool:      Vec<ObjectRef>,
+
+  /// Where `receiver()` looks before locking an object, after its own cache.
+  /// See `object::cache::shared`.
+  shared:           Option<SharedCache>,
+
+  config:           CacheConfig,
   stats:            CacheStats
 }
 
+/// How many entries each of a `Cache`'s caches can hold, and whether they're
+/// used at all. Each reactor has its own `Cache`, so these are per reactor.
+///
+/// Usually taken from `Machine::cache_config`, which reactors read when they're
+/// created, and which `paws_rs --cache-size` and `--cache-config` set (see
+/// `with_spec()`). Scripts can see the sizes and how well they're doing
+/// through `implementation cache`.
+///
+/// There's no cache for cloning stageables, so there's no size for it either.
+///
+///     let config = CacheConfig::new().with_sym_lookup_size(256);
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct CacheConfig {
+  /// The number of `sym_lookup()` results to keep.
+  pub sym_lookup_size:    uint,
+
+  /// Whether `sym_lookup()` keeps results at all. If not, every lookup scans
+  /// the container, except for frozen ones.
+  pub sym_lookup_enabled: bool,
+
+  /// The number of `receiver()` results to keep. Only used by parallel
+  /// reactors.
+  pub receiver_size:      uint,
+
+  /// Whether parallel reactors keep `receiver()` results at all. If not, every
+  /// call locks the object.
+  pub receiver_enabled:   bool,
+
+  /// The number of params objects `params()` keeps around to reuse. Zero
+  /// turns reuse off.
+  pub params_pool_size:   uint
+}
+
+impl CacheConfig {
+  /// Creates a `CacheConfig` with the default sizes (64 entries each).
+  pub fn new() -> CacheConfig {
+    CacheConfig {
+      sym_lookup_size:    DEFAULT_CACHE_SIZE,
+      sym_lookup_enabled: true,
+      receiver_size:      DEFAULT_CACHE_SIZE,
+      receiver_enabled:   true,
+      params_pool_size:   DEFAULT_PARAMS_POOL_SIZE
+    }
+  }
+
+  /// Sets the size of every cache to `size`. The params pool isn't a cache,
+  /// so its size is left alone.
+  ///
+  /// # Failure
+  ///
+  /// Fails if `size` is zero. The same goes for the other setters.
+  pub fn with_size(self, size: uint) -> CacheConfig {
+    self.with_sym_lookup_size(size).with_receiver_size(size)
+  }
+
+  /// Sets the size of the `sym_lookup()` cache.
+  pub fn with_sym_lookup_size(mut self, size: uint) -> CacheConfig {
+    assert!(size > 0, "cache sizes must be at least 1");
+
+    self.sym_lookup_size = size;
+    self
+  }
+
+  /// Sets the size of the `receiver()` cache.
+  pub fn with_receiver_size(mut self, size: uint) -> CacheConfig {
+    assert!(size > 0, "cache sizes must be at least 1");
+
+    self.receiver_size = size;
+    self
+  }
+
+  /// Sets the size of the params pool. Unlike the caches, it may be zero.
+  pub fn with_params_pool_size(mut self, size: uint) -> CacheConfig {
+    self.params_pool_size = size;
+    self
+  }
+
+  /// Turns the `sym_lookup()` cache on or off.
+  pub fn with_sym_lookup_enabled(mut self, enabled: bool) -> CacheConfig {
+    self.sym_lookup_enabled = enabled;
+    self
+  }
+
+  /// Turns the `receiver()` cache on or off.
+  pub fn with_receiver_enabled(mut self, enabled: bool) -> CacheConfig {
+    self.receiver_enabled = enabled;
+    self
+  }
+
+  /// Applies settings from a comma-separated list of `name=value`, where the
+  /// name is `sym-lookup`, `receiver` or `params-pool`, and the value is a
+  /// size, `on` or `off`. A cache that's given a size is also turned on, and
+  /// turning the params pool off sets its size to zero.
+  ///
+  /// Fails with a description of the problem if `spec` isn't valid.
+  ///
+  ///     let config = CacheConfig::new()
+  ///                    .with_spec("sym-lookup=1024,receiver=off").unwrap();
+  pub fn with_spec(self, spec: &str) -> Result<CacheConfig, String> {
+    enum Value { On, Off, Size(uint) }
+
+    let mut config = self;
+
+    for setting in spec.split(',').filter(|s| !s.trim().is_empty()) {
+      let (name, value) = match setting.find('=') {
+        Some(index) => (setting.slice_to(index).trim(),
+                        setting.slice_from(index + 1).trim()),
+
+        None => return Err(format!("expected name=value, got \"{}\"",
+                                   setting))
+      };
+
+      let value = match value {
+        "on"  => On,
+        "off" => Off,
+
+        _ => match from_str::<uint>(value) {
+          Some(size) if size > 0 || name == "params-pool" => Size(size),
+
+          _ => return Err(format!("invalid value \"{}\" for {}",
+                                  value, name))
+        }
+      };
+
+      config = match (name, value) {
+        ("sym-lookup", Size(size)) =>
+          config.with_sym_lookup_size(size).with_sym_lookup_enabled(true),
+        ("sym-lookup", On)  => config.with_sym_lookup_enabled(true),
+        ("sym-lookup", Off) => config.with_sym_lookup_enabled(false),
+
+        ("receiver", Size(size)) =>
+          config.with_receiver_size(size).with_receiver_enabled(true),
+        ("receiver", On)  => config.with_receiver_enabled(true),
+        ("receiver", Off) => config.with_receiver_enabled(false),
+
+        ("params-pool", Size(size)) => config.with_params_pool_size(size),
+        ("params-pool", On)  =>
+          config.with_params_pool_size(DEFAULT_PARAMS_POOL_SIZE),
+        ("params-pool", Off) => config.with_params_pool_size(0),
+
+        _ => return Err(format!("unknown cache \"{}\"", name))
+      };
+    }
+
+    Ok(config)
+  }
+}
+
 /// Provides performance-related information for a `Cache`.
+#[deriving(Clone, PartialEq, Eq, Show)]
 pub struct CacheStats {
   /// The number of times `sym_lookup()` has failed to find a match in the cache
   /// since it was created.
@@ -34,7 +201,72 @@ pub struct CacheStats {
 
   /// The number of times `receiver()` has successfully found a match in the
   /// cache since it was created.
-  pub receiver_hits:     u64
+  pub receiver_hits:     u64,
+
+  /// The number of times `sym_lookup()` was answered by a frozen object's
+  /// lookup table, bypassing the cache entirely.
+  pub frozen_lookups:    u64,
+
+  /// The number of times `params()` reused a params object instead of
+  /// allocating a new one.
+  pub params_reused:     u64,
+
+  /// The number of `sym_lookup_hits` that were answered by remembering that
+  /// the container doesn't have the key. See `Cache::sym_lookup()`.
+  pub negative_hits:     u64,
+
+  /// The number of `receiver_misses` that were answered by the shared cache
+  /// instead of locking the object. See `object::cache::shared`.
+  pub shared_hits:       u64
+}
+
+impl CacheStats {
+  /// Creates a new `CacheStats` with all of the counters at zero.
+  pub fn new() -> CacheStats {
+    CacheStats {
+      sym_lookup_misses: 0,
+      sym_lookup_hits:   0,
+      receiver_misses:   0,
+      receiver_hits:     0,
+      frozen_lookups:    0,
+      params_reused:     0,
+      negative_hits:     0,
+      shared_hits:       0
+    }
+  }
+
+  /// Adds the counters from `other` to these, for aggregating the statistics
+  /// of several caches.
+  pub fn add(&mut self, other: &CacheStats) {
+    self.sym_lookup_misses += other.sym_lookup_misses;
+    self.sym_lookup_hits   += other.sym_lookup_hits;
+    self.receiver_misses   += other.receiver_misses;
+    self.receiver_hits     += other.receiver_hits;
+    self.frozen_lookups    += other.frozen_lookups;
+    self.params_reused     += other.params_reused;
+    self.negative_hits     += other.negative_hits;
+    self.shared_hits       += other.shared_hits;
+  }
+
+  /// The fraction of `sym_lookup()`s that hit the cache, from 0 to 1, leaving
+  /// out frozen lookups. `None` if there haven't been any.
+  pub fn sym_lookup_hit_rate(&self) -> Option<f64> {
+    hit_rate(self.sym_lookup_hits, self.sym_lookup_misses)
+  }
+
+  /// The fraction of `receiver()`s that hit the cache, from 0 to 1. `None` if
+  /// there haven't been any.
+  pub fn receiver_hit_rate(&self) -> Option<f64> {
+    hit_rate(self.receiver_hits, self.receiver_misses)
+  }
+}
+
+fn hit_rate(hits: u64, misses: u64) -> Option<f64> {
+  if hits + misses == 0 {
+    None
+  } else {
+    Some(hits as f64 / (hits + misses) as f64)
+  }
 }
 
 #[allow(raw_pointer_deriving)]
@@ -48,6 +280,22 @@ struct SymLookupCacheEntry {
   value:             WeakObjectRef
 }
 
+/// A `sym_lookup()` that found nothing. Only `promoted` entries are trusted;
+/// see `Cache::sym_lookup()`.
+struct SymMissCacheEntry {
+  container_version: uint,
+  promoted:          bool,
+
+  /// The versions of the members that were scanned, since any of them could
+  /// be made into a matching pair without changing the container.
+  member_versions:   Vec<(WeakObjectRef, uint)>,
+
+  /// Keeps the symbol's string alive, since the key only has its address.
+  /// Otherwise the `SymbolMap` could free it, and a different symbol
+  /// interned at the same address would be answered with this miss.
+  _symbol:           Arc<String>
+}
+
 type ReceiverCacheKey = ObjectRef;
 
 struct ReceiverCacheEntry {
@@ -57,7 +305,7 @@ struct ReceiverCacheEntry {
 
 impl Cache {
   /// Construct a new cache.
-  fn new(parallel: bool) -> Cache {
+  fn new(parallel: bool, config: &CacheConfig) -> Cache {
     // This is clever. Maybe too clever/ugly?
     let if_parallel =
       if parallel {
@@ -73,16 +321,24 @@ impl Cache {
       };
 
     Cache {
-      sym_lookup_cache: LruCache::new(SYM_LOOKUP_CACHE_SIZE),
+      sym_lookup_cache: LruCache::new(config.sym_lookup_size),
 
-      receiver_cache:   if_parallel(|| LruCache::new(RECEIVER_CACHE_SIZE)),
+      sym_miss_cache:   LruCache::new(config.sym_lookup_size),
 
-      stats: CacheStats {
-        sym_lookup_misses: 0,
-        sym_lookup_hits:   0,
-        receiver_misses:   0,
-        receiver_hits:     0
-      }
+      receiver_cache:
+        if config.receiver_enabled {
+          if_parallel(|| LruCache::new(config.receiver_size))
+        } else {
+          None
+        },
+
+      params_pool:      Vec::new(),
+
+      shared:           None,
+
+      config: config.clone(),
+
+      stats:  CacheStats::new()
     }
   }
 
@@ -91,7 +347,12 @@ impl Cache {
   /// This disables optimizations that are only useful for reactors that run in
   /// parallel and need to avoid locking on objects.
   pub fn new_serial() -> Cache {
-    Cache::new(false)
+    Cache::new_serial_with(&CacheConfig::new())
+  }
+
+  /// Like `new_serial()`, but with the given sizes instead of the defaults.
+  pub fn new_serial_with(config: &CacheConfig) -> Cache {
+    Cache::new(false, config)
   }
 
   /// Construct a new cache for a parallel reactor.
@@ -99,7 +360,24 @@ impl Cache {
   /// This enables optimizations that are useful for reactors that run in
   /// parallel.
   pub fn new_parallel() -> Cache {
-    Cache::new(true)
+    Cache::new_parallel_with(&CacheConfig::new())
+  }
+
+  /// Like `new_parallel()`, but with the given sizes instead of the defaults.
+  pub fn new_parallel_with(config: &CacheConfig) -> Cache {
+    Cache::new(true, config)
+  }
+
+  /// Makes `receiver()` fall back to `shared` (if it's enabled) before locking
+  /// objects. Only has an effect on caches for parallel reactors.
+  pub fn with_shared(mut self, shared: SharedCache) -> Cache {
+    self.shared = Some(shared);
+    self
+  }
+
+  /// The sizes the cache was created with.
+  pub fn config(&self) -> &CacheConfig {
+    &self.config
   }
 
   /// Get information about cache performance.
@@ -109,14 +387,50 @@ impl Cache {
 
   /// Cache-optimized variant of `Members::lookup_pair()` specialized for
   /// lookups with a Symbol key only.
+  ///
+  /// Lookups that find nothing are remembered too, with the container's
+  /// version. One that finds nothing again in the same version of the
+  /// container is promoted, and from then on answered without looking until
+  /// the container changes. Waiting for a second miss keeps one-off misses
+  /// (like those against a caller's locals before a name is defined) from
+  /// pushing useful entries out. The versions of the members that were scanned
+  /// are checked too, since any of them could be changed in place into a pair
+  /// with the key without changing the container.
   pub fn sym_lookup(&mut self,
                     container: ObjectRef,
                     symbol:    Arc<String>)
                     -> Option<ObjectRef> {
 
+    // Frozen objects have their own immutable lookup table, which is always
+    // up to date and needs no locking, so that's better than anything we could
+    // do here.
+    if container.is_frozen() {
+      self.stats.frozen_lookups += 1;
+
+      return container.frozen_lookup(&symbol)
+    }
+
     let key = SymLookupCacheKey(container, &*symbol as *const String);
 
-    match self.sym_lookup_cache.get(&key) {
+    let enabled = self.config.sym_lookup_enabled;
+
+    if enabled {
+      let SymLookupCacheKey(ref container, _) = key;
+
+      match self.sym_miss_cache.get(&key) {
+        Some(entry) if entry.promoted &&
+                       entry.container_version == container.meta_version() &&
+                       unchanged(entry.member_versions.as_slice()) => {
+          self.stats.sym_lookup_hits += 1;
+          self.stats.negative_hits   += 1;
+
+          return None
+        },
+        _ => ()
+      }
+    }
+
+    match if enabled { self.sym_lookup_cache.get(&key) } else { None } {
       Some(entry) => {
         // The lookup was cached. Let's check to see whether it's still valid.
         //
@@ -150,6 +464,9 @@ impl Cache {
 
     let mut result: Option<(ObjectRef, ObjectRef)> = None;
 
+    // Only needed to remember a miss.
+    let mut member_versions: Vec<(WeakObjectRef, uint)> = Vec::new();
+
     self.stats.sym_lookup_misses += 1;
 
     debug!("sym_lookup miss: ({} hits / {} misses)",
@@ -171,6 +488,11 @@ impl Cache {
             let object  = relationship.to().lock();
             let members = &object.meta().members;
 
+            if enabled {
+              member_versions.push((relationship.to().downgrade(),
+                                    relationship.to().meta_version()));
+            }
+
             // Pair objects look approximately like [, key, value].
             match (members.get(1), members.get(2)) {
               (Some(rel_key), Some(rel_value)) =>
@@ -205,7 +527,10 @@ impl Cache {
           value:             value.downgrade()
         };
 
-        self.sym_lookup_cache.put(key, entry);
+        if enabled {
+          self.sym_miss_cache.pop(&key);
+          self.sym_lookup_cache.put(key, entry);
+        }
 
         // Return the value we found.
         return Some(value)
@@ -215,10 +540,33 @@ impl Cache {
         // the cache (if it existed) so we aren't doing this over and over.
         self.sym_lookup_cache.pop(&key);
 
+        // And remember that, promoting the entry if this is the second time
+        // in a row for this version of the container.
+        if enabled {
+          let promoted = match self.sym_miss_cache.get(&key) {
+            Some(entry) => entry.container_version == container_version,
+            None        => false
+          };
+
+          self.sym_miss_cache.put(key, SymMissCacheEntry {
+            container_version: container_version,
+            promoted:          promoted,
+            member_versions:   member_versions,
+            _symbol:           symbol.clone()
+          });
+        }
+
         return None
       }
     }
 
+    /// Whether all of the members scanned for a miss are still as they were.
+    fn unchanged(member_versions: &[(WeakObjectRef, uint)]) -> bool {
+      member_versions.iter().all(|&(ref member, version)|
+        member.upgrade().map(|member| member.meta_version() == version)
+          == Some(true))
+    }
+
     fn sym_match(sym: *const String, object: &ObjectRef) -> bool {
       match object.symbol_ref() {
         Some(other_sym) => sym == (&**other_sym as *const String),
@@ -227,6 +575,44 @@ impl Cache {
     }
   }
 
+  /// Makes a params object, `[, caller, subject, message]`, for staging a
+  /// receiver with. Reuses one made by an earlier call if nothing else refers
+  /// to it anymore (usually because the receiver it was given to has finished
+  /// and been dropped), which saves an allocation per combination.
+  pub fn params(&mut self,
+                caller:  ObjectRef,
+                subject: ObjectRef,
+                message: ObjectRef)
+                -> ObjectRef {
+
+    let mut meta = Meta::new();
+
+    meta.members.set(1, caller);
+    meta.members.set(2, subject);
+    meta.members.set(3, message);
+
+    // Only the pool refers to a unique one, so no one else can get hold of it
+    // while it's being reset.
+    match self.params_pool.iter().find(|params| params.is_unique()) {
+      Some(params) => {
+        *params.lock().meta_mut() = meta;
+
+        self.stats.params_reused += 1;
+
+        return params.clone()
+      },
+      None => ()
+    }
+
+    let params = Thing::create(meta);
+
+    if self.params_pool.len() < self.config.params_pool_size {
+      self.params_pool.push(params.clone());
+    }
+
+    params
+  }
+
   /// Get an object's `meta().receiver` with caching.
   ///
   /// This optimization is disabled for serial reactors, as it's purely to avoid
@@ -245,7 +631,10 @@ impl Cache {
     // short-lived object. If the meta version is 0, we just lock and get the
     // receiver, and then increment it. Should definitely revisit this and do
     // something better, though.
-    if object.meta_version() == 0 {
+    //
+    // Frozen objects are exempt, since their metadata never changes (and
+    // can't, so `meta_mut()` would fail).
+    if object.meta_version() == 0 && !object.is_frozen() {
       // Using `meta_mut()` ensures we increment the version so this doesn't
       // happen next time.
       return object.lock().meta_mut().receiver.clone()
@@ -272,6 +661,21 @@ impl Cache {
     debug!("receiver miss: ({} hits / {} misses)",
       self.stats.receiver_hits, self.stats.receiver_misses);
 
+    // Another reactor may have already done the work.
+    match self.shared.as_ref().and_then(|shared| shared.get(&object)) {
+      Some((version, receiver)) => {
+        self.stats.shared_hits += 1;
+
+        receiver_cache.put(object.clone(), ReceiverCacheEntry {
+          version:  version,
+          receiver: receiver.clone()
+        });
+
+        return receiver
+      },
+      None => ()
+    }
+
     let entry = {
       // This is written this way to ensure we get the `meta_version()`
       // while the object is locked, otherwise it could be inconsistent.
@@ -284,8 +688,21 @@ impl Cache {
       }
     };
 
+    // An Alien that just wraps a native receiver can be skipped entirely,
+    // now that the object's lock isn't held.
+    let entry = ReceiverCacheEntry {
+      version:  entry.version,
+      receiver: entry.receiver.unwrap_native()
+    };
+
     let receiver = entry.receiver.clone();
 
+    match self.shared {
+      Some(ref shared) =>
+        shared.put(object.clone(), entry.version, receiver.clone()),
+      None => ()
+    }
+
     receiver_cache.put(object, entry);
 
     receiver
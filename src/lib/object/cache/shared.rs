@@ -0,0 +1,89 @@
+//! A receiver cache shared by all of a Machine's reactors.
+//!
+//! Each parallel reactor's `Cache` keeps its own receiver cache, so with N
+//! reactors, the receivers of commonly used objects (like the system
+//! namespaces) are looked up N times, each taking the object's lock. When
+//! enabled, a `SharedCache` sits behind them: a reactor that misses its own
+//! cache looks here before locking the object, and adds what it finds so that
+//! other reactors don't have to.
+//!
+//! Lookups only take a read lock, so reactors don't get in each other's way
+//! once it's warm. Adding takes a write lock, which is why this is only worth
+//! it for objects that are combined against often, from many reactors.
+
+use object::{ObjectRef, Receiver};
+
+use std::collections::HashMap;
+use std::sync::{Arc, RWLock};
+use std::sync::atomics::{AtomicBool, AtomicUint, Relaxed};
+
+/// A receiver cache shared between reactors. Disabled by default. Clones share
+/// the same state.
+#[deriving(Clone)]
+pub struct SharedCache {
+  enabled:   Arc<AtomicBool>,
+  capacity:  Arc<AtomicUint>,
+  receivers: Arc<RWLock<HashMap<ObjectRef, (uint, Receiver)>>>
+}
+
+impl SharedCache {
+  /// Creates a new, disabled cache.
+  pub fn new() -> SharedCache {
+    SharedCache {
+      enabled:   Arc::new(AtomicBool::new(false)),
+      capacity:  Arc::new(AtomicUint::new(0)),
+      receivers: Arc::new(RWLock::new(HashMap::new()))
+    }
+  }
+
+  /// Starts caching, keeping up to `capacity` receivers. When it's full, it's
+  /// emptied and starts over, which is cheaper to do under a lock than keeping
+  /// track of which entries are least recently used.
+  ///
+  /// # Failure
+  ///
+  /// Fails if `capacity` is zero.
+  pub fn enable(&self, capacity: uint) {
+    assert!(capacity > 0, "cache sizes must be at least 1");
+
+    self.capacity.store(capacity, Relaxed);
+    self.enabled.store(true, Relaxed);
+  }
+
+  /// Returns true if the cache is in use.
+  pub fn is_enabled(&self) -> bool {
+    self.enabled.load(Relaxed)
+  }
+
+  /// The number of receivers in the cache.
+  pub fn len(&self) -> uint {
+    self.receivers.read().len()
+  }
+
+  /// Gets the receiver cached for `object`, and the version of its metadata it
+  /// was cached for, if there is one and the metadata hasn't changed since.
+  pub fn get(&self, object: &ObjectRef) -> Option<(uint, Receiver)> {
+    if !self.is_enabled() { return None }
+
+    match self.receivers.read().find(object) {
+      Some(&(version, ref receiver)) if version == object.meta_version() =>
+        Some((version, receiver.clone())),
+
+      _ => None
+    }
+  }
+
+  /// Caches `receiver` for `object` as of `version` of its metadata.
+  pub fn put(&self, object: ObjectRef, version: uint, receiver: Receiver) {
+    if !self.is_enabled() { return }
+
+    let mut receivers = self.receivers.write();
+
+    if receivers.len() >= self.capacity.load(Relaxed) &&
+       !receivers.contains_key(&object) {
+      receivers.clear();
+    }
+
+    receivers.insert(object, (version, receiver));
+  }
+}
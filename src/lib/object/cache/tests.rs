@@ -1,10 +1,10 @@
-use super::Cache;
+use super::{Cache, CacheStats, CacheConfig, SharedCache};
 
 use object;
 
 use machine::Machine;
 
-use nuketype::Thing;
+use nuketype::{Thing, Alien};
 
 #[test]
 pub fn sym_lookup_miss_and_hit() {
@@ -114,6 +114,98 @@ pub fn sym_lookup_invalidate() {
   assert_eq!(3, cache.stats().sym_lookup_misses);
 }
 
+#[test]
+pub fn sym_lookup_remembers_repeated_misses() {
+  let machine = Machine::new();
+
+  let foo_sym = machine.symbol_map.lock().intern("foo");
+
+  let dictionary = Thing::from_fn(|dictionary| {
+    dictionary.members.push_pair(machine.symbol("bar"), Thing::empty());
+  });
+
+  let mut cache = Cache::new_serial();
+
+  // The first two misses scan; the second promotes the entry.
+  for _ in range(0u, 2) {
+    assert!(cache.sym_lookup(dictionary.clone(), foo_sym.clone()).is_none());
+  }
+
+  assert_eq!(2, cache.stats().sym_lookup_misses);
+  assert_eq!(0, cache.stats().negative_hits);
+
+  // Now it's answered without scanning.
+  assert!(cache.sym_lookup(dictionary.clone(), foo_sym.clone()).is_none());
+
+  assert_eq!(2, cache.stats().sym_lookup_misses);
+  assert_eq!(1, cache.stats().sym_lookup_hits);
+  assert_eq!(1, cache.stats().negative_hits);
+
+  // Until the dictionary changes.
+  let foo = Thing::empty();
+
+  dictionary.lock().meta_mut().members
+    .push_pair(machine.symbol("foo"), foo.clone());
+
+  assert!(cache.sym_lookup(dictionary.clone(), foo_sym.clone()) == Some(foo));
+
+  assert_eq!(3, cache.stats().sym_lookup_misses);
+  assert_eq!(1, cache.stats().negative_hits);
+}
+
+#[test]
+pub fn sym_lookup_notices_members_made_into_pairs() {
+  let machine = Machine::new();
+
+  let foo_sym = machine.symbol_map.lock().intern("foo");
+
+  let slot       = Thing::empty();
+  let dictionary = Thing::from_fn(|dictionary| {
+    dictionary.members.push(slot.clone());
+  });
+
+  let mut cache = Cache::new_serial();
+
+  // Promoted, as in `sym_lookup_remembers_repeated_misses`.
+  for _ in range(0u, 2) {
+    assert!(cache.sym_lookup(dictionary.clone(), foo_sym.clone()).is_none());
+  }
+
+  // The dictionary doesn't change, but its member becomes a matching pair.
+  let foo = Thing::empty();
+
+  {
+    let mut slot = slot.lock();
+
+    slot.meta_mut().members.push(machine.symbol("foo"));
+    slot.meta_mut().members.push(foo.clone());
+  }
+
+  assert!(cache.sym_lookup(dictionary.clone(), foo_sym.clone()) == Some(foo));
+
+  assert_eq!(0, cache.stats().negative_hits);
+}
+
+#[test]
+pub fn sym_lookup_miss_keeps_symbol_alive() {
+  let machine = Machine::new();
+
+  let dictionary = Thing::empty();
+
+  let mut cache = Cache::new_serial();
+
+  let foo_sym = machine.symbol_map.lock().intern("foo");
+  let foo_ptr = &*foo_sym as *const String;
+
+  cache.sym_lookup(dictionary.clone(), foo_sym);
+
+  // Dropped by us; the miss entry still has it, so no other symbol can be
+  // interned at the same address while the entry is around.
+  let again = machine.symbol_map.lock().intern("foo");
+
+  assert!(&*again as *const String == foo_ptr);
+}
+
 #[test]
 pub fn receiver_miss_and_hit() {
   let receiver1 = Thing::empty();
@@ -185,6 +277,65 @@ pub fn receiver_miss_and_hit() {
   assert_eq!(2, cache.stats().receiver_hits);
 }
 
+#[test]
+pub fn receiver_falls_back_to_shared_cache() {
+  let receiver = Thing::empty();
+
+  let object = Thing::from_fn(|meta| {
+    meta.receiver = object::ObjectReceiver(receiver.clone());
+  });
+
+  // Ensure the version is > 0
+  object.lock().meta_mut();
+
+  let shared = SharedCache::new();
+
+  shared.enable(8);
+
+  let mut first  = Cache::new_parallel().with_shared(shared.clone());
+  let mut second = Cache::new_parallel().with_shared(shared.clone());
+
+  first.receiver(object.clone());
+
+  assert_eq!(1, shared.len());
+  assert_eq!(0, first.stats().shared_hits);
+
+  // The second cache hasn't seen it, but doesn't have to lock it.
+  match second.receiver(object.clone()) {
+    object::ObjectReceiver(found) => assert_eq!(receiver, found),
+    _                             => fail!("expected ObjectReceiver")
+  }
+
+  assert_eq!(1, second.stats().receiver_misses);
+  assert_eq!(1, second.stats().shared_hits);
+
+  // Changes invalidate the shared entry too.
+  object.lock().meta_mut();
+
+  second.receiver(object.clone());
+  first.receiver(object.clone());
+
+  assert_eq!(1, second.stats().shared_hits);
+  assert_eq!(1, first.stats().shared_hits);
+}
+
+#[test]
+pub fn shared_cache_is_bounded_and_off_by_default() {
+  let shared = SharedCache::new();
+
+  shared.put(Thing::empty(), 1, object::ObjectReceiver(Thing::empty()));
+
+  assert_eq!(0, shared.len());
+
+  shared.enable(2);
+
+  for _ in range(0u, 3) {
+    shared.put(Thing::empty(), 1, object::ObjectReceiver(Thing::empty()));
+  }
+
+  assert!(shared.len() <= 2);
+}
+
 #[test]
 pub fn receiver_invalidate() {
   let receiver1 = Thing::empty();
@@ -239,3 +390,174 @@ pub fn receiver_invalidate() {
   assert_eq!(2, cache.stats().receiver_misses);
   assert_eq!(2, cache.stats().receiver_hits);
 }
+
+#[test]
+pub fn sym_lookup_frozen() {
+  let machine = Machine::new();
+
+  let foo_sym = machine.symbol_map.lock().intern("foo");
+
+  let foo = Thing::empty();
+
+  let mut meta = object::Meta::new();
+
+  meta.members.push_pair(machine.symbol("foo"), foo.clone());
+
+  let namespace = Thing::frozen(meta, "namespace");
+
+  let mut cache = Cache::new_serial();
+
+  for _ in range(0u, 3) {
+    assert_eq!(Some(foo.clone()),
+               cache.sym_lookup(namespace.clone(), foo_sym.clone()));
+  }
+
+  assert_eq!(3, cache.stats().frozen_lookups);
+  assert_eq!(0, cache.stats().sym_lookup_misses);
+  assert_eq!(0, cache.stats().sym_lookup_hits);
+}
+
+#[test]
+pub fn hit_rates() {
+  let mut stats = CacheStats::new();
+
+  assert_eq!(None, stats.sym_lookup_hit_rate());
+  assert_eq!(None, stats.receiver_hit_rate());
+
+  stats.sym_lookup_hits   = 3;
+  stats.sym_lookup_misses = 1;
+  stats.frozen_lookups    = 100;
+  stats.receiver_misses   = 2;
+
+  assert_eq!(Some(0.75), stats.sym_lookup_hit_rate());
+  assert_eq!(Some(0.0),  stats.receiver_hit_rate());
+}
+
+#[test]
+pub fn config_sizes() {
+  let config = CacheConfig::new().with_size(16).with_receiver_size(8);
+
+  assert_eq!(16, config.sym_lookup_size);
+  assert_eq!(8,  config.receiver_size);
+
+  assert!(Cache::new_parallel_with(&config).config() == &config);
+  assert!(Cache::new_serial().config() == &CacheConfig::new());
+}
+
+#[test]
+pub fn config_spec() {
+  let config = CacheConfig::new()
+                 .with_spec("sym-lookup=256, receiver=off,params-pool=0")
+                 .ok().expect("spec rejected");
+
+  assert_eq!(256,   config.sym_lookup_size);
+  assert_eq!(true,  config.sym_lookup_enabled);
+  assert_eq!(false, config.receiver_enabled);
+  assert_eq!(0,     config.params_pool_size);
+
+  assert!(CacheConfig::new().with_spec("receiver=0").is_err());
+  assert!(CacheConfig::new().with_spec("receiver").is_err());
+  assert!(CacheConfig::new().with_spec("clone=on").is_err());
+}
+
+#[test]
+pub fn disabled_caches_are_bypassed() {
+  let machine = Machine::new();
+
+  let config = CacheConfig::new().with_sym_lookup_enabled(false)
+                                 .with_receiver_enabled(false);
+
+  let mut cache = Cache::new_parallel_with(&config);
+
+  let container = Thing::empty();
+  let symbol    = machine.symbol("key");
+
+  container.lock().meta_mut().members
+    .push_pair(symbol.clone(), Thing::empty());
+
+  let name = symbol.symbol_ref().unwrap().clone();
+
+  for _ in range(0u, 3) {
+    assert!(cache.sym_lookup(container.clone(), name.clone()).is_some());
+    cache.receiver(container.clone());
+    cache.receiver(container.clone());
+  }
+
+  assert_eq!(0, cache.stats().sym_lookup_hits);
+  assert_eq!(3, cache.stats().sym_lookup_misses);
+  assert_eq!(0, cache.stats().receiver_hits);
+  assert_eq!(0, cache.stats().receiver_misses);
+}
+
+#[test]
+#[should_fail]
+pub fn config_sizes_must_be_positive() {
+  CacheConfig::new().with_size(0);
+}
+
+#[test]
+pub fn receiver_unwraps_native_receiver_aliens() {
+  let alien  = Alien::from_native_receiver(object::lookup_receiver);
+  let object = Thing::from_fn(|meta| {
+    meta.receiver = object::ObjectReceiver(alien.clone());
+  });
+
+  // Ensure the version is > 0
+  object.lock().meta_mut();
+
+  let mut cache = Cache::new_parallel();
+
+  for _ in range(0u, 2) {
+    match cache.receiver(object.clone()) {
+      object::NativeReceiver(_) => (),
+
+      _ =>
+        fail!("expected NativeReceiver")
+    }
+  }
+
+  assert_eq!(1, cache.stats().receiver_hits);
+}
+
+#[test]
+pub fn params_are_reused_once_dropped() {
+  let mut cache = Cache::new_serial();
+
+  let (a, b, c) = (Thing::empty(), Thing::empty(), Thing::empty());
+
+  let first  = cache.params(a.clone(), b.clone(), c.clone());
+  let second = cache.params(c.clone(), b.clone(), a.clone());
+
+  // Still in use, so not reused.
+  assert!(first != second);
+  assert_eq!(0, cache.stats().params_reused);
+
+  drop(first);
+
+  let third = cache.params(c.clone(), a.clone(), b.clone());
+
+  assert_eq!(1, cache.stats().params_reused);
+
+  let third_lock = third.lock();
+  let members    = &third_lock.meta().members;
+
+  let member = |index: uint| members.get(index).map(|r| r.to().clone());
+
+  assert!(member(1) == Some(c));
+  assert!(member(2) == Some(a));
+  assert!(member(3) == Some(b));
+}
+
+#[test]
+pub fn params_pool_of_zero_never_reuses() {
+  let config    = CacheConfig::new().with_params_pool_size(0);
+  let mut cache = Cache::new_serial_with(&config);
+
+  let thing = Thing::empty();
+
+  for _ in range(0u, 3) {
+    cache.params(thing.clone(), thing.clone(), thing.clone());
+  }
+
+  assert_eq!(0, cache.stats().params_reused);
+}
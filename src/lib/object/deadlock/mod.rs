@@ -0,0 +1,175 @@
+//! A process-wide lock-ordering tracker, for turning deadlocks into failures.
+//!
+//! When enabled (with `enable()`), `ObjectRef::lock()` records which task holds
+//! each object's lock and which object each task is waiting on. Before a task
+//! blocks, it follows that chain from the object it wants: if it leads back to
+//! the task itself, the lock could never be acquired, so the task fails with
+//! the cycle of objects and tasks involved instead of hanging forever.
+//!
+//! Every lock and unlock takes a global lock of its own while this is on, so
+//! it's off by default.
+
+use object::ObjectRef;
+
+use std::mem;
+use std::sync::Mutex;
+use std::sync::atomics::{AtomicBool, AtomicUint, INIT_ATOMIC_BOOL};
+use std::sync::atomics::{INIT_ATOMIC_UINT, Relaxed, SeqCst};
+use std::sync::one::{Once, ONCE_INIT};
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests;
+
+static mut ENABLED:   AtomicBool = INIT_ATOMIC_BOOL;
+static mut NEXT_TASK: AtomicUint = INIT_ATOMIC_UINT;
+
+static mut INIT:  Once = ONCE_INIT;
+static mut STATE: *const Mutex<LockState> = 0 as *const Mutex<LockState>;
+
+local_data_key!(task_id_key: uint)
+
+struct LockState {
+  /// The task holding each object's lock, keyed by the object's address.
+  holders: HashMap<uint, (uint, ObjectRef)>,
+
+  /// The object each task is waiting to lock.
+  waiting: HashMap<uint, ObjectRef>
+}
+
+fn state() -> &'static Mutex<LockState> {
+  unsafe {
+    INIT.doit(|| {
+      STATE = mem::transmute(box Mutex::new(LockState {
+        holders: HashMap::new(),
+        waiting: HashMap::new()
+      }));
+    });
+
+    &*STATE
+  }
+}
+
+/// A number identifying the current task, assigned the first time it's asked
+/// for.
+fn task_id() -> uint {
+  match task_id_key.get() {
+    Some(id) => *id,
+
+    None => {
+      let id = unsafe { NEXT_TASK.fetch_add(1, SeqCst) };
+
+      task_id_key.replace(Some(id));
+      id
+    }
+  }
+}
+
+/// Starts tracking locks. Locks already held aren't known about, so this
+/// should be called before any objects are shared between tasks.
+pub fn enable() {
+  unsafe { ENABLED.store(true, SeqCst) }
+}
+
+/// Returns true if locks are being tracked.
+pub fn is_enabled() -> bool {
+  unsafe { ENABLED.load(Relaxed) }
+}
+
+/// Records that the current task is about to block on `object`'s lock.
+/// Called by `ObjectRef::lock()` itself.
+///
+/// # Failure
+///
+/// Fails if the lock is held, directly or through other waiting tasks, by the
+/// current task, which would otherwise deadlock.
+pub fn waiting(object: &ObjectRef) {
+  let task = task_id();
+
+  let cycle = {
+    let mut state = state().lock();
+
+    let cycle = find_cycle(&*state, task, object);
+
+    if cycle.is_none() {
+      state.waiting.insert(task, object.clone());
+    }
+
+    cycle
+  };
+
+  // Failing with the state locked would poison it for every other task.
+  match cycle {
+    Some(cycle) => fail!("deadlock: {}", describe(cycle.as_slice())),
+    None        => ()
+  }
+}
+
+/// Records that the current task now holds `object`'s lock. Called by
+/// `ObjectRef::lock()` itself.
+pub fn acquired(object: &ObjectRef) {
+  let task = task_id();
+
+  let mut state = state().lock();
+
+  state.waiting.pop(&task);
+  state.holders.insert(object.address(), (task, object.clone()));
+}
+
+/// Records that `object`'s lock is about to be released. Called when an
+/// `ObjectRefGuard` is dropped.
+pub fn released(object: &ObjectRef) {
+  state().lock().holders.pop(&object.address());
+}
+
+/// Follows the chain of holders and the objects they're waiting on from
+/// `object`, returning each (holder, object held) on the way if it leads back
+/// to `task`.
+fn find_cycle(state: &LockState, task: uint, object: &ObjectRef)
+              -> Option<Vec<(uint, ObjectRef)>> {
+
+  let mut cycle  = Vec::new();
+  let mut object = object.clone();
+
+  loop {
+    let holder = match state.holders.find(&object.address()) {
+      Some(&(holder, _)) => holder,
+      None               => return None
+    };
+
+    cycle.push((holder, object));
+
+    if holder == task { return Some(cycle) }
+
+    // More holders than there are waiting tasks means we've come around to
+    // one we've already seen, in a cycle that doesn't involve us. Whoever
+    // closed that one will have failed already.
+    if cycle.len() > state.waiting.len() + 1 { return None }
+
+    match state.waiting.find(&holder) {
+      Some(next) => object = next.clone(),
+      None       => return None
+    }
+  }
+}
+
+/// Lists what each task in `cycle` holds and is waiting for, e.g. `task #1
+/// holds [#0x... ~a] and waits for [#0x... ~b]; task #2 (this task) holds
+/// [#0x... ~b] and waits for [#0x... ~a]`.
+fn describe(cycle: &[(uint, ObjectRef)]) -> String {
+  let mut out = String::new();
+
+  for (index, &(holder, ref object)) in cycle.iter().enumerate() {
+    let &(_, ref wanted) = &cycle[(index + 1) % cycle.len()];
+
+    if index > 0 { out.push_str("; ") }
+
+    out.push_str(format!("task #{}{} holds {} and waits for {}",
+                         holder,
+                         if index == cycle.len() - 1 { " (this task)" }
+                         else { "" },
+                         object, wanted).as_slice());
+  }
+
+  out
+}
@@ -0,0 +1,72 @@
+use super::enable;
+
+use nuketype::Thing;
+use object::Meta;
+
+use util;
+
+use std::task;
+
+#[test]
+fn locking_twice_fails() {
+  enable();
+
+  let object = Thing::tagged(Meta::new(), "test deadlock twice");
+
+  let result = task::try(proc() {
+    let _first  = object.lock();
+    let _second = object.lock();
+  });
+
+  assert!(result.is_err());
+}
+
+#[test]
+fn opposite_lock_order_fails_one_task() {
+  util::timeout(1000, proc() {
+    enable();
+
+    let a = Thing::tagged(Meta::new(), "test deadlock a");
+    let b = Thing::tagged(Meta::new(), "test deadlock b");
+
+    let (ready_tx, ready_rx) = channel();
+    let (done_tx,  done_rx)  = channel();
+
+    let mut go = vec![];
+
+    for &(ref first, ref second) in [(a.clone(), b.clone()),
+                                     (b.clone(), a.clone())].iter() {
+      let first    = first.clone();
+      let second   = second.clone();
+      let ready_tx = ready_tx.clone();
+      let done_tx  = done_tx.clone();
+
+      let (go_tx, go_rx) = channel::<()>();
+
+      go.push(go_tx);
+
+      spawn(proc() {
+        done_tx.send(task::try(proc() {
+          let _first = first.lock();
+
+          ready_tx.send(());
+          go_rx.recv();
+
+          let _second = second.lock();
+        }).is_ok());
+      });
+    }
+
+    // Only let them go for their second lock once both have their first.
+    ready_rx.recv();
+    ready_rx.recv();
+
+    for go_tx in go.iter() { go_tx.send(()) }
+
+    let results = [done_rx.recv(), done_rx.recv()];
+
+    // Exactly one of them should have seen the cycle and failed, letting the
+    // other one through.
+    assert_eq!(1, results.iter().filter(|&&ok| ok).count());
+  })
+}
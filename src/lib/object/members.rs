@@ -265,6 +265,40 @@ impl Members {
     self.push_child(pair);
   }
 
+  /// Merges another members list into this one according to `policy`. See
+  /// `MergePolicy`.
+  ///
+  /// Relationships are copied as they are, child or not. The noughty (0th)
+  /// member of `other` is never looked at.
+  pub fn merge(&mut self, other: &Members, policy: MergePolicy) {
+    match policy {
+      MergeReplace =>
+        *self = other.clone(),
+
+      MergeTheirs | MergeOurs => {
+        self.expand_to(other.len());
+
+        for (index, theirs) in other.vec.iter().enumerate().skip(1) {
+          match *theirs {
+            Some(ref relationship)
+              if policy == MergeTheirs || self.vec[index].is_none() =>
+                self.vec[index] = Some(relationship.clone()),
+
+            _ => ()
+          }
+        }
+      },
+
+      MergeAppend => {
+        self.expand_to(1);
+
+        for theirs in other.iter() {
+          self.vec.push(theirs.clone());
+        }
+      }
+    }
+  }
+
   /// Creates holes to grow the list to the given size.
   pub fn expand_to(&mut self, size: uint) {
     self.vec.reserve(size);
@@ -275,6 +309,40 @@ impl Members {
   }
 }
 
+/// Decides how conflicts are resolved when merging members lists. See
+/// `Members::merge()`.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum MergePolicy {
+  /// Discard our members entirely and take theirs. This is what
+  /// `infrastructure adopt` does.
+  MergeReplace,
+
+  /// Where both lists have a member at an index, theirs wins. Holes in their
+  /// list leave ours alone.
+  MergeTheirs,
+
+  /// Where both lists have a member at an index, ours wins. Their members only
+  /// fill our holes (and extend our list).
+  MergeOurs,
+
+  /// Their members (holes included) are appended after ours.
+  MergeAppend
+}
+
+impl MergePolicy {
+  /// Looks up a policy by the name used by `infrastructure merge`: `replace`,
+  /// `theirs`, `ours`, or `append`.
+  pub fn from_name(name: &str) -> Option<MergePolicy> {
+    match name {
+      "replace" => Some(MergeReplace),
+      "theirs"  => Some(MergeTheirs),
+      "ours"    => Some(MergeOurs),
+      "append"  => Some(MergeAppend),
+      _         => None
+    }
+  }
+}
+
 impl Collection for Members {
   fn len(&self) -> uint {
     self.vec.len()
@@ -1,9 +1,11 @@
 //! Paws objects and metadata.
 
-use nuketype::{Nuketype, Symbol};
+use nuketype::{Nuketype, Symbol, Alien};
 
 use machine::reactor::Reactor;
 
+use alloc::arc;
+
 use std::any::{AnyRefExt, AnyMutRefExt};
 
 use std::hash::Hash;
@@ -12,13 +14,18 @@ use std::hash::sip::SipState;
 use std::sync::{Arc, Weak, Mutex, MutexGuard};
 use std::sync::atomics::{AtomicUint, SeqCst};
 
+use std::collections::HashMap;
+
 use std::fmt::Show;
 use std::fmt;
 
-pub use self::cache::Cache;
-pub use self::members::Members;
+pub use self::cache::{Cache, CacheStats, CacheConfig, SharedCache};
+pub use self::members::{Members, MergePolicy};
+pub use self::members::{MergeReplace, MergeTheirs, MergeOurs, MergeAppend};
 
 pub mod cache;
+pub mod registry;
+pub mod deadlock;
 
 mod members;
 
@@ -73,6 +80,11 @@ struct ObjectBox {
 
   /// For metadata caching.
   meta_version: AtomicUint,
+
+  /// If the object is frozen, a lookup table from Symbol string pointers
+  /// (as `uint`s) to the values of the object's pair members. See
+  /// `ObjectRef::store_frozen()`.
+  frozen:       Option<HashMap<uint, ObjectRef>>,
 }
 
 struct ObjectData {
@@ -108,37 +120,175 @@ impl ObjectRef {
     ObjectRef::make(symbol, Meta::new(), Some(symbol_ref), None)
   }
 
+  /// Boxes a `Nuketype` and `Meta` along with a tag, and freezes the object.
+  ///
+  /// The metadata of a frozen object can never be modified, which allows an
+  /// immutable lookup table for its Symbol-keyed pair members to be generated
+  /// once, here, and shared by all reactors without any locking or caching.
+  /// This is intended for namespaces, like those in `paws::system`.
+  pub fn store_frozen<T: Tag>(
+                      nuketype: Box<Nuketype+Send+Sync>,
+                      meta:     Meta,
+                      tag:      T)
+                    -> ObjectRef {
+
+    let mut table = HashMap::new();
+
+    // Later pairs take precedence, as with `Members::lookup_pair()`.
+    for maybe_relationship in meta.members.iter() {
+      match *maybe_relationship {
+        Some(ref relationship) => {
+          let pair    = relationship.to().lock();
+          let members = &pair.meta().members;
+
+          match (members.get(1), members.get(2)) {
+            (Some(key), Some(value)) =>
+              match key.to().symbol_ref() {
+                Some(string) => {
+                  table.insert(&**string as *const String as uint,
+                               value.to().clone());
+                },
+                None => ()
+              },
+            _ => ()
+          }
+        },
+        None => ()
+      }
+    }
+
+    ObjectRef::make_with_table(nuketype, meta, None, tag.to_tag(), Some(table))
+  }
+
   fn make(nuketype:   Box<Nuketype+Send+Sync>,
           meta:       Meta,
           symbol_ref: Option<Arc<String>>,
           tag:        Option<Arc<String>>)
           -> ObjectRef {
 
-    ObjectRef {
+    ObjectRef::make_with_table(nuketype, meta, symbol_ref, tag, None)
+  }
+
+  fn make_with_table(nuketype:   Box<Nuketype+Send+Sync>,
+                     meta:       Meta,
+                     symbol_ref: Option<Arc<String>>,
+                     tag:        Option<Arc<String>>,
+                     frozen:     Option<HashMap<uint, ObjectRef>>)
+                     -> ObjectRef {
+
+    let object = ObjectRef {
       reference: Arc::new(ObjectBox {
         symbol_ref:   symbol_ref,
         tag:          tag,
         meta_version: AtomicUint::new(0),
+        frozen:       frozen,
 
         data: Mutex::new(ObjectData {
           nuketype: nuketype,
           meta:     meta
         })
       })
-    }
+    };
+
+    registry::register(&object);
+
+    object
   }
 
   /// Obtain exclusive access to the data this reference points to.
   ///
   /// The Nuketype and Meta can be accessed via the returned RAII guard. The
   /// returned guard also contains a reference to this ObjectRef.
+  ///
+  /// # Failure
+  ///
+  /// If `deadlock` tracking is enabled, fails instead of blocking forever when
+  /// the lock can never be acquired.
   pub fn lock<'a>(&'a self) -> ObjectRefGuard<'a> {
-    ObjectRefGuard {
-      object_ref: self,
-      guard:      self.reference.data.lock()
+    if deadlock::is_enabled() {
+      deadlock::waiting(self);
+
+      let guard = self.reference.data.lock();
+
+      deadlock::acquired(self);
+
+      ObjectRefGuard {
+        object_ref: self,
+        guard:      guard,
+        tracked:    true
+      }
+    } else {
+      ObjectRefGuard {
+        object_ref: self,
+        guard:      self.reference.data.lock(),
+        tracked:    false
+      }
+    }
+  }
+
+  /// Obtain exclusive access to two objects at once, returning their guards in
+  /// the same order as the arguments.
+  ///
+  /// The locks are always acquired in order of address, no matter which order
+  /// the objects are given in, so two reactors locking the same pair can't
+  /// deadlock. Code that needs more than one object locked at a time should
+  /// always go through this or `lock_many()`.
+  ///
+  /// # Failure
+  ///
+  /// Fails if `a` and `b` are the same object, which would otherwise deadlock.
+  pub fn lock_pair<'a>(a: &'a ObjectRef, b: &'a ObjectRef)
+                       -> (ObjectRefGuard<'a>, ObjectRefGuard<'a>) {
+
+    if a == b {
+      fail!("tried to lock {} twice", a);
+    }
+
+    if a.address() < b.address() {
+      let guard_a = a.lock();
+      let guard_b = b.lock();
+
+      (guard_a, guard_b)
+    } else {
+      let guard_b = b.lock();
+      let guard_a = a.lock();
+
+      (guard_a, guard_b)
     }
   }
 
+  /// Obtain exclusive access to any number of objects at once, returning their
+  /// guards in the same order as `objects`. See `lock_pair()`.
+  ///
+  /// # Failure
+  ///
+  /// Fails if the same object appears more than once.
+  pub fn lock_many<'a>(objects: &'a [ObjectRef]) -> Vec<ObjectRefGuard<'a>> {
+    let mut order: Vec<uint> = range(0, objects.len()).collect();
+
+    order.sort_by(|&i, &j| objects[i].address().cmp(&objects[j].address()));
+
+    for window in order.as_slice().windows(2) {
+      if objects[window[0]] == objects[window[1]] {
+        fail!("tried to lock {} twice", objects[window[0]]);
+      }
+    }
+
+    let mut guards: Vec<Option<ObjectRefGuard<'a>>> =
+      objects.iter().map(|_| None).collect();
+
+    for &index in order.iter() {
+      *guards.get_mut(index) = Some(objects[index].lock());
+    }
+
+    guards.move_iter().map(|guard| guard.unwrap()).collect()
+  }
+
+  /// The address of the object, which decides locking order.
+  fn address(&self) -> uint {
+    &*self.reference as *const ObjectBox as uint
+  }
+
   /// Returns a new weak reference to the object that this reference points to.
   ///
   /// The weak reference will not keep the object alive, and so is suitable for
@@ -149,6 +299,13 @@ impl ObjectRef {
     }
   }
 
+  /// Returns true if this is the only reference to the object, strong or weak,
+  /// so that nothing else could ever see it change.
+  pub fn is_unique(&self) -> bool {
+    arc::strong_count(&self.reference) == 1 &&
+      arc::weak_count(&self.reference) == 0
+  }
+
   /// Returns true if both `ObjectRef`s are Symbol references that point at the
   /// same Symbol string.
   pub fn eq_as_symbol(&self, other: &ObjectRef) -> bool {
@@ -180,6 +337,21 @@ impl ObjectRef {
   pub fn meta_version(&self) -> uint {
     self.reference.meta_version.load(SeqCst)
   }
+
+  /// Returns true if the object was created with `ObjectRef::store_frozen()`,
+  /// and therefore its metadata can't be modified.
+  pub fn is_frozen(&self) -> bool {
+    self.reference.frozen.is_some()
+  }
+
+  /// If the object is frozen, looks up the value of a pair member with the
+  /// given Symbol key without locking.
+  ///
+  /// Returns `None` if the object isn't frozen or there is no such pair.
+  pub fn frozen_lookup(&self, symbol: &Arc<String>) -> Option<ObjectRef> {
+    self.reference.frozen.as_ref().and_then(|table|
+      table.find(&(&**symbol as *const String as uint)).map(|v| v.clone()))
+  }
 }
 
 impl PartialEq for ObjectRef {
@@ -294,7 +466,21 @@ impl<T: Tag> Tag for Option<T> {
 /// Exclusive access is dropped when this guard is dropped.
 pub struct ObjectRefGuard<'a> {
   object_ref:    &'a ObjectRef,
-  guard:         MutexGuard<'a, ObjectData>
+  guard:         MutexGuard<'a, ObjectData>,
+
+  /// Whether `deadlock` knows about this lock, and needs to be told when it's
+  /// released.
+  tracked:       bool
+}
+
+#[unsafe_destructor]
+impl<'a> Drop for ObjectRefGuard<'a> {
+  fn drop(&mut self) {
+    // Runs before `guard` is dropped, so the lock is still held.
+    if self.tracked {
+      deadlock::released(self.object_ref);
+    }
+  }
 }
 
 impl<'a> ObjectRefGuard<'a> {
@@ -323,7 +509,17 @@ impl<'a> ObjectRefGuard<'a> {
   ///
   /// Increments the metadata version of the object so that any metadata caches
   /// will be invalidated.
+  ///
+  /// # Failure
+  ///
+  /// Fails if the object is frozen. Check `ObjectRef::is_frozen()` first if
+  /// that's a possibility.
   pub fn meta_mut(&mut self) -> &mut Meta {
+    if self.object_ref.is_frozen() {
+      fail!("tried to modify the metadata of frozen object {}",
+            self.object_ref);
+    }
+
     self.object_ref.reference.meta_version.fetch_add(1, SeqCst);
 
     &mut self.guard.deref_mut().meta
@@ -517,6 +713,26 @@ pub enum Receiver {
   NativeReceiver(fn (&mut Reactor, Params))
 }
 
+impl Receiver {
+  /// Replaces an `ObjectReceiver` that points at an Alien wrapping a native
+  /// receiver (see `Alien::new_from_native_receiver()`) with that
+  /// `NativeReceiver`, which does the same thing without allocating a params
+  /// object and staging the Alien for every combination.
+  ///
+  /// Locks the receiver object, so don't call it while holding that lock.
+  pub fn unwrap_native(self) -> Receiver {
+    match self {
+      ObjectReceiver(object_ref) =>
+        match Alien::native_receiver_of(&object_ref) {
+          Some(function) => NativeReceiver(function),
+          None           => ObjectReceiver(object_ref)
+        },
+
+      native => native
+    }
+  }
+}
+
 impl Clone for Receiver {
   fn clone(&self) -> Receiver {
     match *self {
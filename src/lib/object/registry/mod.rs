@@ -0,0 +1,145 @@
+//! A process-wide registry of live objects, for tracking down leaks.
+//!
+//! When enabled (with `enable()`), every object created afterward is recorded
+//! along with its tag and a serial number. Since only weak references are
+//! kept, objects that are dropped simply disappear from the registry, and
+//! whatever is left when the program is done (e.g. reference cycles, or native
+//! code holding onto references longer than intended) can be listed with
+//! `live()` or summarized with `report()`.
+//!
+//! Registration costs a global lock per object created, so it's off by
+//! default.
+
+use object::{ObjectRef, WeakObjectRef};
+
+use std::mem;
+use std::cmp::Equal;
+use std::sync::{Arc, Mutex};
+use std::sync::atomics::{AtomicBool, AtomicUint, INIT_ATOMIC_BOOL};
+use std::sync::atomics::{INIT_ATOMIC_UINT, Relaxed, SeqCst};
+use std::sync::one::{Once, ONCE_INIT};
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests;
+
+static mut ENABLED: AtomicBool = INIT_ATOMIC_BOOL;
+static mut SERIAL:  AtomicUint = INIT_ATOMIC_UINT;
+
+static mut INIT:    Once = ONCE_INIT;
+static mut ENTRIES: *const Mutex<Vec<Entry>> = 0 as *const Mutex<Vec<Entry>>;
+
+struct Entry {
+  object: WeakObjectRef,
+  tag:    Option<Arc<String>>,
+  serial: uint
+}
+
+/// An object that was still alive when `live()` was called.
+pub struct LiveObject {
+  /// The object itself.
+  pub object: ObjectRef,
+
+  /// The object's serial number: the number of objects registered before it.
+  pub serial: uint
+}
+
+fn entries() -> &'static Mutex<Vec<Entry>> {
+  unsafe {
+    INIT.doit(|| {
+      ENTRIES = mem::transmute(box Mutex::new(Vec::<Entry>::new()));
+    });
+
+    &*ENTRIES
+  }
+}
+
+/// Starts registering new objects. Objects created before this are never
+/// registered.
+pub fn enable() {
+  unsafe { ENABLED.store(true, SeqCst) }
+}
+
+/// Returns true if new objects are being registered.
+pub fn is_enabled() -> bool {
+  unsafe { ENABLED.load(Relaxed) }
+}
+
+/// Registers a newly created object. Called by `ObjectRef` itself.
+pub fn register(object: &ObjectRef) {
+  if !is_enabled() { return }
+
+  let serial = unsafe { SERIAL.fetch_add(1, SeqCst) };
+
+  let mut entries = entries().lock();
+
+  entries.push(Entry {
+    object: object.downgrade(),
+    tag:    object.tag().map(|tag| tag.clone()),
+    serial: serial
+  });
+
+  // Sweep out dead entries every so often so the registry doesn't grow without
+  // bound.
+  if entries.len() % 4096 == 0 {
+    entries.retain(|entry| entry.object.upgrade().is_some());
+  }
+}
+
+/// Lists every registered object that is still alive, in creation order.
+pub fn live() -> Vec<LiveObject> {
+  let mut entries = entries().lock();
+
+  entries.retain(|entry| entry.object.upgrade().is_some());
+
+  entries.iter().filter_map(|entry|
+    entry.object.upgrade().map(|object|
+      LiveObject { object: object, serial: entry.serial })).collect()
+}
+
+/// Counts the registered objects that are still alive, grouped by tag.
+/// Untagged objects are grouped under `(symbol)` or `(untagged)`.
+pub fn live_by_tag() -> Vec<(String, uint)> {
+  let mut counts: HashMap<String, uint> = HashMap::new();
+
+  for live in live().move_iter() {
+    let group = match live.object.tag() {
+      Some(tag) => tag.as_slice().to_string(),
+
+      None =>
+        if live.object.symbol_ref().is_some() {
+          "(symbol)".to_string()
+        } else {
+          "(untagged)".to_string()
+        }
+    };
+
+    *counts.find_or_insert(group, 0) += 1;
+  }
+
+  let mut counts: Vec<(String, uint)> = counts.move_iter().collect();
+
+  // Most common first.
+  counts.sort_by(|&(ref a_tag, a_count), &(ref b_tag, b_count)|
+    match b_count.cmp(&a_count) {
+      Equal => a_tag.cmp(b_tag),
+      order => order
+    });
+
+  counts
+}
+
+/// Warns about every registered object that is still alive, grouped by tag.
+/// Meant to be called once a machine is done.
+pub fn report() {
+  let counts = live_by_tag();
+
+  if counts.is_empty() { return }
+
+  warn!("{} object(s) still alive:",
+        counts.iter().fold(0, |sum, &(_, count)| sum + count));
+
+  for &(ref tag, count) in counts.iter() {
+    warn!("  {:>8} {}", count, tag);
+  }
+}
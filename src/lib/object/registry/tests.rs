@@ -0,0 +1,25 @@
+use super::{enable, live};
+
+use nuketype::Thing;
+use object::{ObjectRef, Meta};
+
+#[test]
+fn registers_live_objects_only() {
+  enable();
+
+  let kept    = ObjectRef::store_with_tag(box Thing, Meta::new(), "test kept");
+  let dropped = ObjectRef::store_with_tag(box Thing, Meta::new(), "test dropped");
+
+  drop(dropped);
+
+  // Other tests may be creating objects concurrently, so only look at ours.
+  let ours: Vec<ObjectRef> = live().move_iter()
+    .map(|live| live.object)
+    .filter(|object| match object.tag() {
+      Some(tag) => tag.as_slice().starts_with("test "),
+      None      => false
+    })
+    .collect();
+
+  assert!(ours == vec![kept]);
+}
@@ -1,4 +1,5 @@
 use super::{ObjectRef, Params, Members, Meta};
+use super::{MergeReplace, MergeTheirs, MergeOurs, MergeAppend};
 use super::{lookup_receiver, Relationship};
 
 use nuketype::{Thing, Symbol};
@@ -6,6 +7,8 @@ use nuketype::{Thing, Symbol};
 use machine::Machine;
 use machine::reactor::MockReactor;
 
+use util;
+
 use std::sync::Arc;
 
 #[test]
@@ -94,6 +97,53 @@ fn members_push() {
   assert!( members.vec[2].get_ref().to() == &object2);
 }
 
+fn members_merge_case() -> (Members, Members, Vec<ObjectRef>) {
+  let objects = Vec::from_fn(4, |_| Thing::empty());
+
+  // ours:   [-, 0, -]
+  // theirs: [-, 1, 2, 3]
+  let mut ours = Members::new();
+
+  ours.push(objects[0].clone());
+  ours.vec.push(None);
+
+  let mut theirs = Members::new();
+
+  theirs.push(objects[1].clone());
+  theirs.push(objects[2].clone());
+  theirs.push(objects[3].clone());
+
+  (ours, theirs, objects)
+}
+
+fn members_targets(members: &Members) -> Vec<Option<ObjectRef>> {
+  members.iter().map(|m| m.as_ref().map(|r| r.to().clone())).collect()
+}
+
+#[test]
+fn members_merge() {
+  let (mut ours, theirs, o) = members_merge_case();
+  ours.merge(&theirs, MergeReplace);
+  assert!(members_targets(&ours) ==
+    vec![Some(o[1].clone()), Some(o[2].clone()), Some(o[3].clone())]);
+
+  let (mut ours, theirs, o) = members_merge_case();
+  ours.merge(&theirs, MergeTheirs);
+  assert!(members_targets(&ours) ==
+    vec![Some(o[1].clone()), Some(o[2].clone()), Some(o[3].clone())]);
+
+  let (mut ours, theirs, o) = members_merge_case();
+  ours.merge(&theirs, MergeOurs);
+  assert!(members_targets(&ours) ==
+    vec![Some(o[0].clone()), Some(o[2].clone()), Some(o[3].clone())]);
+
+  let (mut ours, theirs, o) = members_merge_case();
+  ours.merge(&theirs, MergeAppend);
+  assert!(members_targets(&ours) ==
+    vec![Some(o[0].clone()), None,
+         Some(o[1].clone()), Some(o[2].clone()), Some(o[3].clone())]);
+}
+
 #[test]
 fn members_pop() {
   let object0 = Thing::empty();
@@ -321,6 +371,89 @@ fn object_ref_guards() {
   assert!(object_ref.lock().meta().members.len() == 0);
 }
 
+#[test]
+fn object_ref_lock_pair_keeps_argument_order() {
+  let a = Thing::empty();
+  let b = Thing::empty();
+
+  a.lock().meta_mut().members.push(Thing::empty());
+
+  let (guard_a, guard_b) = ObjectRef::lock_pair(&b, &a);
+
+  assert_eq!(0, guard_a.meta().members.len());
+  assert_eq!(2, guard_b.meta().members.len());
+}
+
+#[test]
+#[should_fail]
+fn object_ref_lock_pair_refuses_same_object() {
+  let a = Thing::empty();
+
+  ObjectRef::lock_pair(&a, &a);
+}
+
+#[test]
+fn object_ref_lock_pair_in_opposite_orders() {
+  util::timeout(1000, proc() {
+    let a = Thing::empty();
+    let b = Thing::empty();
+
+    let (tx, rx) = channel();
+
+    for &flip in [false, true].iter() {
+      let a  = a.clone();
+      let b  = b.clone();
+      let tx = tx.clone();
+
+      spawn(proc() {
+        for _ in range(0u, 1000) {
+          let (mut first, _) =
+            if flip {
+              ObjectRef::lock_pair(&b, &a)
+            } else {
+              ObjectRef::lock_pair(&a, &b)
+            };
+
+          first.meta_mut().members.push(Thing::empty());
+        }
+
+        tx.send(());
+      });
+    }
+
+    rx.recv();
+    rx.recv();
+
+    // Each one got a thousand, after the noughty slot.
+    assert_eq!(1001, a.lock().meta().members.len());
+    assert_eq!(1001, b.lock().meta().members.len());
+  })
+}
+
+#[test]
+fn object_ref_lock_many() {
+  let objects = [Thing::empty(), Thing::empty(), Thing::empty()];
+
+  objects[1].lock().meta_mut().members.push(Thing::empty());
+
+  let guards = ObjectRef::lock_many(objects);
+
+  assert_eq!(3, guards.len());
+
+  let lens: Vec<uint> =
+    guards.iter().map(|guard| guard.meta().members.len()).collect();
+
+  assert_eq!(vec![0, 2, 0], lens);
+}
+
+#[test]
+#[should_fail]
+fn object_ref_lock_many_refuses_duplicates() {
+  let a = Thing::empty();
+
+  ObjectRef::lock_many([a.clone(), Thing::empty(), a]);
+}
+
 #[test]
 fn typed_ref_guards() {
   let sym        = Arc::new("foo".to_string());
@@ -457,14 +590,8 @@ fn lookup_receiver_hit_object_key() {
     message: env.obj_key_ref.clone()
   });
 
-  match env.reactor.stagings.as_slice() {
-    [(ref execution, ref response)] => {
-      assert!(execution == &env.caller_ref);
-      assert!(response  == &env.obj_val_ref);
-    },
-
-    _ => fail!("unexpected reaction!")
-  }
+  env.reactor.expect_stage(&env.caller_ref, &env.obj_val_ref);
+  env.reactor.expect_no_stagings();
 }
 
 #[test]
@@ -478,14 +605,8 @@ fn lookup_receiver_hit_symbol_key() {
                Symbol::new(env.sym_key_sym.clone()))
   });
 
-  match env.reactor.stagings.as_slice() {
-    [(ref execution, ref response)] => {
-      assert!(execution == &env.caller_ref);
-      assert!(response  == &env.sym_val_ref);
-    },
-
-    _ => fail!("unexpected reaction!")
-  }
+  env.reactor.expect_stage(&env.caller_ref, &env.sym_val_ref);
+  env.reactor.expect_no_stagings();
 }
 
 #[test]
@@ -515,3 +636,37 @@ fn lookup_receiver_miss_symbol_key() {
 
   assert!(env.reactor.stagings.is_empty());
 }
+
+#[test]
+fn frozen_lookup_uses_last_matching_pair() {
+  let machine = Machine::new();
+
+  let first  = Thing::empty();
+  let second = Thing::empty();
+
+  let mut meta = Meta::new();
+
+  meta.members.push_pair(machine.symbol("foo"), first);
+  meta.members.push_pair(machine.symbol("foo"), second.clone());
+
+  let frozen = Thing::frozen(meta, "frozen");
+
+  let foo_sym = machine.symbol_map.lock().intern("foo");
+  let bar_sym = machine.symbol_map.lock().intern("bar");
+
+  assert!(frozen.is_frozen());
+
+  assert!(frozen.frozen_lookup(&foo_sym) == Some(second));
+  assert!(frozen.frozen_lookup(&bar_sym).is_none());
+
+  assert!(!Thing::empty().is_frozen());
+  assert!(Thing::empty().frozen_lookup(&foo_sym).is_none());
+}
+
+#[test]
+#[should_fail]
+fn frozen_objects_can_not_be_modified() {
+  let frozen = Thing::frozen(Meta::new(), "frozen");
+
+  frozen.lock().meta_mut().members.push(Thing::empty());
+}
@@ -0,0 +1,507 @@
+//! Paws packages: a manifest plus a collection of cPaws modules.
+//!
+//! A package is either a directory or a (ustar) tar archive containing a file
+//! named `package.manifest` at its root, along with any number of `.paws`
+//! files. The manifest is a list of `key = value` lines:
+//!
+//!     # Comments start with a hash.
+//!     name    = hello
+//!     version = 0.1.0
+//!     entry   = main.paws
+//!
+//! Every `.paws` file in the package becomes a module, named by its path
+//! relative to the manifest without the extension (e.g. `lib/util`). The entry
+//! module is staged when the package is run, and any module can use
+//! `package import[] "lib/util"` to get at another module's locals.
+//!
+//! A `.paws` file may also be bytecode written by `Script::serialize()` (as
+//! `paws_rs --compile` does), in which case it's loaded without parsing. Such
+//! modules have no span tables, so warnings from them don't say where they
+//! came from.
+
+use cpaws;
+
+use script::Script;
+
+use object::{ObjectRef, TypedRefGuard, Meta};
+
+use nuketype::{Thing, Alien, Execution};
+
+use machine::{Machine, Reactor};
+
+use std::any::AnyMutRefExt;
+use std::collections::HashMap;
+use std::io::BufReader;
+use std::io::fs::{mod, File};
+use std::num::from_str_radix;
+use std::path::Path;
+use std::str;
+use std::sync::{Arc, Mutex};
+
+#[cfg(test)]
+mod tests;
+
+/// The name of the manifest file within a package.
+pub static MANIFEST_NAME: &'static str = "package.manifest";
+
+/// Describes a package.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct Manifest {
+  /// The name of the package. Required.
+  pub name:    String,
+
+  /// The version of the package, if specified.
+  pub version: Option<String>,
+
+  /// The path of the entry file, relative to the manifest. Defaults to
+  /// `main.paws`.
+  pub entry:   String
+}
+
+impl Manifest {
+  /// Parses a manifest from its textual representation.
+  pub fn parse(text: &str) -> Result<Manifest, String> {
+    let mut name    = None;
+    let mut version = None;
+    let mut entry   = None;
+
+    for (index, line) in text.lines().enumerate() {
+      let line = line.trim();
+
+      if line.is_empty() || line.starts_with("#") { continue }
+
+      let (key, value) =
+        match line.find('=') {
+          Some(at) => (line.slice_to(at).trim(), line.slice_from(at + 1).trim()),
+          None     =>
+            return Err(format!("{}:{}: expected `key = value`",
+                               MANIFEST_NAME, index + 1))
+        };
+
+      match key {
+        "name"    => name    = Some(value.to_string()),
+        "version" => version = Some(value.to_string()),
+        "entry"   => entry   = Some(value.to_string()),
+
+        _ =>
+          return Err(format!("{}:{}: unknown key '{}'",
+                             MANIFEST_NAME, index + 1, key))
+      }
+    }
+
+    match name {
+      Some(name) =>
+        Ok(Manifest {
+          name:    name,
+          version: version,
+          entry:   entry.unwrap_or_else(|| "main.paws".to_string())
+        }),
+
+      None =>
+        Err(format!("{}: missing required key 'name'", MANIFEST_NAME))
+    }
+  }
+}
+
+/// The contents of a module within a package.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum Module {
+  /// cPaws source.
+  Source(String),
+
+  /// Bytecode written by `Script::serialize()`.
+  Bytecode(Vec<u8>)
+}
+
+/// A loaded package: its manifest and the contents of each of its modules.
+#[deriving(Clone)]
+pub struct Package {
+  /// The package's manifest.
+  pub manifest: Manifest,
+
+  /// Module contents, by module name.
+  pub modules:  HashMap<String, Module>
+}
+
+impl Package {
+  /// Loads a package from either a directory or a tar archive.
+  pub fn load(path: &Path) -> Result<Package, String> {
+    if path.is_dir() {
+      Package::from_dir(path)
+    } else {
+      match File::open(path).read_to_end() {
+        Ok(bytes) => Package::from_tar(bytes.as_slice()),
+        Err(e)    => Err(format!("{}: {}", path.display(), e))
+      }
+    }
+  }
+
+  /// Loads a package from a directory containing a manifest.
+  pub fn from_dir(dir: &Path) -> Result<Package, String> {
+    let mut files = Vec::new();
+
+    let paths = match fs::walk_dir(dir) {
+      Ok(paths) => paths,
+      Err(e)    => return Err(format!("{}: {}", dir.display(), e))
+    };
+
+    for path in paths {
+      let relative = match path.path_relative_from(dir) {
+        Some(relative) => relative,
+        None           => continue
+      };
+
+      let name = match relative.as_str() {
+        Some(name) => name.to_string(),
+        None       => continue
+      };
+
+      if name.as_slice() != MANIFEST_NAME &&
+         relative.extension_str() != Some("paws") {
+        continue
+      }
+
+      match File::open(&path).read_to_end() {
+        Ok(bytes) => files.push((name, bytes)),
+        Err(e)    => return Err(format!("{}: {}", path.display(), e))
+      }
+    }
+
+    Package::from_files(files)
+  }
+
+  /// Loads a package from the bytes of a ustar archive.
+  ///
+  /// If the manifest isn't at the root of the archive, but within a single
+  /// directory (as is common), that directory is treated as the root.
+  pub fn from_tar(bytes: &[u8]) -> Result<Package, String> {
+    let entries = try!(read_tar(bytes));
+
+    let root =
+      match entries.iter().find(|&&(ref name, _)|
+              name.as_slice().ends_with(MANIFEST_NAME)) {
+        Some(&(ref name, _)) =>
+          name.as_slice().slice_to(name.len() - MANIFEST_NAME.len())
+            .to_string(),
+        None =>
+          return Err(format!("archive is missing {}", MANIFEST_NAME))
+      };
+
+    let mut files = Vec::new();
+
+    for (name, bytes) in entries.move_iter() {
+      if !name.as_slice().starts_with(root.as_slice()) { continue }
+
+      let name = name.as_slice().slice_from(root.len()).to_string();
+
+      if name.as_slice() != MANIFEST_NAME &&
+         !name.as_slice().ends_with(".paws") {
+        continue
+      }
+
+      files.push((name, bytes));
+    }
+
+    Package::from_files(files)
+  }
+
+  /// Builds a package from a list of `(path, contents)` pairs, one of which
+  /// must be the manifest.
+  ///
+  /// Modules that start like bytecode (see `Script::is_bytecode()`) are kept
+  /// as bytecode; anything else must be UTF-8 cPaws source.
+  pub fn from_files(files: Vec<(String, Vec<u8>)>)
+                    -> Result<Package, String> {
+    let mut manifest = None;
+    let mut modules  = HashMap::new();
+
+    for (name, bytes) in files.move_iter() {
+      if name.as_slice() == MANIFEST_NAME {
+        let text = try!(utf8(name.as_slice(), bytes));
+
+        manifest = Some(try!(Manifest::parse(text.as_slice())));
+      } else if name.as_slice().ends_with(".paws") {
+        let module = name.as_slice().slice_to(name.len() - 5).to_string();
+
+        if Script::is_bytecode(bytes.as_slice()) {
+          modules.insert(module, Bytecode(bytes));
+        } else {
+          modules.insert(module, Source(try!(utf8(name.as_slice(), bytes))));
+        }
+      }
+    }
+
+    let manifest = match manifest {
+      Some(manifest) => manifest,
+      None           => return Err(format!("missing {}", MANIFEST_NAME))
+    };
+
+    let package = Package { manifest: manifest, modules: modules };
+
+    if package.modules.contains_key(&package.entry_module()) {
+      Ok(package)
+    } else {
+      Err(format!("entry '{}' not found in package", package.manifest.entry))
+    }
+  }
+
+  /// The name of the module that `manifest.entry` refers to.
+  pub fn entry_module(&self) -> String {
+    let entry = self.manifest.entry.as_slice();
+
+    if entry.ends_with(".paws") {
+      entry.slice_to(entry.len() - 5).to_string()
+    } else {
+      entry.to_string()
+    }
+  }
+
+  /// Compiles and stages the package's entry module on the given reactor.
+  pub fn run(self, reactor: &mut Reactor) -> Result<(), String> {
+    let entry  = self.entry_module();
+    let loader = Loader::new(self);
+
+    loader.import(reactor, entry.as_slice()).map(|_| ())
+  }
+}
+
+/// Loads modules from a package on demand, each at most once.
+#[deriving(Clone)]
+pub struct Loader {
+  package: Arc<Package>,
+  loaded:  Arc<Mutex<HashMap<String, ObjectRef>>>
+}
+
+impl Loader {
+  /// Creates a loader for the given package with nothing loaded yet.
+  pub fn new(package: Package) -> Loader {
+    Loader {
+      package: Arc::new(package),
+      loaded:  Arc::new(Mutex::new(HashMap::new()))
+    }
+  }
+
+  /// Imports a module, returning its locals.
+  ///
+  /// The first time a module is imported, it is compiled (or deserialized, if
+  /// it's bytecode) and staged, with the system interface and `package`
+  /// exposed to it. The locals are returned
+  /// straight away, so importers should be aware that the module may not have
+  /// finished (or started) populating them yet.
+  pub fn import(&self, reactor: &mut Reactor, module: &str)
+                -> Result<ObjectRef, String> {
+
+    let mut loaded = self.loaded.lock();
+
+    match loaded.find_equiv(&module) {
+      Some(locals) => return Ok(locals.clone()),
+      None         => ()
+    }
+
+    let contents = match self.package.modules.find_equiv(&module) {
+      Some(contents) => contents,
+      None           => return Err(format!("no such module '{}'", module))
+    };
+
+    let filename =
+      format!("{}/{}.paws", self.package.manifest.name, module);
+
+    let machine = reactor.machine().clone();
+
+    let execution = match *contents {
+      Source(ref source) => {
+        let (nodes, spans) =
+          try!(cpaws::parse_nodes_with_spans(source.as_slice(),
+                                             filename.as_slice()));
+
+        let (script, spans) =
+          cpaws::build_fused_script_with_spans(&machine, nodes.as_slice(),
+                                               spans.as_slice());
+
+        Execution::create_with_spans(&machine, script, spans)
+      },
+
+      Bytecode(ref bytes) => {
+        let script =
+          try!(Script::deserialize(&mut BufReader::new(bytes.as_slice()),
+                                   &machine)
+                 .map_err(|e| format!("{}: {}", filename, e)));
+
+        Execution::create(&machine, script)
+      }
+    };
+
+    machine.expose_system_to(&execution);
+    self.expose_to(&execution, &machine);
+
+    let locals = execution.lock().meta().members
+                   .lookup_pair(&machine.locals_sym)
+                   .expect("Execution is missing locals!");
+
+    loaded.insert(module.to_string(), locals.clone());
+
+    drop(loaded);
+
+    reactor.stage(execution.clone(), execution);
+
+    Ok(locals)
+  }
+
+  /// Exposes the `package` object to the given Execution's locals.
+  ///
+  /// It contains `name`, `version` (if specified), and `import`.
+  pub fn expose_to(&self, execution: &ObjectRef, machine: &Machine) {
+    let manifest = &self.package.manifest;
+
+    let mut package = Meta::new();
+
+    package.members.push_pair(machine.symbol("name"),
+                              machine.symbol(manifest.name.as_slice()));
+
+    match manifest.version {
+      Some(ref version) =>
+        package.members.push_pair(machine.symbol("version"),
+                                  machine.symbol(version.as_slice())),
+      None => ()
+    }
+
+    package.members.push_pair(machine.symbol("import"),
+      Alien::create("import", import_routine, box ImportData {
+        loader:   self.clone(),
+        caller:   None,
+        complete: false
+      }));
+
+    let package = Thing::tagged(package, format!("(package {})", manifest.name));
+
+    let     locals_ref = execution.lock().meta().members
+                           .lookup_pair(&machine.locals_sym)
+                           .expect("Execution is missing locals!");
+    let mut locals_obj = locals_ref.lock();
+
+    locals_obj.meta_mut().members
+      .push_pair(machine.symbol("package"), package);
+  }
+}
+
+#[deriving(Clone)]
+struct ImportData {
+  loader:   Loader,
+  caller:   Option<ObjectRef>,
+  complete: bool
+}
+
+/// Call-pattern style: accepts a caller, then a module name Symbol, and
+/// responds with the module's locals.
+fn import_routine<'a>(
+                  mut alien: TypedRefGuard<'a, Alien>,
+                  reactor:   &mut Reactor,
+                  response:  ObjectRef) {
+
+  let call = {
+    let data = alien.data.downcast_mut::<ImportData>().unwrap();
+
+    if data.complete { return }
+
+    match data.caller.clone() {
+      None => {
+        data.caller = Some(response.clone());
+        None
+      },
+
+      Some(caller) => {
+        data.complete = true;
+        Some((data.loader.clone(), caller))
+      }
+    }
+  };
+
+  let (loader, caller) = match call {
+    Some(call) => call,
+
+    // We just got the caller; ask for the module name.
+    None => return reactor.stage(response, alien.unlock().clone())
+  };
+
+  drop(alien);
+
+  let module = match response.symbol_ref() {
+    Some(module) => module.clone(),
+    None         => {
+      machine_warn!(reactor.machine(), "package",
+                    "tried to import {}, which is not a Symbol", response);
+      return
+    }
+  };
+
+  match loader.import(reactor, module.as_slice()) {
+    Ok(locals)   => reactor.stage(caller, locals),
+    Err(message) =>
+      machine_warn!(reactor.machine(), "package", "import failed: {}", message)
+  }
+}
+
+/// Turns the contents of a file into a `String`, if they're valid UTF-8.
+fn utf8(name: &str, bytes: Vec<u8>) -> Result<String, String> {
+  String::from_utf8(bytes).map_err(|_| format!("{}: not valid UTF-8", name))
+}
+
+/// Reads the regular files out of a ustar archive as `(path, bytes)` pairs.
+fn read_tar(bytes: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String> {
+  let mut entries = Vec::new();
+  let mut offset  = 0u;
+
+  while offset + 512 <= bytes.len() {
+    let header = bytes.slice(offset, offset + 512);
+
+    // Two zero blocks mark the end, but one is enough for us.
+    if header.iter().all(|&b| b == 0) { break }
+
+    let name = {
+      let prefix = c_string(header.slice(345, 500));
+      let name   = c_string(header.slice(0, 100));
+
+      if prefix.is_empty() {
+        name
+      } else {
+        format!("{}/{}", prefix, name)
+      }
+    };
+
+    let size =
+      match from_str_radix::<uint>(c_string(header.slice(124, 136))
+                                     .as_slice().trim(), 8) {
+        Some(size) => size,
+        None       => return Err(format!("{}: bad size in tar header", name))
+      };
+
+    let data_start = offset + 512;
+    let data_end   = data_start + size;
+
+    if data_end > bytes.len() {
+      return Err(format!("{}: truncated tar entry", name))
+    }
+
+    // '0' and NUL are regular files; ignore directories, links, etc.
+    if header[156] == '0' as u8 || header[156] == 0 {
+      let name = if name.as_slice().starts_with("./") {
+        name.as_slice().slice_from(2).to_string()
+      } else {
+        name
+      };
+
+      entries.push((name, Vec::from_slice(bytes.slice(data_start, data_end))));
+    }
+
+    // Data is padded to a multiple of 512 bytes.
+    offset = data_start + (size + 511) / 512 * 512;
+  }
+
+  return Ok(entries);
+
+  fn c_string(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+
+    str::from_utf8(field.slice_to(end)).unwrap_or("").to_string()
+  }
+}
@@ -0,0 +1,119 @@
+use super::{Manifest, Package, MANIFEST_NAME};
+use super::{Source, Bytecode};
+
+use script::{Script, Discard};
+
+use std::io::MemWriter;
+
+fn file(name: &str, contents: &str) -> (String, Vec<u8>) {
+  (name.to_string(), Vec::from_slice(contents.as_bytes()))
+}
+
+#[test]
+fn manifest_parse() {
+  let manifest = Manifest::parse(concat!(
+    "# A comment\n",
+    "name    = hello\n",
+    "\n",
+    "version = 0.1.0\n")).ok().expect("manifest failed to parse");
+
+  assert_eq!("hello".to_string(), manifest.name);
+  assert_eq!(Some("0.1.0".to_string()), manifest.version);
+  assert_eq!("main.paws".to_string(), manifest.entry);
+}
+
+#[test]
+fn manifest_parse_requires_name() {
+  assert!(Manifest::parse("version = 1").is_err());
+}
+
+#[test]
+fn manifest_parse_rejects_garbage() {
+  assert!(Manifest::parse("name = a\nwhat is this").is_err());
+  assert!(Manifest::parse("name = a\ncolour = blue").is_err());
+}
+
+#[test]
+fn package_from_files() {
+  let package = Package::from_files(vec![
+    file(MANIFEST_NAME,    "name = p\nentry = bin/start.paws"),
+    file("bin/start.paws", "a b c"),
+    file("lib/util.paws",  "d e f")
+  ]).ok().expect("package failed to load");
+
+  assert_eq!("bin/start".to_string(), package.entry_module());
+
+  assert!(package.modules.find_equiv(&"bin/start") ==
+          Some(&Source("a b c".to_string())));
+  assert!(package.modules.find_equiv(&"lib/util") ==
+          Some(&Source("d e f".to_string())));
+}
+
+#[test]
+fn package_from_files_with_bytecode() {
+  let mut writer = MemWriter::new();
+
+  Script(vec![Discard]).serialize(&mut writer).ok().expect("serialize failed");
+
+  let bytecode = writer.unwrap();
+
+  let package = Package::from_files(vec![
+    file(MANIFEST_NAME, "name = p"),
+    ("main.paws".to_string(), bytecode.clone())
+  ]).ok().expect("package failed to load");
+
+  assert!(package.modules.find_equiv(&"main") == Some(&Bytecode(bytecode)));
+
+  // Anything else still has to be text.
+  assert!(Package::from_files(vec![
+    file(MANIFEST_NAME, "name = p"),
+    ("main.paws".to_string(), vec![0xff, 0xfe])
+  ]).is_err());
+}
+
+#[test]
+fn package_from_files_requires_entry() {
+  assert!(Package::from_files(vec![file(MANIFEST_NAME, "name = p")]).is_err());
+}
+
+#[test]
+fn package_from_tar() {
+  fn entry(name: &str, contents: &str) -> Vec<u8> {
+    let mut header = Vec::from_elem(512, 0u8);
+
+    for (i, b) in name.bytes().enumerate() {
+      *header.get_mut(i) = b;
+    }
+
+    let size = format!("{:011o}", contents.len());
+
+    for (i, b) in size.as_slice().bytes().enumerate() {
+      *header.get_mut(124 + i) = b;
+    }
+
+    *header.get_mut(156) = '0' as u8;
+
+    let mut data = Vec::from_slice(contents.as_bytes());
+
+    while data.len() % 512 != 0 {
+      data.push(0);
+    }
+
+    header.push_all(data.as_slice());
+    header
+  }
+
+  let mut archive = Vec::new();
+
+  archive.push_all(entry("p/package.manifest", "name = p").as_slice());
+  archive.push_all(entry("p/main.paws", "hello").as_slice());
+  archive.push_all(Vec::from_elem(1024, 0u8).as_slice());
+
+  let package = Package::from_tar(archive.as_slice())
+                  .ok().expect("package failed to load");
+
+  assert_eq!("p".to_string(), package.manifest.name);
+
+  assert!(package.modules.find_equiv(&"main") ==
+          Some(&Source("hello".to_string())));
+}
@@ -17,22 +17,91 @@
 
 #![feature(globs)]
 #![feature(phase)]
+#![feature(macro_rules)]
+#![feature(unsafe_destructor)]
 
 #![warn(missing_doc)]
 
 extern crate native;
+extern crate alloc;
+extern crate libc;
 extern crate term;
+extern crate time;
+extern crate serialize;
 
 #[phase(plugin, link)]
 extern crate log;
 
+/// Emits a warning through a Machine's `Warnings`, tracked by category and the
+/// call site of the macro. See `machine::warnings`.
+///
+/// # Example
+///
+///     machine_warn!(reactor.machine(), "alien", "{} is not a Symbol", object);
+macro_rules! machine_warn(
+  ($machine:expr, $category:expr, $($arg:tt)*) => ({
+    $machine.warnings.warn($category, file!(), line!(), format!($($arg)*));
+  })
+)
+
+/// Fails because a routine was given the wrong number of arguments, pointing at
+/// the source of the combination responsible, if known. See
+/// `machine::reactor::current_span()`.
+macro_rules! wrong_arguments(
+  () => (
+    fail!("wrong number of arguments{}", ::machine::reactor::at_current_span())
+  )
+)
+
+/// Warns like `machine_warn!`, and then responds to `caller` with an error
+/// object carrying the same message, so that it isn't left waiting. See
+/// `util::error`.
+macro_rules! respond_error(
+  ($reactor:expr, $caller:expr, $category:expr, $($arg:tt)*) => ({
+    let message = format!($($arg)*);
+
+    machine_warn!($reactor.machine(), $category, "{}", message);
+
+    ::util::error::respond($reactor, $caller, $category, message);
+  })
+)
+
+// Everything but `prelude` is internal, and kept out of the documentation.
+
+#[doc(hidden)]
 pub mod cpaws;
+
+#[doc(hidden)]
 pub mod object;
+
+#[doc(hidden)]
 pub mod nuketype;
+
+#[doc(hidden)]
 pub mod script;
+
+#[doc(hidden)]
 pub mod machine;
+
+#[doc(hidden)]
 pub mod system;
+
+#[doc(hidden)]
 pub mod specification;
+
+#[doc(hidden)]
 pub mod interact;
 
-mod util;
+#[doc(hidden)]
+pub mod package;
+
+#[doc(hidden)]
+pub mod format;
+
+#[doc(hidden)]
+pub mod bench;
+
+#[doc(hidden)]
+pub mod util;
+
+pub mod prelude;
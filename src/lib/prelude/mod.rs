@@ -0,0 +1,33 @@
+//! The intentionally public surface of Paws.rs, for embedding it in other
+//! programs.
+//!
+//! Everything here follows `API_VERSION`: removing or changing any of it
+//! incompatibly bumps the major version. Everything else in the crate is fair
+//! game for internal refactoring, so depend on it at your own risk.
+//!
+//! # Example
+//!
+//!     use paws::prelude::*;
+//!
+//!     let machine = Machine::new();
+//!     let reactor = SerialReactor::new(machine.clone());
+
+pub use machine::{Machine, Reactor, ReactorStats, WarningPolicy};
+pub use machine::reactor::{SerialReactor, ReactorPool, ParallelReactor};
+
+pub use object::{ObjectRef, Meta, Params, Tag};
+
+pub use nuketype::{Thing, Symbol, Execution, Alien, Locals, Number};
+pub use nuketype::alien;
+
+pub use script::{Script, Span, SpanTable};
+
+pub use cpaws::Node;
+pub use cpaws::{parse_nodes_with_spans, build_fused_script_with_spans};
+
+#[cfg(test)]
+mod tests;
+
+/// The version of the API exported by this module, as `(major, minor, patch)`,
+/// following [Semantic Versioning](http://semver.org).
+pub static API_VERSION: (uint, uint, uint) = (0, 1, 0);
@@ -0,0 +1,62 @@
+//! The API manifest. If any of these fail to compile, the prelude has changed
+//! incompatibly, and `API_VERSION` needs a major version bump.
+
+use super::*;
+
+#[test]
+fn api_version() {
+  // Update the manifest below along with this.
+  assert_eq!((0, 1, 0), API_VERSION);
+}
+
+#[test]
+fn api_manifest_machine() {
+  let _: fn () -> Machine                   = Machine::new;
+  let _: fn () -> WarningPolicy             = WarningPolicy::new;
+  let _: fn (Machine) -> SerialReactor      = SerialReactor::new;
+  let _: fn (Machine, uint) -> ReactorPool  = ReactorPool::spawn;
+
+  let _: fn (Machine, uint, &[&str]) -> ReactorPool =
+    ReactorPool::spawn_with_specialties;
+}
+
+#[test]
+fn api_manifest_objects() {
+  let _: fn () -> ObjectRef                   = Thing::empty;
+  let _: fn (Meta) -> ObjectRef               = Thing::create;
+  let _: fn (Meta, &'static str) -> ObjectRef = Thing::tagged;
+  let _: fn () -> Meta                        = Meta::new;
+  let _: fn (&Machine, Script) -> ObjectRef   = Execution::create;
+
+  let _: fn (&Machine, Script, SpanTable) -> ObjectRef =
+    Execution::create_with_spans;
+}
+
+#[test]
+fn api_manifest_aliens() {
+  let _: fn (&'static str,
+             alien::Routine,
+             Box<alien::Data+Send+Sync>) -> ObjectRef = Alien::create;
+
+  let _: fn (&'static str,
+             alien::CallPatternRoutine,
+             uint) -> ObjectRef = Alien::call_pattern;
+
+  let _: fn (&'static str,
+             alien::OneshotRoutine) -> ObjectRef = Alien::oneshot;
+
+  let _: fn (fn (&mut Reactor, Params)) -> ObjectRef =
+    Alien::from_native_receiver;
+
+  let _: fn (&mut Reactor, ObjectRef, ObjectRef, alien::Continuation) =
+    alien::then;
+}
+
+#[test]
+fn api_manifest_cpaws() {
+  let _: fn (&str, &str) -> Result<(Vec<Node>, Vec<Span>), String> =
+    parse_nodes_with_spans;
+
+  let _: fn (&Machine, &[Node], &[Span]) -> (Script, SpanTable) =
+    build_fused_script_with_spans;
+}
@@ -3,6 +3,18 @@
 
 use object::ObjectRef;
 
+use nuketype::Execution;
+
+use machine::Machine;
+
+use std::io::{IoResult, IoError, InvalidInput};
+use std::sync::Arc;
+use std::fmt::Show;
+use std::fmt;
+
+#[cfg(test)]
+mod tests;
+
 /// Represents an instruction to be carried out over the Execution's stack.
 #[deriving(Clone, PartialEq, Eq, Show)]
 pub enum Instruction {
@@ -20,9 +32,358 @@ pub enum Instruction {
   Combine,
 
   /// Drop the top item off the stack, if there was one.
-  Discard
+  Discard,
+
+  /// Push two objects onto the stack, first the key and then the value.
+  /// Equivalent to `Push(key), Push(value)`.
+  PushPair(ObjectRef, ObjectRef),
+
+  /// Pop the highest item off the stack as the subject and combine it with a
+  /// Symbol as the message, then unstage. Equivalent to `Push(symbol),
+  /// Combine`, which is what every bare symbol in cPaws compiles to.
+  LookupCombine(ObjectRef),
+
+  /// Combine the Execution's locals with a Symbol as the message, then
+  /// unstage. Equivalent to `PushLocals, Push(symbol), Combine`, which is what
+  /// every statement starting with a bare symbol compiles to.
+  ///
+  /// Reactors can often carry this out without staging anything; see
+  /// `machine::reactor::realize()`.
+  LookupLocal(ObjectRef)
 }
 
 /// A script is a sequence of instructions.
 #[deriving(Clone, PartialEq, Eq, Show)]
 pub struct Script(pub Vec<Instruction>);
+
+/// Identifies the bytecode format written by `Script::serialize()`.
+static BYTECODE_MAGIC:   &'static [u8] = b"PAWSBC";
+
+/// The version of the bytecode format. Bumped on any incompatible change.
+static BYTECODE_VERSION: u8 = 2;
+
+static OP_PUSH_LOCALS:    u8 = 0;
+static OP_PUSH_SELF:      u8 = 1;
+static OP_PUSH:           u8 = 2;
+static OP_COMBINE:        u8 = 3;
+static OP_DISCARD:        u8 = 4;
+static OP_PUSH_PAIR:      u8 = 5;
+static OP_LOOKUP_COMBINE: u8 = 6;
+static OP_LOOKUP_LOCAL:   u8 = 7;
+
+static OBJECT_SYMBOL:     u8 = 0;
+static OBJECT_EXECUTION:  u8 = 1;
+
+impl Script {
+  /// Writes the Script to `writer` in a compact binary format, which can be
+  /// loaded back with `Script::deserialize()`.
+  ///
+  /// Only Symbols and Executions can be serialized as the objects of `Push`
+  /// instructions, which covers everything cPaws compiles to. Executions are
+  /// written as their root Script, so they come back fresh (and without span
+  /// tables). Anything else is an `InvalidInput` error.
+  pub fn serialize(&self, writer: &mut Writer) -> IoResult<()> {
+    try!(writer.write(BYTECODE_MAGIC));
+    try!(writer.write_u8(BYTECODE_VERSION));
+
+    self.write_to(writer)
+  }
+
+  /// Reads a Script written by `Script::serialize()` from `reader`.
+  ///
+  /// Symbols are interned through `machine`, and Executions are recreated
+  /// within it.
+  pub fn deserialize(reader: &mut Reader, machine: &Machine)
+                     -> IoResult<Script> {
+
+    let magic = try!(reader.read_exact(BYTECODE_MAGIC.len()));
+
+    if magic.as_slice() != BYTECODE_MAGIC {
+      return Err(invalid("not Paws bytecode", None));
+    }
+
+    let version = try!(reader.read_u8());
+
+    if version != BYTECODE_VERSION {
+      return Err(invalid("unsupported bytecode version",
+                         Some(version.to_string())));
+    }
+
+    Script::read_from(reader, machine)
+  }
+
+  /// Checks whether `bytes` start like something written by
+  /// `Script::serialize()`, to tell bytecode apart from cPaws source.
+  pub fn is_bytecode(bytes: &[u8]) -> bool {
+    bytes.starts_with(BYTECODE_MAGIC)
+  }
+
+  fn write_to(&self, writer: &mut Writer) -> IoResult<()> {
+    let Script(ref instructions) = *self;
+
+    try!(writer.write_be_u32(instructions.len() as u32));
+
+    for instruction in instructions.iter() {
+      match *instruction {
+        PushLocals =>
+          try!(writer.write_u8(OP_PUSH_LOCALS)),
+
+        PushSelf =>
+          try!(writer.write_u8(OP_PUSH_SELF)),
+
+        Push(ref object) => {
+          try!(writer.write_u8(OP_PUSH));
+          try!(write_object(writer, object));
+        },
+
+        Combine =>
+          try!(writer.write_u8(OP_COMBINE)),
+
+        Discard =>
+          try!(writer.write_u8(OP_DISCARD)),
+
+        PushPair(ref key, ref value) => {
+          try!(writer.write_u8(OP_PUSH_PAIR));
+          try!(write_object(writer, key));
+          try!(write_object(writer, value));
+        },
+
+        LookupCombine(ref symbol) => {
+          try!(writer.write_u8(OP_LOOKUP_COMBINE));
+          try!(write_object(writer, symbol));
+        },
+
+        LookupLocal(ref symbol) => {
+          try!(writer.write_u8(OP_LOOKUP_LOCAL));
+          try!(write_object(writer, symbol));
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  fn read_from(reader: &mut Reader, machine: &Machine) -> IoResult<Script> {
+    let len = try!(reader.read_be_u32()) as uint;
+
+    // Don't trust the length too much when allocating.
+    let mut instructions = Vec::with_capacity(::std::cmp::min(len, 4096));
+
+    for _ in range(0, len) {
+      let opcode = try!(reader.read_u8());
+
+      instructions.push(match opcode {
+        OP_PUSH_LOCALS => PushLocals,
+        OP_PUSH_SELF   => PushSelf,
+        OP_PUSH        => Push(try!(read_object(reader, machine))),
+        OP_COMBINE     => Combine,
+        OP_DISCARD     => Discard,
+
+        OP_PUSH_PAIR => {
+          let key   = try!(read_object(reader, machine));
+          let value = try!(read_object(reader, machine));
+
+          PushPair(key, value)
+        },
+
+        OP_LOOKUP_COMBINE => {
+          let symbol = try!(read_object(reader, machine));
+
+          if symbol.symbol_ref().is_none() {
+            return Err(invalid("LookupCombine of a non-Symbol", None));
+          }
+
+          LookupCombine(symbol)
+        },
+
+        OP_LOOKUP_LOCAL => {
+          let symbol = try!(read_object(reader, machine));
+
+          if symbol.symbol_ref().is_none() {
+            return Err(invalid("LookupLocal of a non-Symbol", None));
+          }
+
+          LookupLocal(symbol)
+        },
+
+        _ => return Err(invalid("unknown opcode", Some(opcode.to_string())))
+      });
+    }
+
+    Ok(Script(instructions))
+  }
+}
+
+fn write_object(writer: &mut Writer, object: &ObjectRef) -> IoResult<()> {
+  match object.symbol_ref() {
+    Some(name) => {
+      try!(writer.write_u8(OBJECT_SYMBOL));
+      try!(writer.write_be_u32(name.len() as u32));
+
+      writer.write_str(name.as_slice())
+    },
+
+    None => {
+      let root = match object.lock().try_cast::<Execution>() {
+        Ok(execution) => execution.deref().root().clone(),
+
+        Err(_) =>
+          return Err(invalid("only Symbols and Executions can be serialized",
+                             Some(object.to_string())))
+      };
+
+      try!(writer.write_u8(OBJECT_EXECUTION));
+
+      root.write_to(writer)
+    }
+  }
+}
+
+fn read_object(reader: &mut Reader, machine: &Machine) -> IoResult<ObjectRef> {
+  let kind = try!(reader.read_u8());
+
+  match kind {
+    OBJECT_SYMBOL => {
+      let len   = try!(reader.read_be_u32()) as uint;
+      let bytes = try!(reader.read_exact(len));
+
+      match String::from_utf8(bytes) {
+        Ok(name) => Ok(machine.symbol(name.as_slice())),
+        Err(_)   => Err(invalid("Symbol is not valid UTF-8", None))
+      }
+    },
+
+    OBJECT_EXECUTION => {
+      let script = try!(Script::read_from(reader, machine));
+
+      Ok(Execution::create(machine, script))
+    },
+
+    _ => Err(invalid("unknown object kind", Some(kind.to_string())))
+  }
+}
+
+fn invalid(desc: &'static str, detail: Option<String>) -> IoError {
+  IoError {
+    kind:   InvalidInput,
+    desc:   desc,
+    detail: detail
+  }
+}
+
+/// A position within a source file that an instruction was compiled from.
+#[deriving(Clone, PartialEq, Eq)]
+pub struct Span {
+  /// The name of the file (or pseudo-file, like `<stdin>`) the source came
+  /// from.
+  pub filename: Arc<String>,
+
+  /// The line the source token started on, starting from 1.
+  pub line:     uint,
+
+  /// The column the source token started on, starting from 1.
+  pub column:   uint
+}
+
+impl Show for Span {
+  fn fmt(&self, out: &mut fmt::Formatter) -> fmt::Result {
+    write!(out, "{:s}:{}:{}", self.filename.as_slice(), self.line, self.column)
+  }
+}
+
+/// A side table for a `Script` mapping each instruction (by index) to the span
+/// it was compiled from, if known.
+///
+/// Kept separate from the `Script` so that hand-built Scripts don't need to
+/// care about it.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct SpanTable(pub Vec<Option<Span>>);
+
+impl SpanTable {
+  /// Gets the span associated with the instruction at `index`, if there is one.
+  pub fn get<'a>(&'a self, index: uint) -> Option<&'a Span> {
+    let SpanTable(ref spans) = *self;
+
+    if index < spans.len() {
+      spans[index].as_ref()
+    } else {
+      None
+    }
+  }
+}
+
+/// Fuses common instruction sequences into superinstructions:
+///
+/// * `Push(symbol), Combine` becomes `LookupCombine(symbol)`
+/// * `PushLocals, Push(symbol), Combine` becomes `LookupLocal(symbol)`
+/// * `Push(key), Push(value)` becomes `PushPair(key, value)`
+///
+/// The span table is kept in step with the instructions; each fused
+/// instruction takes the span of the first instruction it replaced, except
+/// that `LookupLocal` takes the span of the symbol rather than the `PushLocals`
+/// (which usually has none).
+pub fn fuse(script: Script, spans: SpanTable) -> (Script, SpanTable) {
+  let Script(instructions) = script;
+  let SpanTable(mut spans) = spans;
+
+  // Make sure there's exactly one span per instruction.
+  spans.truncate(instructions.len());
+
+  while spans.len() < instructions.len() {
+    spans.push(None);
+  }
+
+  let mut fused_instructions = Vec::with_capacity(instructions.len());
+  let mut fused_spans        = Vec::with_capacity(instructions.len());
+
+  let mut iter = instructions.move_iter().zip(spans.move_iter()).peekable();
+
+  loop {
+    let (instruction, span) = match iter.next() {
+      Some(pair) => pair,
+      None       => break
+    };
+
+    let fused = match (&instruction, iter.peek()) {
+      (&Push(ref symbol), Some(&(Combine, _)))
+        if symbol.symbol_ref().is_some() =>
+          Some(LookupCombine(symbol.clone())),
+
+      (&Push(ref key), Some(&(Push(ref value), _))) =>
+        Some(PushPair(key.clone(), value.clone())),
+
+      _ => None
+    };
+
+    match fused {
+      Some(LookupCombine(symbol)) => {
+        // Skip the Combine.
+        iter.next();
+
+        // Fold in the PushLocals before it, if there was one.
+        if fused_instructions.last() == Some(&PushLocals) {
+          fused_instructions.pop();
+          fused_spans.pop();
+
+          fused_instructions.push(LookupLocal(symbol));
+        } else {
+          fused_instructions.push(LookupCombine(symbol));
+        }
+      },
+
+      Some(fused) => {
+        // Skip the instruction that got fused in.
+        iter.next();
+
+        fused_instructions.push(fused);
+      },
+
+      None =>
+        fused_instructions.push(instruction)
+    }
+
+    fused_spans.push(span);
+  }
+
+  (Script(fused_instructions), SpanTable(fused_spans))
+}
@@ -0,0 +1,123 @@
+use super::*;
+
+use machine::Machine;
+
+use nuketype::{Thing, Execution};
+
+use cpaws;
+
+use std::io::{MemReader, MemWriter, InvalidInput};
+
+#[test]
+fn fuse_lookups_and_push_pair() {
+  let machine = Machine::new();
+
+  let hello = machine.symbol("hello");
+  let thing = Thing::empty();
+  let other = Thing::empty();
+
+  let (Script(instructions), SpanTable(spans)) = fuse(
+    Script(vec![Discard,
+                PushLocals,
+                Push(hello.clone()),
+                Combine,
+                Push(hello.clone()),
+                Combine,
+                Push(thing.clone()),
+                Push(other.clone()),
+                Combine,
+                Push(thing.clone()),
+                Combine]),
+    SpanTable(vec![]));
+
+  assert_eq!(vec![Discard,
+                  LookupLocal(hello.clone()),
+                  LookupCombine(hello.clone()),
+                  PushPair(thing.clone(), other.clone()),
+                  Combine,
+                  // Not a symbol, so it's not a lookup.
+                  Push(thing.clone()),
+                  Combine],
+             instructions);
+
+  assert_eq!(instructions.len(), spans.len());
+}
+
+fn serialize_to_vec(script: &Script) -> Vec<u8> {
+  let mut writer = MemWriter::new();
+
+  script.serialize(&mut writer).ok().expect("serialize failed");
+
+  writer.unwrap()
+}
+
+#[test]
+fn serialize_round_trip() {
+  let machine = Machine::new();
+
+  let nodes = cpaws::parse_nodes("a [b c] {d {e}}; f", "<test_case>")
+                .ok().expect("parse failed");
+
+  let script = fuse(cpaws::build_script(&machine, nodes.as_slice()),
+                    SpanTable(vec![])).val0();
+
+  let bytes = serialize_to_vec(&script);
+
+  let Script(loaded) =
+    Script::deserialize(&mut MemReader::new(bytes.clone()), &machine)
+      .ok().expect("deserialize failed");
+
+  let Script(instructions) = script;
+
+  assert_eq!(instructions.len(), loaded.len());
+
+  // Symbols are interned, so they should come back as the same symbols.
+  for (original, copy) in instructions.iter().zip(loaded.iter()) {
+    match (original, copy) {
+      (&LookupCombine(ref a), &LookupCombine(ref b)) =>
+        assert!(a.eq_as_symbol(b)),
+
+      (&LookupLocal(ref a), &LookupLocal(ref b)) =>
+        assert!(a.eq_as_symbol(b)),
+
+      (&Push(_), &Push(ref b)) =>
+        assert!(b.lock().try_cast::<Execution>().is_ok()),
+
+      (a, b) =>
+        assert_eq!(a, b)
+    }
+  }
+
+  // And writing it back out again should produce the same bytes.
+  assert_eq!(bytes, serialize_to_vec(&Script(loaded)));
+}
+
+#[test]
+fn is_bytecode_checks_magic() {
+  assert!(Script::is_bytecode(serialize_to_vec(&Script(vec![])).as_slice()));
+
+  assert!(!Script::is_bytecode(b"PAWS"));
+  assert!(!Script::is_bytecode(b"say hello"));
+}
+
+#[test]
+fn serialize_rejects_other_objects() {
+  let script = Script(vec![Push(Thing::empty())]);
+
+  let mut writer = MemWriter::new();
+
+  match script.serialize(&mut writer) {
+    Err(e) => assert_eq!(InvalidInput, e.kind),
+    Ok(_)  => fail!("serialized a Thing")
+  }
+}
+
+#[test]
+fn deserialize_rejects_garbage() {
+  let machine = Machine::new();
+
+  match Script::deserialize(&mut MemReader::new(b"PAWSXX".to_vec()), &machine) {
+    Err(e) => assert_eq!(InvalidInput, e.kind),
+    Ok(_)  => fail!("deserialized garbage")
+  }
+}
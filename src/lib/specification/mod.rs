@@ -1,32 +1,84 @@
 //! Paws Rulebook-compliant specification interface.
 //!
 //! The output conforms to the
-//! [Test Anything Protocol](http://testanything.org/).
+//! [Test Anything Protocol](http://testanything.org/). Results are kept in the
+//! Suite as rules pass or fail, possibly on several reactors at once, and
+//! reported in the order the rules were registered once the Suite is done
+//! (see `report()`). Failures are followed by `# diag` lines saying what
+//! failed them.
+//!
+//! Rules that might never pass or fail can be given a time limit with
+//! `with_timeout()`. See there for how that works with `eventually`.
 
 use object::{ObjectRef, TypedRefGuard, Meta};
 
 use nuketype::{Thing, Alien};
 
 use machine::{Machine, Reactor};
+use machine::reactor::{Remote, at_current_span};
 
 use std::any::AnyMutRefExt;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::time::duration::Duration;
+
+#[cfg(test)]
+mod tests;
 
 /// Represents a test suite, containing rules that are added via the
 /// specification interface (see `expose_to()`).
 #[deriving(Clone)]
 pub struct Suite {
-  rules: Arc<Mutex<Vec<Rule>>>
+  state: Arc<Mutex<State>>
+}
+
+struct State {
+  rules:   Vec<Rule>,
+
+  /// Whether `run()` has been called. Rules registered after that are started
+  /// straight away.
+  running:   bool,
+
+  /// How long each rule gets, if limited. See `Suite::with_timeout()`.
+  timeout:   Option<Duration>,
+
+  /// Keeps the reactor from stalling until the timeouts of rules without a
+  /// result yet have gone off, by rule. Dropped once the rule has a result.
+  timeouts:  HashMap<uint, Remote>,
+
+  /// Whether any rule has failed by timing out.
+  timed_out: bool,
+
+  /// Whether the report has been printed.
+  finished:  bool
 }
 
 impl Suite {
   /// Construct a new Suite.
   pub fn new() -> Suite {
     Suite {
-      rules: Arc::new(Mutex::new(Vec::new()))
+      state: Arc::new(Mutex::new(State {
+        rules:     Vec::new(),
+        running:   false,
+        timeout:   None,
+        timeouts:  HashMap::new(),
+        timed_out: false,
+        finished:  false
+      }))
     }
   }
 
+  /// Limits how long each rule has to pass or fail once it's started, after
+  /// which it fails with a diagnostic and the Suite goes on without it.
+  ///
+  /// The reactor won't stall while a rule is waiting on its timeout, so a
+  /// rule with `eventually` has its `eventually` staged when its timeout goes
+  /// off instead, and then gets as long again.
+  pub fn with_timeout(self, timeout: Duration) -> Suite {
+    self.state.lock().timeout = Some(timeout);
+    self
+  }
+
   /// Expose the specification interface to the given Execution's locals.
   pub fn expose_to(&self, execution: &ObjectRef, machine: &Machine) {
     let mut specification = Meta::new();
@@ -46,35 +98,131 @@ impl Suite {
   }
 
   /// Start running the Suite with all of the known rules up to this point.
+  /// Rules registered from now on are started as soon as they're registered.
   ///
-  /// Stops the Machine once the Suite has completed.
+  /// Prints the `report()` and stops the Machine once the Suite has completed.
   pub fn run(&self, reactor: &mut Reactor) {
-    let rules = self.rules.lock();
+    {
+      let mut state = self.state.lock();
+
+      state.running = true;
 
-    println!("1..{}", rules.deref().len());
+      for (index, rule) in state.rules.iter().enumerate() {
+        rule.start(self, reactor, index);
+      }
 
-    for (index, rule) in rules.deref().iter().enumerate() {
-      rule.start(self, reactor, index);
+      for index in range(0, state.rules.len()) {
+        self.start_timeout(&mut *state, reactor, index);
+      }
     }
 
     let suite = self.clone();
     reactor.on_stall(proc(reactor) {
-      for rule in suite.rules.lock().iter() {
+      for rule in suite.state.lock().rules.mut_iter() {
         if rule.result.is_none() {
-          match rule.eventually.clone() {
-            Some(eventually) =>
-              reactor.stage(eventually.clone(), eventually),
-            None => ()
-          }
+          rule.stage_eventually(reactor);
         }
       }
 
       reactor.on_stall(proc(reactor) {
-        reactor.stop();
+        suite.finish(reactor);
       });
     });
   }
 
+  /// The results of every rule registered so far as TAP, in the order they
+  /// were registered. Rules without a result yet are reported as failures.
+  ///
+  /// The plan covers every rule, including those registered after `run()`.
+  pub fn report(&self) -> String {
+    let state = self.state.lock();
+
+    let mut report = format!("1..{}\n", state.rules.len());
+
+    for (index, rule) in state.rules.iter().enumerate() {
+      let status = match rule.result {
+        Some(Pass) => "ok",
+        _          => "not ok"
+      };
+
+      report.push_str(format!("{} {} - {:s}\n",
+                              status, index + 1, rule.name).as_slice());
+
+      if rule.result.is_none() {
+        report.push_str("# diag: never passed or failed\n");
+      }
+
+      for diagnostic in rule.diagnostics.iter() {
+        report.push_str(format!("# diag: {}\n", diagnostic).as_slice());
+      }
+    }
+
+    report
+  }
+
+  /// Prints the `report()` and stops the reactor, unless that's already been
+  /// done.
+  fn finish(&self, reactor: &mut Reactor) {
+    {
+      let mut state = self.state.lock();
+
+      if state.finished { return }
+
+      state.finished = true;
+      state.timeouts.clear();
+    }
+
+    print!("{}", self.report());
+
+    reactor.stop();
+  }
+
+  /// Finishes if a rule has timed out and every rule has a result. Timed out
+  /// rules may still be keeping the reactor busy, so it may never stall.
+  fn finish_if_complete(&self, reactor: &mut Reactor) {
+    let complete = {
+      let state = self.state.lock();
+
+      state.timed_out && state.rules.iter().all(|rule| rule.result.is_some())
+    };
+
+    if complete {
+      self.finish(reactor);
+    }
+  }
+
+  /// Has the timer go off for the rule at `index` after the timeout, if
+  /// there is one.
+  fn start_timeout(&self, state: &mut State, reactor: &mut Reactor,
+                   index: uint) {
+
+    let timeout = match state.timeout {
+      Some(timeout) => timeout,
+      None          => return
+    };
+
+    state.timeouts.insert(index, reactor.remote());
+
+    let suite = self.clone();
+
+    reactor.machine().timer.after(timeout, proc() {
+      let remote = match suite.state.lock().timeouts.pop(&index) {
+        Some(remote) => remote,
+
+        // The rule already has a result.
+        None => return
+      };
+
+      let alien = Alien::create("timeout", timeout_routine,
+                                box TimeoutAlienData {
+                                  suite: suite.clone(),
+                                  rule:  index
+                                });
+
+      remote.stage(alien.clone(), alien);
+    });
+  }
+
   fn rule_alien(&self) -> ObjectRef {
     let data = box RuleAlienData {
       suite:          self.clone(),
@@ -94,94 +242,116 @@ impl Suite {
 
 #[deriving(Clone, PartialEq, Eq, Show)]
 struct Rule {
-  name:       String,
-  body:       ObjectRef,
-  eventually: Option<ObjectRef>,
-  result:     Option<RuleResult>
+  name:              String,
+  body:              ObjectRef,
+  eventually:        Option<ObjectRef>,
+  staged_eventually: bool,
+  result:            Option<RuleResult>,
+  diagnostics:       Vec<String>
 }
 
 impl Rule {
   fn start(&self, suite: &Suite, reactor: &mut Reactor, index: uint) {
-    let pass =
-      Alien::create("pass",
-                    set_rule_result_routine,
-                    box SetRuleResultAlienData {
-                      suite: suite.clone(),
-                      rule:  index,
-                      to:    Pass
-                    });
-
-    let fail =
-      Alien::create("fail",
-                    set_rule_result_routine,
-                    box SetRuleResultAlienData {
-                      suite: suite.clone(),
-                      rule:  index,
-                      to:    Fail
-                    });
-
-    // Add pass and fail to locals of `body`
-    {
-      let machine = reactor.machine();
-
-      let body_locals =
-        self.body.lock().meta_mut().members
-          .lookup_pair(&machine.locals_sym)
-          .expect("Execution is missing locals!");
-
-      let mut body_locals = body_locals.lock();
-
-      body_locals.meta_mut().members
-        .push_pair(machine.symbol("pass"), pass.clone());
-      body_locals.meta_mut().members
-        .push_pair(machine.symbol("fail"), fail.clone());
-    }
+    expose_results_to(&self.body, suite, reactor.machine(), index);
 
     // Stage `body`
     reactor.stage(self.body.clone(), self.body.clone());
 
     // Handle `eventually`
     match self.eventually {
-      Some(ref eventually) => {
-        // Add pass and fail to locals of `eventually`
-        {
-          let machine = reactor.machine();
-
-          let eventually_locals =
-            eventually.lock().meta_mut().members
-              .lookup_pair(&machine.locals_sym)
-              .expect("Execution is missing locals!");
-
-          let mut eventually_locals = eventually_locals.lock();
-
-          eventually_locals.meta_mut().members
-            .push_pair(machine.symbol("pass"), pass.clone());
-          eventually_locals.meta_mut().members
-            .push_pair(machine.symbol("fail"), fail.clone());
-        }
-      },
+      Some(ref eventually) =>
+        expose_results_to(eventually, suite, reactor.machine(), index),
       _ => ()
     }
   }
 
-  fn set_result(&mut self, index: uint, result: RuleResult) {
-    self.result = Some(result);
+  /// Stages `eventually`, if there is one and it hasn't been staged yet.
+  /// Returns whether it was.
+  fn stage_eventually(&mut self, reactor: &mut Reactor) -> bool {
+    if self.staged_eventually { return false }
+
+    match self.eventually.clone() {
+      Some(eventually) => {
+        self.staged_eventually = true;
+
+        reactor.stage(eventually.clone(), eventually);
+        true
+      },
+      None => false
+    }
+  }
+
+  /// Records the result, unless there already is one, in which case it's only
+  /// noted in the diagnostics. `diagnostic` says what set it, and is only
+  /// kept for failures.
+  fn set_result(&mut self, result: RuleResult, diagnostic: String) {
+    match self.result {
+      None => {
+        self.result = Some(result);
 
-    match result {
-      Pass =>
-        println!("ok {} - {:s}", index + 1, self.name),
-      Fail =>
-        println!("not ok {} - {:s}", index + 1, self.name)
+        if result == Fail {
+          self.diagnostics.push(diagnostic);
+        }
+      },
+
+      Some(earlier) if earlier != result =>
+        self.diagnostics.push(format!("{} after {}, ignored: {}",
+                                      result.name(), earlier.name(),
+                                      diagnostic)),
+
+      Some(_) => ()
     }
   }
 }
 
+/// Adds `pass` and `fail` Aliens for the rule at `index` to the locals of
+/// `execution`.
+fn expose_results_to(execution: &ObjectRef, suite: &Suite, machine: &Machine,
+                     index: uint) {
+  let pass =
+    Alien::create("pass",
+                  set_rule_result_routine,
+                  box SetRuleResultAlienData {
+                    suite: suite.clone(),
+                    rule:  index,
+                    to:    Pass
+                  });
+
+  let fail =
+    Alien::create("fail",
+                  set_rule_result_routine,
+                  box SetRuleResultAlienData {
+                    suite: suite.clone(),
+                    rule:  index,
+                    to:    Fail
+                  });
+
+  let locals =
+    execution.lock().meta_mut().members
+      .lookup_pair(&machine.locals_sym)
+      .expect("Execution is missing locals!");
+
+  let mut locals = locals.lock();
+
+  locals.meta_mut().members.push_pair(machine.symbol("pass"), pass);
+  locals.meta_mut().members.push_pair(machine.symbol("fail"), fail);
+}
+
 #[deriving(Clone, PartialEq, Eq, Show)]
 enum RuleResult {
   Pass,
   Fail
 }
 
+impl RuleResult {
+  fn name(&self) -> &'static str {
+    match *self {
+      Pass => "pass",
+      Fail => "fail"
+    }
+  }
+}
+
 #[deriving(Clone)]
 struct RuleAlienData {
   suite:          Suite,
@@ -200,6 +370,10 @@ fn rule_routine<'a>(
                 reactor:   &mut Reactor,
                 response:  ObjectRef) {
 
+  // Not borrowed from the reactor, which newly registered rules are started
+  // on.
+  let locals_sym = reactor.machine().locals_sym.clone();
+
   let caller;
   {
     let data = alien.data.downcast_mut::<RuleAlienData>().unwrap();
@@ -208,7 +382,7 @@ fn rule_routine<'a>(
       let caller_locals_members = {
         let caller_locals =
           caller.lock().meta().members
-            .lookup_pair(&reactor.machine().locals_sym)
+            .lookup_pair(&locals_sym)
             .expect("Execution is missing locals!");
 
         caller_locals.lock().meta().members.clone()
@@ -216,7 +390,7 @@ fn rule_routine<'a>(
 
       let dest_locals =
         dest.lock().meta().members
-          .lookup_pair(&reactor.machine().locals_sym)
+          .lookup_pair(&locals_sym)
           .expect("Execution is missing locals!"); // FIXME: omfg, DRY this
 
       let mut dest_locals = dest_locals.lock();
@@ -240,21 +414,32 @@ fn rule_routine<'a>(
       data.name = Some(response);
 
     } else if data.rule.is_none() {
-      let mut rules = data.suite.rules.lock();
+      let mut state = data.suite.state.lock();
 
-      data.rule = Some(rules.len());
+      let index = state.rules.len();
 
+      data.rule = Some(index);
+
+      let name = (**data.name.get_ref().symbol_ref().unwrap()).clone();
       let body = response;
 
       add_caller_locals_to(data.caller.get_ref(), &body);
 
-      rules.push(Rule {
-        name:       (**data.name.get_ref().symbol_ref().unwrap()).clone(),
-        body:       body,
-        eventually: None,
-        result:     None
+      state.rules.push(Rule {
+        name:              name,
+        body:              body,
+        eventually:        None,
+        staged_eventually: false,
+        result:            None,
+        diagnostics:       Vec::new()
       });
 
+      if state.running {
+        state.rules.get(index).start(&data.suite, reactor, index);
+
+        data.suite.start_timeout(&mut *state, reactor, index);
+      }
+
     } else if !data.got_eventually {
       match response.symbol_ref() {
         Some(sym) if sym.as_slice() == "eventually" => {
@@ -267,13 +452,19 @@ fn rule_routine<'a>(
         }
       }
     } else {
-      let mut rules = data.suite.rules.lock();
+      let mut state = data.suite.state.lock();
 
+      let index      = data.rule.unwrap();
       let eventually = response;
 
       add_caller_locals_to(data.caller.get_ref(), &eventually);
 
-      rules.get_mut(data.rule.unwrap()).eventually = Some(eventually);
+      if state.running {
+        // The rule has already been started without it.
+        expose_results_to(&eventually, &data.suite, reactor.machine(), index);
+      }
+
+      state.rules.get_mut(index).eventually = Some(eventually);
     }
 
     caller = data.caller.get_ref().clone();
@@ -291,12 +482,69 @@ struct SetRuleResultAlienData {
 
 fn set_rule_result_routine<'a>(
                             mut alien: TypedRefGuard<'a, Alien>,
-                            _reactor:  &mut Reactor,
-                            _response: ObjectRef) {
+                            reactor:   &mut Reactor,
+                            response:  ObjectRef) {
+
+  let suite = {
+    let data = alien.data.downcast_mut::<SetRuleResultAlienData>().unwrap();
+
+    let diagnostic = format!("{} called with {}{}",
+                             data.to.name(), response, at_current_span());
+
+    let mut state = data.suite.state.lock();
+
+    state.rules.get_mut(data.rule).set_result(data.to, diagnostic);
 
-  let data = alien.data.downcast_mut::<SetRuleResultAlienData>().unwrap();
+    // No need to wait for the timeout anymore.
+    state.timeouts.pop(&data.rule);
 
-  let mut rules = data.suite.rules.lock();
+    data.suite.clone()
+  };
+
+  drop(alien);
+
+  suite.finish_if_complete(reactor);
+}
+
+#[deriving(Clone)]
+struct TimeoutAlienData {
+  suite: Suite,
+  rule:  uint
+}
+
+/// Staged when a rule's timeout goes off. Stages its `eventually` and starts
+/// the timeout over if it has one that hasn't been staged yet, and fails it
+/// otherwise.
+fn timeout_routine<'a>(
+                    alien:     TypedRefGuard<'a, Alien>,
+                    reactor:   &mut Reactor,
+                    _response: ObjectRef) {
+
+  let (suite, index) = match alien.data.downcast_ref::<TimeoutAlienData>() {
+    Some(data) => (data.suite.clone(), data.rule),
+    None       => fail!("timeout called on a non-timeout Alien!")
+  };
+
+  drop(alien);
+
+  {
+    let mut state = suite.state.lock();
+
+    if state.rules.get(index).result.is_some() { return }
+
+    if state.rules.get_mut(index).stage_eventually(reactor) {
+      suite.start_timeout(&mut *state, reactor, index);
+      return
+    }
+
+    let millis = state.timeout.map(|timeout| timeout.num_milliseconds())
+                   .unwrap_or(0);
+
+    state.rules.get_mut(index)
+      .set_result(Fail, format!("timed out after {} ms", millis));
+
+    state.timed_out = true;
+  }
 
-  rules.get_mut(data.rule).set_result(data.rule, data.to.clone());
+  suite.finish_if_complete(reactor);
 }
@@ -0,0 +1,143 @@
+use super::Suite;
+
+use cpaws;
+
+use nuketype::Execution;
+
+use machine::{Machine, Reactor};
+use machine::reactor::SerialReactor;
+
+use util;
+
+use std::time::duration::Duration;
+
+/// Runs `source` in specification mode, as `paws_rs --spec` does, and returns
+/// the Suite once it's done.
+fn run_spec(source: &'static str) -> Suite {
+  run_suite(Suite::new(), source)
+}
+
+/// Like `run_spec()`, with a particular Suite.
+fn run_suite(suite: Suite, source: &'static str) -> Suite {
+  let ran = suite.clone();
+
+  util::timeout(1000, proc() {
+    let machine = Machine::new();
+
+    let (nodes, spans) =
+      cpaws::parse_nodes_with_spans(source, "<spec>")
+        .ok().expect("parse failed");
+
+    let (script, table) =
+      cpaws::build_fused_script_with_spans(&machine, nodes.as_slice(),
+                                           spans.as_slice());
+
+    let execution = Execution::create_with_spans(&machine, script, table);
+
+    machine.expose_system_to(&execution);
+    ran.expose_to(&execution, &machine);
+
+    let mut reactor = SerialReactor::new(machine);
+
+    reactor.stage(execution.clone(), execution);
+    reactor.on_stall(proc (reactor) ran.run(reactor));
+
+    // Ends once the Suite stops the reactor.
+    reactor.run();
+  });
+
+  suite
+}
+
+#[test]
+fn report_in_registration_order() {
+  let suite = run_spec(r#"
+    specification rule[] "first" { pass[] };
+    specification rule[] "second" { fail[] }
+  "#);
+
+  assert!(suite.report().as_slice().starts_with(
+    "1..2\nok 1 - first\nnot ok 2 - second\n# diag: fail called with "));
+}
+
+#[test]
+fn report_rules_without_results_as_failures() {
+  let suite = run_spec(r#"specification rule[] "hangs" { }"#);
+
+  assert_eq!(suite.report().as_slice(),
+             "1..1\nnot ok 1 - hangs\n# diag: never passed or failed\n");
+}
+
+#[test]
+fn first_result_wins() {
+  let suite = run_spec(r#"specification rule[] "both" { pass[]; fail[] }"#);
+
+  let report = suite.report();
+
+  assert!(report.as_slice().starts_with(
+    "1..1\nok 1 - both\n# diag: fail after pass, ignored: fail called with "));
+}
+
+#[test]
+fn rules_registered_while_running_are_run_and_planned() {
+  let suite = run_spec(r#"
+    specification rule[] "outer" {
+      pass[];
+      specification rule[] "inner" { pass[] }
+    }
+  "#);
+
+  assert_eq!(suite.report().as_slice(),
+             "1..2\nok 1 - outer\nok 2 - inner\n");
+}
+
+#[test]
+fn eventually_of_rules_registered_while_running() {
+  let suite = run_spec(r#"
+    specification rule[] "outer" {
+      pass[];
+      specification rule[] "later" { } eventually { pass[] }
+    }
+  "#);
+
+  assert_eq!(suite.report().as_slice(),
+             "1..2\nok 1 - outer\nok 2 - later\n");
+}
+
+#[test]
+fn rules_time_out() {
+  let suite = Suite::new().with_timeout(Duration::milliseconds(50));
+
+  // The timer keeps the reactor from ever stalling.
+  let suite = run_suite(suite, r#"
+    specification rule[] "hangs" { implementation timer after[] 60000 { } };
+    specification rule[] "passes" { pass[] }
+  "#);
+
+  assert_eq!(suite.report().as_slice(),
+             "1..2\nnot ok 1 - hangs\n# diag: timed out after 50 ms\n\
+              ok 2 - passes\n");
+}
+
+#[test]
+fn results_before_the_timeout_dont_wait_for_it() {
+  let suite = Suite::new().with_timeout(Duration::minutes(1));
+
+  let suite = run_suite(suite, r#"specification rule[] "quick" { pass[] }"#);
+
+  assert_eq!(suite.report().as_slice(), "1..1\nok 1 - quick\n");
+}
+
+#[test]
+fn timeouts_stage_eventually() {
+  let suite = Suite::new().with_timeout(Duration::milliseconds(20));
+
+  let suite = run_suite(suite, r#"
+    specification rule[] "later" { } eventually { pass[] };
+    specification rule[] "never" { } eventually { }
+  "#);
+
+  assert_eq!(suite.report().as_slice(),
+             "1..2\nok 1 - later\n\
+              not ok 2 - never\n# diag: timed out after 20 ms\n");
+}
@@ -0,0 +1,28 @@
+//! The arguments the program was started with. See `Machine::arguments`.
+
+use object::{ObjectRef, Meta};
+
+use nuketype::Thing;
+
+use machine::Machine;
+
+#[cfg(test)]
+mod tests;
+
+/// Generates an `implementation arguments` object: a list of the Machine's
+/// `arguments` as Symbols, starting at member 1, in order.
+///
+/// # Example
+///
+///     implementation arguments
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut arguments = Meta::new();
+
+  arguments.members.expand_to(1);
+
+  for argument in machine.arguments.iter() {
+    arguments.members.push(machine.symbol(argument.as_slice()));
+  }
+
+  Thing::frozen(arguments, "(impl. arguments)")
+}
@@ -0,0 +1,19 @@
+use super::make;
+
+use machine::Machine;
+
+#[test]
+fn lists_the_machines_arguments() {
+  let mut machine = Machine::new();
+
+  machine.arguments = vec!["one".to_string(), "two".to_string()];
+
+  let arguments     = make(&machine);
+  let arguments_obj = arguments.lock();
+  let members       = &arguments_obj.meta().members;
+
+  assert!(members.get(0).is_none());
+  assert!(members.get(1).unwrap().to().eq_as_symbol(&machine.symbol("one")));
+  assert!(members.get(2).unwrap().to().eq_as_symbol(&machine.symbol("two")));
+  assert!(members.get(3).is_none());
+}
@@ -0,0 +1,64 @@
+//! The current reactor's cache.
+
+#![allow(unused_variable)]
+
+use object::{ObjectRef, Meta};
+
+use nuketype::Thing;
+
+use machine::{Machine, Reactor};
+
+use system::implementation::stats::{cache_object, push_counters};
+
+use util::namespace::NamespaceBuilder;
+
+/// Generates an `implementation cache` namespace object.
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut cache = Meta::new();
+
+  {
+    let mut add = NamespaceBuilder::new(machine, &mut cache);
+
+    add.call_pattern( "stats",                   stats, 0                     );
+    add.call_pattern( "sizes",                   sizes, 0                     );
+  }
+
+  Thing::frozen(cache, "(impl. cache)")
+}
+
+/// Responds with the current reactor's cache hit and miss counts. The same as
+/// `implementation stats cache[]`.
+///
+/// # Example
+///
+///     implementation cache stats[]
+pub fn stats(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  let stats = reactor.cache().stats().clone();
+
+  reactor.stage(caller, cache_object(reactor.machine(), &stats))
+}
+
+/// Responds with the sizes the current reactor's cache was created with, as an
+/// object with `sym-lookup-size` and `receiver-size` pairs. The values are
+/// Symbols of decimal numbers, and zero for a cache that's turned off.
+///
+/// # Example
+///
+///     implementation cache sizes[]
+pub fn sizes(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  let config = reactor.cache().config().clone();
+
+  let mut meta = Meta::new();
+
+  let size_of = |enabled: bool, size: uint|
+    if enabled { size as u64 } else { 0 };
+
+  push_counters(reactor.machine(), &mut meta, [
+    ("sym-lookup-size", size_of(config.sym_lookup_enabled,
+                                config.sym_lookup_size)),
+    ("receiver-size",   size_of(config.receiver_enabled,
+                                config.receiver_size))
+  ]);
+
+  reactor.stage(caller, Thing::tagged(meta, "(cache sizes)"))
+}
@@ -7,14 +7,42 @@ use object::{ObjectRef, Meta};
 use nuketype::Thing;
 
 use machine::{Machine, Reactor};
+use machine::reactor::{Remote, current_span, set_current_span};
+
+use script::Span;
 
 use util::namespace::NamespaceBuilder;
+use util::error;
+use util::serialize;
+use util::pretty::PrettyPrinter;
 
-use std::io::stdio;
+use std::io::{stdio, IoError, EndOfFile};
+use std::mem;
+use std::str;
+use std::sync::Mutex;
+use std::sync::one::{Once, ONCE_INIT};
+use std::task::TaskBuilder;
+use std::collections::{RingBuf, Deque};
 
 use term;
 use term::Terminal;
 
+#[cfg(test)]
+mod tests;
+
+/// A `read()` waiting for its turn: who to respond to, and how.
+type ReadRequest = (Machine, Remote, ObjectRef, Option<Span>);
+
+/// The `read()`s waiting for their turn, oldest first, and whether a task is
+/// reading for them.
+struct ReadQueue {
+  requests: RingBuf<ReadRequest>,
+  reading:  bool
+}
+
+static mut QUEUE_INIT: Once = ONCE_INIT;
+static mut QUEUE:      *const Mutex<ReadQueue> = 0 as *const Mutex<ReadQueue>;
+
 /// Generates an `implementation console` namespace object.
 pub fn make(machine: &Machine) -> ObjectRef {
   let mut console = Meta::new();
@@ -25,10 +53,12 @@ pub fn make(machine: &Machine) -> ObjectRef {
     add.oneshot(      "print",                   print                        );
     add.oneshot(      "show",                    show                         );
     add.oneshot(      "inspect",                 inspect                      );
+    add.oneshot(      "dump-json",               dump_json                    );
     add.call_pattern( "trace",                   trace, 1                     );
+    add.call_pattern( "read",                    read, 0                      );
   }
 
-  Thing::tagged(console, "(impl. console)")
+  Thing::frozen(console, "(impl. console)")
 }
 
 /// Prints a Symbol to stdout. Doesn't return. Oneshot.
@@ -42,7 +72,8 @@ pub fn print(reactor: &mut Reactor, response: ObjectRef) {
       stdio::println(string.as_slice()),
 
     None =>
-      warn!("tried to print[] a non-symbol")
+      machine_warn!(reactor.machine(), "implementation",
+                    "tried to print[] a non-symbol")
   }
 }
 
@@ -61,8 +92,8 @@ pub fn show(reactor: &mut Reactor, response: ObjectRef) {
   println!("{}", response);
 }
 
-/// Debug-prints the given Object (`fmt_paws()`) to stdout. Doesn't return.
-/// Oneshot.
+/// Debug-prints the given Object and its members a few levels down, as a tree
+/// (see `util::pretty`), to stdout. Doesn't return. Oneshot.
 ///
 /// # Example
 ///
@@ -70,8 +101,26 @@ pub fn show(reactor: &mut Reactor, response: ObjectRef) {
 pub fn inspect(reactor: &mut Reactor, response: ObjectRef) {
   let mut stdout = stdio::stdout();
 
+  // FIXME: do something if this fails
+  let _ = PrettyPrinter::new().write(&mut stdout, &response);
+}
+
+/// Prints the given Object and everything reachable from it to stdout as JSON,
+/// in the format written by `util::serialize`, which includes tags, nuketypes
+/// and whether each relationship is a child relationship. Doesn't return.
+/// Oneshot.
+///
+/// Unlike `inspect()`, this goes all the way down, however deep the objects
+/// are.
+///
+/// # Example
+///
+///     implementation console dump-json [locals]
+pub fn dump_json(reactor: &mut Reactor, response: ObjectRef) {
+  let mut stdout = stdio::stdout();
+
   // FIXME: do something if these fail
-  let _ = response.lock().nuketype().fmt_paws(&mut stdout);
+  let _ = serialize::write_json(reactor.machine(), &response, &mut stdout);
   let _ = stdout.write_char('\n');
 }
 
@@ -99,7 +148,7 @@ pub fn trace(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
       Some(string) => write!(terminal, "{:s}", string.as_slice()),
       None         => symbol.lock().nuketype().fmt_paws(terminal.get_mut())
     },
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
   };
 
   let _ = terminal.reset();
@@ -107,3 +156,107 @@ pub fn trace(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
 
   reactor.stage(caller, args[0].clone())
 }
+
+/// Reads a line from stdin and responds with it as a Symbol, without the line
+/// ending.
+///
+/// The read happens off the reactor, on a task of its own that reads for
+/// every `read[]` in the process, one after the other, in the order they were
+/// called. That keeps the machine from stalling while it waits for input, and
+/// keeps a read from holding up unrelated work (like file I/O, see
+/// `file::in_background()`). The task ends once nothing is waiting to read.
+/// At the end of input, or if the line isn't UTF-8, responds with an error
+/// object (see `util::error`).
+///
+/// # Example
+///
+///     implementation console read[]
+pub fn read(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  let remote: Remote = reactor.remote();
+  let machine        = reactor.machine().clone();
+
+  let mut queue = read_queue().lock();
+
+  queue.requests.push_back((machine, remote, caller, current_span()));
+
+  if !queue.reading {
+    queue.reading = true;
+
+    TaskBuilder::new().named("console reader").spawn(proc() {
+      // Unbuffered, so that nothing past the line is taken away from the
+      // next read.
+      let mut stdin = stdio::stdin_raw();
+
+      loop {
+        let (machine, remote, caller, span) = {
+          let mut queue = read_queue().lock();
+
+          match queue.requests.pop_front() {
+            Some(request) => request,
+            None          => { queue.reading = false; break }
+          }
+        };
+
+        set_current_span(span);
+
+        let message = match read_line(&mut stdin) {
+          Ok(Some(line)) => {
+            remote.stage(caller, machine.symbol(line.as_slice()));
+            continue
+          },
+
+          Ok(None)     => "read[] reached the end of input".to_string(),
+          Err(message) => message
+        };
+
+        // As `respond_error!` would, but through the remote.
+        machine_warn!(machine, "implementation", "{}", message);
+
+        remote.stage(caller, error::create(&machine, "implementation",
+                                           message.as_slice()));
+      }
+    });
+  }
+}
+
+/// The process-wide queue of `read()`s, since there's only the one stdin.
+fn read_queue() -> &'static Mutex<ReadQueue> {
+  unsafe {
+    QUEUE_INIT.doit(|| {
+      QUEUE = mem::transmute(box Mutex::new(ReadQueue {
+        requests: RingBuf::new(),
+        reading:  false
+      }));
+    });
+
+    &*QUEUE
+  }
+}
+
+/// Reads bytes from `reader` up to and including a newline, one at a time,
+/// and returns them without the line ending (`\n` or `\r\n`). Returns `None`
+/// if the input ends before anything is read.
+pub fn read_line<R: Reader>(reader: &mut R) -> Result<Option<String>, String> {
+  let mut bytes = Vec::new();
+
+  loop {
+    match reader.read_byte() {
+      Ok(b'\n') => break,
+      Ok(byte)  => bytes.push(byte),
+
+      Err(IoError { kind: EndOfFile, .. }) =>
+        if bytes.is_empty() { return Ok(None) } else { break },
+
+      Err(e) => return Err(format!("read[] failed: {}", e))
+    }
+  }
+
+  if bytes.last() == Some(&b'\r') {
+    bytes.pop();
+  }
+
+  match str::from_utf8(bytes.as_slice()) {
+    Some(line) => Ok(Some(line.to_string())),
+    None       => Err("read[] got a line that isn't UTF-8".to_string())
+  }
+}
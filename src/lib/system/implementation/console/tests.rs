@@ -0,0 +1,29 @@
+use super::read_line;
+
+use std::io::MemReader;
+
+#[test]
+fn read_line_strips_line_endings() {
+  let mut reader = MemReader::new(b"one\ntwo\r\nthree".to_vec());
+
+  assert_eq!(Ok(Some("one".to_string())),   read_line(&mut reader));
+  assert_eq!(Ok(Some("two".to_string())),   read_line(&mut reader));
+  assert_eq!(Ok(Some("three".to_string())), read_line(&mut reader));
+  assert_eq!(Ok(None),                      read_line(&mut reader));
+}
+
+#[test]
+fn read_line_empty_lines() {
+  let mut reader = MemReader::new(b"\n\n".to_vec());
+
+  assert_eq!(Ok(Some("".to_string())), read_line(&mut reader));
+  assert_eq!(Ok(Some("".to_string())), read_line(&mut reader));
+  assert_eq!(Ok(None),                 read_line(&mut reader));
+}
+
+#[test]
+fn read_line_rejects_invalid_utf8() {
+  let mut reader = MemReader::new(vec![0xff, b'\n']);
+
+  assert!(read_line(&mut reader).is_err());
+}
@@ -0,0 +1,90 @@
+//! Reading the process' environment variables.
+
+#![allow(unused_variable)]
+
+use object::{ObjectRef, Meta};
+
+use nuketype::Thing;
+
+use machine::{Machine, Reactor};
+
+use util::namespace::NamespaceBuilder;
+use util::error;
+
+use std::os;
+
+#[cfg(test)]
+mod tests;
+
+/// Generates an `implementation environment` namespace object.
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut environment = Meta::new();
+
+  {
+    let mut add = NamespaceBuilder::new(machine, &mut environment);
+
+    add.call_pattern( "get",                     get, 1                       );
+    add.call_pattern( "all",                     all, 0                       );
+  }
+
+  Thing::frozen(environment, "(impl. environment)")
+}
+
+/// Responds with the value of an environment variable, as a Symbol. Responds
+/// with an error (without warning, since that's often expected) if it isn't
+/// set.
+///
+/// # Call-pattern arguments
+///
+/// 1. The name of the variable, as a Symbol.
+///
+/// # Example
+///
+///     implementation environment get "HOME"
+pub fn get(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref name] => {
+      let name = match name.symbol_ref() {
+        Some(name) => name.clone(),
+
+        None => {
+          respond_error!(reactor, caller, "implementation",
+                         "tried to environment get[] a non-symbol name");
+          return
+        }
+      };
+
+      match os::getenv(name.as_slice()) {
+        Some(value) => {
+          let value = reactor.machine().symbol(value.as_slice());
+
+          reactor.stage(caller, value)
+        },
+
+        None =>
+          error::respond(reactor, caller, "implementation",
+                         format!("{} is not set", name))
+      }
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Responds with every environment variable, as an object with a pair for
+/// each, from Symbol names to Symbol values.
+///
+/// # Example
+///
+///     implementation environment all[]
+pub fn all(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  let machine = reactor.machine().clone();
+
+  let mut meta = Meta::new();
+
+  for (name, value) in os::env().move_iter() {
+    meta.members.push_pair(machine.symbol(name.as_slice()),
+                           machine.symbol(value.as_slice()));
+  }
+
+  reactor.stage(caller, Thing::tagged(meta, "(environment)"))
+}
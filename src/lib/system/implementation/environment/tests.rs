@@ -0,0 +1,61 @@
+use super::{get, all};
+
+use nuketype::Thing;
+
+use machine::Machine;
+use machine::reactor::MockReactor;
+
+use util::error;
+
+use std::os;
+
+#[test]
+fn get_responds_with_value() {
+  os::setenv("PAWS_ENVIRONMENT_TEST_GET", "hello");
+
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller = Thing::empty();
+
+  get(&mut reactor, caller.clone(),
+      [machine.symbol("PAWS_ENVIRONMENT_TEST_GET")]);
+
+  let (execution, value) = reactor.stagings.remove(0).expect("no response");
+
+  assert!(execution == caller);
+  assert!(value.eq_as_symbol(&machine.symbol("hello")));
+}
+
+#[test]
+fn get_responds_with_error_if_unset() {
+  os::unsetenv("PAWS_ENVIRONMENT_TEST_UNSET");
+
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  get(&mut reactor, Thing::empty(),
+      [machine.symbol("PAWS_ENVIRONMENT_TEST_UNSET")]);
+
+  let (_, response) = reactor.stagings.remove(0).expect("no response");
+
+  assert!(error::is_error(&response));
+}
+
+#[test]
+fn all_includes_every_variable() {
+  os::setenv("PAWS_ENVIRONMENT_TEST_ALL", "yes");
+
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  all(&mut reactor, Thing::empty(), []);
+
+  let (_, object) = reactor.stagings.remove(0).expect("no response");
+
+  let value = object.lock().meta().members
+                .lookup_pair(&machine.symbol("PAWS_ENVIRONMENT_TEST_ALL"))
+                .expect("variable missing");
+
+  assert!(value.eq_as_symbol(&machine.symbol("yes")));
+}
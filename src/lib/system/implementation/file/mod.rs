@@ -0,0 +1,539 @@
+//! File I/O that doesn't block the reactor.
+//!
+//! Each alien here hands its I/O off to the machine's `BlockingPool` and
+//! returns to the reactor straight away. The caller is staged with the result
+//! once the operation completes. If it fails, a warning is emitted and the
+//! caller is never staged.
+
+#![allow(unused_variable)]
+
+use object::{ObjectRef, Meta};
+
+use nuketype::{Nuketype, Thing, Number};
+
+use machine::{Machine, Reactor};
+use machine::reactor::{Remote, current_span, set_current_span};
+
+use util::namespace::NamespaceBuilder;
+
+use std::io::{File, FileMode, FileAccess, Open, Append, Truncate};
+use std::io::{Read, Write, IoResult, EndOfFile};
+use std::io::fs;
+use std::sync::{Arc, Mutex};
+
+#[cfg(test)]
+mod tests;
+
+/// Generates an `implementation file` namespace object.
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut file = Meta::new();
+
+  {
+    let mut add = NamespaceBuilder::new(machine, &mut file);
+
+    add.call_pattern( "open",                    open, 2                      );
+    add.call_pattern( "read",                    read, 2                      );
+    add.call_pattern( "read-all",                read_all, 1                  );
+    add.call_pattern( "write",                   write, 2                     );
+    add.call_pattern( "close",                   close, 1                     );
+
+    add.call_pattern( "read-file",               read_file, 1                 );
+    add.call_pattern( "write-file",              write_file, 2                );
+    add.call_pattern( "append-file",             append_file, 2               );
+    add.call_pattern( "exists",                  exists, 1                    );
+    add.call_pattern( "delete",                  delete, 1                    );
+  }
+
+  Thing::frozen(file, "(impl. file)")
+}
+
+/// An open file, as returned by `open()`. Closing it with `close()` leaves the
+/// handle behind, but any further I/O on it fails.
+pub struct FileHandle {
+  path: Path,
+  file: Arc<Mutex<Option<File>>>
+}
+
+impl FileHandle {
+  /// Boxes up a handle to an already open file.
+  pub fn create(path: Path, file: File) -> ObjectRef {
+    ObjectRef::store(box FileHandle {
+      path: path,
+      file: Arc::new(Mutex::new(Some(file)))
+    }, Meta::new())
+  }
+
+  /// The path the file was opened from.
+  pub fn path<'a>(&'a self) -> &'a Path {
+    &self.path
+  }
+
+  /// Whether the file has been closed.
+  pub fn is_closed(&self) -> bool {
+    self.file.lock().is_none()
+  }
+}
+
+impl Nuketype for FileHandle {
+  fn fmt_paws(&self, writer: &mut Writer) -> IoResult<()> {
+    write!(writer, "FileHandle[{}{}]",
+           self.path.display(),
+           if self.is_closed() { ", closed" } else { "" })
+  }
+}
+
+/// Opens a file. Responds with a handle to it.
+///
+/// # Call-pattern arguments
+///
+/// 1. The path to the file, as a Symbol.
+/// 2. The mode: `read`, `write` (creating or truncating the file), or `append`
+///    (creating the file if necessary).
+///
+/// # Example
+///
+///     implementation file open "notes.txt" write
+pub fn open(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref path, ref mode] => {
+      let path = match path_of(reactor, path, "open") {
+        Some(path) => path,
+        None       => return
+      };
+
+      let (file_mode, file_access): (FileMode, FileAccess) =
+        match mode.symbol_ref().map(|mode| mode.as_slice()) {
+          Some("read")   => (Open,     Read),
+          Some("write")  => (Truncate, Write),
+          Some("append") => (Append,   Write),
+
+          _ => {
+            machine_warn!(reactor.machine(), "implementation",
+                          "tried to open[] with unknown mode {}", mode);
+            return
+          }
+        };
+
+      in_background(reactor, caller, proc(machine) {
+        File::open_mode(&path, file_mode, file_access)
+          .map(|file| FileHandle::create(path.clone(), file))
+          .map_err(|error| format!("couldn't open {}: {}",
+                                   path.display(), error))
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Reads up to a given number of bytes from a file, which may be fewer than
+/// asked for even if the end of the file hasn't been reached yet. Responds
+/// with what was read as a Symbol, which is empty at the end of the file.
+///
+/// Invalid UTF-8 (including a character split across two reads) is replaced
+/// with U+FFFD.
+///
+/// # Call-pattern arguments
+///
+/// 1. The handle, from `open()`.
+/// 2. The maximum number of bytes to read, as a Number or a decimal Symbol.
+///
+/// # Example
+///
+///     implementation file read [handle] 4096
+pub fn read(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref handle, ref count] => {
+      let count = match count_of(count) {
+        Some(count) => count,
+
+        None => {
+          machine_warn!(reactor.machine(), "implementation",
+                        "tried to read[] a bad byte count {}", count);
+          return
+        }
+      };
+
+      let (path, file) = match handle_of(reactor, handle, "read") {
+        Some(handle) => handle,
+        None         => return
+      };
+
+      in_background(reactor, caller, proc(machine) {
+        let mut buffer = Vec::from_elem(count, 0u8);
+
+        let result = with_file(&path, &file, |file| {
+          match file.read(buffer.as_mut_slice()) {
+            Ok(read) => {
+              buffer.truncate(read);
+              Ok(())
+            },
+
+            Err(ref error) if error.kind == EndOfFile => {
+              buffer.truncate(0);
+              Ok(())
+            },
+
+            Err(error) => Err(error)
+          }
+        });
+
+        result.map(|()| {
+          machine.symbol(String::from_utf8_lossy(buffer.as_slice()).as_slice())
+        })
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Reads everything left in a file. Responds with it as a Symbol, which is
+/// empty if the end of the file had already been reached.
+///
+/// Invalid UTF-8 is replaced with U+FFFD, as with `read()`.
+///
+/// # Call-pattern arguments
+///
+/// 1. The handle, from `open()`.
+///
+/// # Example
+///
+///     implementation file read-all [handle]
+pub fn read_all(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref handle] => {
+      let (path, file) = match handle_of(reactor, handle, "read-all") {
+        Some(handle) => handle,
+        None         => return
+      };
+
+      in_background(reactor, caller, proc(machine) {
+        with_file(&path, &file, |file| file.read_to_end()).map(|bytes| {
+          machine.symbol(String::from_utf8_lossy(bytes.as_slice()).as_slice())
+        })
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Writes a Symbol to a file. Responds with the handle.
+///
+/// # Call-pattern arguments
+///
+/// 1. The handle, from `open()`.
+/// 2. The Symbol to write.
+///
+/// # Example
+///
+///     implementation file write [handle] "Hello, world!"
+pub fn write(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref handle_ref, ref label] => {
+      let label = match label.symbol_ref() {
+        Some(label) => label.clone(),
+
+        None => {
+          machine_warn!(reactor.machine(), "implementation",
+                        "tried to write[] a non-symbol");
+          return
+        }
+      };
+
+      let (path, file) = match handle_of(reactor, handle_ref, "write") {
+        Some(handle) => handle,
+        None         => return
+      };
+
+      let handle_ref = handle_ref.clone();
+
+      in_background(reactor, caller, proc(machine) {
+        with_file(&path, &file, |file| {
+          file.write_str(label.as_slice()).and_then(|()| file.flush())
+        }).map(|()| handle_ref)
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Closes a file. Responds with the handle, which can't be used for I/O
+/// anymore.
+///
+/// # Call-pattern arguments
+///
+/// 1. The handle, from `open()`.
+///
+/// # Example
+///
+///     implementation file close [handle]
+pub fn close(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref handle_ref] => {
+      let (path, file) = match handle_of(reactor, handle_ref, "close") {
+        Some(handle) => handle,
+        None         => return
+      };
+
+      let handle_ref = handle_ref.clone();
+
+      in_background(reactor, caller, proc(machine) {
+        // Dropping the file closes it; do that off the reactor too, since it
+        // may have to flush.
+        match file.lock().take() {
+          Some(file) => {
+            drop(file);
+            Ok(handle_ref)
+          },
+
+          None => Err(format!("{} is already closed", path.display()))
+        }
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Reads a whole file, without needing a handle. Responds with its contents
+/// as a Symbol, with invalid UTF-8 replaced as with `read()`.
+///
+/// # Call-pattern arguments
+///
+/// 1. The path to the file, as a Symbol.
+///
+/// # Example
+///
+///     implementation file read-file "notes.txt"
+pub fn read_file(reactor: &mut Reactor, caller: ObjectRef,
+                 args: &[ObjectRef]) {
+  match args {
+    [ref path] => {
+      let path = match path_of(reactor, path, "read-file") {
+        Some(path) => path,
+        None       => return
+      };
+
+      in_background(reactor, caller, proc(machine) {
+        File::open(&path).read_to_end()
+          .map(|bytes| {
+            machine.symbol(
+              String::from_utf8_lossy(bytes.as_slice()).as_slice())
+          })
+          .map_err(|error| format!("couldn't read {}: {}",
+                                   path.display(), error))
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Replaces the contents of a file with a Symbol, creating it if necessary,
+/// without needing a handle. Responds with the path.
+///
+/// # Call-pattern arguments
+///
+/// 1. The path to the file, as a Symbol.
+/// 2. The Symbol to write.
+///
+/// # Example
+///
+///     implementation file write-file "notes.txt" "Hello, world!"
+pub fn write_file(reactor: &mut Reactor, caller: ObjectRef,
+                  args: &[ObjectRef]) {
+  write_whole(reactor, caller, args, "write-file", Truncate)
+}
+
+/// Appends a Symbol to a file, creating it if necessary, without needing a
+/// handle. Responds with the path.
+///
+/// # Call-pattern arguments
+///
+/// 1. The path to the file, as a Symbol.
+/// 2. The Symbol to append.
+///
+/// # Example
+///
+///     implementation file append-file "log.txt" "another line"
+pub fn append_file(reactor: &mut Reactor, caller: ObjectRef,
+                   args: &[ObjectRef]) {
+  write_whole(reactor, caller, args, "append-file", Append)
+}
+
+/// Responds with the path if something exists there. Otherwise, doesn't
+/// respond, like `infrastructure compare`.
+///
+/// # Call-pattern arguments
+///
+/// 1. The path, as a Symbol.
+///
+/// # Example
+///
+///     implementation file exists "notes.txt"
+pub fn exists(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref path_ref] => {
+      let path = match path_of(reactor, path_ref, "exists") {
+        Some(path) => path,
+        None       => return
+      };
+
+      let path_ref = path_ref.clone();
+      let remote   = reactor.remote();
+
+      // Not finding anything isn't worth a warning, so this doesn't go through
+      // `in_background()`.
+      reactor.machine().blocking.run(proc() {
+        if path.exists() {
+          remote.stage(caller, path_ref);
+        }
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Deletes a file. Responds with the path.
+///
+/// # Call-pattern arguments
+///
+/// 1. The path to the file, as a Symbol.
+///
+/// # Example
+///
+///     implementation file delete "notes.txt"
+pub fn delete(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref path_ref] => {
+      let path = match path_of(reactor, path_ref, "delete") {
+        Some(path) => path,
+        None       => return
+      };
+
+      let path_ref = path_ref.clone();
+
+      in_background(reactor, caller, proc(machine) {
+        fs::unlink(&path)
+          .map(|()| path_ref)
+          .map_err(|error| format!("couldn't delete {}: {}",
+                                   path.display(), error))
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Opens the file at the path in `args[0]` with `mode`, writes the Symbol in
+/// `args[1]` to it, and closes it again.
+fn write_whole(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef],
+               routine: &str, mode: FileMode) {
+  match args {
+    [ref path_ref, ref label] => {
+      let path = match path_of(reactor, path_ref, routine) {
+        Some(path) => path,
+        None       => return
+      };
+
+      let label = match label.symbol_ref() {
+        Some(label) => label.clone(),
+
+        None => {
+          machine_warn!(reactor.machine(), "implementation",
+                        "tried to {}[] a non-symbol", routine);
+          return
+        }
+      };
+
+      let path_ref = path_ref.clone();
+
+      in_background(reactor, caller, proc(machine) {
+        File::open_mode(&path, mode, Write)
+          .and_then(|mut file| file.write_str(label.as_slice()))
+          .map(|()| path_ref)
+          .map_err(|error| format!("couldn't write to {}: {}",
+                                   path.display(), error))
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Runs `job` on the machine's `BlockingPool`, then stages `caller` with the
+/// object it produces, or warns with the message it fails with.
+///
+/// Warnings from the job point at the same source as warnings from the
+/// reactor would have.
+pub fn in_background(reactor: &mut Reactor, caller: ObjectRef,
+                     job: proc(Machine): Send -> Result<ObjectRef, String>) {
+
+  let remote: Remote = reactor.remote();
+  let machine        = reactor.machine().clone();
+  let span           = current_span();
+
+  reactor.machine().blocking.run(proc() {
+    set_current_span(span);
+
+    match job(machine.clone()) {
+      Ok(response) =>
+        remote.stage(caller, response),
+
+      Err(message) =>
+        // Dropping the remote lets the reactor know not to wait for it.
+        machine_warn!(machine, "implementation", "{}", message)
+    }
+  });
+}
+
+/// Gets a path from a Symbol, warning if `object` isn't one.
+fn path_of(reactor: &mut Reactor, object: &ObjectRef, routine: &str)
+           -> Option<Path> {
+
+  match object.symbol_ref() {
+    Some(path) => Some(Path::new(path.as_slice())),
+
+    None => {
+      machine_warn!(reactor.machine(), "implementation",
+                    "tried to {}[] a non-symbol path", routine);
+      None
+    }
+  }
+}
+
+/// Gets the path and file out of a `FileHandle`, warning if `object` isn't
+/// one.
+fn handle_of(reactor: &mut Reactor, object: &ObjectRef, routine: &str)
+             -> Option<(Path, Arc<Mutex<Option<File>>>)> {
+
+  match object.lock().try_cast::<FileHandle>() {
+    Ok(handle) =>
+      Some((handle.deref().path.clone(), handle.deref().file.clone())),
+
+    Err(_) => {
+      machine_warn!(reactor.machine(), "implementation",
+                    "tried to {}[] a non-file {}", routine, object);
+      None
+    }
+  }
+}
+
+/// Performs `operation` on the file if it's still open.
+fn with_file<T>(path: &Path, file: &Arc<Mutex<Option<File>>>,
+                operation: |&mut File| -> IoResult<T>)
+                -> Result<T, String> {
+
+  match *file.lock() {
+    Some(ref mut file) =>
+      operation(file).map_err(|error| format!("I/O on {} failed: {}",
+                                              path.display(), error)),
+
+    None =>
+      Err(format!("{} is closed", path.display()))
+  }
+}
+
+/// Gets a byte count from a non-negative Number or a decimal Symbol.
+fn count_of(object: &ObjectRef) -> Option<uint> {
+  match Number::of(object) {
+    Some(value) if value >= 0 => Some(value as uint),
+    Some(_)                   => None,
+    None => object.symbol_ref().and_then(|label| from_str(label.as_slice()))
+  }
+}
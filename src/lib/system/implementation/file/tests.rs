@@ -0,0 +1,243 @@
+use super::{open, read, read_all, write, close, FileHandle};
+use super::{read_file, write_file, append_file, exists, delete};
+
+use object::ObjectRef;
+
+use nuketype::{Thing, Number, Execution};
+
+use script::Script;
+
+use machine::{Machine, Reactor};
+use machine::reactor::{MockReactor, SerialReactor};
+
+use util;
+
+use std::io::{File, TempDir};
+
+/// Calls `routine` and waits for it to stage the caller, if it does.
+fn respond(reactor: &mut MockReactor,
+           routine: fn(&mut Reactor, ObjectRef, &[ObjectRef]),
+           args: &[ObjectRef]) -> Option<ObjectRef> {
+
+  let caller = Thing::empty();
+
+  routine(reactor, caller.clone(), args);
+
+  // Nothing should be staged until the I/O is done.
+  assert!(reactor.stagings.is_empty());
+  assert_eq!(1, reactor.outstanding);
+
+  if reactor.receive_remote() {
+    reactor.stagings.remove(0).map(|(execution, response)| {
+      assert!(execution == caller);
+      response
+    })
+  } else {
+    None
+  }
+}
+
+fn label(object: &ObjectRef) -> String {
+  object.symbol_ref().expect("not a Symbol").as_slice().to_string()
+}
+
+#[test]
+fn write_then_read() {
+  let     dir     = TempDir::new("paws-file").unwrap();
+  let     path    = dir.path().join("test.txt");
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let path_sym = machine.symbol(path.as_str().unwrap());
+
+  let handle = respond(&mut reactor, open,
+                       [path_sym.clone(), machine.symbol("write")])
+                 .expect("open for write failed");
+
+  let written = respond(&mut reactor, write,
+                        [handle.clone(), machine.symbol("Hello, world!")])
+                  .expect("write failed");
+
+  assert!(written == handle);
+
+  respond(&mut reactor, close, [handle.clone()]).expect("close failed");
+
+  assert!(handle.lock().try_cast::<FileHandle>().unwrap().deref().is_closed());
+
+  let handle = respond(&mut reactor, open,
+                       [path_sym, machine.symbol("read")])
+                 .expect("open for read failed");
+
+  let first = respond(&mut reactor, read, [handle.clone(), Number::create(5)])
+                .expect("read failed");
+
+  assert_eq!("Hello".to_string(), label(&first));
+
+  let rest = respond(&mut reactor, read, [handle.clone(), machine.symbol("64")])
+               .expect("read failed");
+
+  assert_eq!(", world!".to_string(), label(&rest));
+
+  let eof = respond(&mut reactor, read, [handle.clone(), Number::create(64)])
+              .expect("read at end of file failed");
+
+  assert_eq!("".to_string(), label(&eof));
+}
+
+#[test]
+fn append_keeps_contents() {
+  let     dir     = TempDir::new("paws-file").unwrap();
+  let     path    = dir.path().join("test.txt");
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  File::create(&path).write_str("a").unwrap();
+
+  let handle = respond(&mut reactor, open,
+                       [machine.symbol(path.as_str().unwrap()),
+                        machine.symbol("append")])
+                 .expect("open for append failed");
+
+  respond(&mut reactor, write, [handle.clone(), machine.symbol("b")])
+    .expect("write failed");
+
+  respond(&mut reactor, close, [handle]).expect("close failed");
+
+  assert_eq!("ab".to_string(),
+             File::open(&path).read_to_string().unwrap());
+}
+
+#[test]
+fn failures_drop_the_caller() {
+  let     dir     = TempDir::new("paws-file").unwrap();
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let missing = dir.path().join("missing.txt");
+
+  assert!(respond(&mut reactor, open,
+                  [machine.symbol(missing.as_str().unwrap()),
+                   machine.symbol("read")]).is_none());
+
+  assert_eq!(0, reactor.outstanding);
+}
+
+#[test]
+fn closed_handles_refuse_io() {
+  let     dir     = TempDir::new("paws-file").unwrap();
+  let     path    = dir.path().join("test.txt");
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let handle = FileHandle::create(path.clone(), File::create(&path).unwrap());
+
+  respond(&mut reactor, close, [handle.clone()]).expect("close failed");
+
+  assert!(respond(&mut reactor, write,
+                  [handle.clone(), machine.symbol("x")]).is_none());
+  assert!(respond(&mut reactor, close, [handle]).is_none());
+}
+
+#[test]
+fn bad_arguments_are_refused_up_front() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  // Neither of these should promise anything.
+  open(&mut reactor, Thing::empty(),
+       [machine.symbol("x"), machine.symbol("sideways")]);
+  read(&mut reactor, Thing::empty(), [Thing::empty(), Number::create(1)]);
+
+  assert!(reactor.stagings.is_empty());
+  assert_eq!(0, reactor.outstanding);
+}
+
+#[test]
+fn read_all_reads_the_rest() {
+  let     dir     = TempDir::new("paws-file").unwrap();
+  let     path    = dir.path().join("test.txt");
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  File::create(&path).write_str("line one\nline two\n").unwrap();
+
+  let handle = respond(&mut reactor, open,
+                       [machine.symbol(path.as_str().unwrap()),
+                        machine.symbol("read")])
+                 .expect("open for read failed");
+
+  respond(&mut reactor, read, [handle.clone(), Number::create(5)])
+    .expect("read failed");
+
+  let rest = respond(&mut reactor, read_all, [handle.clone()])
+               .expect("read-all failed");
+
+  assert_eq!("one\nline two\n".to_string(), label(&rest));
+
+  let nothing = respond(&mut reactor, read_all, [handle])
+                  .expect("read-all at end of file failed");
+
+  assert_eq!("".to_string(), label(&nothing));
+}
+
+#[test]
+fn whole_file_operations() {
+  let     dir     = TempDir::new("paws-file").unwrap();
+  let     path    = dir.path().join("test.txt");
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let path_sym = machine.symbol(path.as_str().unwrap());
+
+  assert!(respond(&mut reactor, exists, [path_sym.clone()]).is_none());
+
+  let written = respond(&mut reactor, write_file,
+                        [path_sym.clone(), machine.symbol("abc")])
+                  .expect("write-file failed");
+
+  assert!(written == path_sym);
+
+  respond(&mut reactor, append_file, [path_sym.clone(), machine.symbol("def")])
+    .expect("append-file failed");
+
+  let contents = respond(&mut reactor, read_file, [path_sym.clone()])
+                   .expect("read-file failed");
+
+  assert_eq!("abcdef".to_string(), label(&contents));
+
+  assert!(respond(&mut reactor, exists, [path_sym.clone()]).is_some());
+
+  respond(&mut reactor, delete, [path_sym.clone()]).expect("delete failed");
+
+  assert!(!path.exists());
+  assert!(respond(&mut reactor, read_file, [path_sym.clone()]).is_none());
+  assert!(respond(&mut reactor, delete, [path_sym]).is_none());
+}
+
+#[test]
+fn serial_reactor_waits_for_io_before_stalling() {
+  util::timeout(1000, proc() {
+    let     dir     = TempDir::new("paws-file").unwrap();
+    let     path    = dir.path().join("test.txt");
+    let     machine = Machine::new();
+    let mut reactor = SerialReactor::new(machine.clone());
+
+    let (tx, rx) = channel();
+
+    reactor.on_stall(proc (reactor) {
+      tx.send(reactor.stats().steps);
+      reactor.stop();
+    });
+
+    let caller = Execution::create(&machine, Script(vec![]));
+
+    write_file(&mut reactor, caller,
+               [machine.symbol(path.as_str().unwrap()), machine.symbol("x")]);
+
+    reactor.run();
+
+    // The response was realized before the stall.
+    assert_eq!(1, rx.recv());
+    assert!(path.exists());
+  })
+}
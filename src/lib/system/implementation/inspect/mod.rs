@@ -0,0 +1,83 @@
+//! Looking at the objects a program can reach. See `machine::inspect`.
+
+#![allow(unused_variable)]
+
+use object::{ObjectRef, Meta};
+
+use nuketype::Thing;
+
+use machine::{Machine, Reactor};
+use machine::inspect;
+
+use system::implementation::stats::push_counters;
+
+use util::namespace::NamespaceBuilder;
+
+#[cfg(test)]
+mod tests;
+
+/// Generates an `implementation inspect` namespace object.
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut inspect = Meta::new();
+
+  {
+    let mut add = NamespaceBuilder::new(machine, &mut inspect);
+
+    add.call_pattern( "graph",                   graph, 0                     );
+  }
+
+  Thing::frozen(inspect, "(impl. inspect)")
+}
+
+/// Responds with a report on everything reachable from the caller (including
+/// its locals) and the system namespaces, as an object with these pairs:
+///
+/// * `objects`, `members` and `children`: the numbers of objects, member
+///   relationships, and child relationships among those.
+/// * `kinds`: an object with a pair for each nuketype, counting the objects of
+///   that nuketype.
+/// * `tags`: likewise, for each tag.
+///
+/// The counts are Symbols of decimal numbers.
+///
+/// # Example
+///
+///     implementation inspect graph[]
+pub fn graph(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  let report = inspect::inspect(
+    inspect::roots(reactor.machine(), [caller.clone()]).as_slice());
+
+  let machine = reactor.machine().clone();
+
+  let mut meta = Meta::new();
+
+  push_counters(&machine, &mut meta, [
+    ("objects",  report.objects() as u64),
+    ("members",  report.members as u64),
+    ("children", report.children as u64)
+  ]);
+
+  meta.members.push_pair(machine.symbol("kinds"),
+                         counts_object(&machine, report.kinds.as_slice(),
+                                       "(inspect kinds)"));
+
+  meta.members.push_pair(machine.symbol("tags"),
+                         counts_object(&machine, report.tags.as_slice(),
+                                       "(inspect tags)"));
+
+  reactor.stage(caller, Thing::tagged(meta, "(inspect report)"))
+}
+
+fn counts_object(machine: &Machine, counts: &[(String, uint)], tag: &str)
+                 -> ObjectRef {
+
+  let mut meta = Meta::new();
+
+  let counters: Vec<(&str, u64)> = counts.iter()
+    .map(|&(ref name, count)| (name.as_slice(), count as u64))
+    .collect();
+
+  push_counters(machine, &mut meta, counters.as_slice());
+
+  Thing::tagged(meta, tag)
+}
@@ -0,0 +1,44 @@
+use super::graph;
+
+use nuketype::Execution;
+
+use machine::Machine;
+use machine::reactor::MockReactor;
+
+use script::Script;
+
+#[test]
+fn graph_reports_on_the_caller() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller = Execution::create(&machine, Script(vec![]));
+
+  graph(&mut reactor, caller.clone(), []);
+
+  let (execution, report) = reactor.stagings.remove(0).expect("no response");
+
+  assert!(execution == caller);
+
+  let report_obj = report.lock();
+  let members    = &report_obj.meta().members;
+
+  let count = |name: &str| {
+    members.lookup_pair(&machine.symbol(name))
+      .and_then(|value| value.symbol_ref()
+                          .and_then(|label| from_str::<uint>(label.as_slice())))
+      .expect("count missing")
+  };
+
+  assert!(count("objects") > 0);
+  assert!(count("members") >= count("children"));
+
+  let kinds = members.lookup_pair(&machine.symbol("kinds"))
+                .expect("kinds missing");
+
+  // The caller itself is an Execution.
+  assert!(kinds.lock().meta().members
+            .lookup_pair(&machine.symbol("execution")).is_some());
+
+  assert!(members.lookup_pair(&machine.symbol("tags")).is_some());
+}
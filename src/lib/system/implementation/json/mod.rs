@@ -0,0 +1,97 @@
+//! Exchanging data as JSON. See `format::json` for how objects and JSON
+//! correspond.
+
+#![allow(unused_variable)]
+
+use object::{ObjectRef, Meta};
+
+use nuketype::Thing;
+
+use machine::{Machine, Reactor};
+
+use format::json;
+
+use util::namespace::NamespaceBuilder;
+
+#[cfg(test)]
+mod tests;
+
+/// Generates an `implementation json` namespace object.
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut json = Meta::new();
+
+  {
+    let mut add = NamespaceBuilder::new(machine, &mut json);
+
+    add.call_pattern( "parse",                   parse, 1                     );
+    add.call_pattern( "serialize",               serialize, 1                 );
+  }
+
+  Thing::frozen(json, "(impl. json)")
+}
+
+/// Builds objects out of a JSON document, and responds with them. Responds
+/// with an error if the document isn't valid JSON.
+///
+/// # Call-pattern arguments
+///
+/// 1. The JSON document, as a Symbol.
+///
+/// # Example
+///
+///     implementation json parse "{\"name\": \"paws\"}"
+pub fn parse(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref input] => {
+      let input = match input.symbol_ref() {
+        Some(input) => input.clone(),
+
+        None => {
+          respond_error!(reactor, caller, "implementation",
+                         "tried to json parse[] a non-symbol");
+          return
+        }
+      };
+
+      let result = json::from_json(reactor.machine(), input.as_slice());
+
+      match result {
+        Ok(object) => reactor.stage(caller, object),
+
+        Err(error) => {
+          respond_error!(reactor, caller, "implementation",
+                         "couldn't json parse[]: {}", error);
+        }
+      }
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Converts an object, and everything under it, to JSON, and responds with it
+/// as a Symbol. Responds with an error if something can't be converted.
+///
+/// # Call-pattern arguments
+///
+/// 1. The object to convert.
+///
+/// # Example
+///
+///     implementation json serialize [data]
+pub fn serialize(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref root] => match json::to_json(root) {
+      Ok(output) => {
+        let output = reactor.machine().symbol(output.as_slice());
+
+        reactor.stage(caller, output)
+      },
+
+      Err(error) => {
+        respond_error!(reactor, caller, "implementation",
+                       "couldn't json serialize[]: {}", error);
+      }
+    },
+    _ => wrong_arguments!()
+  }
+}
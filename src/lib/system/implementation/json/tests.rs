@@ -0,0 +1,61 @@
+use super::{parse, serialize};
+
+use nuketype::{Thing, Number};
+
+use machine::Machine;
+use machine::reactor::MockReactor;
+
+use util::error;
+
+#[test]
+fn parse_then_serialize() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller = Thing::empty();
+
+  parse(&mut reactor, caller.clone(), [machine.symbol("{\"count\":3}")]);
+
+  let (execution, object) = reactor.stagings.remove(0).expect("no response");
+
+  assert!(execution == caller);
+
+  let count = object.lock().meta().members
+                .lookup_pair(&machine.symbol("count")).expect("count missing");
+
+  assert_eq!(Number::of(&count), Some(3));
+
+  serialize(&mut reactor, caller.clone(), [object]);
+
+  let (_, output) = reactor.stagings.remove(0).expect("no response");
+
+  assert!(output.eq_as_symbol(&machine.symbol("{\"count\":3}")));
+}
+
+#[test]
+fn parse_responds_with_error_on_invalid_json() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  parse(&mut reactor, Thing::empty(), [machine.symbol("{")]);
+
+  let (_, response) = reactor.stagings.remove(0).expect("no response");
+
+  assert!(error::is_error(&response));
+}
+
+#[test]
+fn serialize_responds_with_error_on_cycles() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let thing = Thing::empty();
+
+  thing.lock().meta_mut().members.push(thing.clone());
+
+  serialize(&mut reactor, Thing::empty(), [thing]);
+
+  let (_, response) = reactor.stagings.remove(0).expect("no response");
+
+  assert!(error::is_error(&response));
+}
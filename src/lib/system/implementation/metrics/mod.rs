@@ -0,0 +1,106 @@
+//! Statistics for every reactor on the Machine. See `machine::metrics`.
+
+#![allow(unused_variable)]
+
+use object::{ObjectRef, Meta};
+
+use nuketype::Thing;
+
+use machine::{Machine, Reactor, MetricsSnapshot};
+
+use system::implementation::stats::{reactor_object, push_counters};
+
+use util::namespace::NamespaceBuilder;
+
+use std::io::stdio;
+
+#[cfg(test)]
+mod tests;
+
+/// Generates an `implementation metrics` namespace object.
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut metrics = Meta::new();
+
+  {
+    let mut add = NamespaceBuilder::new(machine, &mut metrics);
+
+    add.call_pattern( "snapshot",                snapshot, 0                  );
+    add.call_pattern( "dump",                    dump, 0                      );
+  }
+
+  Thing::frozen(metrics, "(impl. metrics)")
+}
+
+/// Responds with the last reported statistics of every reactor on the Machine,
+/// as an object with these pairs:
+///
+/// * `elapsed-ms`: how long the Machine has been around.
+/// * `realized-per-second`: stagings realized per second, across all reactors.
+/// * `reactors`: an object with a pair for each reactor, from its name to its
+///   statistics (like `implementation stats reactor[]`).
+/// * `total`: the statistics of all of the reactors, added up.
+///
+/// The counts are Symbols of decimal numbers. The current reactor reports its
+/// statistics first, so they're up to date; the others' are as of the last
+/// time they stalled or stopped.
+///
+/// # Example
+///
+///     implementation metrics snapshot[]
+pub fn snapshot(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  reactor.report_metrics();
+
+  let snapshot = reactor.machine().metrics.snapshot();
+
+  reactor.stage(caller, snapshot_object(reactor.machine(), &snapshot))
+}
+
+/// Like `snapshot()`, but also writes a human-readable report to stderr, like
+/// the `--metrics` flag does.
+///
+/// # Example
+///
+///     implementation metrics dump[]
+pub fn dump(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  reactor.report_metrics();
+
+  let snapshot = reactor.machine().metrics.snapshot();
+
+  match snapshot.write_text(&mut stdio::stderr()) {
+    Ok(()) => (),
+
+    Err(e) =>
+      machine_warn!(reactor.machine(), "implementation",
+                    "metrics dump[] couldn't write to stderr: {}", e)
+  }
+
+  reactor.stage(caller, snapshot_object(reactor.machine(), &snapshot))
+}
+
+/// Converts a `MetricsSnapshot` to a Paws object. See `snapshot()`.
+pub fn snapshot_object(machine: &Machine, snapshot: &MetricsSnapshot)
+                       -> ObjectRef {
+  let mut meta = Meta::new();
+
+  let per_second = snapshot.realized_per_second().unwrap_or(0.0);
+
+  push_counters(machine, &mut meta, [
+    ("elapsed-ms",          snapshot.elapsed_ns / 1_000_000),
+    ("realized-per-second", per_second as u64)
+  ]);
+
+  let mut reactors = Meta::new();
+
+  for &(ref name, ref stats) in snapshot.reactors.iter() {
+    reactors.members.push_pair(machine.symbol(name.as_slice()),
+                               reactor_object(machine, stats));
+  }
+
+  meta.members.push_pair(machine.symbol("reactors"),
+                         Thing::tagged(reactors, "(metrics reactors)"));
+
+  meta.members.push_pair(machine.symbol("total"),
+                         reactor_object(machine, &snapshot.total()));
+
+  Thing::tagged(meta, "(metrics)")
+}
@@ -0,0 +1,33 @@
+use super::snapshot;
+
+use object::ObjectRef;
+
+use nuketype::Thing;
+
+use machine::Machine;
+use machine::reactor::MockReactor;
+
+#[test]
+fn snapshot_includes_registered_reactors() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  machine.metrics.register("first");
+  machine.metrics.register("second");
+
+  let caller = Thing::empty();
+
+  snapshot(&mut reactor, caller.clone(), []);
+
+  let (_, response) = reactor.expect_staged(&caller);
+
+  let pair = |object: &ObjectRef, name: &str| {
+    object.lock().meta().members.lookup_pair(&machine.symbol(name))
+  };
+
+  let reactors = pair(&response, "reactors").expect("reactors missing");
+
+  assert!(pair(&reactors, "first").is_some());
+  assert!(pair(&reactors, "second").is_some());
+  assert!(pair(&response, "total").is_some());
+}
@@ -11,9 +11,23 @@ use machine::{Machine, Reactor};
 use util::namespace::NamespaceBuilder;
 use util::clone;
 
-use std::any::AnyMutRefExt;
+use std::any::{AnyRefExt, AnyMutRefExt};
 
 pub mod console;
+pub mod stats;
+pub mod metrics;
+pub mod file;
+pub mod cache;
+pub mod snapshot;
+pub mod time;
+pub mod timer;
+pub mod network;
+pub mod inspect;
+pub mod json;
+pub mod environment;
+pub mod arguments;
+pub mod process;
+pub mod remote;
 
 #[cfg(test)]
 mod tests;
@@ -26,12 +40,28 @@ pub fn make(machine: &Machine) -> ObjectRef {
     let mut add = NamespaceBuilder::new(machine, &mut implementation);
 
     add.factory(      "console",                 console::make                );
+    add.factory(      "stats",                   stats::make                  );
+    add.factory(      "metrics",                 metrics::make                );
+    add.factory(      "file",                    file::make                   );
+    add.factory(      "cache",                   cache::make                  );
+    add.factory(      "snapshot",                snapshot::make               );
+    add.factory(      "time",                    time::make                   );
+    add.factory(      "timer",                   timer::make                  );
+    add.factory(      "network",                 network::make                );
+    add.factory(      "inspect",                 inspect::make                );
+    add.factory(      "json",                    json::make                   );
+    add.factory(      "environment",             environment::make            );
+    add.factory(      "arguments",               arguments::make              );
+    add.factory(      "process",                 process::make                );
+    add.factory(      "remote",                  remote::make                 );
     add.factory(      "void",                    void                         );
     add.oneshot(      "stop",                    stop                         );
+    add.oneshot(      "shutdown",                shutdown                     );
     add.call_pattern( "branch",                  branch, 1                    );
+    add.call_pattern( "broadcast",               broadcast, 1                 );
   }
 
-  Thing::tagged(implementation, "(implementation)")
+  Thing::frozen(implementation, "(implementation)")
 }
 
 /// Acts as a void, accepting and discarding objects and then returning itself
@@ -91,6 +121,21 @@ pub fn stop(reactor: &mut Reactor, _response: ObjectRef) {
   reactor.stop()
 }
 
+/// How many more stagings `shutdown` lets the reactors realize before they
+/// stop.
+pub static SHUTDOWN_LIMIT: uint = 100000;
+
+/// Halts the machine gracefully, realizing what has already been staged (up
+/// to `SHUTDOWN_LIMIT` stagings) and running the machine's finalizers first.
+/// See `Reactor::shutdown()`. The response is ignored.
+///
+/// # Example
+///
+///     implementation shutdown[]
+pub fn shutdown(reactor: &mut Reactor, _response: ObjectRef) {
+  reactor.shutdown(SHUTDOWN_LIMIT)
+}
+
 /// Clones an Execution. If the Execution is the caller, both the caller and the
 /// clone are staged with each other.
 ///
@@ -109,9 +154,10 @@ pub fn branch(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
         Some(clone) => clone,
 
         None => {
-          warn!(concat!("tried to branch {}, which is neither",
-                        " an execution nor an alien"),
-                executionish);
+          machine_warn!(reactor.machine(), "implementation",
+                        concat!("tried to branch {}, which is neither",
+                                " an execution nor an alien"),
+                        executionish);
 
           return
         }
@@ -132,6 +178,65 @@ pub fn branch(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
         reactor.stage(caller, clone)
       }
     },
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
+  }
+}
+
+/// Creates a broadcaster for a list of stageables.
+///
+/// # Call pattern arguments
+///
+/// 1. A list of stageables: a Thing whose members are Executions or Aliens.
+///
+/// # Queueing semantics
+///
+/// The caller is resumed with the broadcaster, which accepts responses
+/// indefinitely. Each response it receives is staged into every stageable in
+/// the list, in order.
+///
+/// The list's members are read again for every response, so further stageables
+/// can be registered just by adding them to the list.
+///
+/// # Example
+///
+///     implementation broadcast[] list
+pub fn broadcast(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref list] => reactor.stage(caller, broadcaster(list.clone())),
+
+    _ => wrong_arguments!()
   }
 }
+
+/// Creates a broadcaster Alien for `list` directly, for use from native code.
+/// See `broadcast()`.
+pub fn broadcaster(list: ObjectRef) -> ObjectRef {
+  #[deriving(Clone)]
+  struct BroadcastList(ObjectRef);
+
+  fn broadcast_routine<'a>(
+                       alien:    TypedRefGuard<'a, Alien>,
+                       reactor:  &mut Reactor,
+                       response: ObjectRef) {
+
+    let list = match alien.data.downcast_ref::<BroadcastList>() {
+      Some(&BroadcastList(ref list)) => list.clone(),
+
+      None =>
+        fail!("broadcast_routine called on a non-broadcaster() Alien!")
+    };
+
+    drop(alien);
+
+    let stageables: Vec<ObjectRef> =
+      list.lock().meta().members.iter()
+        .filter_map(|member| member.as_ref().map(|r| r.to().clone()))
+        .collect();
+
+    for stageable in stageables.move_iter() {
+      reactor.stage(stageable, response.clone());
+    }
+  }
+
+  Alien::create("broadcaster", broadcast_routine, box BroadcastList(list))
+}
@@ -0,0 +1,331 @@
+//! TCP networking that doesn't block the reactor.
+//!
+//! Like `implementation file`, each alien here returns to the reactor straight
+//! away and stages the caller with the result later, or warns and never
+//! stages it if the operation fails. Since a read from a socket can wait on
+//! the other end indefinitely, each operation runs on a task of its own rather
+//! than on the machine's `BlockingPool`. The reactor counts it as outstanding
+//! work until it finishes, so waiting on a socket isn't mistaken for a stall.
+
+#![allow(unused_variable)]
+
+use object::{ObjectRef, Meta};
+
+use nuketype::{Nuketype, Thing};
+
+use machine::{Machine, Reactor};
+use machine::reactor::{Remote, current_span, set_current_span};
+
+use system::infrastructure::unsignedish;
+
+use util::namespace::NamespaceBuilder;
+
+use std::io::{IoResult, EndOfFile};
+use std::io::net::tcp::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::task::TaskBuilder;
+
+#[cfg(test)]
+mod tests;
+
+/// Generates an `implementation network` namespace object.
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut network = Meta::new();
+
+  {
+    let mut add = NamespaceBuilder::new(machine, &mut network);
+
+    add.call_pattern( "connect",                 connect, 2                   );
+    add.call_pattern( "read",                    read, 2                      );
+    add.call_pattern( "write",                   write, 2                     );
+    add.call_pattern( "close",                   close, 1                     );
+  }
+
+  Thing::frozen(network, "(impl. network)")
+}
+
+/// An open TCP connection, as returned by `connect()`. Closing it with
+/// `close()` leaves the handle behind, but any further I/O on it fails.
+pub struct TcpHandle {
+  peer:   String,
+  stream: Arc<Mutex<Option<TcpStream>>>
+}
+
+impl TcpHandle {
+  /// Boxes up a handle to an already open connection. `peer` describes the
+  /// other end, for display.
+  pub fn create(peer: String, stream: TcpStream) -> ObjectRef {
+    ObjectRef::store(box TcpHandle {
+      peer:   peer,
+      stream: Arc::new(Mutex::new(Some(stream)))
+    }, Meta::new())
+  }
+
+  /// The other end of the connection, as `host:port`.
+  pub fn peer<'a>(&'a self) -> &'a str {
+    self.peer.as_slice()
+  }
+
+  /// Whether the connection has been closed.
+  pub fn is_closed(&self) -> bool {
+    self.stream.lock().is_none()
+  }
+}
+
+impl Nuketype for TcpHandle {
+  fn fmt_paws(&self, writer: &mut Writer) -> IoResult<()> {
+    write!(writer, "TcpHandle[{}{}]",
+           self.peer,
+           if self.is_closed() { ", closed" } else { "" })
+  }
+}
+
+/// Opens a TCP connection. Responds with a handle to it.
+///
+/// # Call-pattern arguments
+///
+/// 1. The host to connect to, as a Symbol.
+/// 2. The port, as a Number or a decimal Symbol.
+///
+/// # Example
+///
+///     implementation network connect "example.com" 80
+pub fn connect(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref host, ref port] => {
+      let host = match host.symbol_ref() {
+        Some(host) => host.clone(),
+
+        None => {
+          machine_warn!(reactor.machine(), "implementation",
+                        "tried to connect[] to a non-symbol host");
+          return
+        }
+      };
+
+      let port = match unsignedish(port) {
+        Some(port) if port <= 65535 => port as u16,
+
+        _ => {
+          machine_warn!(reactor.machine(), "implementation",
+                        "tried to connect[] to a bad port {}", port);
+          return
+        }
+      };
+
+      in_task(reactor, caller, proc(machine) {
+        let peer = format!("{}:{}", host, port);
+
+        TcpStream::connect(host.as_slice(), port)
+          .map(|stream| TcpHandle::create(peer.clone(), stream))
+          .map_err(|error| format!("couldn't connect to {}: {}",
+                                   peer, error))
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Reads up to a given number of bytes from a connection, once some are
+/// available. Responds with what was read as a Symbol, which is empty once the
+/// other end has closed the connection.
+///
+/// Invalid UTF-8 (including a character split across two reads) is replaced
+/// with U+FFFD.
+///
+/// # Call-pattern arguments
+///
+/// 1. The handle, from `connect()`.
+/// 2. The maximum number of bytes to read, as a Number or a decimal Symbol.
+///
+/// # Example
+///
+///     implementation network read [handle] 4096
+pub fn read(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref handle, ref count] => {
+      let count = match unsignedish(count) {
+        Some(count) => count,
+
+        None => {
+          machine_warn!(reactor.machine(), "implementation",
+                        "tried to read[] a bad byte count {}", count);
+          return
+        }
+      };
+
+      let (peer, stream) = match stream_of(reactor, handle, "read") {
+        Some(handle) => handle,
+        None         => return
+      };
+
+      in_task(reactor, caller, proc(machine) {
+        let mut stream = stream;
+        let mut buffer = Vec::from_elem(count, 0u8);
+
+        let result = match stream.read(buffer.as_mut_slice()) {
+          Ok(read) => {
+            buffer.truncate(read);
+            Ok(())
+          },
+
+          Err(ref error) if error.kind == EndOfFile => {
+            buffer.truncate(0);
+            Ok(())
+          },
+
+          Err(error) =>
+            Err(format!("I/O on {} failed: {}", peer, error))
+        };
+
+        result.map(|()| {
+          machine.symbol(String::from_utf8_lossy(buffer.as_slice()).as_slice())
+        })
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Writes a Symbol to a connection. Responds with the handle.
+///
+/// # Call-pattern arguments
+///
+/// 1. The handle, from `connect()`.
+/// 2. The Symbol to write.
+///
+/// # Example
+///
+///     implementation network write [handle] "Hello, world!"
+pub fn write(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref handle_ref, ref label] => {
+      let label = match label.symbol_ref() {
+        Some(label) => label.clone(),
+
+        None => {
+          machine_warn!(reactor.machine(), "implementation",
+                        "tried to write[] a non-symbol");
+          return
+        }
+      };
+
+      let (peer, stream) = match stream_of(reactor, handle_ref, "write") {
+        Some(handle) => handle,
+        None         => return
+      };
+
+      let handle_ref = handle_ref.clone();
+
+      in_task(reactor, caller, proc(machine) {
+        let mut stream = stream;
+
+        stream.write_str(label.as_slice())
+          .and_then(|()| stream.flush())
+          .map(|()| handle_ref)
+          .map_err(|error| format!("I/O on {} failed: {}", peer, error))
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Closes a connection in both directions, which also ends any reads still
+/// waiting on it. Responds with the handle, which can't be used for I/O
+/// anymore.
+///
+/// # Call-pattern arguments
+///
+/// 1. The handle, from `connect()`.
+///
+/// # Example
+///
+///     implementation network close [handle]
+pub fn close(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref handle_ref] => {
+      let stream = match handle_ref.lock().try_cast::<TcpHandle>() {
+        Ok(handle) => handle.deref().stream.clone(),
+
+        Err(_) => {
+          machine_warn!(reactor.machine(), "implementation",
+                        "tried to close[] a non-connection {}", handle_ref);
+          return
+        }
+      };
+
+      let handle_ref = handle_ref.clone();
+
+      in_task(reactor, caller, proc(machine) {
+        match stream.lock().take() {
+          Some(mut stream) => {
+            // Other tasks may still hold clones of the stream, so dropping
+            // ours isn't enough to close it.
+            let _ = stream.close_read();
+            let _ = stream.close_write();
+
+            Ok(handle_ref)
+          },
+
+          None => Err(format!("{} is already closed", handle_ref))
+        }
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Runs `job` on a task of its own, then stages `caller` with the object it
+/// produces, or warns with the message it fails with. Otherwise the same as
+/// `implementation::file::in_background()`.
+fn in_task(reactor: &mut Reactor, caller: ObjectRef,
+           job: proc(Machine): Send -> Result<ObjectRef, String>) {
+
+  let remote: Remote = reactor.remote();
+  let machine        = reactor.machine().clone();
+  let span           = current_span();
+
+  TaskBuilder::new().named("network").spawn(proc() {
+    set_current_span(span);
+
+    match job(machine.clone()) {
+      Ok(response) =>
+        remote.stage(caller, response),
+
+      Err(message) =>
+        // Dropping the remote lets the reactor know not to wait for it.
+        machine_warn!(machine, "implementation", "{}", message)
+    }
+  });
+}
+
+/// Gets the peer and a clone of the stream out of a `TcpHandle`, warning if
+/// `object` isn't one or if it's closed.
+///
+/// The stream is cloned so that a read waiting on the other end doesn't keep
+/// the handle locked against writes and `close()`.
+fn stream_of(reactor: &mut Reactor, object: &ObjectRef, routine: &str)
+             -> Option<(String, TcpStream)> {
+
+  match object.lock().try_cast::<TcpHandle>() {
+    Ok(handle) => {
+      let handle = handle.deref();
+
+      match *handle.stream.lock() {
+        Some(ref stream) => Some((handle.peer.clone(), stream.clone())),
+
+        None => {
+          machine_warn!(reactor.machine(), "implementation",
+                        "tried to {}[] on closed {}", routine, handle.peer);
+          None
+        }
+      }
+    },
+
+    Err(_) => {
+      machine_warn!(reactor.machine(), "implementation",
+                    "tried to {}[] a non-connection {}", routine, object);
+      None
+    }
+  }
+}
@@ -0,0 +1,128 @@
+use super::{connect, read, write, close, TcpHandle};
+
+use object::ObjectRef;
+
+use nuketype::{Thing, Number};
+
+use machine::{Machine, Reactor};
+use machine::reactor::MockReactor;
+
+use util;
+
+use std::io::{Listener, Acceptor};
+use std::io::net::tcp::TcpListener;
+
+/// Calls `routine` and waits for it to stage the caller, if it does.
+fn respond(reactor: &mut MockReactor,
+           routine: fn(&mut Reactor, ObjectRef, &[ObjectRef]),
+           args: &[ObjectRef]) -> Option<ObjectRef> {
+
+  let caller = Thing::empty();
+
+  routine(reactor, caller.clone(), args);
+
+  assert_eq!(1, reactor.outstanding);
+
+  if reactor.receive_remote() {
+    reactor.stagings.remove(0).map(|(execution, response)| {
+      assert!(execution == caller);
+      response
+    })
+  } else {
+    None
+  }
+}
+
+fn label(object: &ObjectRef) -> String {
+  object.symbol_ref().expect("not a Symbol").as_slice().to_string()
+}
+
+/// Listens on a free local port, and echoes back everything sent to the first
+/// connection. Returns the port.
+fn echo_server() -> u16 {
+  let listener = TcpListener::bind("127.0.0.1", 0).unwrap();
+  let port     = listener.socket_name().unwrap().port;
+
+  let mut acceptor = listener.listen().unwrap();
+
+  spawn(proc() {
+    let mut stream = acceptor.accept().unwrap();
+    let mut buffer = [0u8, ..64];
+
+    loop {
+      match stream.read(buffer) {
+        Ok(read) => stream.write(buffer.slice_to(read)).unwrap(),
+        Err(_)   => break
+      }
+    }
+  });
+
+  port
+}
+
+#[test]
+fn write_then_read() {
+  util::timeout(1000, proc() {
+    let     machine = Machine::new();
+    let mut reactor = MockReactor::new(machine.clone());
+
+    let port = echo_server();
+
+    let handle = respond(&mut reactor, connect,
+                         [machine.symbol("127.0.0.1"),
+                          Number::create(port as i64)])
+                   .expect("connect failed");
+
+    let written = respond(&mut reactor, write,
+                          [handle.clone(), machine.symbol("ping")])
+                    .expect("write failed");
+
+    assert!(written == handle);
+
+    let echo = respond(&mut reactor, read, [handle.clone(), Number::create(4)])
+                 .expect("read failed");
+
+    assert_eq!("ping".to_string(), label(&echo));
+
+    respond(&mut reactor, close, [handle.clone()]).expect("close failed");
+
+    assert!(handle.lock().try_cast::<TcpHandle>().unwrap().deref()
+              .is_closed());
+  })
+}
+
+#[test]
+fn failed_connections_drop_the_caller() {
+  util::timeout(1000, proc() {
+    let     machine = Machine::new();
+    let mut reactor = MockReactor::new(machine.clone());
+
+    // Nothing is listening here once the listener is dropped.
+    let port = {
+      let listener = TcpListener::bind("127.0.0.1", 0).unwrap();
+      listener.socket_name().unwrap().port
+    };
+
+    assert!(respond(&mut reactor, connect,
+                    [machine.symbol("127.0.0.1"),
+                     Number::create(port as i64)]).is_none());
+
+    assert_eq!(0, reactor.outstanding);
+  })
+}
+
+#[test]
+fn bad_arguments_are_refused_up_front() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  // None of these should promise anything.
+  connect(&mut reactor, Thing::empty(),
+          [machine.symbol("localhost"), Number::create(70000)]);
+  connect(&mut reactor, Thing::empty(),
+          [Thing::empty(), Number::create(80)]);
+  read(&mut reactor, Thing::empty(), [Thing::empty(), Number::create(1)]);
+
+  assert!(reactor.stagings.is_empty());
+  assert_eq!(0, reactor.outstanding);
+}
@@ -0,0 +1,139 @@
+//! Running other programs.
+//!
+//! Like `implementation network`, a child runs on a task of its own, since it
+//! may take any amount of time to finish. The reactor counts it as outstanding
+//! work until it does, so waiting on a child isn't mistaken for a stall.
+
+#![allow(unused_variable)]
+
+use object::{ObjectRef, Meta};
+
+use nuketype::Thing;
+
+use machine::{Machine, Reactor};
+use machine::reactor::{Remote, current_span, set_current_span};
+
+use util::namespace::NamespaceBuilder;
+use util::error;
+
+use std::io::process::{Command, ExitStatus, ExitSignal};
+use std::str;
+use std::task::TaskBuilder;
+
+#[cfg(test)]
+mod tests;
+
+/// Generates an `implementation process` namespace object.
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut process = Meta::new();
+
+  {
+    let mut add = NamespaceBuilder::new(machine, &mut process);
+
+    add.call_pattern( "spawn",                   spawn, 2                     );
+  }
+
+  Thing::frozen(process, "(impl. process)")
+}
+
+/// Runs a program to completion, and responds with an object with these
+/// pairs:
+///
+/// * `stdout` and `stderr`: everything the program wrote to each, as Symbols.
+///   Anything that isn't UTF-8 is replaced.
+/// * `status`: the exit code, as a Symbol of a decimal number, if it exited.
+/// * `signal`: the signal number, likewise, if it was killed by a signal
+///   instead.
+///
+/// Standard input is empty. Responds with an error if the program couldn't be
+/// started at all.
+///
+/// # Call-pattern arguments
+///
+/// 1. The program to run, as a Symbol. Looked up in `PATH` if it isn't a path.
+/// 2. A list of arguments to give it: a Thing whose members are Symbols.
+///
+/// # Example
+///
+///     implementation process spawn "ls" [, "-l"]
+pub fn spawn(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref program, ref arguments] => {
+      let program = match program.symbol_ref() {
+        Some(program) => program.as_slice().to_string(),
+
+        None => {
+          respond_error!(reactor, caller, "implementation",
+                         "tried to process spawn[] a non-symbol program");
+          return
+        }
+      };
+
+      let arguments: Vec<ObjectRef> =
+        arguments.lock().meta().members.iter().skip(1)
+          .filter_map(|member| member.as_ref().map(|r| r.to().clone()))
+          .collect();
+
+      let mut strings = Vec::with_capacity(arguments.len());
+
+      for argument in arguments.iter() {
+        match argument.symbol_ref() {
+          Some(string) => strings.push(string.as_slice().to_string()),
+
+          None => {
+            respond_error!(reactor, caller, "implementation",
+                           "tried to process spawn[] {} with non-symbol \
+                            argument {}", program, argument);
+            return
+          }
+        }
+      }
+
+      let remote: Remote = reactor.remote();
+      let machine        = reactor.machine().clone();
+      let span           = current_span();
+
+      TaskBuilder::new().named("process").spawn(proc() {
+        set_current_span(span);
+
+        let response = match Command::new(program.as_slice())
+                               .args(strings.as_slice()).output() {
+          Ok(output) => {
+            let mut meta = Meta::new();
+
+            meta.members.push_pair(machine.symbol("stdout"),
+              machine.symbol(str::from_utf8_lossy(output.output.as_slice())
+                               .as_slice()));
+
+            meta.members.push_pair(machine.symbol("stderr"),
+              machine.symbol(str::from_utf8_lossy(output.error.as_slice())
+                               .as_slice()));
+
+            let (key, value) = match output.status {
+              ExitStatus(code)   => ("status", code),
+              ExitSignal(signal) => ("signal", signal)
+            };
+
+            let value = value.to_string();
+
+            meta.members.push_pair(machine.symbol(key),
+                                   machine.symbol(value.as_slice()));
+
+            Thing::tagged(meta, "(process result)")
+          },
+
+          Err(e) => {
+            let message = format!("couldn't spawn {}: {}", program, e);
+
+            machine_warn!(machine, "implementation", "{}", message);
+
+            error::create(&machine, "implementation", message.as_slice())
+          }
+        };
+
+        remote.stage(caller, response)
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
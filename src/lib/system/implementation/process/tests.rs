@@ -0,0 +1,67 @@
+use super::spawn;
+
+use object::{ObjectRef, Meta};
+
+use nuketype::Thing;
+
+use machine::Machine;
+use machine::reactor::MockReactor;
+
+use util::error;
+
+/// Calls `spawn()` and waits for it to stage the caller.
+fn run(reactor: &mut MockReactor, program: &str, arguments: &[&str])
+       -> ObjectRef {
+
+  let machine = reactor.machine.clone();
+
+  let mut meta = Meta::new();
+
+  meta.members.expand_to(1);
+
+  for argument in arguments.iter() {
+    meta.members.push(machine.symbol(*argument));
+  }
+
+  let caller = Thing::empty();
+
+  spawn(reactor, caller.clone(),
+        [machine.symbol(program), Thing::create(meta)]);
+
+  assert!(reactor.receive_remote());
+
+  let (execution, response) = reactor.stagings.remove(0).expect("no response");
+
+  assert!(execution == caller);
+
+  response
+}
+
+fn pair(machine: &Machine, object: &ObjectRef, key: &str) -> Option<String> {
+  object.lock().meta().members.lookup_pair(&machine.symbol(key))
+    .and_then(|value| value.symbol_ref().map(|s| s.as_slice().to_string()))
+}
+
+#[test]
+fn spawn_responds_with_output_and_status() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let result = run(&mut reactor, "sh",
+                   ["-c", "echo out; echo err >&2; exit 3"]);
+
+  assert_eq!(pair(&machine, &result, "stdout"), Some("out\n".to_string()));
+  assert_eq!(pair(&machine, &result, "stderr"), Some("err\n".to_string()));
+  assert_eq!(pair(&machine, &result, "status"), Some("3".to_string()));
+  assert_eq!(pair(&machine, &result, "signal"), None);
+}
+
+#[test]
+fn spawn_responds_with_error_if_program_is_missing() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let result = run(&mut reactor, "/nonexistent/paws-test-program", []);
+
+  assert!(error::is_error(&result));
+}
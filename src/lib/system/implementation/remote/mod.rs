@@ -0,0 +1,293 @@
+//! Staging work on other Machines, in the same process (see `machine::bridge`)
+//! or in others over TCP (see `machine::remote`).
+
+#![allow(unused_variable)]
+
+use object::{ObjectRef, Meta};
+
+use nuketype::Thing;
+
+use machine::{Machine, Reactor};
+use machine::reactor::{Remote, current_span, set_current_span};
+use machine::remote::{Listener, Client};
+
+use system::infrastructure::unsignedish;
+
+use format::json::to_json_value;
+
+use util::namespace::NamespaceBuilder;
+use util::error;
+
+use std::task::TaskBuilder;
+
+#[cfg(test)]
+mod tests;
+
+/// Generates an `implementation remote` namespace object.
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut remote = Meta::new();
+
+  {
+    let mut add = NamespaceBuilder::new(machine, &mut remote);
+
+    add.call_pattern( "stage",                   stage, 2                     );
+    add.call_pattern( "entry",                   entry, 1                     );
+    add.call_pattern( "advertise",               advertise, 2                 );
+    add.call_pattern( "serve",                   serve, 2                     );
+    add.call_pattern( "send",                    send, 4                      );
+  }
+
+  Thing::frozen(remote, "(impl. remote)")
+}
+
+/// Sends a message to a peer Machine, which stages a clone of its entry with
+/// it, and responds with the message. The message has to be plain data
+/// (Symbols, Numbers, and Things of them; see `format::json`), and is rebuilt
+/// within the peer. Responds with an error if there's no such peer, or if the
+/// message can't be sent.
+///
+/// # Call-pattern arguments
+///
+/// 1. The name of the peer, as a Symbol.
+/// 2. The message.
+///
+/// # Example
+///
+///     implementation remote stage "worker" [, "job" "42"]
+pub fn stage(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref peer, ref message] => {
+      let peer = match peer.symbol_ref() {
+        Some(peer) => peer.clone(),
+
+        None => {
+          respond_error!(reactor, caller, "implementation",
+                         "tried to remote stage[] to a non-symbol peer");
+          return
+        }
+      };
+
+      let sent = reactor.machine().bridges.send(peer.as_slice(), message);
+
+      match sent {
+        Ok(()) =>
+          reactor.stage(caller, message.clone()),
+
+        Err(problem) =>
+          respond_error!(reactor, caller, "implementation",
+                         "remote stage[] failed: {}", problem)
+      }
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Sets the Execution (or Alien) that this Machine stages a clone of with each
+/// message sent to it by its peers, and starts listening for them. Responds
+/// with the entry.
+///
+/// While listening, the Machine doesn't stall, since another message could
+/// always come in.
+///
+/// # Call-pattern arguments
+///
+/// 1. The entry.
+///
+/// # Example
+///
+///     implementation remote entry[] {
+///       implementation console print [locals]
+///     }
+pub fn entry(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref execution] => {
+      let bridges = reactor.machine().bridges.clone();
+
+      bridges.set_entry(execution.clone());
+      bridges.listen(reactor);
+
+      reactor.stage(caller, execution.clone())
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Advertises an Execution (or Alien) under a name, for other processes to
+/// stage messages against through `serve()`, and starts listening for them.
+/// Responds with the entry.
+///
+/// # Call-pattern arguments
+///
+/// 1. The name, as a Symbol.
+/// 2. The entry.
+///
+/// # Example
+///
+///     implementation remote advertise[] "greet" {
+///       implementation console print [locals]
+///     }
+pub fn advertise(reactor: &mut Reactor, caller: ObjectRef,
+                 args: &[ObjectRef]) {
+  match args {
+    [ref name, ref execution] => {
+      let name = match name.symbol_ref() {
+        Some(name) => name.clone(),
+
+        None => {
+          respond_error!(reactor, caller, "implementation",
+                         "tried to advertise[] a non-symbol name");
+          return
+        }
+      };
+
+      let bridges = reactor.machine().bridges.clone();
+
+      bridges.advertise(name.as_slice(), execution.clone());
+      bridges.listen(reactor);
+
+      reactor.stage(caller, execution.clone())
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Starts accepting connections from other processes, which can stage
+/// messages against this Machine's advertised entries (see `advertise()`).
+/// Responds with the port, which is useful with port 0. The listener is
+/// closed when the reactors shut down.
+///
+/// # Call-pattern arguments
+///
+/// 1. The host to listen on, as a Symbol.
+/// 2. The port, as a Number or a decimal Symbol.
+///
+/// # Example
+///
+///     implementation remote serve "127.0.0.1" 7474
+pub fn serve(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref host, ref port] => {
+      let (host, port) = match host_and_port(host, port) {
+        Ok(address) => address,
+
+        Err(problem) => {
+          respond_error!(reactor, caller, "implementation",
+                         "remote serve[] failed: {}", problem);
+          return
+        }
+      };
+
+      let bound = Listener::bind(reactor.machine(), host.as_slice(), port);
+
+      match bound {
+        Ok(listener) => {
+          let port = listener.address().port;
+
+          reactor.machine().finalizers.register(proc (_) {
+            let mut listener = listener;
+
+            listener.close()
+          });
+
+          let response = reactor.machine().number(port as i64);
+
+          reactor.stage(caller, response)
+        },
+
+        Err(error) =>
+          respond_error!(reactor, caller, "implementation",
+                         "remote serve[] on {}:{} failed: {}",
+                         host, port, error)
+      }
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Stages a message against an entry advertised by a Machine in another
+/// process, and responds with the message once it has been queued there. The
+/// message has to be plain data, as with `stage()`. Responds with an error if
+/// it can't be converted, or if it can't be delivered.
+///
+/// Each message goes over a connection of its own, on a task of its own, so
+/// the reactor doesn't wait on the network.
+///
+/// # Call-pattern arguments
+///
+/// 1. The host the other process is serving on, as a Symbol.
+/// 2. The port, as a Number or a decimal Symbol.
+/// 3. The name of the entry, as a Symbol.
+/// 4. The message.
+///
+/// # Example
+///
+///     implementation remote send "127.0.0.1" 7474 "greet" [, "name" "Paws"]
+pub fn send(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref host, ref port, ref entry, ref message] => {
+      let prepared = host_and_port(host, port).and_then(|(host, port)| {
+        let entry = match entry.symbol_ref() {
+          Some(entry) => entry.as_slice().to_string(),
+          None        => return Err("the entry must be a Symbol".to_string())
+        };
+
+        to_json_value(message).map(|json| (host, port, entry, json))
+      });
+
+      let (host, port, entry, json) = match prepared {
+        Ok(prepared) => prepared,
+
+        Err(problem) => {
+          respond_error!(reactor, caller, "implementation",
+                         "remote send[] failed: {}", problem);
+          return
+        }
+      };
+
+      let remote: Remote = reactor.remote();
+      let machine        = reactor.machine().clone();
+      let span           = current_span();
+      let message        = message.clone();
+
+      TaskBuilder::new().named("remote send").spawn(proc() {
+        set_current_span(span);
+
+        let sent = Client::connect(host.as_slice(), port)
+          .map_err(|error| format!("couldn't connect to {}:{}: {}",
+                                   host, port, error))
+          .and_then(|mut client| client.stage_json(entry.as_slice(), json));
+
+        match sent {
+          Ok(()) =>
+            remote.stage(caller, message),
+
+          Err(problem) => {
+            let problem = format!("remote send[] failed: {}", problem);
+
+            machine_warn!(machine, "implementation", "{}", problem);
+
+            remote.stage(caller, error::create(&machine, "implementation",
+                                               problem.as_slice()))
+          }
+        }
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Checks that `host` is a Symbol and `port` is a valid port.
+fn host_and_port(host: &ObjectRef, port: &ObjectRef)
+                 -> Result<(String, u16), String> {
+
+  let host = match host.symbol_ref() {
+    Some(host) => host.as_slice().to_string(),
+    None       => return Err("the host must be a Symbol".to_string())
+  };
+
+  match unsignedish(port) {
+    Some(port) if port <= 65535 => Ok((host, port as u16)),
+
+    _ => Err(format!("bad port {}", port))
+  }
+}
@@ -0,0 +1,148 @@
+use super::{stage, entry, advertise, serve, send};
+
+use nuketype::{Thing, Execution, Number};
+
+use script::Script;
+
+use machine::Machine;
+use machine::bridge::connect;
+use machine::reactor::MockReactor;
+
+use system::infrastructure::unsignedish;
+
+use util;
+use util::error;
+
+#[test]
+fn stage_sends_to_the_peer() {
+  let a = Machine::new();
+  let b = Machine::new();
+
+  connect(&a, "a", &b, "b");
+
+  let mut reactor = MockReactor::new(a.clone());
+  let mut peer    = MockReactor::new(b.clone());
+
+  let caller  = Thing::empty();
+  let message = a.symbol("hello");
+
+  stage(&mut reactor, caller.clone(), [a.symbol("b"), message.clone()]);
+
+  reactor.expect_stage(&caller, &message);
+  reactor.expect_no_stagings();
+
+  b.bridges.listen(&mut peer);
+
+  assert!(peer.receive_remote());
+
+  let (_, received) = peer.stagings.remove(0).unwrap();
+
+  assert!(received.eq_as_symbol(&b.symbol("hello")));
+}
+
+#[test]
+fn stage_responds_with_error_without_peer() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  stage(&mut reactor, Thing::empty(),
+        [machine.symbol("nobody"), machine.symbol("hello")]);
+
+  let (_, response) = reactor.stagings.remove(0).expect("no response");
+
+  assert!(error::is_error(&response));
+}
+
+#[test]
+fn entry_sets_entry_and_listens() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller    = Thing::empty();
+  let execution = Execution::create(&machine, Script(vec![]));
+
+  entry(&mut reactor, caller.clone(), [execution.clone()]);
+
+  reactor.expect_stage(&caller, &execution);
+
+  assert!(machine.bridges.entry() == Some(execution));
+  assert_eq!(1, reactor.outstanding);
+
+  machine.bridges.close();
+
+  assert!(!reactor.receive_remote());
+}
+
+#[test]
+fn advertise_advertises_and_listens() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller    = Thing::empty();
+  let execution = Execution::create(&machine, Script(vec![]));
+
+  advertise(&mut reactor, caller.clone(),
+            [machine.symbol("job"), execution.clone()]);
+
+  reactor.expect_stage(&caller, &execution);
+
+  assert!(machine.bridges.advertised_entry("job") == Some(execution));
+  assert!(machine.bridges.is_listening());
+
+  machine.bridges.close();
+
+  assert!(!reactor.receive_remote());
+}
+
+#[test]
+fn send_stages_on_a_serving_machine() {
+  util::timeout(1000, proc() {
+    let a = Machine::new();
+    let b = Machine::new();
+
+    let mut reactor = MockReactor::new(a.clone());
+    let mut server  = MockReactor::new(b.clone());
+
+    b.bridges.advertise("job", Thing::empty());
+
+    let caller = Thing::empty();
+
+    serve(&mut server, caller.clone(),
+          [b.symbol("127.0.0.1"), Number::create(0)]);
+
+    let (_, port) = server.stagings.remove(0).expect("no response");
+    let port      = unsignedish(&port).expect("port isn't a number");
+
+    assert_eq!(1, b.finalizers.len());
+
+    let message = a.symbol("hello");
+
+    send(&mut reactor, caller.clone(),
+         [a.symbol("127.0.0.1"), Number::create(port as i64),
+          a.symbol("job"), message.clone()]);
+
+    assert!(reactor.receive_remote());
+
+    reactor.expect_stage(&caller, &message);
+
+    send(&mut reactor, caller.clone(),
+         [a.symbol("127.0.0.1"), Number::create(port as i64),
+          a.symbol("nobody"), message.clone()]);
+
+    assert!(reactor.receive_remote());
+
+    let (_, response) = reactor.stagings.remove(0).expect("no response");
+
+    assert!(error::is_error(&response));
+
+    b.bridges.listen(&mut server);
+
+    assert!(server.receive_remote());
+
+    let (_, received) = server.stagings.remove(0).unwrap();
+
+    assert!(received.eq_as_symbol(&b.symbol("hello")));
+
+    b.finalizers.run(&mut server);
+  })
+}
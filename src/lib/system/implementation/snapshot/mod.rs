@@ -0,0 +1,114 @@
+//! Saving objects to files and loading them back, possibly in another run. See
+//! `util::serialize` for what can be saved.
+//!
+//! Like `implementation file`, the I/O happens off the reactor.
+
+#![allow(unused_variable)]
+
+use object::{ObjectRef, Meta};
+
+use nuketype::Thing;
+
+use machine::{Machine, Reactor};
+
+use system::implementation::file::in_background;
+
+use util::namespace::NamespaceBuilder;
+use util::serialize;
+
+use std::io::File;
+
+#[cfg(test)]
+mod tests;
+
+/// Generates an `implementation snapshot` namespace object.
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut snapshot = Meta::new();
+
+  {
+    let mut add = NamespaceBuilder::new(machine, &mut snapshot);
+
+    add.call_pattern( "save",                    save, 2                      );
+    add.call_pattern( "load",                    load, 1                      );
+  }
+
+  Thing::frozen(snapshot, "(impl. snapshot)")
+}
+
+/// Saves an object, and everything reachable from it, to a file as JSON.
+/// Responds with the object.
+///
+/// # Call-pattern arguments
+///
+/// 1. The object to save.
+/// 2. The path to save it to, as a Symbol. The file is replaced if it exists.
+///
+/// # Example
+///
+///     implementation snapshot save [state] "state.json"
+pub fn save(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref root, ref path] => {
+      let path = match path.symbol_ref() {
+        Some(path) => Path::new(path.as_slice()),
+
+        None => {
+          machine_warn!(reactor.machine(), "implementation",
+                        "tried to snapshot save[] to a non-symbol path");
+          return
+        }
+      };
+
+      let root = root.clone();
+
+      in_background(reactor, caller, proc(machine) {
+        let result = File::create(&path).and_then(|mut file| {
+          serialize::write_json(&machine, &root, &mut file)
+        });
+
+        match result {
+          Ok(())     => Ok(root),
+          Err(error) => Err(format!("couldn't save snapshot to {}: {}",
+                                    path.display(), error))
+        }
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Loads an object saved with `save()`. Responds with the object.
+///
+/// # Call-pattern arguments
+///
+/// 1. The path to load from, as a Symbol.
+///
+/// # Example
+///
+///     implementation snapshot load "state.json"
+pub fn load(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref path] => {
+      let path = match path.symbol_ref() {
+        Some(path) => Path::new(path.as_slice()),
+
+        None => {
+          machine_warn!(reactor.machine(), "implementation",
+                        "tried to snapshot load[] from a non-symbol path");
+          return
+        }
+      };
+
+      in_background(reactor, caller, proc(machine) {
+        let result = match File::open(&path) {
+          Ok(mut file) => serialize::read_json(&machine, &mut file),
+          Err(error)   => Err(error.to_string())
+        };
+
+        result.map_err(|error| format!("couldn't load snapshot from {}: {}",
+                                       path.display(), error))
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
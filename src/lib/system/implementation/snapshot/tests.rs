@@ -0,0 +1,83 @@
+use super::{save, load};
+
+use object::{ObjectRef, Meta};
+
+use nuketype::{Thing, Number};
+
+use machine::{Machine, Reactor};
+use machine::reactor::MockReactor;
+
+use std::io::TempDir;
+
+/// Calls `routine` and waits for it to stage the caller, if it does.
+fn respond(reactor: &mut MockReactor,
+           routine: fn(&mut Reactor, ObjectRef, &[ObjectRef]),
+           args: &[ObjectRef]) -> Option<ObjectRef> {
+
+  let caller = Thing::empty();
+
+  routine(reactor, caller.clone(), args);
+
+  if reactor.receive_remote() {
+    reactor.stagings.remove(0).map(|(execution, response)| {
+      assert!(execution == caller);
+      response
+    })
+  } else {
+    None
+  }
+}
+
+#[test]
+fn save_then_load_in_another_machine() {
+  let dir  = TempDir::new("paws-snapshot").unwrap();
+  let path = dir.path().join("state.json");
+
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let mut meta = Meta::new();
+
+  meta.members.push_pair(machine.symbol("count"), Number::create(3));
+
+  let state = Thing::tagged(meta, "state");
+
+  let saved = respond(&mut reactor, save,
+                      [state.clone(), machine.symbol(path.as_str().unwrap())])
+                .expect("save failed");
+
+  assert!(saved == state);
+
+  let     other         = Machine::new();
+  let mut other_reactor = MockReactor::new(other.clone());
+
+  let loaded = respond(&mut other_reactor, load,
+                       [other.symbol(path.as_str().unwrap())])
+                 .expect("load failed");
+
+  let count = loaded.lock().meta().members
+                .lookup_pair(&other.symbol("count"))
+                .expect("count not restored");
+
+  assert_eq!(Some(3), Number::of(&count));
+}
+
+#[test]
+fn load_failures_drop_the_caller() {
+  let dir = TempDir::new("paws-snapshot").unwrap();
+
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let missing = dir.path().join("missing.json");
+
+  assert!(respond(&mut reactor, load,
+                  [machine.symbol(missing.as_str().unwrap())]).is_none());
+
+  let garbage = dir.path().join("garbage.json");
+
+  ::std::io::File::create(&garbage).write_str("not json").unwrap();
+
+  assert!(respond(&mut reactor, load,
+                  [machine.symbol(garbage.as_str().unwrap())]).is_none());
+}
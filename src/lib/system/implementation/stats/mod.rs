@@ -0,0 +1,101 @@
+//! Performance numbers for the reactor a program is running on.
+
+#![allow(unused_variable)]
+
+use object::{ObjectRef, Meta, CacheStats};
+
+use nuketype::Thing;
+
+use machine::{Machine, Reactor, ReactorStats};
+
+use util::namespace::NamespaceBuilder;
+
+/// Generates an `implementation stats` namespace object.
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut stats = Meta::new();
+
+  {
+    let mut add = NamespaceBuilder::new(machine, &mut stats);
+
+    add.call_pattern( "cache",                   cache, 0                     );
+    add.call_pattern( "reactor",                 reactor, 0                   );
+  }
+
+  Thing::frozen(stats, "(impl. stats)")
+}
+
+/// Responds with the current reactor's cache statistics, as an object with a
+/// pair for each counter. The values are Symbols of decimal numbers.
+///
+/// # Example
+///
+///     implementation stats cache[]
+pub fn cache(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  let stats = reactor.stats();
+
+  reactor.stage(caller, cache_object(reactor.machine(), &stats.cache))
+}
+
+/// Responds with the current reactor's statistics, as an object with a pair for
+/// each counter, plus `cache` for the cache statistics. See `cache()`.
+///
+/// Only covers the current reactor, even if it's part of a pool.
+///
+/// # Example
+///
+///     implementation stats reactor[]
+pub fn reactor(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  let stats = reactor.stats();
+
+  reactor.stage(caller, reactor_object(reactor.machine(), &stats))
+}
+
+/// Converts `CacheStats` to a Paws object. See `cache()`.
+pub fn cache_object(machine: &Machine, stats: &CacheStats) -> ObjectRef {
+  let mut meta = Meta::new();
+
+  push_counters(machine, &mut meta, [
+    ("sym-lookup-misses", stats.sym_lookup_misses),
+    ("sym-lookup-hits",   stats.sym_lookup_hits),
+    ("receiver-misses",   stats.receiver_misses),
+    ("receiver-hits",     stats.receiver_hits),
+    ("frozen-lookups",    stats.frozen_lookups),
+    ("params-reused",     stats.params_reused),
+    ("negative-hits",     stats.negative_hits),
+    ("shared-hits",       stats.shared_hits)
+  ]);
+
+  Thing::tagged(meta, "(cache stats)")
+}
+
+/// Converts `ReactorStats` to a Paws object. See `reactor()`.
+pub fn reactor_object(machine: &Machine, stats: &ReactorStats) -> ObjectRef {
+  let mut meta = Meta::new();
+
+  push_counters(machine, &mut meta, [
+    ("queue-depth", stats.queue_depth as u64),
+    ("stagings",    stats.stagings),
+    ("steps",       stats.steps),
+    ("executions",  stats.executions),
+    ("aliens",      stats.aliens),
+    ("stalls",      stats.stalls),
+    ("steals",      stats.steals),
+    ("local-hits",  stats.local_hits)
+  ]);
+
+  meta.members.push_pair(machine.symbol("cache"),
+                         cache_object(machine, &stats.cache));
+
+  Thing::tagged(meta, "(reactor stats)")
+}
+
+/// Pushes a pair for each counter onto `meta`, with the value as a Symbol of
+/// its decimal representation.
+pub fn push_counters(machine:  &Machine,
+                     meta:     &mut Meta,
+                     counters: &[(&str, u64)]) {
+  for &(name, value) in counters.iter() {
+    meta.members.push_pair(machine.symbol(name),
+                           machine.symbol(value.to_string().as_slice()));
+  }
+}
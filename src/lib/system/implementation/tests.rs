@@ -2,6 +2,8 @@ use system::implementation;
 
 use nuketype::{Thing, Alien};
 
+use object::CacheConfig;
+
 use machine::Machine;
 use machine::reactor::MockReactor;
 
@@ -58,3 +60,124 @@ fn stop_stops() {
   assert!(reactor.stagings.is_empty());
   assert!(reactor.alive == false);
 }
+
+#[test]
+fn shutdown_shuts_down() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  implementation::shutdown(&mut reactor, Thing::empty());
+
+  assert!(reactor.stagings.is_empty());
+  assert_eq!(Some(implementation::SHUTDOWN_LIMIT), reactor.shutdown_limit);
+}
+
+#[test]
+fn broadcast_fans_out() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller = Thing::empty();
+  let list   = Thing::empty();
+  let a      = Thing::empty();
+  let b      = Thing::empty();
+  let obj    = Thing::empty();
+
+  {
+    let mut list = list.lock();
+
+    list.meta_mut().members.push(a.clone());
+    list.meta_mut().members.push(b.clone());
+  }
+
+  implementation::broadcast(&mut reactor, caller.clone(), [list.clone()]);
+
+  let broadcaster = match reactor.stagings.remove(0) {
+    Some((execution, response)) => {
+      assert!(execution == caller);
+      response
+    },
+    None => fail!("stage() wasn't called")
+  };
+
+  Alien::realize(
+    broadcaster.lock().try_cast::<Alien>().ok().unwrap(),
+    &mut reactor,
+    obj.clone()
+  );
+
+  assert!(reactor.stagings == vec![(a.clone(), obj.clone()),
+                                   (b.clone(), obj.clone())]);
+
+  reactor.stagings.clear();
+
+  // Stageables added to the list later are included too.
+  let c = Thing::empty();
+
+  list.lock().meta_mut().members.push(c.clone());
+
+  Alien::realize(
+    broadcaster.lock().try_cast::<Alien>().ok().unwrap(),
+    &mut reactor,
+    obj.clone()
+  );
+
+  assert!(reactor.stagings == vec![(a, obj.clone()),
+                                   (b, obj.clone()),
+                                   (c, obj)]);
+}
+
+#[test]
+fn stats_cache_responds_with_counters() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller = Thing::empty();
+
+  implementation::stats::cache(&mut reactor, caller.clone(), []);
+
+  let stats = match reactor.stagings.remove(0) {
+    Some((execution, response)) => {
+      assert!(execution == caller);
+      response
+    },
+    None => fail!("stage() wasn't called")
+  };
+
+  let hits = stats.lock().meta().members
+               .lookup_pair(&machine.symbol("sym-lookup-hits"))
+               .expect("no sym-lookup-hits");
+
+  assert_eq!("0", hits.symbol_ref().unwrap().as_slice());
+}
+
+#[test]
+fn cache_sizes_follow_the_machine() {
+  let mut machine = Machine::new();
+
+  machine.cache_config = CacheConfig::new().with_sym_lookup_size(7);
+
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller = Thing::empty();
+
+  implementation::cache::sizes(&mut reactor, caller.clone(), []);
+
+  let sizes = match reactor.stagings.remove(0) {
+    Some((execution, response)) => {
+      assert!(execution == caller);
+      response
+    },
+    None => fail!("stage() wasn't called")
+  };
+
+  let size_of = |name: &str| {
+    sizes.lock().meta().members
+      .lookup_pair(&machine.symbol(name))
+      .expect("size missing")
+      .symbol_ref().unwrap().as_slice().to_string()
+  };
+
+  assert_eq!("7".to_string(),  size_of("sym-lookup-size"));
+  assert_eq!("64".to_string(), size_of("receiver-size"));
+}
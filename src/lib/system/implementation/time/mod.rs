@@ -0,0 +1,146 @@
+//! Time, and waiting for it to pass.
+//!
+//! Waiting happens on the Machine's timer (see `machine::timer`), which stages
+//! the caller through a `Remote` (see `machine::reactor::remote`) when the time
+//! is up. Until then, the reactor counts the timer as outstanding work, so it
+//! doesn't stall early.
+
+#![allow(unused_variable)]
+
+use object::{ObjectRef, Meta};
+
+use nuketype::{Thing, Number};
+
+use machine::{Machine, Reactor};
+
+use system::infrastructure::unsignedish;
+
+use util::namespace::NamespaceBuilder;
+
+use std::time::duration::Duration;
+
+use time;
+
+#[cfg(test)]
+mod tests;
+
+/// Generates an `implementation time` namespace object.
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut time = Meta::new();
+
+  {
+    let mut add = NamespaceBuilder::new(machine, &mut time);
+
+    add.call_pattern( "now",                     now, 0                       );
+    add.call_pattern( "delay",                   delay, 1                     );
+    add.call_pattern( "interval",                interval, 2                  );
+  }
+
+  Thing::frozen(time, "(impl. time)")
+}
+
+/// Responds with the current time, as a Number of milliseconds since the Unix
+/// epoch. Use `infrastructure number to-label` to get a Symbol.
+///
+/// # Example
+///
+///     implementation time now[]
+pub fn now(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  let now = time::get_time();
+
+  let millis = now.sec * 1000 + (now.nsec / 1000000) as i64;
+
+  reactor.stage(caller, Number::create(millis))
+}
+
+/// Responds with the given number of milliseconds, once that many have passed.
+///
+/// # Call-pattern arguments
+///
+/// 1. The number of milliseconds to wait, as a Number or a decimal Symbol.
+///
+/// # Example
+///
+///     implementation time delay 500
+pub fn delay(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref millis_ref] => {
+      let millis = match millis_of(reactor, millis_ref, "delay") {
+        Some(millis) => millis,
+        None         => return
+      };
+
+      let remote   = reactor.remote();
+      let response = millis_ref.clone();
+
+      reactor.machine().timer.after(Duration::milliseconds(millis), proc() {
+        remote.stage(caller, response);
+      });
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Responds every given number of milliseconds, a given number of times, with
+/// a Number counting up from 1. Like `implementation void`, this stages the
+/// same caller over and over.
+///
+/// # Call-pattern arguments
+///
+/// 1. The number of milliseconds between responses, as a Number or a decimal
+///    Symbol.
+/// 2. The number of times to respond, likewise.
+///
+/// # Example
+///
+///     implementation time interval[] 1000 10
+pub fn interval(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref millis_ref, ref count_ref] => {
+      let millis = match millis_of(reactor, millis_ref, "interval") {
+        Some(millis) => millis,
+        None         => return
+      };
+
+      let count = match unsignedish(count_ref) {
+        Some(count) => count,
+
+        None => {
+          machine_warn!(reactor.machine(), "implementation",
+                        "tried to interval[] a bad count {}", count_ref);
+          return
+        }
+      };
+
+      // Promise every response up front, so that the reactor waits for all of
+      // them. Each is scheduled from the start rather than from the last, so
+      // they don't drift.
+      for index in range(1, count + 1) {
+        let remote = reactor.remote();
+        let caller = caller.clone();
+        let delay  = Duration::milliseconds(millis) * index as i32;
+
+        reactor.machine().timer.after(delay, proc() {
+          remote.stage(caller, Number::create(index as i64));
+        });
+      }
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Gets a number of milliseconds, warning if `object` isn't one.
+pub fn millis_of(reactor: &mut Reactor, object: &ObjectRef, routine: &str)
+             -> Option<i32> {
+
+  match unsignedish(object) {
+    Some(millis) if millis <= ::std::i32::MAX as uint => Some(millis as i32),
+
+    _ => {
+      machine_warn!(reactor.machine(), "implementation",
+                    "tried to {}[] a bad number of milliseconds {}",
+                    routine, object);
+      None
+    }
+  }
+}
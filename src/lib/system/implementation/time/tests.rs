@@ -0,0 +1,86 @@
+use super::{now, delay, interval};
+
+use nuketype::{Thing, Number, Execution};
+
+use script::Script;
+
+use machine::{Machine, Reactor};
+use machine::reactor::{MockReactor, SerialReactor};
+
+use util;
+
+#[test]
+fn now_is_after_2014() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine);
+
+  now(&mut reactor, Thing::empty(), []);
+
+  let (_, response) = reactor.stagings.remove(0).expect("no response");
+
+  assert!(Number::of(&response).expect("not a Number") > 1388534400000);
+}
+
+#[test]
+fn delay_responds_later() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller = Thing::empty();
+  let millis = machine.symbol("10");
+
+  delay(&mut reactor, caller.clone(), [millis.clone()]);
+
+  assert!(reactor.stagings.is_empty());
+  assert_eq!(1, reactor.outstanding);
+
+  assert!(reactor.receive_remote());
+
+  assert!(reactor.stagings == vec![(caller, millis)]);
+}
+
+#[test]
+fn interval_responds_count_times() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine);
+
+  let caller = Thing::empty();
+
+  interval(&mut reactor, caller.clone(),
+           [Number::create(1), Number::create(3)]);
+
+  assert_eq!(3, reactor.outstanding);
+
+  for _ in range(0u, 3) {
+    assert!(reactor.receive_remote());
+  }
+
+  let ticks: Vec<Option<i64>> = reactor.stagings.iter()
+    .map(|&(_, ref response)| Number::of(response)).collect();
+
+  assert_eq!(vec![Some(1), Some(2), Some(3)], ticks);
+}
+
+#[test]
+fn pending_timers_hold_off_stalls() {
+  util::timeout(1000, proc() {
+    let     machine = Machine::new();
+    let mut reactor = SerialReactor::new(machine.clone());
+
+    let (tx, rx) = channel();
+
+    reactor.on_stall(proc (reactor) {
+      tx.send(reactor.stats().steps);
+      reactor.stop();
+    });
+
+    let caller = Execution::create(&machine, Script(vec![]));
+
+    delay(&mut reactor, caller, [Number::create(50)]);
+
+    reactor.run();
+
+    // The response from the delay was realized before the stall.
+    assert_eq!(1, rx.recv());
+  })
+}
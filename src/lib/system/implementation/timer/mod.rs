@@ -0,0 +1,71 @@
+//! Staging things later, on the Machine's timer. See `machine::timer`.
+
+#![allow(unused_variable)]
+
+use object::{ObjectRef, Meta};
+
+use nuketype::Thing;
+
+use machine::{Machine, Reactor};
+
+use system::implementation::time::millis_of;
+
+use util::namespace::NamespaceBuilder;
+
+use std::time::duration::Duration;
+
+#[cfg(test)]
+mod tests;
+
+/// Generates an `implementation timer` namespace object.
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut timer = Meta::new();
+
+  {
+    let mut add = NamespaceBuilder::new(machine, &mut timer);
+
+    add.call_pattern( "after",                   after, 2                     );
+  }
+
+  Thing::frozen(timer, "(impl. timer)")
+}
+
+/// Stages an execution with the given number of milliseconds once that many
+/// have passed, and responds with the execution right away. Until then, the
+/// reactor counts it as outstanding work, so it doesn't stall early.
+///
+/// Unlike `implementation time delay`, the caller doesn't wait.
+///
+/// # Call-pattern arguments
+///
+/// 1. The number of milliseconds to wait, as a Number or a decimal Symbol.
+/// 2. The execution to stage.
+///
+/// # Example
+///
+///     implementation timer after[] 500 [implementation console print done]
+pub fn after(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref millis_ref, ref execution] => {
+      let millis = match millis_of(reactor, millis_ref, "after") {
+        Some(millis) => millis,
+        None         => return
+      };
+
+      let remote    = reactor.remote();
+      let execution = execution.clone();
+      let response  = millis_ref.clone();
+
+      {
+        let execution = execution.clone();
+
+        reactor.machine().timer.after(Duration::milliseconds(millis), proc() {
+          remote.stage(execution, response);
+        });
+      }
+
+      reactor.stage(caller, execution);
+    },
+    _ => wrong_arguments!()
+  }
+}
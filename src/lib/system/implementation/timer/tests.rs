@@ -0,0 +1,27 @@
+use super::after;
+
+use nuketype::{Thing, Number};
+
+use machine::Machine;
+use machine::reactor::MockReactor;
+
+#[test]
+fn after_responds_now_and_stages_later() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine);
+
+  let caller    = Thing::empty();
+  let execution = Thing::empty();
+  let millis    = Number::create(10);
+
+  after(&mut reactor, caller.clone(), [millis.clone(), execution.clone()]);
+
+  reactor.expect_stage(&caller, &execution);
+  reactor.expect_no_stagings();
+
+  assert_eq!(1, reactor.outstanding);
+
+  assert!(reactor.receive_remote());
+
+  reactor.expect_stage(&execution, &millis);
+}
@@ -0,0 +1,112 @@
+//! Procedures for cloning objects, with explicit semantics.
+//!
+//! The namespace itself can still be used as the shallow `clone` call-pattern
+//! alien, for compatibility with programs that expect `infrastructure clone`
+//! to be an alien rather than a namespace.
+
+#![allow(unused_variable)]
+#![allow(missing_doc)]
+
+use object::{ObjectRef, Meta, Params};
+
+use nuketype::Thing;
+use nuketype::execution::stage_receiver;
+
+use machine::{Machine, Reactor};
+
+use util::namespace::NamespaceBuilder;
+use util;
+
+/// Generates an `infrastructure clone` namespace object.
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut clone = Meta::with_receiver(clone_receiver);
+
+  {
+    let mut add = NamespaceBuilder::new(machine, &mut clone);
+
+    add.call_pattern( "clone",                   shallow, 1                   );
+    add.call_pattern( "clone-stageable",         stageable, 1                 );
+    add.call_pattern( "clone-deep",              deep, 1                      );
+  }
+
+  Thing::frozen(clone, "(infra. clone)")
+}
+
+/// Looks up Symbol messages on the namespace, like `lookup_receiver`.
+///
+/// Anything else is treated as if it were a combination against the namespace's
+/// `clone` member, so that `infrastructure clone[] foo` continues to act as a
+/// shallow clone.
+pub fn clone_receiver(reactor: &mut Reactor, params: Params) {
+  let found =
+    match params.message.symbol_ref() {
+      Some(symbol) =>
+        reactor.cache().sym_lookup(params.subject.clone(), symbol.clone()),
+      None =>
+        None
+    };
+
+  match found {
+    Some(value) =>
+      reactor.stage(params.caller, value),
+
+    None => {
+      let clone_sym = reactor.machine().symbol_map.lock().intern("clone");
+
+      match reactor.cache().sym_lookup(params.subject.clone(), clone_sym) {
+        Some(shallow) =>
+          stage_receiver(reactor, Params {
+            caller:  params.caller,
+            subject: shallow,
+            message: params.message
+          }),
+
+        None =>
+          respond_error!(reactor, params.caller, "infrastructure",
+                         concat!("can't clone {}: the clone namespace {} has",
+                                 " no clone member to fall back to"),
+                         params.message, params.subject)
+      }
+    }
+  }
+}
+
+/// Creates a new Thing with the same members as the original.
+pub fn shallow(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref original] =>
+      reactor.stage(caller, util::clone::to_thing(original)),
+
+    _ => wrong_arguments!()
+  }
+}
+
+/// Clones an Execution or Alien such that it can be staged independently of
+/// the original, as `infrastructure execution branch` does.
+pub fn stageable(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref original] =>
+      match util::clone::stageable(original, reactor.machine()) {
+        Some(clone) => reactor.stage(caller, clone),
+
+        None =>
+          respond_error!(reactor, caller, "infrastructure",
+                         concat!("tried to clone-stageable {}, which is",
+                                 " neither an execution nor an alien"),
+                         original)
+      },
+
+    _ => wrong_arguments!()
+  }
+}
+
+/// Clones the original and everything it owns (its child relationships,
+/// recursively).
+pub fn deep(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref original] =>
+      reactor.stage(caller, util::clone::deep(original)),
+
+    _ => wrong_arguments!()
+  }
+}
@@ -0,0 +1,75 @@
+//! Procedures for error objects. See `util::error`.
+//!
+//! **FIXME:** Not part of Nucleus; a Paws.rs extension.
+
+use object::{ObjectRef, Meta};
+
+use nuketype::Thing;
+
+use machine::{Machine, Reactor};
+
+use util::namespace::NamespaceBuilder;
+use util::error;
+
+/// Generates an `infrastructure error` namespace object.
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut error = Meta::new();
+
+  {
+    let mut add = NamespaceBuilder::new(machine, &mut error);
+
+    add.call_pattern( "create",                  create, 2                    );
+    add.call_pattern( "describe",                describe, 1                  );
+  }
+
+  Thing::frozen(error, "(infra. error)")
+}
+
+/// Responds with a new error object with the given kind and message, just like
+/// the ones system aliens respond with.
+///
+/// # Example
+///
+///     infrastructure error create[] my-library "something went wrong"
+pub fn create(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref kind, ref message] => {
+      let error = error::create_from(reactor.machine(), kind.clone(),
+                                     message.clone());
+
+      reactor.stage(caller, error)
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Responds with a Symbol describing an error object, as `kind: message`.
+/// Responds with another error if it isn't one.
+///
+/// # Example
+///
+///     implementation console print (infrastructure error describe[] error)
+pub fn describe(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref error] => {
+      let kind    = error::kind_of(reactor.machine(), error);
+      let message = error::message_of(reactor.machine(), error);
+
+      match (kind, message) {
+        (Some(kind), Some(message)) => {
+          let description = format!("{}: {}", kind, message);
+
+          let symbol = reactor.machine().symbol(description.as_slice());
+
+          reactor.stage(caller, symbol)
+        },
+
+        _ =>
+          respond_error!(reactor, caller, "infrastructure",
+                         "tried to error describe[] {}, which is not an error \
+                          with Symbols for its kind and message", error)
+      }
+    },
+    _ => wrong_arguments!()
+  }
+}
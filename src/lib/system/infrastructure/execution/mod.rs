@@ -5,13 +5,15 @@
 
 use object::{ObjectRef, Meta};
 
-use nuketype::Thing;
+use nuketype::{Thing, Execution};
 
 use machine::{Machine, Reactor};
 
 use util::namespace::NamespaceBuilder;
 use util::clone;
 
+use std::any::AnyRefExt;
+
 /// Generates an `infrastructure execution` namespace object.
 pub fn make(machine: &Machine) -> ObjectRef {
   let mut execution = Meta::new();
@@ -23,9 +25,14 @@ pub fn make(machine: &Machine) -> ObjectRef {
 
     add.call_pattern( "stage",                   stage, 2                     );
     add.oneshot(      "unstage",                 unstage                      );
+
+    add.call_pattern( "adopt",                   adopt, 1                     );
+    add.call_pattern( "abandon",                 abandon, 1                   );
+
+    add.call_pattern( "on-complete",             on_complete, 2               );
   }
 
-  Thing::tagged(execution, "(infra. execution)")
+  Thing::frozen(execution, "(infra. execution)")
 }
 
 pub fn branch(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
@@ -36,11 +43,12 @@ pub fn branch(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
         Some(clone) => reactor.stage(caller, clone),
 
         None =>
-          warn!(concat!("tried to branch {}, which is neither",
-                        " an execution nor an alien"),
-                executionish)
+          respond_error!(reactor, caller, "infrastructure",
+                         concat!("tried to branch {}, which is neither",
+                                 " an execution nor an alien"),
+                         executionish)
       },
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
   }
 }
 
@@ -51,10 +59,79 @@ pub fn stage(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
       reactor.stage(caller, execution.clone());
     },
     _ =>
-      fail!("wrong number of arguments")
+      wrong_arguments!()
   }
 }
 
 pub fn unstage(reactor: &mut Reactor, response: ObjectRef) {
   // Do nothing! :D
 }
+
+/// Makes the caller responsible for the object and everything it holds by
+/// child relationship, then stages the caller with the object. If another
+/// Execution is responsible for any of it, the caller waits until it isn't.
+///
+/// Without responsibility being enforced, this just stages the caller.
+pub fn adopt(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref object] => {
+      let responsibility = reactor.machine().responsibility.clone();
+
+      if !responsibility.is_enabled() ||
+         responsibility.adopt(&caller, object) {
+
+        reactor.stage(caller, object.clone());
+      }
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Gives up the caller's responsibility for the object and everything it
+/// holds by child relationship, then stages the caller with the object.
+pub fn abandon(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref object] => {
+      let responsibility = reactor.machine().responsibility.clone();
+
+      if responsibility.is_enabled() &&
+         !responsibility.abandon(&caller, object) {
+
+        machine_warn!(reactor.machine(), "infrastructure",
+                      "{} tried to abandon {}, which it isn't responsible for",
+                      caller, object);
+      }
+
+      reactor.stage(caller, object.clone());
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Arranges for the second argument to be staged with the first's final
+/// response, once it has completed (see `machine::completions`), then stages
+/// the caller with the first argument. Responds with an error if the first
+/// argument isn't an Execution.
+///
+/// # Example
+///
+///     infrastructure execution on-complete [worker] [implementation console
+///       print "worker done"]
+pub fn on_complete(reactor: &mut Reactor, caller: ObjectRef,
+                   args: &[ObjectRef]) {
+  match args {
+    [ref execution, ref hook] => {
+      if !execution.lock().nuketype().is::<Execution>() {
+        respond_error!(reactor, caller, "infrastructure",
+                       "tried to wait for {} to complete, which isn't an \
+                        execution", execution);
+        return
+      }
+
+      reactor.machine().completions.register(execution.clone(), hook.clone());
+
+      reactor.stage(caller, execution.clone());
+    },
+    _ => wrong_arguments!()
+  }
+}
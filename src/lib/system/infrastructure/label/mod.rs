@@ -8,8 +8,13 @@ use nuketype::{Thing, Symbol};
 
 use machine::{Machine, Reactor};
 
+use system::infrastructure::unsignedish;
+
 use util::namespace::NamespaceBuilder;
 
+#[cfg(test)]
+mod tests;
+
 /// Generates an `infrastructure label` namespace object.
 pub fn make(machine: &Machine) -> ObjectRef {
   let mut label = Meta::new();
@@ -20,9 +25,16 @@ pub fn make(machine: &Machine) -> ObjectRef {
     add.call_pattern( "clone",                   clone, 1                     );
     add.call_pattern( "compare",                 compare, 2                   );
     add.call_pattern( "explode",                 explode, 1                   );
+    add.call_pattern( "concatenate",             concatenate, 2               );
+    add.call_pattern( "slice",                   slice, 3                     );
+    add.call_pattern( "index-of",                index_of, 2                  );
+    add.call_pattern( "uppercase",               uppercase, 1                 );
+    add.call_pattern( "lowercase",               lowercase, 1                 );
+    add.call_pattern( "trim",                    trim, 1                      );
+    add.call_pattern( "length",                  length, 1                    );
   }
 
-  Thing::tagged(label, "(infra. label)")
+  Thing::frozen(label, "(infra. label)")
 }
 
 pub fn clone(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
@@ -40,10 +52,11 @@ pub fn clone(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
         },
 
         None =>
-          warn!("tried to label clone[] {}, which is not a Symbol",
-            original)
+          respond_error!(reactor, caller, "infrastructure",
+                         "tried to label clone[] {}, which is not a Symbol",
+                         original)
       },
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
   }
 }
 
@@ -55,7 +68,7 @@ pub fn compare(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
       } else {
         return
       },
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
   }
 }
 
@@ -78,9 +91,173 @@ pub fn explode(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
           reactor.stage(caller, Thing::create(meta))
         },
         None =>
-          warn!("tried to label explode[] {}, which is not a Symbol",
-            symbol)
+          respond_error!(reactor, caller, "infrastructure",
+                         "tried to label explode[] {}, which is not a Symbol",
+                         symbol)
+      },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Responds with a Symbol made of the first Symbol followed by the second.
+///
+/// # Example
+///
+///     infrastructure label concatenate[] foo bar
+pub fn concatenate(reactor: &mut Reactor, caller: ObjectRef,
+                   args: &[ObjectRef]) {
+  match args {
+    [ref a, ref b] =>
+      match (a.symbol_ref(), b.symbol_ref()) {
+        (Some(a_string), Some(b_string)) => {
+          let mut string = String::from_str(a_string.as_slice());
+
+          string.push_str(b_string.as_slice());
+
+          let result = reactor.machine().symbol(string.as_slice());
+
+          reactor.stage(caller, result)
+        },
+        _ =>
+          respond_error!(reactor, caller, "infrastructure",
+                         "tried to label concatenate[] {}, {}, which are not \
+                          both Symbols", a, b)
+      },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Responds with the characters of a Symbol from the start index up to (but
+/// not including) the end index. Indices count characters, not bytes.
+///
+/// # Example
+///
+///     infrastructure label slice[] foobar 1 4
+pub fn slice(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref symbol, ref start, ref end] =>
+      match (symbol.symbol_ref(), unsignedish(start), unsignedish(end)) {
+        (Some(string), Some(start), Some(end))
+          if start <= end && end <= string.as_slice().char_len() => {
+
+          let result = reactor.machine().symbol(
+            string.as_slice().slice_chars(start, end));
+
+          reactor.stage(caller, result)
+        },
+        (Some(_), _, _) =>
+          respond_error!(reactor, caller, "infrastructure",
+                         "tried to label slice[] {} from {} to {}, which is \
+                          not a valid range", symbol, start, end),
+        (None, _, _) =>
+          respond_error!(reactor, caller, "infrastructure",
+                         "tried to label slice[] {}, which is not a Symbol",
+                         symbol)
+      },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Responds with the index (in characters) of the first place the second
+/// Symbol occurs in the first, as a Symbol. Like `compare`, doesn't respond if
+/// it doesn't occur at all.
+///
+/// # Example
+///
+///     infrastructure label index-of[] foobar bar
+pub fn index_of(reactor: &mut Reactor, caller: ObjectRef,
+                args: &[ObjectRef]) {
+  match args {
+    [ref haystack, ref needle] =>
+      match (haystack.symbol_ref(), needle.symbol_ref()) {
+        (Some(haystack_string), Some(needle_string)) => {
+          let haystack_str = haystack_string.as_slice();
+
+          match haystack_str.find_str(needle_string.as_slice()) {
+            Some(byte_index) => {
+              let index = haystack_str.slice_to(byte_index).char_len();
+
+              let result =
+                reactor.machine().symbol(index.to_string().as_slice());
+
+              reactor.stage(caller, result)
+            },
+            None => return
+          }
+        },
+        _ =>
+          respond_error!(reactor, caller, "infrastructure",
+                         "tried to label index-of[] {}, {}, which are not \
+                          both Symbols", haystack, needle)
+      },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Responds with a Symbol with every character of the given one in uppercase.
+///
+/// # Example
+///
+///     infrastructure label uppercase[] foo
+pub fn uppercase(reactor: &mut Reactor, caller: ObjectRef,
+                 args: &[ObjectRef]) {
+  map_string(reactor, caller, args, "uppercase", |string|
+    string.chars().map(|c| c.to_uppercase()).collect())
+}
+
+/// Responds with a Symbol with every character of the given one in lowercase.
+///
+/// # Example
+///
+///     infrastructure label lowercase[] FOO
+pub fn lowercase(reactor: &mut Reactor, caller: ObjectRef,
+                 args: &[ObjectRef]) {
+  map_string(reactor, caller, args, "lowercase", |string|
+    string.chars().map(|c| c.to_lowercase()).collect())
+}
+
+/// Responds with a Symbol without the whitespace at either end of the given
+/// one.
+///
+/// # Example
+///
+///     infrastructure label trim[] "  foo  "
+pub fn trim(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  map_string(reactor, caller, args, "trim", |string|
+    String::from_str(string.trim()))
+}
+
+/// Responds with the number of characters (not bytes) in a Symbol, as a
+/// Symbol.
+///
+/// # Example
+///
+///     infrastructure label length[] foo
+pub fn length(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  map_string(reactor, caller, args, "length", |string|
+    string.char_len().to_string())
+}
+
+/// Responds with the Symbol for `operation` applied to the string of a single
+/// Symbol argument. Responds with an error instead (see `util::error`) if the
+/// argument isn't a Symbol.
+fn map_string(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef],
+              name: &str, operation: |&str| -> String) {
+  match args {
+    [ref symbol] =>
+      match symbol.symbol_ref() {
+        Some(string) => {
+          let result = operation(string.as_slice());
+
+          let result = reactor.machine().symbol(result.as_slice());
+
+          reactor.stage(caller, result)
+        },
+        None =>
+          respond_error!(reactor, caller, "infrastructure",
+                         "tried to label {}[] {}, which is not a Symbol",
+                         name, symbol)
       },
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
   }
 }
@@ -0,0 +1,98 @@
+use super::{concatenate, slice, index_of, uppercase, lowercase, trim, length};
+
+use object::ObjectRef;
+
+use nuketype::{Thing, Number};
+
+use machine::{Machine, Reactor};
+use machine::reactor::MockReactor;
+
+use util::error;
+
+fn respond(routine: fn(&mut Reactor, ObjectRef, &[ObjectRef]),
+           args: |&Machine| -> Vec<ObjectRef>) -> Option<String> {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller = Thing::empty();
+
+  routine(&mut reactor, caller.clone(), args(&machine).as_slice());
+
+  if reactor.stagings.is_empty() {
+    None
+  } else {
+    let (_, response) = reactor.expect_staged(&caller);
+
+    reactor.expect_no_stagings();
+
+    if error::is_error(&response) {
+      Some("(error)".to_string())
+    } else {
+      response.symbol_ref().map(|string| string.as_slice().to_string())
+    }
+  }
+}
+
+fn ok(string: &str) -> Option<String> {
+  Some(string.to_string())
+}
+
+#[test]
+fn concatenate_joins_symbols() {
+  assert_eq!(ok("foobar"), respond(concatenate, |m|
+    vec![m.symbol("foo"), m.symbol("bar")]));
+
+  assert_eq!(ok("(error)"), respond(concatenate, |m|
+    vec![m.symbol("foo"), Thing::empty()]));
+}
+
+#[test]
+fn slice_counts_characters() {
+  assert_eq!(ok("oba"), respond(slice, |m|
+    vec![m.symbol("foobar"), m.symbol("2"), Number::create(5)]));
+
+  assert_eq!(ok("ö"), respond(slice, |m|
+    vec![m.symbol("föo"), m.symbol("1"), m.symbol("2")]));
+
+  assert_eq!(ok(""), respond(slice, |m|
+    vec![m.symbol("foo"), m.symbol("3"), m.symbol("3")]));
+}
+
+#[test]
+fn slice_of_bad_range_responds_with_error() {
+  assert_eq!(ok("(error)"), respond(slice, |m|
+    vec![m.symbol("foo"), m.symbol("2"), m.symbol("1")]));
+
+  assert_eq!(ok("(error)"), respond(slice, |m|
+    vec![m.symbol("foo"), m.symbol("0"), m.symbol("4")]));
+
+  assert_eq!(ok("(error)"), respond(slice, |m|
+    vec![m.symbol("foo"), Number::create(-1), m.symbol("1")]));
+}
+
+#[test]
+fn index_of_counts_characters() {
+  assert_eq!(ok("3"), respond(index_of, |m|
+    vec![m.symbol("foobar"), m.symbol("bar")]));
+
+  assert_eq!(ok("2"), respond(index_of, |m|
+    vec![m.symbol("ööbar"), m.symbol("b")]));
+
+  assert_eq!(None, respond(index_of, |m|
+    vec![m.symbol("foobar"), m.symbol("baz")]));
+}
+
+#[test]
+fn case_and_trim() {
+  assert_eq!(ok("FOO"),   respond(uppercase, |m| vec![m.symbol("foo")]));
+  assert_eq!(ok("foo"),   respond(lowercase, |m| vec![m.symbol("FoO")]));
+  assert_eq!(ok("f o"),   respond(trim,      |m| vec![m.symbol(" f o\n")]));
+
+  assert_eq!(ok("(error)"), respond(trim, |_| vec![Thing::empty()]));
+}
+
+#[test]
+fn length_counts_characters() {
+  assert_eq!(ok("3"), respond(length, |m| vec![m.symbol("föo")]));
+  assert_eq!(ok("0"), respond(length, |m| vec![m.symbol("")]));
+}
@@ -7,18 +7,26 @@
 #![allow(unused_variable)]
 #![allow(missing_doc)]
 
-use object::{ObjectRef, Meta};
+use object::{ObjectRef, Meta, MergePolicy, MergeReplace};
 use object::{ObjectReceiver, NativeReceiver};
 
-use nuketype::{Thing, Alien};
+use nuketype::{Thing, Alien, Number};
 
 use machine::{Machine, Reactor};
 
+use system::implementation::cache;
+
 use util::namespace::NamespaceBuilder;
-use util::clone;
+use util::compare::deep_eq;
 
 pub mod label;
 pub mod execution;
+pub mod clone;
+pub mod number;
+pub mod error;
+
+#[cfg(test)]
+mod tests;
 
 /// Generates an `infrastructure` namespace object.
 pub fn make(machine: &Machine) -> ObjectRef {
@@ -29,6 +37,9 @@ pub fn make(machine: &Machine) -> ObjectRef {
 
     add.factory(      "label",                   label::make                  );
     add.factory(      "execution",               execution::make              );
+    add.factory(      "clone",                   clone::make                  );
+    add.factory(      "number",                  number::make                 );
+    add.factory(      "error",                   error::make                  );
 
     add.call_pattern( "empty",                   empty, 0                     );
 
@@ -46,17 +57,20 @@ pub fn make(machine: &Machine) -> ObjectRef {
     add.call_pattern( "find",                    find, 2                      );
 
     add.call_pattern( "compare",                 compare, 2                   );
-    add.call_pattern( "clone",                   clone, 1                     );
+    add.call_pattern( "equals",                  equals, 2                    );
     add.call_pattern( "adopt",                   adopt, 2                     );
+    add.call_pattern( "merge",                   adopt, 3                     );
 
     add.call_pattern( "receiver",                receiver, 1                  );
     add.call_pattern( "receive",                 receive, 2                   );
 
     add.call_pattern( "own",                     own, 2                       );
     add.call_pattern( "disown",                  disown, 2                    );
+
+    add.call_pattern( "cache-stats",             cache_stats, 0               );
   }
 
-  Thing::tagged(infrastructure, "(infrastructure)")
+  Thing::frozen(infrastructure, "(infrastructure)")
 }
 
 pub fn empty(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
@@ -69,21 +83,32 @@ pub fn get(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
     [ref from, ref index] => {
       let index = match unsignedish(index) {
         Some(index) => index,
-        None        => return 
+        None        =>
+          return respond_error!(reactor, caller, "infrastructure",
+                                "tried to get[] {}, which is not an index",
+                                index)
       };
 
-      match from.lock().meta().members.get(index) {
-        Some(relationship) => reactor.stage(caller, relationship.to().clone()),
-        None               => return
+      let member = from.lock().meta().members.get(index)
+                     .map(|relationship| relationship.to().clone());
+
+      match member {
+        Some(member) => reactor.stage(caller, member),
+        None         =>
+          respond_error!(reactor, caller, "infrastructure",
+                         "tried to get[] from {}, which has no member #{}",
+                         from, index)
       }
     },
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
   }
 }
 
 pub fn set(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
   match args {
     [ref on, ref index, ref what] => {
+      if frozen(reactor, on) { return }
+
       let index = match unsignedish(index) {
         Some(index) => index,
         None        => return
@@ -91,64 +116,104 @@ pub fn set(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
 
       on.lock().meta_mut().members.set(index, what.clone());
     },
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
   }
 }
 
 pub fn cut(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
   match args {
     [ref from, ref index] => {
+      if from.is_frozen() {
+        return respond_error!(reactor, caller, "infrastructure",
+                              "tried to modify frozen object {}", from)
+      }
+
       let index = match unsignedish(index) {
         Some(index) => index,
-        None        => return
+        None        =>
+          return respond_error!(reactor, caller, "infrastructure",
+                                "tried to cut[] {}, which is not an index",
+                                index)
       };
 
-      match from.lock().meta_mut().members.delete(index) {
-        Some(relationship) => reactor.stage(caller, relationship.to().clone()),
-        None               => return
+      let member = from.lock().meta_mut().members.delete(index)
+                     .map(|relationship| relationship.to().clone());
+
+      match member {
+        Some(member) => reactor.stage(caller, member),
+        None         =>
+          respond_error!(reactor, caller, "infrastructure",
+                         "tried to cut[] from {}, which has no member #{}",
+                         from, index)
       }
     },
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
   }
 }
 
 pub fn affix(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
   match args {
     [ref onto, ref what] =>
-      onto.lock().meta_mut().members.push(what.clone()),
+      if !frozen(reactor, onto) {
+        onto.lock().meta_mut().members.push(what.clone())
+      },
 
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
   }
 }
 
 pub fn unaffix(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
   match args {
-    [ref from] =>
-      match from.lock().meta_mut().members.pop() {
-        Some(relationship) => reactor.stage(caller, relationship.unwrap()),
-        None               => return
-      },
-    _ => fail!("wrong number of arguments")
+    [ref from] if from.is_frozen() =>
+      respond_error!(reactor, caller, "infrastructure",
+                     "tried to modify frozen object {}", from),
+
+    [ref from] => {
+      let member = from.lock().meta_mut().members.pop()
+                     .map(|relationship| relationship.unwrap());
+
+      match member {
+        Some(member) => reactor.stage(caller, member),
+        None         =>
+          respond_error!(reactor, caller, "infrastructure",
+                         "tried to unaffix[] from {}, which has no last member",
+                         from)
+      }
+    },
+    _ => wrong_arguments!()
   }
 }
 
 pub fn prefix(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
   match args {
     [ref onto, ref what] =>
-      onto.lock().meta_mut().members.insert(1, what.clone()),
+      if !frozen(reactor, onto) {
+        onto.lock().meta_mut().members.insert(1, what.clone())
+      },
 
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
   }
 }
 
 pub fn unprefix(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
   match args {
-    [ref from] =>
-      match from.lock().meta_mut().members.remove(1) {
-        Some(relationship) => reactor.stage(caller, relationship.unwrap()),
-        None               => return
-      },
-    _ => fail!("wrong number of arguments")
+    [ref from] if from.is_frozen() =>
+      respond_error!(reactor, caller, "infrastructure",
+                     "tried to modify frozen object {}", from),
+
+    [ref from] => {
+      let member = from.lock().meta_mut().members.remove(1)
+                     .map(|relationship| relationship.unwrap());
+
+      match member {
+        Some(member) => reactor.stage(caller, member),
+        None         =>
+          respond_error!(reactor, caller, "infrastructure",
+                         "tried to unprefix[] from {}, which has no member #1",
+                         from)
+      }
+    },
+    _ => wrong_arguments!()
   }
 }
 
@@ -164,7 +229,7 @@ pub fn length(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
 
       reactor.stage(caller, length_sym);
     },
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
   }
 }
 
@@ -182,7 +247,7 @@ pub fn find(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
         None        => return
       }
     },
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
   }
 }
 
@@ -194,27 +259,82 @@ pub fn compare(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
       } else {
         return
       },
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
   }
 }
 
-pub fn clone(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+/// Like `compare`, but by structure rather than identity: responds with the
+/// first object if the two have equal trees, as by `util::compare::deep_eq()`,
+/// and doesn't respond otherwise. Symbols are compared by string, and members
+/// recursively.
+///
+/// # Example
+///     infrastructure equals[] [expected] [actual]
+///     infrastructure equals[] [locals expected] [locals actual]
+pub fn equals(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
   match args {
-    [ref original] =>
-      reactor.stage(caller, clone::to_thing(original)),
-
-    _ => fail!("wrong number of arguments")
+    [ref a, ref b] =>
+      if deep_eq(a, b) {
+        reactor.stage(caller, a.clone())
+      } else {
+        return
+      },
+    _ => wrong_arguments!()
   }
 }
 
+/// Replaces the members of `onto` with those of `from`, or, given a policy,
+/// merges them: `replace`, `theirs`, `ours` or `append`. See
+/// `object::MergePolicy`.
+///
+/// Registered twice: as `adopt`, which always replaces, and as `merge`, which
+/// takes the policy.
+///
+/// # Example
+///
+///     infrastructure adopt[] from onto
+///     infrastructure merge[] from onto append
 pub fn adopt(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
-  match args {
-    [ref from, ref onto] => {
-      let members = from.lock().meta().members.clone();
+  let (from, onto, policy) = match args {
+    [ref from, ref onto] =>
+      (from, onto, Some(MergeReplace)),
 
-      onto.lock().meta_mut().members = members;
-    },
-    _ => fail!("wrong number of arguments")
+    [ref from, ref onto, ref policy] =>
+      (from, onto, policy.symbol_ref().and_then(|name|
+                     MergePolicy::from_name(name.as_slice()))),
+
+    _ => wrong_arguments!()
+  };
+
+  match policy {
+    Some(policy) =>
+      merge_members(reactor, from, onto, policy),
+
+    None =>
+      machine_warn!(reactor.machine(), "infrastructure",
+                    "tried to merge with unknown policy {}", args[2])
+  }
+}
+
+/// Merges the members of `from` into `onto` with both of them locked, so that
+/// neither can change partway through. See `ObjectRef::lock_pair()`.
+fn merge_members(reactor: &mut Reactor,
+                 from:    &ObjectRef,
+                 onto:    &ObjectRef,
+                 policy:  MergePolicy) {
+
+  if frozen(reactor, onto) { return }
+
+  if from == onto {
+    // Only one lock to take, and the members have to be copied first anyway.
+    let mut object  = onto.lock();
+    let     members = object.meta().members.clone();
+
+    object.meta_mut().members.merge(&members, policy);
+  } else {
+    let (from, mut onto) = ObjectRef::lock_pair(from, onto);
+
+    onto.meta_mut().members.merge(&from.meta().members, policy);
   }
 }
 
@@ -228,57 +348,96 @@ pub fn receiver(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
         NativeReceiver(receiver) =>
           reactor.stage(caller, Alien::from_native_receiver(receiver)),
       },
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
   }
 }
 
 pub fn receive(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
   match args {
     [ref on, ref receiver] => {
-      // TODO: see whether checking whether the 'receiver' is an Alien wrapping
-      // a NativeReceiver and using that yields a performance advantage (it
-      // should)
-      on.lock().meta_mut().receiver = ObjectReceiver(receiver.clone());
+      if frozen(reactor, on) { return }
+
+      // An Alien wrapping a native receiver is stored as the native receiver
+      // itself, which saves a params object and a staging per combination.
+      let receiver = ObjectReceiver(receiver.clone()).unwrap_native();
+
+      on.lock().meta_mut().receiver = receiver;
     },
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
   }
 }
 
 pub fn own(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
   match args {
     [ref on, ref index] => {
+      if frozen(reactor, on) { return }
+
       unsignedish(index).map(|index| {
 
         if !on.lock().meta_mut().members.own(index) {
-          warn!("tried to own a nonexistent member #{} on {}",
-            index, on);
+          machine_warn!(reactor.machine(), "infrastructure",
+                        "tried to own a nonexistent member #{} on {}",
+                        index, on);
         }
 
       });
     },
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
   }
 }
 
 pub fn disown(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
   match args {
     [ref on, ref index] => {
+      if frozen(reactor, on) { return }
+
       unsignedish(index).map(|index| {
 
         if !on.lock().meta_mut().members.disown(index) {
-          warn!("tried to disown a nonexistent member #{} on {}",
-            index, on);
+          machine_warn!(reactor.machine(), "infrastructure",
+                        "tried to disown a nonexistent member #{} on {}",
+                        index, on);
         }
 
       });
     },
-    _ => fail!("wrong number of arguments")
+    _ => wrong_arguments!()
+  }
+}
+
+/// Not standardized: the same as `implementation cache stats[]`, for
+/// programs that only look in `infrastructure`.
+///
+/// # Example
+///
+///     infrastructure cache-stats[]
+pub fn cache_stats(reactor: &mut Reactor, caller: ObjectRef,
+                   args: &[ObjectRef]) {
+  cache::stats(reactor, caller, args)
+}
+
+/// Returns true (and warns) if the object is frozen, and therefore can't be
+/// modified. See `ObjectRef::store_frozen()`.
+fn frozen(reactor: &Reactor, object: &ObjectRef) -> bool {
+  if object.is_frozen() {
+    machine_warn!(reactor.machine(), "infrastructure",
+                  "tried to modify frozen object {}", object);
+    true
+  } else {
+    false
   }
 }
 
 // FIXME when ELLIOTTCABLE decides what he wants to do about numbers.
-fn unsignedish(symbol: &ObjectRef) -> Option<uint> {
-  symbol.symbol_ref().and_then(|string|
-    from_str::<uint>(string.as_slice())
-  )
+
+/// Accepts either a Symbol containing a decimal number, or a non-negative
+/// `Number`.
+pub fn unsignedish(index: &ObjectRef) -> Option<uint> {
+  match index.symbol_ref() {
+    Some(string) => from_str::<uint>(string.as_slice()),
+
+    None =>
+      Number::of(index).and_then(|value|
+        if value >= 0 { Some(value as uint) } else { None })
+  }
 }
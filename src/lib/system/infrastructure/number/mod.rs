@@ -0,0 +1,181 @@
+//! Procedures specific to `Number`s.
+//!
+//! **FIXME:** Not part of Nucleus; a Paws.rs extension.
+
+use object::{ObjectRef, Meta};
+
+use nuketype::{Thing, Number};
+
+use machine::{Machine, Reactor};
+
+use util::namespace::NamespaceBuilder;
+
+use std::num::{CheckedAdd, CheckedSub, CheckedMul, CheckedDiv};
+use std::cmp::max;
+use std::i64;
+
+#[cfg(test)]
+mod tests;
+
+/// Generates an `infrastructure number` namespace object.
+pub fn make(machine: &Machine) -> ObjectRef {
+  let mut number = Meta::new();
+
+  {
+    let mut add = NamespaceBuilder::new(machine, &mut number);
+
+    add.call_pattern( "add",                     sum, 2                       );
+    add.call_pattern( "subtract",                difference, 2                );
+    add.call_pattern( "multiply",                product, 2                   );
+    add.call_pattern( "divide",                  quotient, 2                  );
+    add.call_pattern( "remainder",               remainder, 2                 );
+
+    add.call_pattern( "compare",                 compare, 2                   );
+
+    add.call_pattern( "from-label",              from_label, 1                );
+    add.call_pattern( "to-label",                to_label, 1                  );
+
+    add.call_pattern( "length",                  length, 1                    );
+  }
+
+  Thing::frozen(number, "(infra. number)")
+}
+
+// Named after their results rather than the call patterns, since `add` is
+// already taken by the NamespaceBuilder in `make()`.
+
+pub fn sum(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  arithmetic(reactor, caller, args, "add", |a, b| a.checked_add(&b))
+}
+
+pub fn difference(reactor: &mut Reactor, caller: ObjectRef,
+                  args: &[ObjectRef]) {
+  arithmetic(reactor, caller, args, "subtract", |a, b| a.checked_sub(&b))
+}
+
+pub fn product(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  arithmetic(reactor, caller, args, "multiply", |a, b| a.checked_mul(&b))
+}
+
+pub fn quotient(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  arithmetic(reactor, caller, args, "divide", |a, b| a.checked_div(&b))
+}
+
+/// Responds with the remainder of dividing `a` by `b`, which has the same sign
+/// as `a`.
+pub fn remainder(reactor: &mut Reactor, caller: ObjectRef,
+                 args: &[ObjectRef]) {
+  arithmetic(reactor, caller, args, "remainder", |a, b| {
+    // `i64::MIN % -1` overflows, just like the division would.
+    if b == 0 || (a == i64::MIN && b == -1) { None } else { Some(a % b) }
+  })
+}
+
+/// Responds with -1, 0, or 1 as a Number, depending on whether `a` is less
+/// than, equal to, or greater than `b`.
+pub fn compare(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref a, ref b] =>
+      match (Number::of(a), Number::of(b)) {
+        (Some(a), Some(b)) => {
+          let ordering = match a.cmp(&b) {
+            Less    => -1,
+            Equal   =>  0,
+            Greater =>  1
+          };
+
+          reactor.stage(caller, Number::create(ordering))
+        },
+
+        _ =>
+          respond_error!(reactor, caller, "infrastructure",
+                         "tried to number compare[] {} and {}, which are not \
+                          both Numbers", a, b)
+      },
+    _ => wrong_arguments!()
+  }
+}
+
+pub fn from_label(reactor: &mut Reactor, caller: ObjectRef,
+                  args: &[ObjectRef]) {
+  match args {
+    [ref label] =>
+      match label.symbol_ref().and_then(|s| Number::from_label(s.as_slice())) {
+        Some(number) =>
+          reactor.stage(caller, Number::create(number.value())),
+
+        None =>
+          respond_error!(reactor, caller, "infrastructure",
+                         "tried to number from-label[] {}, which is not a \
+                          Symbol containing a number", label)
+      },
+    _ => wrong_arguments!()
+  }
+}
+
+pub fn to_label(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref number] =>
+      match Number::of(number) {
+        Some(value) => {
+          let label =
+            reactor.machine().symbol(Number::new(value).to_label().as_slice());
+
+          reactor.stage(caller, label)
+        },
+
+        None =>
+          respond_error!(reactor, caller, "infrastructure",
+                         "tried to number to-label[] {}, which is not a Number",
+                         number)
+      },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Like `infrastructure length`, but responds with a Number instead of a
+/// Symbol.
+///
+/// # Example
+///
+///     infrastructure number length[] [locals]
+pub fn length(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef]) {
+  match args {
+    [ref of] => {
+      // The noughty (#0) is not counted, whether or not it's there.
+      let length = max(of.lock().meta().members.len(), 1) as i64 - 1;
+
+      reactor.stage(caller, Number::create(length))
+    },
+    _ => wrong_arguments!()
+  }
+}
+
+/// Applies a checked binary operation to two Numbers and responds with the
+/// result. Responds with an error instead (see `util::error`) if either
+/// argument isn't a Number, or if the operation fails (overflow, division by
+/// zero).
+fn arithmetic(reactor: &mut Reactor, caller: ObjectRef, args: &[ObjectRef],
+              name: &str, operation: |i64, i64| -> Option<i64>) {
+  match args {
+    [ref a, ref b] =>
+      match (Number::of(a), Number::of(b)) {
+        (Some(x), Some(y)) =>
+          match operation(x, y) {
+            Some(result) =>
+              reactor.stage(caller, Number::create(result)),
+
+            None =>
+              respond_error!(reactor, caller, "infrastructure",
+                             "number {}[] of {} and {} overflowed or was \
+                              undefined", name, x, y)
+          },
+
+        _ =>
+          respond_error!(reactor, caller, "infrastructure",
+                         "tried to number {}[] {} and {}, which are not both \
+                          Numbers", name, a, b)
+      },
+    _ => wrong_arguments!()
+  }
+}
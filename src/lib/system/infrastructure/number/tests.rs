@@ -0,0 +1,115 @@
+use super::{sum, difference, product, quotient};
+use super::{remainder, compare, from_label, to_label, length};
+
+use object::{ObjectRef, Meta};
+
+use nuketype::{Thing, Number};
+
+use machine::{Machine, Reactor};
+use machine::reactor::MockReactor;
+
+use util::error;
+
+fn respond(routine: fn(&mut Reactor, ObjectRef, &[ObjectRef]),
+           args: &[ObjectRef]) -> Option<ObjectRef> {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine);
+
+  let caller = Thing::empty();
+
+  routine(&mut reactor, caller.clone(), args);
+
+  reactor.stagings.remove(0).map(|(execution, response)| {
+    assert!(execution == caller);
+    response
+  })
+}
+
+/// Like `respond()`, but expects an error object, and returns true if that's
+/// what the routine responded with.
+fn responds_with_error(routine: fn(&mut Reactor, ObjectRef, &[ObjectRef]),
+                       args: &[ObjectRef]) -> bool {
+  respond(routine, args).map(|response| error::is_error(&response))
+                        .unwrap_or(false)
+}
+
+/// Responds with `None` if the routine responded with an error.
+fn respond_number(routine: fn(&mut Reactor, ObjectRef, &[ObjectRef]),
+                  a: i64, b: i64) -> Option<i64> {
+  let response = respond(routine, [Number::create(a), Number::create(b)])
+                   .expect("no response");
+
+  if error::is_error(&response) {
+    None
+  } else {
+    Some(Number::of(&response).expect("not a Number"))
+  }
+}
+
+#[test]
+fn arithmetic_responds_with_numbers() {
+  assert_eq!(Some(5),  respond_number(sum,        2, 3));
+  assert_eq!(Some(-1), respond_number(difference, 2, 3));
+  assert_eq!(Some(6),  respond_number(product,    2, 3));
+  assert_eq!(Some(3),  respond_number(quotient,   7, 2));
+}
+
+#[test]
+fn arithmetic_refuses_overflow_and_division_by_zero() {
+  assert_eq!(None, respond_number(sum,      ::std::i64::MAX, 1));
+  assert_eq!(None, respond_number(quotient, 1, 0));
+}
+
+#[test]
+fn arithmetic_refuses_non_numbers() {
+  assert!(responds_with_error(sum, [Number::create(1), Thing::empty()]));
+}
+
+#[test]
+fn compare_orders() {
+  assert_eq!(Some(-1), respond_number(compare, 1, 2));
+  assert_eq!(Some(0),  respond_number(compare, 2, 2));
+  assert_eq!(Some(1),  respond_number(compare, 3, 2));
+}
+
+#[test]
+fn label_conversions() {
+  let machine = Machine::new();
+
+  let number = respond(from_label, [machine.symbol("-42")])
+    .expect("from-label didn't respond");
+
+  assert_eq!(Some(-42), Number::of(&number));
+
+  let label = respond(to_label, [number]).expect("to-label didn't respond");
+
+  assert!(label.symbol_ref().map(|s| s.as_slice() == "-42").unwrap_or(false));
+
+  assert!(responds_with_error(from_label, [machine.symbol("nope")]));
+}
+
+#[test]
+fn remainder_follows_the_dividend() {
+  assert_eq!(Some(1),  respond_number(remainder, 7, 3));
+  assert_eq!(Some(-1), respond_number(remainder, -7, 3));
+  assert_eq!(None,     respond_number(remainder, 7, 0));
+  assert_eq!(None,     respond_number(remainder, ::std::i64::MIN, -1));
+}
+
+#[test]
+fn length_responds_with_a_number() {
+  let mut meta = Meta::new();
+
+  meta.members.push(Thing::empty());
+  meta.members.push(Thing::empty());
+
+  let response = respond(length, [Thing::create(meta)])
+                   .expect("no response");
+
+  assert_eq!(Some(2), Number::of(&response));
+
+  let response = respond(length, [Thing::empty()]).expect("no response");
+
+  // Not -1, even though there's no noughty to leave out.
+  assert_eq!(Some(0), Number::of(&response));
+}
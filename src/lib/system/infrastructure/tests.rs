@@ -0,0 +1,333 @@
+use system::infrastructure::{get, set, cut, adopt, receive, equals};
+use system::infrastructure::{unaffix, unprefix};
+use system::infrastructure::{execution, label};
+use system::infrastructure::error as error_namespace;
+
+use object;
+use object::{ObjectReceiver, NativeReceiver};
+
+use nuketype::{Thing, Number, Execution, Alien};
+
+use machine::Machine;
+use machine::reactor::MockReactor;
+
+use script::Script;
+
+use util::error;
+
+use util;
+
+#[test]
+fn get_accepts_symbol_and_number_indices() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller = Thing::empty();
+  let member = Thing::empty();
+  let object = Thing::from_fn(|meta| meta.members.push(member.clone()));
+
+  get(&mut reactor, caller.clone(), [object.clone(), machine.symbol("1")]);
+  get(&mut reactor, caller.clone(), [object.clone(), Number::create(1)]);
+
+  assert!(reactor.stagings == vec![(caller.clone(), member.clone()),
+                                   (caller.clone(), member.clone())]);
+}
+
+#[test]
+fn set_and_cut_accept_number_indices() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller = Thing::empty();
+  let member = Thing::empty();
+  let object = Thing::empty();
+
+  set(&mut reactor, caller.clone(), [object.clone(), Number::create(2),
+                                     member.clone()]);
+
+  assert!(object.lock().meta().members.get(2).map(|r| r.to().clone()) ==
+            Some(member.clone()));
+
+  cut(&mut reactor, caller.clone(), [object.clone(), Number::create(2)]);
+
+  assert!(reactor.stagings == vec![(caller.clone(), member.clone())]);
+  assert!(object.lock().meta().members.get(2).is_none());
+}
+
+#[test]
+fn negative_numbers_are_not_indices() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let object = Thing::from_fn(|meta| meta.members.push(Thing::empty()));
+
+  let caller = Thing::empty();
+
+  get(&mut reactor, caller.clone(), [object, Number::create(-1)]);
+
+  let (_, response) = reactor.expect_staged(&caller);
+
+  assert!(error::is_error(&response));
+}
+
+#[test]
+fn missing_members_respond_with_errors() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let object = Thing::empty();
+  let caller = Thing::empty();
+
+  get(&mut reactor, caller.clone(), [object.clone(), Number::create(3)]);
+  cut(&mut reactor, caller.clone(), [object.clone(), Number::create(3)]);
+  unaffix(&mut reactor, caller.clone(), [object.clone()]);
+  unprefix(&mut reactor, caller.clone(), [object.clone()]);
+
+  for _ in range(0u, 4) {
+    let (_, response) = reactor.expect_staged(&caller);
+
+    assert!(error::is_error(&response));
+  }
+
+  reactor.expect_no_stagings();
+}
+
+#[test]
+fn equals_compares_structure() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let a = Thing::from_fn(|meta| meta.members.push(machine.symbol("x")));
+  let b = Thing::from_fn(|meta| meta.members.push(machine.symbol("x")));
+  let c = Thing::from_fn(|meta| meta.members.push(machine.symbol("y")));
+
+  let caller = Thing::empty();
+
+  equals(&mut reactor, caller.clone(), [a.clone(), b]);
+
+  reactor.expect_stage(&caller, &a);
+
+  equals(&mut reactor, caller.clone(), [a, c]);
+
+  reactor.expect_no_stagings();
+}
+
+#[test]
+fn adopt_onto_itself() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let object = Thing::from_fn(|meta| meta.members.push(Thing::empty()));
+
+  adopt(&mut reactor, Thing::empty(), [object.clone(), object.clone()]);
+
+  // Replacing every member with itself changes nothing.
+  assert_eq!(2, object.lock().meta().members.len());
+}
+
+#[test]
+fn adopt_with_a_policy() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let from = Thing::from_fn(|meta| meta.members.push(machine.symbol("b")));
+  let onto = Thing::from_fn(|meta| meta.members.push(machine.symbol("a")));
+
+  adopt(&mut reactor, Thing::empty(),
+        [from.clone(), onto.clone(), machine.symbol("append")]);
+
+  assert_eq!(3, onto.lock().meta().members.len());
+
+  // Unknown policies leave it alone.
+  adopt(&mut reactor, Thing::empty(),
+        [from.clone(), onto.clone(), machine.symbol("whatever")]);
+
+  assert_eq!(3, onto.lock().meta().members.len());
+}
+
+#[test]
+fn adopt_in_opposite_directions_at_once() {
+  util::timeout(1000, proc() {
+    let machine = Machine::new();
+
+    let a = Thing::from_fn(|meta| meta.members.push(Thing::empty()));
+    let b = Thing::from_fn(|meta| meta.members.push(Thing::empty()));
+
+    let (tx, rx) = channel();
+
+    for &(ref from, ref onto) in [(a.clone(), b.clone()),
+                                  (b.clone(), a.clone())].iter() {
+      let machine = machine.clone();
+      let from    = from.clone();
+      let onto    = onto.clone();
+      let tx      = tx.clone();
+
+      spawn(proc() {
+        let mut reactor = MockReactor::new(machine);
+
+        for _ in range(0u, 1000) {
+          adopt(&mut reactor, Thing::empty(), [from.clone(), onto.clone()]);
+        }
+
+        tx.send(());
+      });
+    }
+
+    rx.recv();
+    rx.recv();
+  })
+}
+
+#[test]
+fn execution_adopt_waits_for_abandon() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  machine.responsibility.enable();
+
+  let a      = Execution::create(&machine, Script(vec![]));
+  let b      = Execution::create(&machine, Script(vec![]));
+  let object = Thing::empty();
+
+  execution::adopt(&mut reactor, a.clone(), [object.clone()]);
+  execution::adopt(&mut reactor, b.clone(), [object.clone()]);
+
+  // Only `a` gets to go on.
+  assert_eq!(1, reactor.stagings.len());
+  assert!(reactor.stagings[0] == (a.clone(), object.clone()));
+
+  execution::abandon(&mut reactor, a.clone(), [object.clone()]);
+
+  assert_eq!(2, reactor.stagings.len());
+  assert!(machine.responsibility.owner(&object) == None);
+
+  // `b` is ready to try again.
+  let ready = machine.responsibility.take_ready();
+
+  assert_eq!(1, ready.len());
+}
+
+#[test]
+fn execution_adopt_without_responsibility() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller = Thing::empty();
+  let object = Thing::empty();
+
+  execution::adopt(&mut reactor, caller.clone(), [object.clone()]);
+
+  assert!(reactor.stagings == vec![(caller, object.clone())]);
+  assert!(machine.responsibility.owner(&object) == None);
+}
+
+#[test]
+fn execution_on_complete_stages_hook_with_final_response() {
+  use machine::reactor::realize;
+
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller = Thing::empty();
+  let target = Execution::create(&machine, Script(vec![]));
+  let hook   = Thing::empty();
+
+  execution::on_complete(&mut reactor, caller.clone(),
+                         [target.clone(), hook.clone()]);
+
+  reactor.expect_stage(&caller, &target);
+
+  let response = Thing::empty();
+
+  realize(&mut reactor, target.clone(), response.clone());
+
+  reactor.expect_stage(&hook, &response);
+
+  // Only once.
+  realize(&mut reactor, target.clone(), response.clone());
+
+  reactor.expect_no_stagings();
+}
+
+#[test]
+fn execution_on_complete_rejects_non_executions() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  execution::on_complete(&mut reactor, Thing::empty(),
+                         [Thing::empty(), Thing::empty()]);
+
+  let (_, response) = reactor.stagings.remove(0).unwrap();
+
+  assert!(error::is_error(&response));
+  assert_eq!(0, machine.completions.len());
+}
+
+#[test]
+fn receive_unwraps_native_receiver_aliens() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let on     = Thing::empty();
+  let native = Alien::from_native_receiver(object::lookup_receiver);
+  let other  = Execution::create(&machine, Script(vec![]));
+
+  receive(&mut reactor, Thing::empty(), [on.clone(), native]);
+
+  match on.lock().meta().receiver {
+    NativeReceiver(_) => (),
+    _                 => fail!("expected NativeReceiver")
+  }
+
+  receive(&mut reactor, Thing::empty(), [on.clone(), other.clone()]);
+
+  match on.lock().meta().receiver {
+    ObjectReceiver(ref receiver) => assert!(*receiver == other),
+    _                            => fail!("expected ObjectReceiver")
+  }
+}
+
+#[test]
+fn label_clone_of_non_symbol_responds_with_error() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller = Thing::empty();
+
+  label::clone(&mut reactor, caller.clone(), [Thing::empty()]);
+
+  let (_, response) = reactor.expect_staged(&caller);
+
+  assert!(error::kind_of(&machine, &response) ==
+            Some("infrastructure".to_string()));
+
+  reactor.expect_no_stagings();
+}
+
+#[test]
+fn error_create_and_describe() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller = Thing::empty();
+
+  error_namespace::create(&mut reactor, caller.clone(),
+                          [machine.symbol("test"), machine.symbol("oops")]);
+
+  let (_, created) = reactor.expect_staged(&caller);
+
+  assert!(error::is_error(&created));
+
+  error_namespace::describe(&mut reactor, caller.clone(), [created]);
+
+  let (_, description) = reactor.expect_staged(&caller);
+
+  assert!(description.eq_as_symbol(&machine.symbol("test: oops")));
+
+  // Describing something that isn't an error is an error itself.
+  error_namespace::describe(&mut reactor, caller.clone(), [Thing::empty()]);
+
+  let (_, response) = reactor.expect_staged(&caller);
+
+  assert!(error::is_error(&response));
+}
@@ -8,11 +8,18 @@
 //!
 //!     clone::to_thing(...);
 //!     clone::stageable(...);
+//!     clone::deep(...);
 
-use object::{ObjectRef, Meta};
-use nuketype::{Thing, Execution, Alien, Locals};
+use object::{ObjectRef, ObjectRefGuard, Meta, Members, Relationship};
+use nuketype::{Nuketype, Thing, Execution, Alien, Locals, Number};
 use machine::Machine;
 
+use std::any::AnyRefExt;
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests;
+
 /// Creates a new Thing object from the metadata of the given object.
 pub fn to_thing(from: &ObjectRef) -> ObjectRef {
   // TODO: Ask @ELLIOTTCABLE if this is supposed to copy the receiver too
@@ -34,25 +41,29 @@ pub fn stageable(from: &ObjectRef, machine: &Machine) -> Option<ObjectRef> {
   match from.lock().try_cast::<Execution>() {
 
     Ok(execution) => {
-      let     new_execution = box execution.deref().clone();
-      let mut new_meta      = execution.meta().clone();
+      let locals_ref = execution.meta().members
+                         .lookup_pair(&machine.locals_sym)
+                         .expect("Execution is missing locals!");
 
       execution.unlock();
 
-      let new_locals = {
+      // Copy the Execution and its locals with both of them locked, so that
+      // the copies are consistent with each other.
+      let (execution, locals) = ObjectRef::lock_pair(from, &locals_ref);
+
+      let execution = execution.try_cast::<Execution>()
+                        .ok().expect("Execution stopped being an Execution!");
 
-        let locals_ref = new_meta.members
-                           .lookup_pair(&machine.locals_sym)
-                           .expect("Execution is missing locals!");
+      let locals    = locals.try_cast::<Locals>()
+                        .ok().expect("locals should be a Locals!");
 
-        let locals = locals_ref.lock().try_cast::<Locals>()
-                       .ok().expect("locals should be a Locals!");
+      let     new_execution = box execution.deref().clone();
+      let mut new_meta      = execution.meta().clone();
 
-        ObjectRef::store_with_tag(
-          box locals.deref().clone(),
-          locals.meta().clone(),
-          locals_ref.tag())
-      };
+      let new_locals = ObjectRef::store_with_tag(
+                         box locals.deref().clone(),
+                         locals.meta().clone(),
+                         locals_ref.tag());
 
       new_meta.members
         .push_pair_to_child(machine.locals_sym.clone(), new_locals);
@@ -72,3 +83,86 @@ pub fn stageable(from: &ObjectRef, machine: &Machine) -> Option<ObjectRef> {
     }
   }
 }
+
+/// Clones an object along with everything it owns.
+///
+/// The object itself is copied, as are all of the objects it has child
+/// relationships to, recursively. Non-child relationships are left pointing at
+/// the original objects, and Symbols are never copied, since they're atoms.
+///
+/// Cycles of child relationships are preserved in the copy.
+pub fn deep(from: &ObjectRef) -> ObjectRef {
+  deep_with(from, &mut HashMap::new())
+}
+
+fn deep_with(from:   &ObjectRef,
+             copies: &mut HashMap<ObjectRef, ObjectRef>)
+             -> ObjectRef {
+
+  if from.symbol_ref().is_some() {
+    return from.clone()
+  }
+
+  match copies.find(from) {
+    Some(copy) => return copy.clone(),
+    None       => ()
+  }
+
+  let (nuketype, meta) = {
+    let object = from.lock();
+
+    (copy_nuketype(&object), object.meta().clone())
+  };
+
+  // Store the copy before recursing, so that cycles find it.
+  let copy = ObjectRef::store_with_tag(
+    nuketype,
+    Meta { members: Members::new(), receiver: meta.receiver.clone() },
+    from.tag());
+
+  copies.insert(from.clone(), copy.clone());
+
+  let mut members = meta.members;
+
+  for maybe_relationship in members.vec.mut_iter() {
+    match *maybe_relationship {
+      Some(ref mut relationship) if relationship.is_child() =>
+        *relationship =
+          Relationship::new_child(deep_with(relationship.to(), copies)),
+
+      _ => ()
+    }
+  }
+
+  copy.lock().meta_mut().members = members;
+
+  copy
+}
+
+/// Copies the nuketype of a locked object, if it's one of the known nuketypes.
+/// Anything unknown becomes a `Thing`.
+fn copy_nuketype(object: &ObjectRefGuard) -> Box<Nuketype+Send+Sync> {
+  let nuketype = object.nuketype();
+
+  match nuketype.downcast_ref::<Execution>() {
+    Some(execution) => return box execution.clone(),
+    None            => ()
+  }
+
+  match nuketype.downcast_ref::<Alien>() {
+    Some(alien) => return box alien.clone(),
+    None        => ()
+  }
+
+  match nuketype.downcast_ref::<Locals>() {
+    Some(locals) => return box locals.clone(),
+    None         => ()
+  }
+
+  match nuketype.downcast_ref::<Number>() {
+    Some(number) => return box Number::new(number.value()),
+    None         => ()
+  }
+
+  box Thing
+}
@@ -0,0 +1,69 @@
+use super::deep;
+
+use nuketype::{Thing, Number};
+
+use machine::Machine;
+
+#[test]
+fn deep_copies_children_and_shares_non_children() {
+  let machine = Machine::new();
+
+  let child     = Thing::empty();
+  let non_child = Thing::empty();
+
+  let original = Thing::from_fn(|meta| {
+    meta.members.push_child(child.clone());
+    meta.members.push(non_child.clone());
+    meta.members.push(machine.symbol("atom"));
+  });
+
+  let copy = deep(&original);
+
+  assert!(copy != original);
+
+  let copy_obj = copy.lock();
+  let members  = &copy_obj.meta().members;
+
+  let copied_child = members.get(1).unwrap();
+
+  assert!(copied_child.is_child());
+  assert!(copied_child.to() != &child);
+
+  assert!(members.get(2).unwrap().to() == &non_child);
+
+  assert!(members.get(3).unwrap().to()
+            .eq_as_symbol(&machine.symbol("atom")));
+}
+
+#[test]
+fn deep_preserves_cycles() {
+  let a = Thing::empty();
+  let b = Thing::empty();
+
+  a.lock().meta_mut().members.push_child(b.clone());
+  b.lock().meta_mut().members.push_child(a.clone());
+
+  let a_copy = deep(&a);
+
+  let b_copy = a_copy.lock().meta().members.get(1).unwrap().to().clone();
+
+  assert!(b_copy != b);
+
+  let a_again = b_copy.lock().meta().members.get(1).unwrap().to().clone();
+
+  assert!(a_again == a_copy);
+}
+
+#[test]
+fn deep_copies_numbers() {
+  let original = Thing::from_fn(|meta| {
+    meta.members.push_child(Number::create(42));
+  });
+
+  let copy = deep(&original);
+
+  let number = copy.lock().meta().members.get(1).unwrap().to().clone();
+
+  assert_eq!(Some(42), Number::of(&number));
+  assert_eq!(Some(-7), Number::of(&deep(&Number::create(-7))));
+}
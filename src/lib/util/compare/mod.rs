@@ -0,0 +1,96 @@
+//! Structural equality of object trees.
+//!
+//! Both trees are recorded with `util::graph` first, so no object is locked
+//! for longer than it takes to copy its metadata, and the comparison itself
+//! happens without holding any locks. Two objects are equal when:
+//!
+//! * they're the same object, or
+//! * they're both Symbols with the same string, or
+//! * they're both Things (or both Locals) whose members are equal in turn,
+//!   position by position, with holes in the same places (trailing holes
+//!   don't count), or
+//! * they're of some other nuketype that isn't an Execution or an Alien (like
+//!   Numbers), with the same description and equal members.
+//!
+//! Executions and Aliens are only ever equal to themselves. The noughty (0th)
+//! member is never looked at, nor are tags, receivers, or whether a
+//! relationship is a child relationship.
+//!
+//! Cycles are handled by assuming that a pair of objects already being
+//! compared is equal, so two cyclic trees are equal when no difference can be
+//! found by following them.
+
+use object::ObjectRef;
+
+use util::graph::{Graph, Node, Edge};
+use util::graph::{SymbolNode, ThingNode, LocalsNode, OtherNode};
+
+use std::cmp::max;
+use std::collections::HashSet;
+
+#[cfg(test)]
+mod tests;
+
+/// Compares the trees under `a` and `b`. See the module documentation.
+pub fn deep_eq(a: &ObjectRef, b: &ObjectRef) -> bool {
+  if a == b { return true }
+
+  // The roots are numbered first, so `a` is node 0 and `b` node 1.
+  let graph = Graph::snapshot_all([a.clone(), b.clone()]);
+
+  let mut assumed: HashSet<(uint, uint)> = HashSet::new();
+  let mut pending: Vec<(uint, uint)>     = vec![(0, 1)];
+
+  loop {
+    let (left, right) = match pending.pop() {
+      Some(pair) => pair,
+      None       => return true
+    };
+
+    if left == right || !assumed.insert((left, right)) {
+      continue
+    }
+
+    let left_node  = &graph.nodes[left];
+    let right_node = &graph.nodes[right];
+
+    if !kinds_eq(left_node, right_node) { return false }
+
+    let len = max(left_node.members.len(), right_node.members.len());
+
+    for index in range(1, len) {
+      match (member(left_node, index), member(right_node, index)) {
+        (Some(left_edge), Some(right_edge)) =>
+          pending.push((left_edge.to, right_edge.to)),
+
+        (None, None) => (),
+
+        _ => return false
+      }
+    }
+  }
+}
+
+/// The member of `node` at `index`, or `None` for a hole or past the end.
+fn member<'a>(node: &'a Node, index: uint) -> Option<&'a Edge> {
+  if index < node.members.len() {
+    node.members[index].as_ref()
+  } else {
+    None
+  }
+}
+
+/// Whether two distinct nodes could be equal, before looking at their members.
+fn kinds_eq(left: &Node, right: &Node) -> bool {
+  match (&left.kind, &right.kind) {
+    (&SymbolNode(ref left), &SymbolNode(ref right)) => left == right,
+
+    (&ThingNode,  &ThingNode)  => true,
+    (&LocalsNode, &LocalsNode) => true,
+
+    (&OtherNode(ref left), &OtherNode(ref right)) => left == right,
+
+    // Including Executions and Aliens, which would have been the same node.
+    _ => false
+  }
+}
@@ -0,0 +1,97 @@
+use super::deep_eq;
+
+use object::ObjectRef;
+
+use nuketype::{Thing, Number, Execution};
+
+use machine::Machine;
+
+use script::Script;
+
+fn list(members: Vec<ObjectRef>) -> ObjectRef {
+  Thing::from_fn(|meta| {
+    for member in members.iter() {
+      meta.members.push(member.clone());
+    }
+  })
+}
+
+#[test]
+fn symbols_by_string() {
+  let machine = Machine::new();
+
+  assert!( deep_eq(&machine.symbol("a"), &machine.symbol("a")));
+  assert!(!deep_eq(&machine.symbol("a"), &machine.symbol("b")));
+}
+
+#[test]
+fn numbers_by_value() {
+  assert!( deep_eq(&Number::create(42), &Number::create(42)));
+  assert!(!deep_eq(&Number::create(42), &Number::create(43)));
+}
+
+#[test]
+fn members_recursively() {
+  let machine = Machine::new();
+
+  let a = list(vec![machine.symbol("x"), list(vec![Number::create(1)])]);
+  let b = list(vec![machine.symbol("x"), list(vec![Number::create(1)])]);
+  let c = list(vec![machine.symbol("x"), list(vec![Number::create(2)])]);
+  let d = list(vec![machine.symbol("x")]);
+
+  assert!( deep_eq(&a, &b));
+  assert!(!deep_eq(&a, &c));
+  assert!(!deep_eq(&a, &d));
+  assert!(!deep_eq(&a, &machine.symbol("x")));
+}
+
+#[test]
+fn holes_and_child_relationships() {
+  let machine = Machine::new();
+
+  let a = Thing::from_fn(|meta| {
+    meta.members.set(2, machine.symbol("x"));
+  });
+
+  let b = Thing::from_fn(|meta| {
+    meta.members.set_child(2, machine.symbol("x"));
+  });
+
+  let c = list(vec![machine.symbol("x")]);
+
+  assert!( deep_eq(&a, &b));
+  assert!(!deep_eq(&a, &c));
+
+  // Trailing holes don't count.
+  assert!( deep_eq(&Thing::empty(), &Thing::from_fn(|meta| {
+    meta.members.expand_to(3);
+  })));
+}
+
+#[test]
+fn executions_by_identity() {
+  let machine = Machine::new();
+
+  let execution = Execution::create(&machine, Script(vec![]));
+  let other     = Execution::create(&machine, Script(vec![]));
+
+  assert!( deep_eq(&list(vec![execution.clone()]),
+                   &list(vec![execution.clone()])));
+  assert!(!deep_eq(&list(vec![execution]), &list(vec![other])));
+}
+
+#[test]
+fn cycles() {
+  let a = Thing::empty();
+  let b = Thing::empty();
+  let c = Thing::from_fn(|meta| meta.members.push(Number::create(1)));
+
+  a.lock().meta_mut().members.push(a.clone());
+  b.lock().meta_mut().members.push(b.clone());
+
+  // `c` also refers to `c`, but has something more in it.
+  c.lock().meta_mut().members.push(c.clone());
+
+  assert!( deep_eq(&a, &b));
+  assert!(!deep_eq(&a, &c));
+}
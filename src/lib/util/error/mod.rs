@@ -0,0 +1,69 @@
+//! Error objects, for system aliens to respond with when they can't do what
+//! they were asked, so that the caller isn't left waiting forever.
+//!
+//! An error is a Thing tagged `(error)` with two pairs: `kind`, a Symbol naming
+//! what failed (the same categories as warnings, like `infrastructure`), and
+//! `message`, a Symbol describing what went wrong. Scripts can look those up
+//! like any other pairs. See also `infrastructure error`.
+
+use object::{ObjectRef, Meta};
+
+use nuketype::Thing;
+
+use machine::{Machine, Reactor};
+
+#[cfg(test)]
+mod tests;
+
+/// The tag every error object has.
+pub static TAG: &'static str = "(error)";
+
+/// Creates an error object with the given `kind` and `message`.
+pub fn create(machine: &Machine, kind: &str, message: &str) -> ObjectRef {
+  create_from(machine, machine.symbol(kind), machine.symbol(message))
+}
+
+/// Like `create()`, but with any objects for the `kind` and `message`.
+pub fn create_from(machine: &Machine, kind: ObjectRef, message: ObjectRef)
+                   -> ObjectRef {
+
+  let mut meta = Meta::new();
+
+  meta.members.push_pair(machine.symbol("kind"),    kind);
+  meta.members.push_pair(machine.symbol("message"), message);
+
+  Thing::tagged(meta, TAG)
+}
+
+/// Returns true if `object` was created by `create()`.
+pub fn is_error(object: &ObjectRef) -> bool {
+  object.tag().map(|tag| tag.as_slice() == TAG).unwrap_or(false)
+}
+
+/// Gets the `kind` of an error object, if it is one and the kind is a Symbol.
+pub fn kind_of(machine: &Machine, error: &ObjectRef) -> Option<String> {
+  pair_of(machine, error, "kind")
+}
+
+/// Gets the `message` of an error object, if it is one and the message is a
+/// Symbol.
+pub fn message_of(machine: &Machine, error: &ObjectRef) -> Option<String> {
+  pair_of(machine, error, "message")
+}
+
+fn pair_of(machine: &Machine, error: &ObjectRef, key: &str) -> Option<String> {
+  if !is_error(error) { return None }
+
+  error.lock().meta().members.lookup_pair(&machine.symbol(key))
+    .and_then(|value| value.symbol_ref().map(|s| s.as_slice().to_string()))
+}
+
+/// Stages `caller` with a new error object. Use `respond_error!` instead,
+/// which also warns.
+pub fn respond(reactor: &mut Reactor, caller: ObjectRef, kind: &str,
+               message: String) {
+
+  let error = create(reactor.machine(), kind, message.as_slice());
+
+  reactor.stage(caller, error);
+}
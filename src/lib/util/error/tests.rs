@@ -0,0 +1,39 @@
+use super::{create, is_error, kind_of, message_of};
+
+use nuketype::Thing;
+
+use machine::{Machine, Reactor};
+use machine::reactor::MockReactor;
+
+#[test]
+fn error_objects_have_kind_and_message() {
+  let machine = Machine::new();
+
+  let error = create(&machine, "infrastructure", "something went wrong");
+
+  assert!(is_error(&error));
+
+  assert_eq!(Some("infrastructure".to_string()),
+             kind_of(&machine, &error));
+  assert_eq!(Some("something went wrong".to_string()),
+             message_of(&machine, &error));
+
+  assert!(!is_error(&Thing::empty()));
+  assert_eq!(None, kind_of(&machine, &Thing::empty()));
+}
+
+#[test]
+fn respond_error_warns_and_stages() {
+  let     machine = Machine::new();
+  let mut reactor = MockReactor::new(machine.clone());
+
+  let caller = Thing::empty();
+
+  respond_error!(&mut reactor, caller.clone(), "test", "bad {}", "input");
+
+  let (_, error) = reactor.expect_staged(&caller);
+
+  assert_eq!(Some("bad input".to_string()), message_of(&machine, &error));
+
+  reactor.expect_no_stagings();
+}
@@ -0,0 +1,256 @@
+//! Snapshots of object graphs, as plain data.
+//!
+//! A `Graph` records everything reachable from a root object, with each object
+//! numbered and relationships recorded by number, so that the graph can be
+//! inspected or written out without holding any locks.
+
+use object::{ObjectRef, ObjectReceiver, NativeReceiver};
+use nuketype::{Nuketype, Thing, Execution, Alien, Locals};
+
+use std::any::AnyRefExt;
+use std::collections::{HashMap, RingBuf, Deque};
+use std::io::{IoResult, MemWriter};
+
+#[cfg(test)]
+mod tests;
+
+/// The version of the text format written by `Graph::write_text()`.
+pub static TEXT_VERSION: uint = 1;
+
+/// A snapshot of an object graph. `nodes[root]` is the root object.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct Graph {
+  /// Every object reachable from the root, numbered by index.
+  pub nodes: Vec<Node>,
+
+  /// The index of the root object.
+  pub root:  uint
+}
+
+/// A single object within a `Graph`.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct Node {
+  /// What kind of nuketype the object has.
+  pub kind:     NodeKind,
+
+  /// The object's tag, if it has one.
+  pub tag:      Option<String>,
+
+  /// The object's members, including the noughty (0th) slot. `None`
+  /// represents a hole.
+  pub members:  Vec<Option<Edge>>,
+
+  /// The object's receiver.
+  pub receiver: NodeReceiver
+}
+
+/// The nuketype of a `Node`.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum NodeKind {
+  /// A Symbol, with its string.
+  SymbolNode(String),
+
+  /// A plain Thing.
+  ThingNode,
+
+  /// A Locals object.
+  LocalsNode,
+
+  /// An Execution, with its debug representation.
+  ExecutionNode(String),
+
+  /// An Alien. Its data is opaque.
+  AlienNode,
+
+  /// Some other nuketype, with its debug representation.
+  OtherNode(String)
+}
+
+/// A relationship from one `Node` to another.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct Edge {
+  /// The index of the target node.
+  pub to:    uint,
+
+  /// Whether the relationship is a child relationship.
+  pub child: bool
+}
+
+/// The receiver of a `Node`.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum NodeReceiver {
+  /// An object receiver, by node index.
+  ObjectNodeReceiver(uint),
+
+  /// A native receiver. Native functions can't be recorded.
+  NativeNodeReceiver
+}
+
+impl Graph {
+  /// Records everything reachable from `root`, through members and object
+  /// receivers, in breadth-first order. The root is always node 0.
+  pub fn snapshot(root: &ObjectRef) -> Graph {
+    Graph::snapshot_all([root.clone()])
+  }
+
+  /// Like `snapshot()`, but records everything reachable from any of `roots`.
+  /// Each root is numbered before anything else, in order, skipping any that
+  /// appear more than once. `root` is the first of them.
+  ///
+  /// # Failure
+  ///
+  /// Fails if `roots` is empty.
+  pub fn snapshot_all(roots: &[ObjectRef]) -> Graph {
+    let mut indices: HashMap<ObjectRef, uint> = HashMap::new();
+    let mut queue:   RingBuf<ObjectRef>       = RingBuf::new();
+    let mut nodes:   Vec<Node>                = Vec::new();
+
+    assert!(!roots.is_empty(), "no roots to snapshot");
+
+    for root in roots.iter() {
+      index_of(root, &mut indices, &mut queue);
+    }
+
+    loop {
+      let object = match queue.pop_front() {
+        Some(object) => object,
+        None         => break
+      };
+
+      let (kind, meta) = {
+        let guard = object.lock();
+
+        (kind_of(&object, guard.nuketype()), guard.meta().clone())
+      };
+
+      let members = meta.members.vec.iter().map(|maybe_relationship|
+        maybe_relationship.as_ref().map(|relationship|
+          Edge {
+            to:    index_of(relationship.to(), &mut indices, &mut queue),
+            child: relationship.is_child()
+          })).collect();
+
+      let receiver = match meta.receiver {
+        ObjectReceiver(ref receiver) =>
+          ObjectNodeReceiver(index_of(receiver, &mut indices, &mut queue)),
+        NativeReceiver(_) =>
+          NativeNodeReceiver
+      };
+
+      nodes.push(Node {
+        kind:     kind,
+        tag:      object.tag().map(|tag| tag.as_slice().to_string()),
+        members:  members,
+        receiver: receiver
+      });
+    }
+
+    Graph { nodes: nodes, root: 0 }
+  }
+
+  /// Writes the graph in a simple line-based text format:
+  ///
+  ///     paws-graph 1
+  ///     root 0
+  ///     object 0 thing
+  ///       tag "example"
+  ///       receiver native
+  ///       member 1 child
+  ///       member none
+  ///     object 1 symbol "hello"
+  ///       receiver native
+  pub fn write_text(&self, writer: &mut Writer) -> IoResult<()> {
+    try!(writeln!(writer, "paws-graph {}", TEXT_VERSION));
+    try!(writeln!(writer, "root {}", self.root));
+
+    for (index, node) in self.nodes.iter().enumerate() {
+      try!(match node.kind {
+        SymbolNode(ref string) =>
+          writeln!(writer, "object {} symbol \"{}\"",
+                   index, string.escape_default()),
+        ThingNode =>
+          writeln!(writer, "object {} thing", index),
+        LocalsNode =>
+          writeln!(writer, "object {} locals", index),
+        ExecutionNode(ref description) =>
+          writeln!(writer, "object {} execution \"{}\"",
+                   index, description.escape_default()),
+        AlienNode =>
+          writeln!(writer, "object {} alien", index),
+        OtherNode(ref description) =>
+          writeln!(writer, "object {} other \"{}\"",
+                   index, description.escape_default())
+      });
+
+      match node.tag {
+        Some(ref tag) =>
+          try!(writeln!(writer, "  tag \"{}\"", tag.escape_default())),
+        None => ()
+      }
+
+      try!(match node.receiver {
+        ObjectNodeReceiver(to) => writeln!(writer, "  receiver object {}", to),
+        NativeNodeReceiver     => writeln!(writer, "  receiver native")
+      });
+
+      for member in node.members.iter() {
+        try!(match *member {
+          Some(Edge { to: to, child: true }) =>
+            writeln!(writer, "  member {} child", to),
+          Some(Edge { to: to, child: false }) =>
+            writeln!(writer, "  member {}", to),
+          None =>
+            writeln!(writer, "  member none")
+        });
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// Assigns an index to an object, queueing it if it hasn't been seen yet.
+fn index_of(object:  &ObjectRef,
+            indices: &mut HashMap<ObjectRef, uint>,
+            queue:   &mut RingBuf<ObjectRef>)
+            -> uint {
+
+  match indices.find(object) {
+    Some(&index) => return index,
+    None         => ()
+  }
+
+  let index = indices.len();
+
+  indices.insert(object.clone(), index);
+  queue.push_back(object.clone());
+
+  index
+}
+
+fn kind_of(object: &ObjectRef, nuketype: &Nuketype) -> NodeKind {
+  match object.symbol_ref() {
+    Some(string) => return SymbolNode(string.as_slice().to_string()),
+    None         => ()
+  }
+
+  if nuketype.is::<Thing>() {
+    ThingNode
+  } else if nuketype.is::<Locals>() {
+    LocalsNode
+  } else if nuketype.is::<Alien>() {
+    AlienNode
+  } else if nuketype.is::<Execution>() {
+    ExecutionNode(describe(nuketype))
+  } else {
+    OtherNode(describe(nuketype))
+  }
+}
+
+fn describe(nuketype: &Nuketype) -> String {
+  let mut writer = MemWriter::new();
+
+  nuketype.fmt_paws(&mut writer).unwrap();
+
+  String::from_utf8(writer.unwrap()).unwrap_or_else(|_| "?".to_string())
+}
@@ -0,0 +1,92 @@
+use super::{Graph, Node, Edge};
+use super::{SymbolNode, ThingNode, NativeNodeReceiver, ObjectNodeReceiver};
+
+use object::ObjectReceiver;
+
+use nuketype::Thing;
+
+use machine::Machine;
+
+use std::io::MemWriter;
+
+#[test]
+fn snapshot_numbers_objects_breadth_first() {
+  let machine = Machine::new();
+
+  let child = Thing::empty();
+
+  let root = Thing::from_fn(|meta| {
+    meta.members.push_child(child.clone());
+    meta.members.push(machine.symbol("hello"));
+    meta.members.push(child.clone());
+  });
+
+  child.lock().meta_mut().receiver = ObjectReceiver(root.clone());
+
+  let graph = Graph::snapshot(&root);
+
+  assert_eq!(0, graph.root);
+
+  assert_eq!(vec![
+    Node {
+      kind:     ThingNode,
+      tag:      None,
+      members:  vec![None,
+                     Some(Edge { to: 1, child: true }),
+                     Some(Edge { to: 2, child: false }),
+                     Some(Edge { to: 1, child: false })],
+      receiver: NativeNodeReceiver
+    },
+    Node {
+      kind:     ThingNode,
+      tag:      None,
+      members:  vec![],
+      receiver: ObjectNodeReceiver(0)
+    },
+    Node {
+      kind:     SymbolNode("hello".to_string()),
+      tag:      None,
+      members:  vec![],
+      receiver: NativeNodeReceiver
+    }], graph.nodes);
+}
+
+#[test]
+fn write_text() {
+  let machine = Machine::new();
+
+  let root = Thing::from_fn(|meta| {
+    meta.members.push(machine.symbol("say \"hi\""));
+  });
+
+  let mut writer = MemWriter::new();
+
+  Graph::snapshot(&root).write_text(&mut writer).unwrap();
+
+  assert_eq!(concat!("paws-graph 1\n",
+                     "root 0\n",
+                     "object 0 thing\n",
+                     "  receiver native\n",
+                     "  member none\n",
+                     "  member 1\n",
+                     "object 1 symbol \"say \\\"hi\\\"\"\n",
+                     "  receiver native\n"),
+             String::from_utf8(writer.unwrap()).unwrap().as_slice());
+}
+
+#[test]
+fn snapshot_all_numbers_roots_first() {
+  let shared = Thing::empty();
+  let first  = Thing::from_fn(|meta| meta.members.push(shared.clone()));
+  let second = Thing::from_fn(|meta| meta.members.push(shared.clone()));
+
+  let graph = Graph::snapshot_all([first.clone(), second.clone(),
+                                   first.clone()]);
+
+  assert_eq!(0, graph.root);
+  assert_eq!(3, graph.nodes.len());
+
+  // Both roots point at the same object, which comes after them.
+  assert_eq!(Some(Edge { to: 2, child: false }), graph.nodes[0].members[1]);
+  assert_eq!(Some(Edge { to: 2, child: false }), graph.nodes[1].members[1]);
+}
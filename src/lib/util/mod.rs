@@ -1,10 +1,16 @@
-///! Utility functions and structures.
+//! Utility functions and structures.
 
 use std::io::timer::Timer;
 use std::time::duration::Duration;
 
 pub mod namespace;
 pub mod clone;
+pub mod transfer;
+pub mod graph;
+pub mod serialize;
+pub mod error;
+pub mod pretty;
+pub mod compare;
 
 /// Spawn the given block and fail if the timeout is reached before it
 /// completes.
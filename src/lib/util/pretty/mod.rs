@@ -0,0 +1,174 @@
+//! Human-readable trees of objects and their members, for debugging.
+//!
+//! Each object gets a line with its reference (see `ObjectRef`'s `Show`) and,
+//! unless it's a Symbol, its nuketype's `fmt_paws()`. Its members follow,
+//! indented, with their indices, and a `*` before those that are children:
+//!
+//!     [#0x7f3a2c0 ~example] Thing
+//!       1: *[#0x7f3a2e0] Thing
+//!         1: [:hello]
+//!         2: [:world]
+//!       2: [#0x7f3a2c0 ~example] (see above)
+//!
+//! Holes are left out. An object that has already been shown isn't shown
+//! again, so cycles end, and so do trees deeper than `max_depth` or wider than
+//! `max_members`.
+//!
+//! With `pairs` set, members that are pairs keyed by a Symbol (see
+//! `Thing::pair()`) are shown as the key and the value's reference instead,
+//! with the value's members below:
+//!
+//!     [#0x7f3a2c0 ~example] Thing
+//!       1: *hello → [:world]
+
+use object::ObjectRef;
+
+use std::collections::HashSet;
+use std::io::{IoResult, MemWriter};
+
+#[cfg(test)]
+mod tests;
+
+/// Renders objects as trees. See the module documentation.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub struct PrettyPrinter {
+  /// How many levels of members to show below the root.
+  pub max_depth:   uint,
+
+  /// How many members to show of any one object.
+  pub max_members: uint,
+
+  /// Whether to show pairs as `key → value`.
+  pub pairs:       bool
+}
+
+impl PrettyPrinter {
+  /// Creates a new `PrettyPrinter` that shows up to 4 levels and 32 members of
+  /// each object, and doesn't treat pairs specially.
+  pub fn new() -> PrettyPrinter {
+    PrettyPrinter {
+      max_depth:   4,
+      max_members: 32,
+      pairs:       false
+    }
+  }
+
+  /// Sets `max_depth`.
+  pub fn with_max_depth(self, max_depth: uint) -> PrettyPrinter {
+    PrettyPrinter { max_depth: max_depth, ..self }
+  }
+
+  /// Sets `max_members`.
+  pub fn with_max_members(self, max_members: uint) -> PrettyPrinter {
+    PrettyPrinter { max_members: max_members, ..self }
+  }
+
+  /// Sets `pairs`.
+  pub fn with_pairs(self, pairs: bool) -> PrettyPrinter {
+    PrettyPrinter { pairs: pairs, ..self }
+  }
+
+  /// Renders `root` to a string, without a trailing newline.
+  pub fn to_string(&self, root: &ObjectRef) -> String {
+    let mut writer = MemWriter::new();
+
+    self.write(&mut writer, root).unwrap();
+
+    let mut string = String::from_utf8(writer.unwrap()).unwrap();
+
+    string.pop_char();
+    string
+  }
+
+  /// Writes `root` to `writer`, ending with a newline.
+  pub fn write(&self, writer: &mut Writer, root: &ObjectRef) -> IoResult<()> {
+    try!(write!(writer, "{}", root));
+
+    self.write_object(writer, root, 0, &mut HashSet::new())
+  }
+
+  /// Writes the rest of the line for `object`, and then its members, at
+  /// `depth`.
+  fn write_object(&self, writer: &mut Writer, object: &ObjectRef, depth: uint,
+                  seen: &mut HashSet<ObjectRef>) -> IoResult<()> {
+
+    if !seen.insert(object.clone()) {
+      return writer.write_str(" (see above)\n");
+    }
+
+    // Take what we need and let go, so that nothing stays locked while we look
+    // at the members.
+    let members: Vec<(uint, bool, ObjectRef)> = {
+      let guard = object.lock();
+
+      if object.symbol_ref().is_none() {
+        try!(writer.write_char(' '));
+        try!(guard.nuketype().fmt_paws(writer));
+      }
+
+      guard.meta().members.iter().enumerate()
+        .filter_map(|(index, member)| member.as_ref().map(|r|
+          (index, r.is_child(), r.to().clone())))
+        .collect()
+    };
+
+    if members.is_empty() {
+      return writer.write_char('\n');
+    }
+
+    if depth >= self.max_depth {
+      return writeln!(writer, " ({} members not shown)", members.len());
+    }
+
+    try!(writer.write_char('\n'));
+
+    let indent = "  ".repeat(depth + 1);
+
+    for &(index, is_child, ref member) in
+        members.iter().take(self.max_members) {
+
+      try!(write!(writer, "{}{}: {}", indent, index,
+                  if is_child { "*" } else { "" }));
+
+      let pair = if self.pairs { as_pair(member) } else { None };
+
+      let shown = match pair {
+        Some((key, value)) => {
+          try!(write!(writer, "{} → ", key));
+          value
+        },
+
+        None => member.clone()
+      };
+
+      try!(write!(writer, "{}", shown));
+      try!(self.write_object(writer, &shown, depth + 1, seen));
+    }
+
+    if members.len() > self.max_members {
+      try!(writeln!(writer, "{}... ({} more)", indent,
+                    members.len() - self.max_members));
+    }
+
+    Ok(())
+  }
+}
+
+/// If `object` is shaped like `[, key, value]` with a Symbol for a key, returns
+/// the key's string and the value.
+fn as_pair(object: &ObjectRef) -> Option<(String, ObjectRef)> {
+  let guard   = object.lock();
+  let members = &guard.meta().members;
+
+  if members.len() != 3 || members.get(0).is_some() {
+    return None;
+  }
+
+  match (members.get(1), members.get(2)) {
+    (Some(key), Some(value)) =>
+      key.to().symbol_ref().map(|string|
+        (string.as_slice().to_string(), value.to().clone())),
+
+    _ => None
+  }
+}
@@ -0,0 +1,88 @@
+use super::PrettyPrinter;
+
+use object::Meta;
+
+use nuketype::Thing;
+
+use machine::Machine;
+
+#[test]
+fn shows_members_with_indices() {
+  let machine = Machine::new();
+
+  let mut meta = Meta::new();
+
+  meta.members.push_pair(machine.symbol("hello"), machine.symbol("world"));
+
+  let root  = Thing::tagged(meta, "root");
+  let lines = PrettyPrinter::new().to_string(&root);
+  let lines: Vec<&str> = lines.as_slice().lines().collect();
+
+  assert_eq!(lines.len(), 4);
+  assert!(lines[0].contains("~root"));
+  assert!(lines[1].starts_with("  1: *"));
+  assert_eq!(lines[2], "    1: [:hello]");
+  assert_eq!(lines[3], "    2: [:world]");
+}
+
+#[test]
+fn cycles_end() {
+  let root = Thing::empty();
+
+  root.lock().meta_mut().members.push(root.clone());
+
+  let output = PrettyPrinter::new().to_string(&root);
+  let lines: Vec<&str> = output.as_slice().lines().collect();
+
+  assert_eq!(lines.len(), 2);
+  assert!(lines[1].ends_with("(see above)"));
+}
+
+#[test]
+fn depth_and_members_are_bounded() {
+  let machine = Machine::new();
+
+  let inner = Thing::empty();
+
+  {
+    let mut inner_obj = inner.lock();
+
+    for name in ["a", "b", "c"].iter() {
+      inner_obj.meta_mut().members.push(machine.symbol(*name));
+    }
+  }
+
+  let mut meta = Meta::new();
+
+  meta.members.push(inner.clone());
+
+  let root = Thing::create(meta);
+
+  let shallow = PrettyPrinter::new().with_max_depth(1).to_string(&root);
+
+  assert!(shallow.as_slice().lines().last().unwrap()
+            .ends_with("(3 members not shown)"));
+
+  let narrow = PrettyPrinter::new().with_max_members(2).to_string(&inner);
+
+  assert!(narrow.as_slice().lines().last().unwrap()
+            .ends_with("... (1 more)"));
+}
+
+#[test]
+fn pairs_show_key_and_value() {
+  let machine = Machine::new();
+
+  let mut meta = Meta::new();
+
+  meta.members.push_pair(machine.symbol("hello"), machine.symbol("world"));
+  meta.members.push(machine.symbol("alone"));
+
+  let root   = Thing::create(meta);
+  let output = PrettyPrinter::new().with_pairs(true).to_string(&root);
+  let lines: Vec<&str> = output.as_slice().lines().collect();
+
+  assert_eq!(lines.len(), 3);
+  assert_eq!(lines[1], "  1: *hello → [:world]");
+  assert_eq!(lines[2], "  2: [:alone]");
+}
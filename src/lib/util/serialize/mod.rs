@@ -0,0 +1,769 @@
+//! Saving object graphs as JSON, and restoring them into another Machine.
+//!
+//! `write_json()` records everything reachable from a root object, much like
+//! `util::graph`, but with enough detail to rebuild it: Symbols' strings,
+//! Executions' Scripts, program counters and stacks, tags, receivers, and
+//! whether objects are frozen. `from_json()` (or `read_json()`) rebuilds the
+//! graph within another (usually fresh) Machine, much like `util::transfer`.
+//!
+//! The format looks like this:
+//!
+//!     {"format":"paws-json","version":1,"root":0,"objects":[
+//!     {"kind":"thing","tag":"example","receiver":{"native":"lookup"},
+//!      "members":[null,{"to":1,"child":true}]},
+//!     {"kind":"symbol","name":"hello","receiver":{"native":"lookup"},
+//!      "members":[]}
+//!     ]}
+//!
+//! Objects are numbered breadth-first from the root, by their index in
+//! `objects`. Some objects can be written, but not restored:
+//!
+//! * Aliens, since their routines and data are opaque.
+//! * Objects with native receivers other than `lookup_receiver`,
+//!   `locals_receiver`, `stage_receiver` and `clone_receiver`.
+//! * Nuketypes that this module doesn't know about.
+//!
+//! The `infrastructure` and `implementation` namespaces are written by name
+//! only, and restored as the other Machine's namespaces.
+
+use object::{ObjectRef, Meta, Members, Relationship, Params};
+use object::{ObjectReceiver, NativeReceiver, lookup_receiver};
+use nuketype::{Nuketype, Thing, Execution, Alien, Locals, Number};
+use nuketype::locals::locals_receiver;
+use nuketype::execution::stage_receiver;
+use system::infrastructure::clone::clone_receiver;
+use script::*;
+use machine::{Machine, Reactor};
+use machine::reactor::{Combinable, FromLocals, FromSelf, From};
+
+use serialize::json;
+use serialize::json::Json;
+
+use std::any::AnyRefExt;
+use std::collections::{HashMap, HashSet, RingBuf, Deque};
+use std::io::{IoResult, MemWriter};
+
+#[cfg(test)]
+mod tests;
+
+/// The version of the format written by `write_json()`.
+pub static FORMAT_VERSION: uint = 1;
+
+type NativeFn = fn (&mut Reactor, Params);
+
+/// The native receivers that can be written and restored, by name.
+fn native_receivers() -> [(&'static str, NativeFn), ..4] {
+  [("lookup", lookup_receiver),
+   ("locals", locals_receiver),
+   ("stage",  stage_receiver),
+   ("clone",  clone_receiver)]
+}
+
+/// Writes everything reachable from `root` to a JSON string. See
+/// `write_json()`.
+pub fn to_json(machine: &Machine, root: &ObjectRef) -> String {
+  let mut writer = MemWriter::new();
+
+  write_json(machine, root, &mut writer).unwrap();
+
+  String::from_utf8(writer.unwrap()).unwrap()
+}
+
+/// Writes everything reachable from `root` as JSON, through members, object
+/// receivers, Locals' names, and the objects referenced by Executions' Scripts
+/// and stacks. `machine`'s system namespaces are written by name only.
+pub fn write_json(machine: &Machine, root: &ObjectRef, writer: &mut Writer)
+                  -> IoResult<()> {
+
+  let mut dump = Dump {
+    indices: HashMap::new(),
+    queue:   RingBuf::new(),
+    system:  vec![(machine.infrastructure(), "infrastructure"),
+                  (machine.implementation(), "implementation")]
+  };
+
+  dump.index_of(root);
+
+  try!(write!(writer,
+              "{{\"format\":\"paws-json\",\"version\":{},\"root\":0,\
+               \"objects\":[\n", FORMAT_VERSION));
+
+  let mut first = true;
+
+  loop {
+    let object = match dump.queue.pop_front() {
+      Some(object) => object,
+      None         => break
+    };
+
+    if !first { try!(writer.write_str(",\n")) }
+
+    first = false;
+
+    try!(dump.write_object(writer, &object));
+  }
+
+  writer.write_str("\n]}\n")
+}
+
+struct Dump {
+  indices: HashMap<ObjectRef, uint>,
+  queue:   RingBuf<ObjectRef>,
+  system:  Vec<(ObjectRef, &'static str)>
+}
+
+/// The nuketype of an object being written, taken out while it's locked.
+enum Kind {
+  SymbolKind(String),
+  ThingKind,
+  NumberKind(i64),
+  LocalsKind(ObjectRef),
+  ExecutionKind(Execution),
+  AlienKind,
+  OtherKind(String)
+}
+
+impl Dump {
+  /// Assigns an index to an object, queueing it if it hasn't been seen yet.
+  fn index_of(&mut self, object: &ObjectRef) -> uint {
+    match self.indices.find(object) {
+      Some(&index) => return index,
+      None         => ()
+    }
+
+    let index = self.indices.len();
+
+    self.indices.insert(object.clone(), index);
+    self.queue.push_back(object.clone());
+
+    index
+  }
+
+  fn write_object(&mut self, writer: &mut Writer, object: &ObjectRef)
+                  -> IoResult<()> {
+
+    for &(ref namespace, name) in self.system.iter() {
+      if object == namespace {
+        return write!(writer, "{{\"kind\":\"system\",\"name\":{}}}",
+                      quote(name));
+      }
+    }
+
+    let (kind, meta) = {
+      let guard = object.lock();
+
+      (kind_of(object, guard.nuketype()), guard.meta().clone())
+    };
+
+    try!(match kind {
+      SymbolKind(ref name) =>
+        write!(writer, "{{\"kind\":\"symbol\",\"name\":{}",
+               quote(name.as_slice())),
+
+      ThingKind =>
+        write!(writer, "{{\"kind\":\"thing\""),
+
+      NumberKind(value) =>
+        write!(writer, "{{\"kind\":\"number\",\"value\":\"{}\"", value),
+
+      LocalsKind(ref name) =>
+        write!(writer, "{{\"kind\":\"locals\",\"name\":{}",
+               self.index_of(name)),
+
+      ExecutionKind(ref execution) =>
+        self.write_execution(writer, execution),
+
+      AlienKind =>
+        write!(writer, "{{\"kind\":\"alien\""),
+
+      OtherKind(ref description) =>
+        write!(writer, "{{\"kind\":\"other\",\"description\":{}",
+               quote(description.as_slice()))
+    });
+
+    match object.tag() {
+      Some(tag) => try!(write!(writer, ",\"tag\":{}", quote(tag.as_slice()))),
+      None      => ()
+    }
+
+    if object.is_frozen() {
+      try!(writer.write_str(",\"frozen\":true"));
+    }
+
+    try!(match meta.receiver {
+      ObjectReceiver(ref receiver) =>
+        write!(writer, ",\"receiver\":{{\"object\":{}}}",
+               self.index_of(receiver)),
+
+      NativeReceiver(function) => {
+        let name = native_receivers().iter()
+          .find(|&&(_, f)| f as *const () == function as *const ())
+          .map(|&(name, _)| quote(name))
+          .unwrap_or_else(|| "null".to_string());
+
+        write!(writer, ",\"receiver\":{{\"native\":{}}}", name)
+      }
+    });
+
+    try!(writer.write_str(",\"members\":["));
+
+    for (position, member) in meta.members.vec.iter().enumerate() {
+      if position > 0 { try!(writer.write_str(",")) }
+
+      try!(match *member {
+        Some(ref relationship) =>
+          write!(writer, "{{\"to\":{},\"child\":{}}}",
+                 self.index_of(relationship.to()), relationship.is_child()),
+        None =>
+          writer.write_str("null")
+      });
+    }
+
+    writer.write_str("]}")
+  }
+
+  fn write_execution(&mut self, writer: &mut Writer, execution: &Execution)
+                     -> IoResult<()> {
+
+    try!(write!(writer, "{{\"kind\":\"execution\",\"pc\":{},\"script\":[",
+                execution.pc()));
+
+    let Script(ref instructions) = *execution.root();
+
+    for (position, instruction) in instructions.iter().enumerate() {
+      if position > 0 { try!(writer.write_str(",")) }
+
+      try!(match *instruction {
+        PushLocals =>
+          writer.write_str("[\"push-locals\"]"),
+        PushSelf =>
+          writer.write_str("[\"push-self\"]"),
+        Push(ref object) =>
+          write!(writer, "[\"push\",{}]", self.index_of(object)),
+        Combine =>
+          writer.write_str("[\"combine\"]"),
+        Discard =>
+          writer.write_str("[\"discard\"]"),
+        PushPair(ref key, ref value) =>
+          write!(writer, "[\"push-pair\",{},{}]",
+                 self.index_of(key), self.index_of(value)),
+        LookupCombine(ref symbol) =>
+          write!(writer, "[\"lookup-combine\",{}]", self.index_of(symbol)),
+        LookupLocal(ref symbol) =>
+          write!(writer, "[\"lookup-local\",{}]", self.index_of(symbol))
+      });
+    }
+
+    try!(writer.write_str("],\"stack\":["));
+
+    for (position, combinable) in execution.stack().iter().enumerate() {
+      if position > 0 { try!(writer.write_str(",")) }
+
+      try!(match *combinable {
+        FromLocals       => writer.write_str("\"locals\""),
+        FromSelf         => writer.write_str("\"self\""),
+        From(ref object) => write!(writer, "{}", self.index_of(object))
+      });
+    }
+
+    writer.write_str("]")
+  }
+}
+
+fn kind_of(object: &ObjectRef, nuketype: &Nuketype) -> Kind {
+  match object.symbol_ref() {
+    Some(string) => return SymbolKind(string.as_slice().to_string()),
+    None         => ()
+  }
+
+  match nuketype.downcast_ref::<Execution>() {
+    Some(execution) => return ExecutionKind(execution.clone()),
+    None            => ()
+  }
+
+  match nuketype.downcast_ref::<Locals>() {
+    Some(locals) => return LocalsKind(locals.name().clone()),
+    None         => ()
+  }
+
+  match nuketype.downcast_ref::<Number>() {
+    Some(number) => return NumberKind(number.value()),
+    None         => ()
+  }
+
+  if nuketype.is::<Thing>() {
+    ThingKind
+  } else if nuketype.is::<Alien>() {
+    AlienKind
+  } else {
+    let mut writer = MemWriter::new();
+
+    nuketype.fmt_paws(&mut writer).unwrap();
+
+    OtherKind(String::from_utf8(writer.unwrap())
+                .unwrap_or_else(|_| "?".to_string()))
+  }
+}
+
+/// Quotes and escapes a string for JSON.
+fn quote(string: &str) -> String {
+  json::String(string.to_string()).to_string()
+}
+
+/// Reads a whole document from `reader` and rebuilds it with `from_json()`.
+pub fn read_json(machine: &Machine, reader: &mut Reader)
+                 -> Result<ObjectRef, String> {
+
+  match reader.read_to_string() {
+    Ok(input) => from_json(machine, input.as_slice()),
+    Err(e)    => Err(format!("couldn't read JSON: {}", e))
+  }
+}
+
+/// Rebuilds the object graph written by `write_json()` within `machine`, and
+/// returns the root object.
+///
+/// Fails with a description of the problem if the input isn't valid, or if it
+/// contains something that can't be restored (see the module documentation).
+/// A cycle that passes through a frozen object can't be restored either: unlike
+/// `util::transfer::copy_graph()`, there's no original to leave it pointing at.
+pub fn from_json(machine: &Machine, input: &str) -> Result<ObjectRef, String> {
+  let document = match json::from_str(input) {
+    Ok(document) => document,
+    Err(e)       => return Err(format!("invalid JSON: {}", e))
+  };
+
+  match try!(field(&document, "format")).as_string() {
+    Some("paws-json") => (),
+    _                 => return Err("not a paws-json document".to_string())
+  }
+
+  let version = try!(uint_of(try!(field(&document, "version"))));
+
+  if version != FORMAT_VERSION {
+    return Err(format!("unsupported paws-json version {}", version));
+  }
+
+  let root    = try!(uint_of(try!(field(&document, "root"))));
+  let objects = try!(list_of(try!(field(&document, "objects"))));
+
+  let mut nodes = Vec::with_capacity(objects.len());
+
+  for (index, object) in objects.iter().enumerate() {
+    match Node::parse(object) {
+      Ok(node) => nodes.push(node),
+      Err(e)   => return Err(format!("object {}: {}", index, e))
+    }
+  }
+
+  let mut restore = Restore {
+    machine:   machine,
+    objects:   Vec::from_elem(nodes.len(), None),
+    nodes:     nodes,
+    in_frozen: HashSet::new()
+  };
+
+  restore.restore(root)
+}
+
+/// An object as read from the JSON, with references by index.
+#[deriving(Clone)]
+struct Node {
+  kind:     NodeKind,
+  tag:      Option<String>,
+  frozen:   bool,
+  receiver: NodeReceiver,
+  members:  Vec<Option<(uint, bool)>>
+}
+
+#[deriving(Clone)]
+enum NodeKind {
+  SymbolNode(String),
+  ThingNode,
+  NumberNode(i64),
+  LocalsNode(uint),
+  ExecutionNode(Vec<Op>, uint, Vec<Slot>),
+  SystemNode(String),
+  UnrestorableNode(String)
+}
+
+/// An instruction, with objects by index.
+#[deriving(Clone)]
+enum Op {
+  PlainOp(Instruction),
+  PushOp(uint),
+  PushPairOp(uint, uint),
+  LookupCombineOp(uint),
+  LookupLocalOp(uint)
+}
+
+/// An item on an Execution's stack, with objects by index.
+#[deriving(Clone)]
+enum Slot {
+  LocalsSlot,
+  SelfSlot,
+  ObjectSlot(uint)
+}
+
+enum NodeReceiver {
+  ObjectNodeReceiver(uint),
+  NativeNodeReceiver(NativeFn)
+}
+
+impl Clone for NodeReceiver {
+  fn clone(&self) -> NodeReceiver {
+    match *self {
+      ObjectNodeReceiver(index)    => ObjectNodeReceiver(index),
+      NativeNodeReceiver(function) => NativeNodeReceiver(function)
+    }
+  }
+}
+
+impl Node {
+  fn parse(object: &Json) -> Result<Node, String> {
+    let kind = match try!(string_of(try!(field(object, "kind")))) {
+      "symbol" =>
+        SymbolNode(try!(string_of(try!(field(object, "name")))).to_string()),
+
+      "thing" =>
+        ThingNode,
+
+      "number" => {
+        let value = try!(string_of(try!(field(object, "value"))));
+
+        match from_str::<i64>(value) {
+          Some(value) => NumberNode(value),
+          None        => return Err(format!("invalid number {}", value))
+        }
+      },
+
+      "locals" =>
+        LocalsNode(try!(uint_of(try!(field(object, "name"))))),
+
+      "execution" => {
+        let pc = try!(uint_of(try!(field(object, "pc"))));
+
+        let mut ops = vec![];
+
+        for op in try!(list_of(try!(field(object, "script")))).iter() {
+          ops.push(try!(parse_op(op)));
+        }
+
+        let mut slots = vec![];
+
+        for slot in try!(list_of(try!(field(object, "stack")))).iter() {
+          slots.push(match slot.as_string() {
+            Some("locals") => LocalsSlot,
+            Some("self")   => SelfSlot,
+            _              => ObjectSlot(try!(uint_of(slot)))
+          });
+        }
+
+        ExecutionNode(ops, pc, slots)
+      },
+
+      "system" =>
+        return Ok(Node {
+          kind:     SystemNode(
+                      try!(string_of(try!(field(object, "name")))).to_string()),
+          tag:      None,
+          frozen:   true,
+          receiver: NativeNodeReceiver(lookup_receiver),
+          members:  vec![]
+        }),
+
+      "alien" =>
+        UnrestorableNode("Aliens can't be restored".to_string()),
+
+      "other" =>
+        UnrestorableNode(format!("unknown nuketype {}",
+          object.find(&"description".to_string())
+            .and_then(|d| d.as_string()).unwrap_or("?"))),
+
+      kind =>
+        return Err(format!("unknown kind \"{}\"", kind))
+    };
+
+    let tag = match object.find(&"tag".to_string()) {
+      Some(tag) => Some(try!(string_of(tag)).to_string()),
+      None      => None
+    };
+
+    let frozen = object.find(&"frozen".to_string())
+                   .and_then(|frozen| frozen.as_boolean()).unwrap_or(false);
+
+    let receiver = try!(field(object, "receiver"));
+
+    let receiver = match receiver.find(&"object".to_string()) {
+      Some(index) => ObjectNodeReceiver(try!(uint_of(index))),
+
+      None => {
+        let name = try!(field(receiver, "native")).as_string();
+
+        match native_receivers().iter().find(|&&(n, _)| Some(n) == name) {
+          Some(&(_, function)) => NativeNodeReceiver(function),
+          None => return Err("native receiver can't be restored".to_string())
+        }
+      }
+    };
+
+    let mut members = vec![];
+
+    for member in try!(list_of(try!(field(object, "members")))).iter() {
+      members.push(if member.is_null() {
+        None
+      } else {
+        let to    = try!(uint_of(try!(field(member, "to"))));
+        let child = try!(field(member, "child")).as_boolean().unwrap_or(false);
+
+        Some((to, child))
+      });
+    }
+
+    Ok(Node {
+      kind:     kind,
+      tag:      tag,
+      frozen:   frozen,
+      receiver: receiver,
+      members:  members
+    })
+  }
+}
+
+fn parse_op(op: &Json) -> Result<Op, String> {
+  let op = try!(list_of(op));
+
+  let name = match op.as_slice().head() {
+    Some(name) => try!(string_of(name)),
+    None       => return Err("empty instruction".to_string())
+  };
+
+  let arg = |n: uint| -> Result<uint, String> {
+    match op.as_slice().get(n) {
+      Some(json) => uint_of(json),
+      None       => Err(format!("{} is missing an argument", name))
+    }
+  };
+
+  Ok(match name {
+    "push-locals"    => PlainOp(PushLocals),
+    "push-self"      => PlainOp(PushSelf),
+    "push"           => PushOp(try!(arg(1))),
+    "combine"        => PlainOp(Combine),
+    "discard"        => PlainOp(Discard),
+    "push-pair"      => PushPairOp(try!(arg(1)), try!(arg(2))),
+    "lookup-combine" => LookupCombineOp(try!(arg(1))),
+    "lookup-local"   => LookupLocalOp(try!(arg(1))),
+
+    _ => return Err(format!("unknown instruction \"{}\"", name))
+  })
+}
+
+fn field<'a>(object: &'a Json, key: &str) -> Result<&'a Json, String> {
+  match object.find(&key.to_string()) {
+    Some(value) => Ok(value),
+    None        => Err(format!("missing \"{}\"", key))
+  }
+}
+
+fn string_of<'a>(json: &'a Json) -> Result<&'a str, String> {
+  match json.as_string() {
+    Some(string) => Ok(string),
+    None         => Err(format!("expected a string, got {}", json))
+  }
+}
+
+fn uint_of(json: &Json) -> Result<uint, String> {
+  match json.as_u64() {
+    Some(n) => Ok(n as uint),
+    None    => Err(format!("expected an index, got {}", json))
+  }
+}
+
+fn list_of<'a>(json: &'a Json) -> Result<&'a Vec<Json>, String> {
+  match json.as_list() {
+    Some(list) => Ok(list),
+    None       => Err(format!("expected a list, got {}", json))
+  }
+}
+
+struct Restore<'a> {
+  machine:   &'a Machine,
+  nodes:     Vec<Node>,
+  objects:   Vec<Option<ObjectRef>>,
+  in_frozen: HashSet<uint>
+}
+
+impl<'a> Restore<'a> {
+  fn restore(&mut self, index: uint) -> Result<ObjectRef, String> {
+    if index >= self.nodes.len() {
+      return Err(format!("reference to nonexistent object {}", index));
+    }
+
+    match self.objects[index] {
+      Some(ref object) => return Ok(object.clone()),
+      None             => ()
+    }
+
+    if self.in_frozen.contains(&index) {
+      return Err(format!("can't restore a cycle through frozen object {}",
+                         index));
+    }
+
+    let node = self.nodes[index].clone();
+
+    let object = match node.kind {
+      SystemNode(ref name) =>
+        match name.as_slice() {
+          "infrastructure" => self.machine.infrastructure(),
+          "implementation" => self.machine.implementation(),
+          _ => return Err(format!("unknown system namespace \"{}\"", name))
+        },
+
+      UnrestorableNode(ref reason) =>
+        return Err(format!("object {}: {}", index, reason)),
+
+      SymbolNode(ref name) => {
+        let symbol = self.machine.symbol(name.as_slice());
+
+        *self.objects.get_mut(index) = Some(symbol.clone());
+
+        let meta = try!(self.meta(&node));
+
+        *symbol.lock().meta_mut() = meta;
+
+        symbol
+      },
+
+      _ if node.frozen => {
+        // The members have to be known before the object is stored, so it
+        // can't be registered until afterward.
+        self.in_frozen.insert(index);
+
+        let nuketype = try!(self.nuketype(&node.kind));
+        let meta     = try!(self.meta(&node));
+
+        self.in_frozen.remove(&index);
+
+        ObjectRef::store_frozen(nuketype, meta, node.tag.clone())
+      },
+
+      _ => {
+        // Store a placeholder before recursing, so that cycles find it.
+        let placeholder: Box<Nuketype+Send+Sync> = match node.kind {
+          ExecutionNode(..) => box Execution::new(Script(vec![])),
+          LocalsNode(..)    => box Locals::new(self.machine.locals_sym.clone()),
+          _                 => try!(self.nuketype(&node.kind))
+        };
+
+        let object =
+          ObjectRef::store_with_tag(placeholder, Meta::new(), node.tag.clone());
+
+        *self.objects.get_mut(index) = Some(object.clone());
+
+        match node.kind {
+          ExecutionNode(ref ops, pc, ref slots) => {
+            let execution =
+              try!(self.execution(ops.as_slice(), pc, slots.as_slice()));
+
+            *object.lock().try_cast::<Execution>().ok().unwrap() = execution;
+          },
+
+          LocalsNode(name) => {
+            let locals = Locals::new(try!(self.restore(name)));
+
+            *object.lock().try_cast::<Locals>().ok().unwrap() = locals;
+          },
+
+          _ => ()
+        }
+
+        let meta = try!(self.meta(&node));
+
+        *object.lock().meta_mut() = meta;
+
+        object
+      }
+    };
+
+    *self.objects.get_mut(index) = Some(object.clone());
+
+    Ok(object)
+  }
+
+  fn nuketype(&mut self, kind: &NodeKind)
+              -> Result<Box<Nuketype+Send+Sync>, String> {
+    Ok(match *kind {
+      ThingNode =>
+        box Thing as Box<Nuketype+Send+Sync>,
+
+      NumberNode(value) =>
+        box Number::new(value) as Box<Nuketype+Send+Sync>,
+
+      LocalsNode(name) =>
+        box Locals::new(try!(self.restore(name))) as Box<Nuketype+Send+Sync>,
+
+      ExecutionNode(ref ops, pc, ref slots) =>
+        box try!(self.execution(ops.as_slice(), pc, slots.as_slice()))
+          as Box<Nuketype+Send+Sync>,
+
+      _ => fail!("not a restorable nuketype")
+    })
+  }
+
+  fn execution(&mut self, ops: &[Op], pc: uint, slots: &[Slot])
+               -> Result<Execution, String> {
+    let mut instructions = Vec::with_capacity(ops.len());
+
+    for op in ops.iter() {
+      instructions.push(match *op {
+        PlainOp(ref instruction) => instruction.clone(),
+        PushOp(object)           => Push(try!(self.restore(object))),
+        PushPairOp(key, value)   => PushPair(try!(self.restore(key)),
+                                             try!(self.restore(value))),
+        LookupCombineOp(symbol)  => LookupCombine(try!(self.restore(symbol))),
+        LookupLocalOp(symbol)    => LookupLocal(try!(self.restore(symbol)))
+      });
+    }
+
+    if pc > instructions.len() {
+      return Err(format!("pc {} is past the end of the script", pc));
+    }
+
+    let mut stack = Vec::with_capacity(slots.len());
+
+    for slot in slots.iter() {
+      stack.push(match *slot {
+        LocalsSlot         => FromLocals,
+        SelfSlot           => FromSelf,
+        ObjectSlot(object) => From(try!(self.restore(object)))
+      });
+    }
+
+    Ok(Execution::resume_at(Script(instructions), pc, stack))
+  }
+
+  fn meta(&mut self, node: &Node) -> Result<Meta, String> {
+    let mut members = Members::new();
+
+    members.vec = Vec::with_capacity(node.members.len());
+
+    for member in node.members.iter() {
+      members.vec.push(match *member {
+        Some((to, true))  =>
+          Some(Relationship::new_child(try!(self.restore(to)))),
+        Some((to, false)) =>
+          Some(Relationship::new(try!(self.restore(to)))),
+        None =>
+          None
+      });
+    }
+
+    let receiver = match node.receiver {
+      ObjectNodeReceiver(index)    => ObjectReceiver(try!(self.restore(index))),
+      NativeNodeReceiver(function) => NativeReceiver(function)
+    };
+
+    Ok(Meta { members: members, receiver: receiver })
+  }
+}
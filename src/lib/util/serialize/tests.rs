@@ -0,0 +1,242 @@
+use super::{to_json, from_json, write_json, read_json};
+
+use object::{Meta, ObjectReceiver};
+
+use nuketype::{Thing, Execution, Locals, Number};
+
+use script::*;
+
+use machine::Machine;
+use machine::reactor::{FromLocals, From};
+
+use system::implementation;
+
+use std::io::{MemWriter, MemReader};
+
+#[test]
+fn round_trip_preserves_structure() {
+  let from = Machine::new();
+  let to   = Machine::new();
+
+  let child    = Thing::empty();
+  let receiver = Thing::empty();
+
+  let root = Thing::tagged(Meta::new(), "root");
+
+  {
+    let mut root_obj = root.lock();
+    let     meta     = root_obj.meta_mut();
+
+    meta.members.push_pair(from.symbol("key"), Number::create(42));
+    meta.members.push_child(child.clone());
+    meta.members.set(4, child.clone());
+    meta.receiver = ObjectReceiver(receiver.clone());
+  }
+
+  child.lock().meta_mut().members.push(root.clone());
+
+  let copy = from_json(&to, to_json(&from, &root).as_slice())
+               .ok().expect("restore failed");
+
+  assert!(copy != root);
+  assert_eq!(Some("root"), copy.tag().map(|tag| tag.as_slice()));
+
+  let copy_obj = copy.lock();
+  let members  = &copy_obj.meta().members;
+
+  // Symbols must be comparable with Symbols from the new machine.
+  let value = members.lookup_pair(&to.symbol("key")).expect("pair not found");
+
+  assert_eq!(Some(42), Number::of(&value));
+
+  let child_copy = members.get(2).unwrap();
+
+  assert!(child_copy.is_child());
+  assert!(members.get(3).is_none());
+  assert!(!members.get(4).unwrap().is_child());
+  assert!(members.get(4).unwrap().to() == child_copy.to());
+
+  // The cycle back to the root is preserved.
+  assert!(child_copy.to().lock().meta().members.get(1).unwrap().to() == &copy);
+
+  match copy_obj.meta().receiver {
+    ObjectReceiver(ref object) => assert!(object != &receiver),
+    _                          => fail!("receiver wasn't restored")
+  }
+}
+
+#[test]
+fn round_trip_preserves_executions_partway() {
+  let from = Machine::new();
+  let to   = Machine::new();
+
+  let execution = Execution::create(&from, Script(vec![
+    Discard,
+    PushLocals,
+    Push(from.symbol("a")),
+    Combine,
+    Push(from.symbol("b")),
+    Combine]));
+
+  from.expose_system_to(&execution);
+
+  // Run up to the first combination, so that there's a stack.
+  execution.lock().try_cast::<Execution>().ok().unwrap()
+    .advance(Thing::empty());
+
+  let copy = from_json(&to, to_json(&from, &execution).as_slice())
+               .ok().expect("restore failed");
+
+  let (pc, stack) = {
+    let copy_execution = copy.lock().try_cast::<Execution>().ok()
+                           .expect("not an Execution");
+
+    (copy_execution.pc(), copy_execution.stack())
+  };
+
+  assert_eq!(4, pc);
+  assert!(stack.is_empty());
+
+  let locals = copy.lock().meta().members.lookup_pair(&to.locals_sym)
+                 .expect("locals not found under the new machine's Symbol");
+
+  {
+    let locals_obj = locals.lock();
+
+    let locals_locals = locals_obj.try_cast::<Locals>().ok()
+                          .expect("not a Locals");
+
+    assert!(locals_locals.name().eq_as_symbol(&to.locals_sym));
+  }
+
+  let locals_obj = locals.lock();
+  let members    = &locals_obj.meta().members;
+
+  assert!(members.lookup_pair(&to.symbol("infrastructure")) ==
+            Some(to.infrastructure()));
+  assert!(members.lookup_pair(&to.symbol("implementation")) ==
+            Some(to.implementation()));
+}
+
+#[test]
+fn round_trip_preserves_stacks() {
+  let from = Machine::new();
+  let to   = Machine::new();
+
+  let object    = Thing::empty();
+  let execution = Execution::create(&from, Script(vec![
+    PushLocals,
+    Push(object.clone()),
+    Push(from.symbol("x")),
+    Combine]));
+
+  execution.lock().try_cast::<Execution>().ok().unwrap()
+    .advance(Thing::empty());
+
+  let copy = from_json(&to, to_json(&from, &execution).as_slice())
+               .ok().expect("restore failed");
+
+  let copy_execution = copy.lock().try_cast::<Execution>().ok().unwrap();
+
+  // [response, locals] were pushed; `object` and `x` were combined away.
+  match copy_execution.stack().as_slice() {
+    [From(_), FromLocals] => (),
+    other                 => fail!("unexpected stack {}", other)
+  }
+}
+
+#[test]
+fn frozen_objects_stay_frozen() {
+  let from = Machine::new();
+  let to   = Machine::new();
+
+  let frozen = Thing::frozen(Meta::new(), "frozen");
+  let root   = Thing::from_fn(|meta| meta.members.push(frozen.clone()));
+
+  let copy = from_json(&to, to_json(&from, &root).as_slice())
+               .ok().expect("restore failed");
+
+  let frozen_copy = copy.lock().meta().members.get(1).unwrap().to().clone();
+
+  assert!(frozen_copy != frozen);
+  assert!(frozen_copy.is_frozen());
+}
+
+#[test]
+fn aliens_cannot_be_restored() {
+  let from = Machine::new();
+  let to   = Machine::new();
+
+  let root = Thing::from_fn(|meta|
+    meta.members.push(implementation::void(&from)));
+
+  match from_json(&to, to_json(&from, &root).as_slice()) {
+    Ok(_)  => fail!("restored an Alien"),
+    Err(e) => assert!(e.as_slice().contains("Alien"), "{}", e)
+  }
+}
+
+#[test]
+fn system_namespaces_are_restored_by_name() {
+  let from = Machine::new();
+  let to   = Machine::new();
+
+  let root = Thing::from_fn(|meta| {
+    meta.members.push(from.infrastructure());
+    meta.members.push(from.implementation());
+  });
+
+  let json = to_json(&from, &root);
+
+  assert!(json.as_slice().contains(
+    "{\"kind\":\"system\",\"name\":\"infrastructure\"}"), "{}", json);
+
+  let copy = from_json(&to, json.as_slice()).ok().expect("restore failed");
+
+  let copy_obj = copy.lock();
+  let members  = &copy_obj.meta().members;
+
+  assert!(members.get(1).unwrap().to() == &to.infrastructure());
+  assert!(members.get(2).unwrap().to() == &to.implementation());
+}
+
+#[test]
+fn invalid_documents_are_rejected() {
+  let machine = Machine::new();
+
+  assert!(from_json(&machine, "not json").is_err());
+  assert!(from_json(&machine, "{}").is_err());
+  assert!(from_json(&machine,
+    "{\"format\":\"paws-json\",\"version\":1,\"root\":1,\"objects\":[]}")
+    .is_err());
+}
+
+#[test]
+fn write_and_read_through_streams() {
+  let machine = Machine::new();
+
+  let mut meta = Meta::new();
+
+  meta.members.push_pair(machine.symbol("answer"), Number::create(42));
+
+  let root = Thing::tagged(meta, "root");
+
+  let mut writer = MemWriter::new();
+
+  write_json(&machine, &root, &mut writer).unwrap();
+
+  let other = Machine::new();
+
+  let mut reader = MemReader::new(writer.unwrap());
+
+  let restored = read_json(&other, &mut reader).unwrap();
+
+  let answer = restored.lock().meta().members
+                 .lookup_pair(&other.symbol("answer"))
+                 .expect("answer not restored");
+
+  assert_eq!(Some(42), Number::of(&answer));
+
+  // Nothing left over to read.
+  assert!(read_json(&other, &mut reader).is_err());
+}
@@ -0,0 +1,203 @@
+//! Copying object graphs from one Machine into another.
+//!
+//! Objects can't simply be shared between Machines: Symbols are compared by
+//! pointer within their Machine's `SymbolMap`, Executions find their locals
+//! with their Machine's `locals` Symbol, and frozen namespaces are keyed by
+//! Symbol pointer. `copy_graph()` takes care of all of that.
+
+use object::{ObjectRef, ObjectRefGuard, Meta, Members, Relationship};
+use object::{ObjectReceiver, NativeReceiver};
+use nuketype::{Nuketype, Thing, Symbol, Execution, Alien, Locals, Number};
+use machine::Machine;
+
+use std::any::AnyRefExt;
+use std::collections::{HashMap, HashSet};
+
+#[cfg(test)]
+mod tests;
+
+/// Deep-copies everything reachable from `root` in `from_machine` into
+/// `to_machine`, and returns the copy of `root`.
+///
+/// * Symbols are re-interned into `to_machine`'s symbol map.
+/// * Every other reachable object is copied, whether it's reachable through a
+///   child relationship or not, along with its receiver (if it's an object),
+///   and the objects referenced by Executions' Scripts and stacks. Cycles are
+///   preserved.
+/// * `from_machine`'s `infrastructure` and `implementation` namespaces are
+///   translated to `to_machine`'s, rather than copied.
+/// * Frozen objects are copied frozen. Their copies can't exist before their
+///   members do, so a cycle that leads back into a frozen object that's still
+///   being copied is treated as already visited, and keeps referring to the
+///   original.
+/// * Aliens' data is opaque, so it's copied as-is; any objects it refers to
+///   still belong to `from_machine`.
+pub fn copy_graph(from_machine: &Machine,
+                  to_machine:   &Machine,
+                  root:         &ObjectRef)
+                  -> ObjectRef {
+
+  let mut transfer = Transfer {
+    to_machine: to_machine,
+    copies:     HashMap::new(),
+    in_frozen:  HashSet::new()
+  };
+
+  transfer.copies.insert(from_machine.infrastructure(),
+                         to_machine.infrastructure());
+  transfer.copies.insert(from_machine.implementation(),
+                         to_machine.implementation());
+
+  transfer.copy(root)
+}
+
+struct Transfer<'a> {
+  to_machine: &'a Machine,
+  copies:     HashMap<ObjectRef, ObjectRef>,
+  in_frozen:  HashSet<ObjectRef>
+}
+
+impl<'a> Transfer<'a> {
+  fn copy(&mut self, from: &ObjectRef) -> ObjectRef {
+    match from.symbol_ref() {
+      Some(string) => return self.to_machine.symbol(string.as_slice()),
+      None         => ()
+    }
+
+    match self.copies.find(from) {
+      Some(copy) => return copy.clone(),
+      None       => ()
+    }
+
+    if self.in_frozen.contains(from) {
+      return from.clone();
+    }
+
+    // Don't hold the lock while recursing.
+    let (nuketype, meta) = {
+      let object = from.lock();
+
+      (Nuketypes::of(&object), object.meta().clone())
+    };
+
+    if from.is_frozen() {
+      // The members have to be known before the object is stored, so the copy
+      // can't be registered until afterward.
+      self.in_frozen.insert(from.clone());
+
+      let nuketype = match nuketype {
+        CopyExecution(execution) =>
+          box execution.map_objects(|object| self.copy(object))
+            as Box<Nuketype+Send+Sync>,
+
+        other => self.finish(other)
+      };
+
+      let meta = self.copy_meta(meta);
+      let copy = ObjectRef::store_frozen(nuketype, meta, from.tag());
+
+      self.in_frozen.remove(from);
+      self.copies.insert(from.clone(), copy.clone());
+
+      copy
+    } else {
+      let execution = match nuketype {
+        CopyExecution(ref execution) => Some(execution.clone()),
+        _                            => None
+      };
+
+      // Store the copy before recursing, so that cycles find it.
+      let copy = ObjectRef::store_with_tag(
+        self.finish(nuketype), Meta::new(), from.tag());
+
+      self.copies.insert(from.clone(), copy.clone());
+
+      match execution {
+        Some(execution) => {
+          let mapped = execution.map_objects(|object| self.copy(object));
+
+          *copy.lock().try_cast::<Execution>().ok().unwrap() = mapped;
+        },
+        None => ()
+      }
+
+      let meta = self.copy_meta(meta);
+
+      *copy.lock().meta_mut() = meta;
+
+      copy
+    }
+  }
+
+  /// Turns everything but an Execution into its final nuketype. Executions are
+  /// returned as-is, to be mapped by the caller.
+  fn finish(&mut self, nuketype: Nuketypes) -> Box<Nuketype+Send+Sync> {
+    match nuketype {
+      CopyExecution(execution) => box execution,
+      CopyLocals(name)         => box Locals::new(self.copy(&name)),
+      CopyOther(other)         => other
+    }
+  }
+
+  fn copy_meta(&mut self, meta: Meta) -> Meta {
+    let mut members = Members::new();
+
+    members.vec = meta.members.vec.iter().map(|maybe_relationship|
+      maybe_relationship.as_ref().map(|relationship| {
+        let to = self.copy(relationship.to());
+
+        if relationship.is_child() {
+          Relationship::new_child(to)
+        } else {
+          Relationship::new(to)
+        }
+      })).collect();
+
+    let receiver = match meta.receiver {
+      ObjectReceiver(ref object) => ObjectReceiver(self.copy(object)),
+      NativeReceiver(function)   => NativeReceiver(function)
+    };
+
+    Meta { members: members, receiver: receiver }
+  }
+}
+
+/// The nuketype of an object being copied, taken out while it's locked.
+enum Nuketypes {
+  CopyExecution(Execution),
+  CopyLocals(ObjectRef),
+  CopyOther(Box<Nuketype+Send+Sync>)
+}
+
+impl Nuketypes {
+  fn of(object: &ObjectRefGuard) -> Nuketypes {
+    let nuketype = object.nuketype();
+
+    match nuketype.downcast_ref::<Execution>() {
+      Some(execution) => return CopyExecution(execution.clone()),
+      None            => ()
+    }
+
+    match nuketype.downcast_ref::<Locals>() {
+      Some(locals) => return CopyLocals(locals.name().clone()),
+      None         => ()
+    }
+
+    match nuketype.downcast_ref::<Alien>() {
+      Some(alien) => return CopyOther(box alien.clone()),
+      None        => ()
+    }
+
+    match nuketype.downcast_ref::<Number>() {
+      Some(number) => return CopyOther(box Number::new(number.value())),
+      None         => ()
+    }
+
+    match nuketype.downcast_ref::<Symbol>() {
+      Some(_) => fail!("Symbol stored without a symbol reference"),
+      None    => ()
+    }
+
+    CopyOther(box Thing)
+  }
+}
@@ -0,0 +1,126 @@
+use super::copy_graph;
+
+use object::Meta;
+
+use nuketype::{Thing, Execution, Number};
+
+use script::Script;
+
+use machine::Machine;
+
+#[test]
+fn copy_graph_reinterns_symbols() {
+  let from = Machine::new();
+  let to   = Machine::new();
+
+  let shared = Thing::empty();
+
+  let original = Thing::from_fn(|meta| {
+    meta.members.push_pair(from.symbol("key"), shared.clone());
+    meta.members.push(shared.clone());
+  });
+
+  let copy = copy_graph(&from, &to, &original);
+
+  assert!(copy != original);
+
+  let copy_obj = copy.lock();
+  let members  = &copy_obj.meta().members;
+
+  // The pair's key must be comparable with Symbols from the new machine.
+  let value = members.lookup_pair(&to.symbol("key")).expect("pair not found");
+
+  assert!(value != shared);
+
+  // Both relationships to `shared` lead to the same copy.
+  assert!(members.get(2).unwrap().to() == &value);
+}
+
+#[test]
+fn copy_graph_preserves_cycles() {
+  let from = Machine::new();
+  let to   = Machine::new();
+
+  let a = Thing::empty();
+  let b = Thing::empty();
+
+  a.lock().meta_mut().members.push_child(b.clone());
+  b.lock().meta_mut().members.push(a.clone());
+
+  let a_copy = copy_graph(&from, &to, &a);
+
+  let b_copy = a_copy.lock().meta().members.get(1).unwrap().to().clone();
+
+  assert!(b_copy != b);
+
+  let a_again = b_copy.lock().meta().members.get(1).unwrap().to().clone();
+
+  assert!(a_again == a_copy);
+}
+
+#[test]
+fn copy_graph_cycle_through_frozen_object() {
+  let from = Machine::new();
+  let to   = Machine::new();
+
+  let back = Thing::empty();
+
+  let mut meta = Meta::new();
+
+  meta.members.push(back.clone());
+
+  let frozen = Thing::frozen(meta, "frozen");
+
+  back.lock().meta_mut().members.push(frozen.clone());
+
+  let copy = copy_graph(&from, &to, &frozen);
+
+  assert!(copy != frozen);
+  assert!(copy.is_frozen());
+
+  let back_copy = copy.lock().meta().members.get(1).unwrap().to().clone();
+
+  assert!(back_copy != back);
+
+  // The frozen copy didn't exist yet, so the cycle leads to the original.
+  let frozen_again =
+    back_copy.lock().meta().members.get(1).unwrap().to().clone();
+
+  assert!(frozen_again == frozen);
+}
+
+#[test]
+fn copy_graph_translates_executions_and_system() {
+  let from = Machine::new();
+  let to   = Machine::new();
+
+  let execution = Execution::create(&from, Script(vec![]));
+
+  from.expose_system_to(&execution);
+
+  let copy = copy_graph(&from, &to, &execution);
+
+  let locals = copy.lock().meta().members.lookup_pair(&to.locals_sym)
+                 .expect("locals not found under the new machine's Symbol");
+
+  let locals_obj = locals.lock();
+  let members    = &locals_obj.meta().members;
+
+  assert!(members.lookup_pair(&to.symbol("infrastructure")) ==
+            Some(to.infrastructure()));
+  assert!(members.lookup_pair(&to.symbol("implementation")) ==
+            Some(to.implementation()));
+}
+
+#[test]
+fn copy_graph_copies_numbers() {
+  let from = Machine::new();
+  let to   = Machine::new();
+
+  let original = Number::create(42);
+
+  let copy = copy_graph(&from, &to, &original);
+
+  assert!(copy != original);
+  assert_eq!(Some(42), Number::of(&copy));
+}